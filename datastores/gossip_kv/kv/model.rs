@@ -27,7 +27,12 @@ pub type NamespaceMap<V> = MapUnionHashMap<Namespace, TableMap<V>>;
 pub type Namespaces<C> = NamespaceMap<RowValue<C>>;
 
 /// Timestamps used in the model.
-// TODO: This will be updated to use a more sophisticated clock type with https://github.com/hydro-project/hydro/issues/1207.
+///
+/// The model is generic over the clock, so rows can instead be versioned by a
+/// [`VClock`](dfir_rs::lattices::VClock) to keep concurrent writes instead of ordering them by
+/// timestamp.
+// TODO: The server still stamps writes with its tick, as it does not read a row's clock before
+// writing it (https://github.com/hydro-project/hydro/issues/1207).
 pub type Clock = Max<u64>;
 
 /// TableMap element to upsert a row in an existing TableMap.
@@ -77,7 +82,7 @@ pub fn delete_row<C>(
 mod tests {
     use std::collections::HashSet;
 
-    use dfir_rs::lattices::Merge;
+    use dfir_rs::lattices::{Merge, VClock};
 
     use crate::Namespace::System;
     use crate::model::{Clock, Namespaces, RowKey, TableName, delete_row, upsert_row};
@@ -163,4 +168,56 @@ mod tests {
             assert_eq!(value, &HashSet::from([]), "Row should be empty");
         }
     }
+
+    #[test]
+    fn test_vclock_concurrent_writes() {
+        let mut namespaces: Namespaces<VClock<&str>> = Namespaces::default();
+
+        let members_table = TableName::from("members");
+        let key_1 = RowKey::from("key1");
+
+        let mut clock_a = VClock::new();
+        clock_a.increment("a");
+        let mut clock_b = VClock::new();
+        clock_b.increment("b");
+
+        for (clock, value) in [(&clock_a, "value_a"), (&clock_b, "value_b")] {
+            let upsert = upsert_row(
+                clock.clone(),
+                System,
+                members_table.clone(),
+                key_1.clone(),
+                value.to_string(),
+            );
+            Merge::merge(&mut namespaces, upsert);
+        }
+
+        let row = |namespaces: &Namespaces<VClock<&str>>| {
+            namespaces.as_reveal_ref()[&System].as_reveal_ref()[&members_table].as_reveal_ref()
+                [&key_1]
+                .as_reveal_ref()
+                .1
+                .as_reveal_ref()
+                .clone()
+        };
+
+        // Neither write has seen the other, so both values are kept.
+        assert_eq!(
+            row(&namespaces),
+            HashSet::from(["value_a".to_string(), "value_b".to_string()])
+        );
+
+        // A write which has seen both values replaces them.
+        let mut clock_c = Merge::merge_owned(clock_a, clock_b);
+        clock_c.increment("a");
+        let upsert = upsert_row(
+            clock_c,
+            System,
+            members_table.clone(),
+            key_1.clone(),
+            "value_c".to_string(),
+        );
+        Merge::merge(&mut namespaces, upsert);
+        assert_eq!(row(&namespaces), HashSet::from(["value_c".to_string()]));
+    }
 }
@@ -230,7 +230,7 @@ where
 
         writes -> namespaces;
 
-        namespaces = state::<'static, Namespaces::<Clock>>();
+        namespaces = state::<'static, 'durable, Namespaces::<Clock>>();
         new_writes = namespaces -> tee(); // TODO: Use the output from here to generate NACKs / ACKs
 
         reads = state::<'tick, MapUnionHashMap<Namespace, MapUnionHashMap<TableName, MapUnionHashMap<RowKey, SetUnionHashSet<Addr>>>>>();
@@ -5,10 +5,12 @@ use std::hash::Hash;
 use std::io::Error;
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::num::ParseFloatError;
+use std::path::PathBuf;
 use std::time::Duration;
 
 use clap::Parser;
 use dfir_rs::futures::{Sink, SinkExt, StreamExt};
+use dfir_rs::scheduled::checkpoint::CheckpointDir;
 use dfir_rs::tokio_stream::wrappers::IntervalStream;
 use dfir_rs::util::{bind_udp_bytes, ipv4_resolve};
 use dfir_rs::{bincode, bytes, tokio};
@@ -26,7 +28,7 @@ mod config;
 
 mod membership;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Parser)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Parser)]
 struct Opts {
     /// Port to listen for gossip messages.
     #[clap(short, long, default_value = "3000")]
@@ -42,6 +44,15 @@ struct Opts {
 
     #[clap(env = "GOSSIP_MEMBER_SUFFIX_LEN", default_value = "4")]
     member_suffix_len: usize,
+
+    /// Directory to checkpoint the key-value state to, so that it survives restarts.
+    #[clap(long, env = "GOSSIP_CHECKPOINT_DIR")]
+    checkpoint_dir: Option<PathBuf>,
+
+    /// Port to serve the DFIR runtime metrics on, at `/metrics` in the OpenMetrics text format.
+    /// Separate from the application metrics served on port 4003.
+    #[clap(long, env = "GOSSIP_DFIR_METRICS_PORT")]
+    dfir_metrics_port: Option<u16>,
 }
 
 /// Parse duration from float string for clap args.
@@ -187,5 +198,22 @@ async fn main() {
         seed_node_stream,
     );
 
+    if let Some(checkpoint_dir) = opts.checkpoint_dir {
+        info!("Checkpointing state to {:?}", checkpoint_dir);
+        server
+            .set_checkpoint_dir(CheckpointDir::open(checkpoint_dir).unwrap())
+            .unwrap();
+    }
+
+    if let Some(dfir_metrics_port) = opts.dfir_metrics_port {
+        let addr = server
+            .serve_metrics(SocketAddr::new(
+                IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+                dfir_metrics_port,
+            ))
+            .unwrap();
+        info!("Serving DFIR metrics at http://{}/metrics", addr);
+    }
+
     server.run_async().await;
 }
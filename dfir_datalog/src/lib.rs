@@ -1,5 +1,5 @@
 use dfir_datalog_core::diagnostic::Diagnostic;
-use dfir_datalog_core::{dfir_graph_to_program, gen_dfir_graph};
+use dfir_datalog_core::{dfir_graph_to_program, gen_dfir_graph, gen_hydro_program};
 use proc_macro2::Span;
 use quote::{ToTokens, quote};
 
@@ -49,3 +49,60 @@ pub fn datalog(item: proc_macro::TokenStream) -> proc_macro::TokenStream {
         }
     }
 }
+
+/// Generate a Hydro program from [Datalog](https://en.wikipedia.org/wiki/Datalog) code, in the same
+/// variant of Datalog as [`datalog!`].
+///
+/// Relations live on the `hydro_lang` locations declared with ``.process name `expr` `` or
+/// ``.cluster name `expr` ``, where `expr` evaluates to a `&Process` or `&Cluster`. Inputs are
+/// streams on a location, declared with ``.input name@location `expr` ``. Every other relation is
+/// on the location of the relations its rules read, and is computed once per tick of that location.
+///
+/// Async rules send their results to another location:
+/// * `relation@process(..) :~ ..` sends to a process.
+/// * `relation@cluster(..) :~ ..` broadcasts to every member of a cluster.
+/// * `relation@cluster[id](..) :~ ..` sends to the member of a cluster with the `ClusterId` `id`.
+///
+/// Each ``.output name `pipeline` `` applies `pipeline` to the stream of `name` across all ticks,
+/// i.e. `` `for_each(q!(|row| println!("{:?}", row)))` ``. The macro evaluates to a tuple of the
+/// result of each output pipeline.
+///
+/// Inputs and received messages are batched into ticks with `tick_batch` and `assume_ordering`,
+/// whose results depend on when data arrives. So the macro must be invoked in an `unsafe` block,
+/// which opts in to this nondeterminism, for example `unsafe { datalog_hydro!(r#"..."#) }`.
+///
+/// For examples, see [the datalog tests in the repo](https://github.com/hydro-project/hydro/blob/main/hydro_test/src/distributed/datalog.rs).
+#[proc_macro]
+pub fn datalog_hydro(item: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let item = proc_macro2::TokenStream::from(item);
+    let literal: proc_macro2::Literal = syn::parse_quote! {
+        #item
+    };
+
+    const HYDRO_CRATE: &str = "hydro_lang";
+
+    let hydro_crate = proc_macro_crate::crate_name(HYDRO_CRATE)
+        .unwrap_or_else(|_| panic!("`{}` should be present in `Cargo.toml`", HYDRO_CRATE));
+    let root = match hydro_crate {
+        proc_macro_crate::FoundCrate::Itself => quote! { crate },
+        proc_macro_crate::FoundCrate::Name(name) => {
+            let ident = syn::Ident::new(&name, Span::call_site());
+            quote! { #ident }
+        }
+    };
+
+    match gen_hydro_program(literal, root) {
+        Ok(program) => program.into(),
+        Err(diagnostics) => {
+            let diagnostic_tokens = Diagnostic::try_emit_all(diagnostics.iter())
+                .err()
+                .unwrap_or_default();
+            proc_macro::TokenStream::from(quote! {
+                {
+                    #diagnostic_tokens
+                    panic!()
+                }
+            })
+        }
+    }
+}
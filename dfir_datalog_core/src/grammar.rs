@@ -14,6 +14,8 @@ pub mod datalog {
         Input(
             #[rust_sitter::leaf(text = ".input")] (),
             Spanned<Ident>,
+            /// The location the input arrives at, only when compiling to Hydro.
+            Option<AtLocation>,
             RustSnippet,
         ),
         Output(
@@ -36,6 +38,35 @@ pub mod datalog {
             Spanned<Ident>,
             RustSnippet,
         ),
+        /// A scalar function which may be called in expressions, given as a Rust closure or path.
+        Function(
+            #[rust_sitter::leaf(text = ".function")] (),
+            Spanned<Ident>,
+            RustSnippet,
+        ),
+        /// Compiles the program to maintain its relations incrementally, with each `.output`
+        /// receiving the changes to its relation as `(row, diff)` pairs.
+        Incremental(#[rust_sitter::leaf(text = ".incremental")] Spanned<()>),
+        /// A Hydro `Process` location, given as a Rust expression.
+        Process(
+            #[rust_sitter::leaf(text = ".process")] (),
+            Spanned<Ident>,
+            RustSnippet,
+        ),
+        /// A Hydro `Cluster` location, given as a Rust expression.
+        Cluster(
+            #[rust_sitter::leaf(text = ".cluster")] (),
+            Spanned<Ident>,
+            RustSnippet,
+        ),
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct AtLocation {
+        #[rust_sitter::leaf(text = "@")]
+        _at: (),
+
+        pub location: Spanned<Ident>,
     }
 
     #[derive(Debug, Clone)]
@@ -141,6 +172,20 @@ pub mod datalog {
         _at: (),
 
         pub node: Spanned<TargetExpr>,
+
+        /// The member of a cluster to send to, only when compiling to Hydro.
+        pub member: Option<Spanned<ClusterMember>>,
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct ClusterMember {
+        #[rust_sitter::leaf(text = "[")]
+        _l_bracket: (),
+
+        pub id: IntExpr,
+
+        #[rust_sitter::leaf(text = "]")]
+        _r_bracket: (),
     }
 
     #[derive(Debug, Clone)]
@@ -168,10 +213,23 @@ pub mod datalog {
                     | Aggregation::Max(_, _, a, _)
                     | Aggregation::Sum(_, _, a, _)
                     | Aggregation::Choose(_, _, a, _),
-                ) => vec![a],
+                ) => a.idents(),
                 TargetExpr::Index(_, _, _) => vec![],
             }
         }
+
+        pub fn calls(&self) -> Vec<(&Spanned<Ident>, usize)> {
+            match self {
+                TargetExpr::Expr(e) => e.calls(),
+                TargetExpr::Aggregation(
+                    Aggregation::Min(_, _, a, _)
+                    | Aggregation::Max(_, _, a, _)
+                    | Aggregation::Sum(_, _, a, _)
+                    | Aggregation::Choose(_, _, a, _),
+                ) => a.calls(),
+                TargetExpr::Aggregation(_) | TargetExpr::Index(_, _, _) => vec![],
+            }
+        }
     }
 
     #[derive(Debug, Clone)]
@@ -179,19 +237,19 @@ pub mod datalog {
         Min(
             #[rust_sitter::leaf(text = "min")] (),
             #[rust_sitter::leaf(text = "(")] (),
-            Spanned<Ident>,
+            Spanned<IntExpr>,
             #[rust_sitter::leaf(text = ")")] (),
         ),
         Max(
             #[rust_sitter::leaf(text = "max")] (),
             #[rust_sitter::leaf(text = "(")] (),
-            Spanned<Ident>,
+            Spanned<IntExpr>,
             #[rust_sitter::leaf(text = ")")] (),
         ),
         Sum(
             #[rust_sitter::leaf(text = "sum")] (),
             #[rust_sitter::leaf(text = "(")] (),
-            Spanned<Ident>,
+            Spanned<IntExpr>,
             #[rust_sitter::leaf(text = ")")] (),
         ),
         Count(#[rust_sitter::leaf(text = "count(*)")] ()),
@@ -208,7 +266,7 @@ pub mod datalog {
         Choose(
             #[rust_sitter::leaf(text = "choose")] (),
             #[rust_sitter::leaf(text = "(")] (),
-            Spanned<Ident>,
+            Spanned<IntExpr>,
             #[rust_sitter::leaf(text = ")")] (),
         ),
         CollectVec(
@@ -266,6 +324,8 @@ pub mod datalog {
 
     #[derive(Debug, Clone)]
     pub enum IntExpr {
+        // Resolves `rel@node(..)` in heads in favor of the node expression over a function call.
+        #[rust_sitter::prec(1)]
         Ident(Spanned<Ident>),
         Integer(
             #[rust_sitter::leaf(pattern = r"[0-9]+", transform = |s| s.parse().unwrap())]
@@ -300,6 +360,29 @@ pub mod datalog {
             #[rust_sitter::leaf(text = "%")] (),
             Box<IntExpr>,
         ),
+        #[rust_sitter::prec_left(2)]
+        Div(
+            Box<IntExpr>,
+            #[rust_sitter::leaf(text = "/")] (),
+            Box<IntExpr>,
+        ),
+        #[rust_sitter::prec_left(0)]
+        Cmp(Box<IntExpr>, BoolOp, Box<IntExpr>),
+        String(
+            #[rust_sitter::leaf(pattern = r#""[^"]*""#, transform = |s| s[1..s.len() - 1].to_string())]
+             Spanned<String>,
+        ),
+        /// A call to a builtin function or a function declared with `.function`.
+        Call(
+            Spanned<Ident>,
+            #[rust_sitter::leaf(text = "(")] (),
+            #[rust_sitter::delimited(
+                #[rust_sitter::leaf(text = ",")]
+                ()
+            )]
+            Vec<IntExpr>,
+            #[rust_sitter::leaf(text = ")")] (),
+        ),
     }
 
     impl IntExpr {
@@ -323,11 +406,36 @@ pub mod datalog {
                     idents.extend(r.idents());
                     idents
                 }
-                IntExpr::Mod(l, _, r) => {
+                IntExpr::Mod(l, _, r) | IntExpr::Div(l, _, r) | IntExpr::Cmp(l, _, r) => {
                     let mut idents = l.idents();
                     idents.extend(r.idents());
                     idents
                 }
+                IntExpr::String(_) => vec![],
+                IntExpr::Call(_, _, args, _) => args.iter().flat_map(IntExpr::idents).collect(),
+            }
+        }
+
+        /// The name and number of arguments of every function called within this expression.
+        pub fn calls(&self) -> Vec<(&Spanned<Ident>, usize)> {
+            match self {
+                IntExpr::Ident(_) | IntExpr::Integer(_) | IntExpr::String(_) => vec![],
+                IntExpr::Parenthesized(_, e, _) => e.calls(),
+                IntExpr::Add(l, _, r)
+                | IntExpr::Sub(l, _, r)
+                | IntExpr::Mul(l, _, r)
+                | IntExpr::Div(l, _, r)
+                | IntExpr::Mod(l, _, r)
+                | IntExpr::Cmp(l, _, r) => {
+                    let mut calls = l.calls();
+                    calls.extend(r.calls());
+                    calls
+                }
+                IntExpr::Call(name, _, args, _) => {
+                    let mut calls = vec![(name, args.len())];
+                    calls.extend(args.iter().flat_map(IntExpr::calls));
+                    calls
+                }
             }
         }
     }
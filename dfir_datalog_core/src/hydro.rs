@@ -0,0 +1,1125 @@
+//! Compiles Datalog programs to Hydro (`hydro_lang`) streams instead of a single DFIR graph.
+//!
+//! Each relation lives on a `.process` or `.cluster` location and is computed once per tick of
+//! that location. Rules with `:~` send their results to another location over the network.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use dfir_lang::diagnostic::{Diagnostic, Level};
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote, quote_spanned};
+use rust_sitter::Spanned;
+
+use crate::grammar::datalog::{
+    Aggregation, Atom, Declaration, ExtractExpr, Ident, InputRelationExpr, IntExpr, Rule, RuleType,
+    TargetExpr,
+};
+use crate::util::repeat_tuple;
+use crate::{
+    MAGIC_RELATIONS, check_calls, gen_comparison, gen_value_expr, literal_span_getter,
+    parse_function, parse_program, stratify,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum LocationKind {
+    Process,
+    Cluster,
+}
+
+struct HydroLocation<'a> {
+    kind: LocationKind,
+    ident: &'a Spanned<Ident>,
+    expr: syn::Expr,
+}
+
+/// Where the results of a rule are sent, see [`rule_destination`].
+enum Destination<'a> {
+    /// The results stay on the location the rule is evaluated on.
+    Local,
+    /// The results are sent to a process, or broadcast to every member of a cluster.
+    Location(&'a Spanned<Ident>),
+    /// The results are sent to the member of a cluster given by the expression.
+    Member(&'a Spanned<Ident>, &'a IntExpr),
+}
+
+fn parse_snippet<T: syn::parse::Parse>(
+    code_str: &Spanned<String>,
+    what: &str,
+    get_span: &impl Fn((usize, usize)) -> Span,
+) -> Result<T, Diagnostic> {
+    syn::LitStr::new(&code_str.value, get_span(code_str.span))
+        .parse()
+        .map_err(|err| Diagnostic {
+            span: err.span(),
+            level: Level::Error,
+            message: format!("Failed to parse {}: {}", what, err),
+        })
+}
+
+/// Generates a Rust expression which adds the program to the Hydro locations given by its
+/// `.process` and `.cluster` declarations. The expression evaluates to a tuple of the results of
+/// each `.output` pipeline, in the order they are declared.
+///
+/// The expression batches its inputs and received messages into ticks with the `unsafe`
+/// `tick_batch` and `assume_ordering` operators, so it must be placed in an `unsafe` block.
+///
+/// `root` is the path to the `hydro_lang` crate.
+pub fn gen_hydro_program(
+    literal: proc_macro2::Literal,
+    root: TokenStream,
+) -> Result<TokenStream, Vec<Diagnostic>> {
+    let get_span = literal_span_getter(&literal)?;
+    let program = parse_program(&literal, &get_span)?;
+
+    let mut locations = BTreeMap::<String, HydroLocation>::new();
+    let mut inputs = Vec::new();
+    let mut outputs = Vec::new();
+    let mut persists = HashSet::new();
+    let mut rules = Vec::new();
+    let mut functions = HashMap::new();
+    let mut diagnostics = Vec::new();
+
+    for stmt in &program.rules {
+        match stmt {
+            Declaration::Process(_, ident, hf_code) | Declaration::Cluster(_, ident, hf_code) => {
+                let kind = if matches!(stmt, Declaration::Process(..)) {
+                    LocationKind::Process
+                } else {
+                    LocationKind::Cluster
+                };
+                match parse_snippet(&hf_code.code, "location expression", &get_span) {
+                    Ok(expr) => {
+                        if locations
+                            .insert(ident.name.clone(), HydroLocation { kind, ident, expr })
+                            .is_some()
+                        {
+                            diagnostics.push(Diagnostic::spanned(
+                                get_span(ident.span),
+                                Level::Error,
+                                format!("Location `{}` is declared more than once", ident.name),
+                            ));
+                        }
+                    }
+                    Err(diagnostic) => diagnostics.push(diagnostic),
+                }
+            }
+            Declaration::Input(_, ident, at_location, hf_code) => {
+                assert!(!MAGIC_RELATIONS.contains(&ident.name.as_str()));
+                let Some(at_location) = at_location else {
+                    diagnostics.push(Diagnostic::spanned(
+                        get_span(ident.span),
+                        Level::Error,
+                        format!(
+                            "Input `{}` must be given a location, i.e. `.input {}@location`",
+                            ident.name, ident.name
+                        ),
+                    ));
+                    continue;
+                };
+                match parse_snippet::<syn::Expr>(&hf_code.code, "input stream", &get_span) {
+                    Ok(expr) => inputs.push((ident, &at_location.location, expr)),
+                    Err(diagnostic) => diagnostics.push(diagnostic),
+                }
+            }
+            Declaration::Output(_, ident, hf_code) => {
+                assert!(!MAGIC_RELATIONS.contains(&ident.name.as_str()));
+                match parse_snippet::<TokenStream>(&hf_code.code, "output pipeline", &get_span) {
+                    Ok(tokens) => outputs.push((ident, tokens)),
+                    Err(diagnostic) => diagnostics.push(diagnostic),
+                }
+            }
+            Declaration::Persist(_, ident) => {
+                persists.insert(ident.name.clone());
+            }
+            Declaration::Rule(rule) => {
+                assert!(!MAGIC_RELATIONS.contains(&rule.target.name.name.as_str()));
+                rules.push(rule)
+            }
+            Declaration::Function(_, ident, hf_code) => match parse_function(&hf_code.code, &get_span)
+            {
+                Ok(function) => {
+                    if functions.insert(ident.name.clone(), function).is_some() {
+                        diagnostics.push(Diagnostic::spanned(
+                            get_span(ident.span),
+                            Level::Error,
+                            format!("Function `{}` is declared more than once", ident.name),
+                        ));
+                    }
+                }
+                Err(errs) => diagnostics.extend(errs),
+            },
+            Declaration::Async(_, ident, _, _) => diagnostics.push(Diagnostic::spanned(
+                get_span(ident.span),
+                Level::Error,
+                "`.async` is not supported when compiling to Hydro, send to a `.process` or `.cluster` with `@` instead",
+            )),
+            Declaration::Static(_, ident, _) => diagnostics.push(Diagnostic::spanned(
+                get_span(ident.span),
+                Level::Error,
+                "`.static` is not supported when compiling to Hydro",
+            )),
+            Declaration::Incremental(keyword) => diagnostics.push(Diagnostic::spanned(
+                get_span(keyword.span),
+                Level::Error,
+                "`.incremental` is not supported when compiling to Hydro",
+            )),
+        }
+    }
+
+    check_calls(&rules, &functions, &mut diagnostics, &get_span);
+
+    let stratification = stratify::stratify(&rules, &persists, &mut diagnostics, &get_span);
+    for (relation, _) in stratification.recursive_aggregations.iter() {
+        let rule = rules
+            .iter()
+            .find(|rule| &rule.target.name.name == relation)
+            .unwrap();
+        diagnostics.push(Diagnostic::spanned(
+            get_span(rule.target.name.span),
+            Level::Error,
+            format!(
+                "`{}` is aggregated through recursion, which is not supported when compiling to Hydro",
+                relation
+            ),
+        ));
+    }
+
+    let mut destinations = Vec::new();
+    for rule in &rules {
+        destinations.push(rule_destination(
+            rule,
+            &locations,
+            &mut diagnostics,
+            &get_span,
+        ));
+        check_supported(rule, &mut diagnostics, &get_span);
+    }
+
+    // Each relation is on the location of its inputs, or of the relations read by the rules for it.
+    let mut relation_locations = HashMap::<String, String>::new();
+    for (ident, location, _) in &inputs {
+        if locations.contains_key(&location.name) {
+            relation_locations.insert(ident.name.clone(), location.name.clone());
+        } else {
+            diagnostics.push(unknown_location(location, &get_span));
+        }
+    }
+    loop {
+        let mut changed = false;
+        for (rule, destination) in rules.iter().zip(&destinations) {
+            if relation_locations.contains_key(&rule.target.name.name) {
+                continue;
+            }
+            let location = match destination {
+                Some(Destination::Location(location) | Destination::Member(location, _)) => {
+                    Some(location.name.clone())
+                }
+                Some(Destination::Local) => {
+                    body_location(rule, &relation_locations).map(|(location, _)| location.clone())
+                }
+                None => None,
+            };
+            if let Some(location) = location {
+                relation_locations.insert(rule.target.name.name.clone(), location);
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    for (rule, destination) in rules.iter().zip(&destinations) {
+        let Some((body_location, _)) = body_location(rule, &relation_locations) else {
+            diagnostics.push(Diagnostic::spanned(
+                get_span(rule.span),
+                Level::Error,
+                "Could not determine the location of this rule, it must read a relation which is an `.input` or the target of another rule",
+            ));
+            continue;
+        };
+        for atom in &rule.sources {
+            let (Atom::PosRelation(e) | Atom::NegRelation(_, e)) = atom else {
+                continue;
+            };
+            match relation_locations.get(&e.name.name) {
+                Some(location) if location != body_location => {
+                    diagnostics.push(Diagnostic::spanned(
+                        get_span(e.name.span),
+                        Level::Error,
+                        format!(
+                            "`{}` is on `{}`, but this rule is evaluated on `{}`. Send it to `{}` with a `:~` rule first",
+                            e.name.name, location, body_location, body_location
+                        ),
+                    ));
+                }
+                Some(_) => {}
+                None => diagnostics.push(Diagnostic::spanned(
+                    get_span(e.name.span),
+                    Level::Error,
+                    format!(
+                        "Could not determine the location of `{}`, it must be an `.input` or the target of a rule",
+                        e.name.name
+                    ),
+                )),
+            }
+        }
+
+        let produced_on = match destination {
+            Some(Destination::Location(location) | Destination::Member(location, _)) => {
+                &location.name
+            }
+            Some(Destination::Local) => body_location,
+            None => continue,
+        };
+        if let Some(location) = relation_locations.get(&rule.target.name.name) {
+            if location != produced_on {
+                diagnostics.push(Diagnostic::spanned(
+                    get_span(rule.target.name.span),
+                    Level::Error,
+                    format!(
+                        "This rule produces `{}` on `{}`, but it is on `{}`",
+                        rule.target.name.name, produced_on, location
+                    ),
+                ));
+            }
+        }
+    }
+
+    for (ident, _) in &outputs {
+        if !relation_locations.contains_key(&ident.name) {
+            diagnostics.push(Diagnostic::spanned(
+                get_span(ident.span),
+                Level::Error,
+                format!(
+                    "Could not determine the location of `{}`, it must be an `.input` or the target of a rule",
+                    ident.name
+                ),
+            ));
+        }
+    }
+
+    if !diagnostics.is_empty() {
+        return Err(diagnostics);
+    }
+
+    // Only relations which contribute to an output are generated.
+    let mut needed = BTreeSet::new();
+    let mut to_visit = outputs
+        .iter()
+        .map(|(ident, _)| ident.name.as_str())
+        .collect::<Vec<_>>();
+    while let Some(relation) = to_visit.pop() {
+        if !needed.insert(relation) {
+            continue;
+        }
+        for rule in rules.iter().filter(|r| r.target.name.name == relation) {
+            to_visit.extend(rule_sources(rule));
+        }
+    }
+
+    // Relations are generated in the order they are declared, unless they read a relation which
+    // has not been generated yet. Relations which are part of a cycle are read through a forward
+    // reference, which is completed once every relation they read has been generated.
+    let mut order = Vec::<&str>::new();
+    for stmt in &program.rules {
+        let name = match stmt {
+            Declaration::Input(_, ident, _, _) => &ident.name,
+            Declaration::Rule(rule) => &rule.target.name.name,
+            _ => continue,
+        };
+        if needed.contains(name.as_str()) && !order.contains(&name.as_str()) {
+            order.push(name);
+        }
+    }
+
+    let relation_var = |name: &str| format_ident!("{}__relation", name);
+    let location_var = |name: &str| format_ident!("{}__location", name);
+    let tick_var = |name: &str| format_ident!("{}__tick", name);
+
+    let mut used_locations = BTreeSet::new();
+    let mut statements = Vec::<TokenStream>::new();
+    let mut available = HashSet::<&str>::new();
+    let mut forward_refs = HashSet::<&str>::new();
+    let mut pending = order;
+    while !pending.is_empty() {
+        let ready = pending.iter().position(|relation| {
+            rules
+                .iter()
+                .filter(|r| r.target.name.name == *relation)
+                .flat_map(|r| rule_sources(r))
+                .all(|source| available.contains(source))
+        });
+
+        let Some(ready) = ready else {
+            let relation = *pending
+                .iter()
+                .find(|relation| !forward_refs.contains(*relation))
+                .expect("every pending relation is waiting on another");
+            let location = &relation_locations[relation];
+            used_locations.insert(location.as_str());
+
+            let complete = format_ident!("{}__complete", relation);
+            let var = relation_var(relation);
+            let tick = tick_var(location);
+            statements.push(quote! {
+                let (#complete, #var) = #tick.forward_ref_stream::<_, #root::NoOrder>();
+            });
+            available.insert(relation);
+            forward_refs.insert(relation);
+            continue;
+        };
+        let relation = pending.remove(ready);
+        let location = &relation_locations[relation];
+        used_locations.insert(location.as_str());
+        let tick = tick_var(location);
+
+        let mut contributions = Vec::new();
+        let mut aggregated = Vec::new();
+        for (ident, _, expr) in inputs.iter().filter(|(i, _, _)| i.name == relation) {
+            contributions.push(quote_spanned! {get_span(ident.span)=>
+                (#expr).tick_batch(&#tick).assume_ordering::<#root::NoOrder>()
+            });
+        }
+        for (rule, destination) in rules.iter().zip(&destinations) {
+            if rule.target.name.name != relation {
+                continue;
+            }
+            let (source_location, _) = body_location(rule, &relation_locations).unwrap();
+            used_locations.insert(source_location.as_str());
+            // Aggregations into a persisted relation fold over the rows of every tick, which are
+            // persisted instead of the aggregated results.
+            let persist_rows = persists.contains(relation)
+                && !matches!(rule.rule_type.value, RuleType::Async(_))
+                && has_aggregation(rule);
+            let body = gen_rule(
+                rule,
+                destination.as_ref().unwrap(),
+                persist_rows.then(|| tick_var(source_location)).as_ref(),
+                &relation_var,
+                &functions,
+                &root,
+                &get_span,
+            );
+
+            let span = get_span(rule.rule_type.span);
+            let contribution = match (&rule.rule_type.value, destination.as_ref().unwrap()) {
+                (RuleType::Sync(_), _) => body,
+                (RuleType::NextTick(_), _) => quote_spanned!(span=> #body.defer_tick()),
+                (
+                    RuleType::Async(_),
+                    Destination::Location(dest) | Destination::Member(dest, _),
+                ) => {
+                    let from_cluster =
+                        locations[source_location.as_str()].kind == LocationKind::Cluster;
+                    let send = match (destination.as_ref().unwrap(), from_cluster) {
+                        (Destination::Member(..), false) => format_ident!("send_bincode"),
+                        (Destination::Member(..), true) => format_ident!("send_bincode_anonymous"),
+                        _ if locations[&dest.name].kind == LocationKind::Process => {
+                            if from_cluster {
+                                format_ident!("send_bincode_anonymous")
+                            } else {
+                                format_ident!("send_bincode")
+                            }
+                        }
+                        (_, false) => format_ident!("broadcast_bincode"),
+                        (_, true) => format_ident!("broadcast_bincode_anonymous"),
+                    };
+                    let dest_location = location_var(&dest.name);
+                    quote_spanned! {span=>
+                        #body
+                            .all_ticks()
+                            .#send(#dest_location)
+                            .tick_batch(&#tick)
+                            .assume_ordering::<#root::NoOrder>()
+                    }
+                }
+                (RuleType::Async(_), Destination::Local) => unreachable!(),
+            };
+            if persist_rows {
+                aggregated.push(contribution);
+            } else {
+                contributions.push(contribution);
+            }
+        }
+
+        let union_all =
+            |streams: Vec<TokenStream>| streams.into_iter().reduce(|a, b| quote!(#a.union(#b)));
+        let var = relation_var(relation);
+        let aggregated = union_all(aggregated);
+        let value = match (union_all(contributions), aggregated) {
+            (Some(combined), aggregated) if persists.contains(relation) => {
+                // The relation is the union of everything derived in this and previous ticks,
+                // along with the current results of its aggregations.
+                let previous_complete = format_ident!("{}__previous_complete", relation);
+                let previous = format_ident!("{}__previous", relation);
+                let all = format_ident!("{}__all", relation);
+                statements.push(quote! {
+                    let (#previous_complete, #previous) = #tick.cycle::<#root::Stream<_, _, _, #root::NoOrder>>();
+                    let #all = #combined.union(#previous).unique();
+                    #previous_complete.complete_next_tick(#all.clone());
+                });
+                match aggregated {
+                    Some(aggregated) => quote!(#all.union(#aggregated).unique()),
+                    None => quote!(#all),
+                }
+            }
+            (Some(combined), None) => quote!(#combined.unique()),
+            (Some(combined), Some(aggregated)) => quote!(#combined.union(#aggregated).unique()),
+            (None, Some(aggregated)) => quote!(#aggregated.unique()),
+            (None, None) => unreachable!("every relation has a rule or input"),
+        };
+        if forward_refs.contains(relation) {
+            let complete = format_ident!("{}__complete", relation);
+            statements.push(quote!(#complete.complete(#value);));
+        } else {
+            statements.push(quote!(let #var = #value;));
+            available.insert(relation);
+        }
+    }
+
+    let output_exprs = outputs.iter().map(|(ident, tokens)| {
+        let var = relation_var(&ident.name);
+        quote_spanned!(get_span(ident.span)=> #var.clone().all_ticks().#tokens)
+    });
+
+    // Locations which relations are sent to must be bound even if nothing is computed on them.
+    for (rule, destination) in rules.iter().zip(&destinations) {
+        if let Some(Destination::Location(dest) | Destination::Member(dest, _)) = destination {
+            if needed.contains(rule.target.name.name.as_str()) {
+                used_locations.insert(dest.name.as_str());
+            }
+        }
+    }
+    let location_bindings = used_locations.iter().map(|name| {
+        let location = &locations[*name];
+        let var = location_var(name);
+        let tick = tick_var(name);
+        let expr = &location.expr;
+        quote_spanned! {get_span(location.ident.span)=>
+            let #var = #expr;
+            let #tick = #var.tick();
+        }
+    });
+
+    Ok(quote! {
+        {
+            use #root::Location as _;
+            #(#location_bindings)*
+            #(#statements)*
+            (#(#output_exprs,)*)
+        }
+    })
+}
+
+fn unknown_location(
+    location: &Spanned<Ident>,
+    get_span: &impl Fn((usize, usize)) -> Span,
+) -> Diagnostic {
+    Diagnostic::spanned(
+        get_span(location.span),
+        Level::Error,
+        format!(
+            "Unknown location `{}`, locations must be declared with `.process` or `.cluster`",
+            location.name
+        ),
+    )
+}
+
+/// The names of the relations read by `rule`.
+fn rule_sources(rule: &Rule) -> impl Iterator<Item = &str> {
+    rule.sources.iter().filter_map(|atom| match atom {
+        Atom::PosRelation(e) | Atom::NegRelation(_, e) => Some(e.name.name.as_str()),
+        Atom::Predicate(_) => None,
+    })
+}
+
+/// The location `rule` is evaluated on, which is the location of the first relation it reads
+/// whose location is known, along with that relation.
+fn body_location<'a>(
+    rule: &'a Rule,
+    relation_locations: &'a HashMap<String, String>,
+) -> Option<(&'a String, &'a str)> {
+    rule_sources(rule).find_map(|source| relation_locations.get(source).map(|l| (l, source)))
+}
+
+/// Checks the `@` of the head of `rule` against its rule type, returning where its results are
+/// sent, or `None` if there is an error.
+fn rule_destination<'a>(
+    rule: &'a Spanned<Rule>,
+    locations: &BTreeMap<String, HydroLocation>,
+    diagnostics: &mut Vec<Diagnostic>,
+    get_span: &impl Fn((usize, usize)) -> Span,
+) -> Option<Destination<'a>> {
+    let at_node = match (&rule.rule_type.value, &rule.target.at_node) {
+        (RuleType::Sync(_) | RuleType::NextTick(_), None) => return Some(Destination::Local),
+        (RuleType::Sync(_) | RuleType::NextTick(_), Some(at_node)) => {
+            diagnostics.push(Diagnostic::spanned(
+                get_span(at_node.node.span),
+                Level::Error,
+                "Rule must be async (`:~`) to send data to another location",
+            ));
+            return None;
+        }
+        (RuleType::Async(_), None) => {
+            diagnostics.push(Diagnostic::spanned(
+                get_span(rule.rule_type.span),
+                Level::Error,
+                "Async rules are only for sending data to other locations, use `@` in the head",
+            ));
+            return None;
+        }
+        (RuleType::Async(_), Some(at_node)) => at_node,
+    };
+
+    let TargetExpr::Expr(IntExpr::Ident(location)) = &at_node.node.value else {
+        diagnostics.push(Diagnostic::spanned(
+            get_span(at_node.node.span),
+            Level::Error,
+            "Data can only be sent to a `.process` or `.cluster`, i.e. `relation@location(..)` or `relation@cluster[member](..)`",
+        ));
+        return None;
+    };
+    let Some(hydro_location) = locations.get(&location.name) else {
+        diagnostics.push(unknown_location(location, get_span));
+        return None;
+    };
+
+    match &at_node.member {
+        None => Some(Destination::Location(location)),
+        Some(member) if hydro_location.kind == LocationKind::Cluster => {
+            Some(Destination::Member(location, &member.id))
+        }
+        Some(member) => {
+            diagnostics.push(Diagnostic::spanned(
+                get_span(member.span),
+                Level::Error,
+                format!(
+                    "`{}` is a process, only members of a `.cluster` can be selected",
+                    location.name
+                ),
+            ));
+            None
+        }
+    }
+}
+
+/// Reports the features of the DFIR frontend which are not supported when compiling to Hydro, and
+/// variables which are used without being bound by a positive relation.
+fn check_supported(
+    rule: &Rule,
+    diagnostics: &mut Vec<Diagnostic>,
+    get_span: &impl Fn((usize, usize)) -> Span,
+) {
+    let mut bound = HashSet::new();
+    for atom in &rule.sources {
+        match atom {
+            Atom::PosRelation(e) | Atom::NegRelation(_, e) => {
+                if MAGIC_RELATIONS.contains(&e.name.name.as_str()) {
+                    diagnostics.push(Diagnostic::spanned(
+                        get_span(e.name.span),
+                        Level::Error,
+                        format!("`{}` is not supported when compiling to Hydro", e.name.name),
+                    ));
+                }
+                for field in &e.fields {
+                    match &field.value {
+                        ExtractExpr::Ident(ident) => {
+                            if matches!(atom, Atom::PosRelation(_)) {
+                                bound.insert(ident.name.as_str());
+                            }
+                        }
+                        ExtractExpr::Underscore(_) => {}
+                        ExtractExpr::Flatten(..) | ExtractExpr::Untuple(..) => {
+                            diagnostics.push(Diagnostic::spanned(
+                                get_span(field.span),
+                                Level::Error,
+                                "Flattening and destructuring columns is not supported when compiling to Hydro",
+                            ));
+                        }
+                    }
+                }
+            }
+            Atom::Predicate(_) => {}
+        }
+    }
+
+    let check_bound = |ident: &Spanned<Ident>, diagnostics: &mut Vec<Diagnostic>| {
+        if !bound.contains(ident.name.as_str()) {
+            diagnostics.push(Diagnostic::spanned(
+                get_span(ident.span),
+                Level::Error,
+                format!(
+                    "Variable `{}` must be bound by a positive relation in the body of the rule",
+                    ident.name
+                ),
+            ));
+        }
+    };
+    for atom in &rule.sources {
+        match atom {
+            Atom::NegRelation(_, e) => {
+                for field in &e.fields {
+                    if let ExtractExpr::Ident(ident) = &field.value {
+                        check_bound(ident, diagnostics);
+                    }
+                }
+            }
+            Atom::Predicate(p) => {
+                for ident in spanned_idents(&p.left)
+                    .into_iter()
+                    .chain(spanned_idents(&p.right))
+                {
+                    check_bound(ident, diagnostics);
+                }
+            }
+            Atom::PosRelation(_) => {}
+        }
+    }
+
+    for field in &rule.target.fields {
+        let idents = match &field.value {
+            TargetExpr::Expr(expr) => spanned_idents(expr),
+            TargetExpr::Aggregation(
+                Aggregation::Min(_, _, a, _)
+                | Aggregation::Max(_, _, a, _)
+                | Aggregation::Sum(_, _, a, _)
+                | Aggregation::Choose(_, _, a, _),
+            ) => spanned_idents(a),
+            TargetExpr::Aggregation(Aggregation::Count(_)) => vec![],
+            TargetExpr::Aggregation(Aggregation::CountUnique(..) | Aggregation::CollectVec(..))
+            | TargetExpr::Index(..) => {
+                diagnostics.push(Diagnostic::spanned(
+                    get_span(field.span),
+                    Level::Error,
+                    "`count(unique ..)`, `collect_vec` and `index()` are not supported when compiling to Hydro",
+                ));
+                vec![]
+            }
+        };
+        for ident in idents {
+            check_bound(ident, diagnostics);
+        }
+    }
+    if let Some(member) = rule.target.at_node.as_ref().and_then(|n| n.member.as_ref()) {
+        for ident in spanned_idents(&member.id) {
+            check_bound(ident, diagnostics);
+        }
+    }
+}
+
+/// Every variable used in `expr`, with its span.
+fn spanned_idents(expr: &IntExpr) -> Vec<&Spanned<Ident>> {
+    match expr {
+        IntExpr::Ident(ident) => vec![ident],
+        IntExpr::Integer(_) | IntExpr::String(_) => vec![],
+        IntExpr::Parenthesized(_, e, _) => spanned_idents(e),
+        IntExpr::Add(l, _, r)
+        | IntExpr::Sub(l, _, r)
+        | IntExpr::Mul(l, _, r)
+        | IntExpr::Div(l, _, r)
+        | IntExpr::Mod(l, _, r)
+        | IntExpr::Cmp(l, _, r) => {
+            let mut idents = spanned_idents(l);
+            idents.extend(spanned_idents(r));
+            idents
+        }
+        IntExpr::Call(_, _, args, _) => args.iter().flat_map(spanned_idents).collect(),
+    }
+}
+
+fn has_aggregation(rule: &Rule) -> bool {
+    rule.target
+        .fields
+        .iter()
+        .any(|field| matches!(field.value, TargetExpr::Aggregation(_)))
+}
+
+fn var_idents<'a>(vars: impl IntoIterator<Item = &'a String>) -> Vec<syn::Ident> {
+    vars.into_iter()
+        .map(|v| syn::Ident::new(v, Span::call_site()))
+        .collect()
+}
+
+/// Generates a stream of the variables bound by a relation in the body of a rule, along with the
+/// names of those variables. Columns which repeat a variable are filtered to be equal.
+fn gen_atom(
+    e: &InputRelationExpr,
+    relation_var: &impl Fn(&str) -> syn::Ident,
+    root: &TokenStream,
+) -> (TokenStream, Vec<String>) {
+    let mut vars = Vec::<String>::new();
+    let mut patterns = Vec::<TokenStream>::new();
+    let mut conditions = Vec::<TokenStream>::new();
+    for (i, field) in e.fields.iter().enumerate() {
+        match &field.value {
+            ExtractExpr::Ident(ident) => {
+                let var = syn::Ident::new(&ident.name, Span::call_site());
+                if vars.contains(&ident.name) {
+                    let repeated = format_ident!("__{}_{}", ident.name, i);
+                    conditions.push(quote!(#var == #repeated));
+                    patterns.push(quote!(#repeated));
+                } else {
+                    vars.push(ident.name.clone());
+                    patterns.push(quote!(#var));
+                }
+            }
+            _ => patterns.push(quote!(_)),
+        }
+    }
+
+    let relation = relation_var(&e.name.name);
+    let row_type = repeat_tuple::<syn::Type, syn::Type>(|| syn::parse_quote!(_), e.fields.len());
+    let out = var_idents(&vars);
+    let stream = if conditions.is_empty() {
+        quote!(#relation.clone().map(#root::q!(|(#(#patterns,)*): #row_type| (#(#out,)*))))
+    } else {
+        quote! {
+            #relation.clone().filter_map(#root::q!(|(#(#patterns,)*): #row_type| if #(#conditions)&&* {
+                Some((#(#out,)*))
+            } else {
+                None
+            }))
+        }
+    };
+    (stream, vars)
+}
+
+/// Generates the stream of results of `rule`, on the tick of the location it is evaluated on.
+fn gen_rule(
+    rule: &Rule,
+    destination: &Destination,
+    persist_rows: Option<&syn::Ident>,
+    relation_var: &impl Fn(&str) -> syn::Ident,
+    functions: &HashMap<String, syn::Expr>,
+    root: &TokenStream,
+    get_span: &impl Fn((usize, usize)) -> Span,
+) -> TokenStream {
+    let mut positives = rule.sources.iter().filter_map(|atom| match atom {
+        Atom::PosRelation(e) => Some(e),
+        _ => None,
+    });
+
+    let (mut stream, mut vars) = gen_atom(positives.next().unwrap(), relation_var, root);
+    for e in positives {
+        let (next, next_vars) = gen_atom(e, relation_var, root);
+        let left = var_idents(&vars);
+        let right = var_idents(&next_vars);
+        let shared = var_idents(vars.iter().filter(|v| next_vars.contains(v)));
+        if shared.is_empty() {
+            stream = quote! {
+                #stream.cross_product(#next).map(#root::q!(|((#(#left,)*), (#(#right,)*))| (#(#left,)* #(#right,)*)))
+            };
+            vars.extend(next_vars);
+        } else {
+            let left_rest = var_idents(vars.iter().filter(|v| !next_vars.contains(v)));
+            let right_rest_names = next_vars
+                .iter()
+                .filter(|v| !vars.contains(v))
+                .cloned()
+                .collect::<Vec<_>>();
+            let right_rest = var_idents(&right_rest_names);
+            stream = quote! {
+                #stream
+                    .map(#root::q!(|(#(#left,)*)| ((#(#shared,)*), (#(#left_rest,)*))))
+                    .join(#next.map(#root::q!(|(#(#right,)*)| ((#(#shared,)*), (#(#right_rest,)*)))))
+                    .map(#root::q!(|((#(#shared,)*), ((#(#left_rest,)*), (#(#right_rest,)*)))| (#(#left,)* #(#right_rest,)*)))
+            };
+            vars.extend(right_rest_names);
+        }
+    }
+
+    for atom in &rule.sources {
+        let Atom::NegRelation(_, e) = atom else {
+            continue;
+        };
+        let (negated, negated_vars) = gen_atom(e, relation_var, root);
+        let all = var_idents(&vars);
+        let key = var_idents(&negated_vars);
+        let rest = var_idents(vars.iter().filter(|v| !negated_vars.contains(v)));
+        stream = quote! {
+            #stream
+                .map(#root::q!(|(#(#all,)*)| ((#(#key,)*), (#(#rest,)*))))
+                .anti_join(#negated)
+                .map(#root::q!(|((#(#key,)*), (#(#rest,)*))| (#(#all,)*)))
+        };
+    }
+
+    let all = var_idents(&vars);
+    let conditions = rule
+        .sources
+        .iter()
+        .filter_map(|atom| match atom {
+            Atom::Predicate(p) => {
+                let mut lookup = |ident: &Spanned<Ident>| -> syn::Expr {
+                    let var = syn::Ident::new(&ident.name, get_span(ident.span));
+                    syn::parse_quote!(::std::clone::Clone::clone(&#var))
+                };
+                let l = gen_value_expr(&p.left, &mut lookup, functions, get_span);
+                let r = gen_value_expr(&p.right, &mut lookup, functions, get_span);
+                Some(gen_comparison(&l, &p.op, &r, get_span(p.span)))
+            }
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    if !conditions.is_empty() {
+        stream = quote! {
+            #stream.filter_map(#root::q!(|(#(#all,)*)| if #(#conditions)&&* {
+                Some((#(#all,)*))
+            } else {
+                None
+            }))
+        };
+    }
+
+    // The expressions in the head, followed by the member to send to, if any.
+    let member = match destination {
+        Destination::Member(_, member) => Some(*member),
+        Destination::Local | Destination::Location(_) => None,
+    };
+    let mut use_counts = HashMap::<&str, usize>::new();
+    for field in &rule.target.fields {
+        for ident in field.idents() {
+            *use_counts.entry(ident.name.as_str()).or_default() += 1;
+        }
+    }
+    for ident in member.iter().flat_map(|m| m.idents()) {
+        *use_counts.entry(ident.name.as_str()).or_default() += 1;
+    }
+    // Variables which are not used in the head are ignored, rather than bound.
+    let head_pattern = vars
+        .iter()
+        .map(|v| {
+            if use_counts.contains_key(v.as_str()) {
+                let var = syn::Ident::new(v, Span::call_site());
+                quote!(#var)
+            } else {
+                quote!(_)
+            }
+        })
+        .collect::<Vec<_>>();
+    let mut uses = HashMap::<&str, usize>::new();
+    let mut lookup = |ident: &Spanned<Ident>| -> syn::Expr {
+        let var = syn::Ident::new(&ident.name, get_span(ident.span));
+        let used = uses
+            .entry(use_counts.get_key_value(ident.name.as_str()).unwrap().0)
+            .or_default();
+        *used += 1;
+        if *used < use_counts[ident.name.as_str()] {
+            syn::parse_quote!(::std::clone::Clone::clone(&#var))
+        } else {
+            syn::parse_quote!(#var)
+        }
+    };
+
+    // Expressions are generated in the order they are evaluated, so that only the last use of
+    // each variable moves it.
+    let mut keys = Vec::<syn::Expr>::new();
+    let member = member.map(|member| {
+        keys.push(gen_value_expr(member, &mut lookup, functions, get_span));
+        0
+    });
+    // For each column, if it is an aggregation and its index among the keys or aggregations.
+    let mut columns = Vec::<(bool, usize)>::new();
+    let mut num_aggregations = 0;
+    for field in &rule.target.fields {
+        match &field.value {
+            TargetExpr::Expr(expr) => {
+                columns.push((false, keys.len()));
+                keys.push(gen_value_expr(expr, &mut lookup, functions, get_span));
+            }
+            TargetExpr::Aggregation(_) => {
+                columns.push((true, num_aggregations));
+                num_aggregations += 1;
+            }
+            TargetExpr::Index(..) => unreachable!("checked in `check_supported`"),
+        }
+    }
+    let mut aggregations = Vec::<(&Aggregation, syn::Expr)>::new();
+    for field in &rule.target.fields {
+        if let TargetExpr::Aggregation(aggregation) = &field.value {
+            let value = match aggregation {
+                Aggregation::Min(_, _, a, _)
+                | Aggregation::Max(_, _, a, _)
+                | Aggregation::Sum(_, _, a, _)
+                | Aggregation::Choose(_, _, a, _) => {
+                    gen_value_expr(a, &mut lookup, functions, get_span)
+                }
+                _ => syn::parse_quote!(()),
+            };
+            aggregations.push((aggregation, value));
+        }
+    }
+
+    if aggregations.is_empty() {
+        let fields = columns.iter().map(|&(_, i)| &keys[i]);
+        let row = match member {
+            Some(m) => {
+                let member = &keys[m];
+                quote!((#member, (#(#fields,)*)))
+            }
+            None => quote!((#(#fields,)*)),
+        };
+        return quote!(#stream.map(#root::q!(|(#(#head_pattern,)*)| #row)));
+    }
+
+    let agg_values = aggregations.iter().map(|(_, value)| value);
+    let initial = aggregations.iter().map(|_| quote!(None));
+    let acc_type = aggregations.iter().map(|_| quote!(Option<_>));
+    let val_type = aggregations.iter().map(|_| quote!(_));
+    // Counts do not read their value.
+    let val = if aggregations
+        .iter()
+        .all(|(aggregation, _)| matches!(aggregation, Aggregation::Count(..)))
+    {
+        format_ident!("_val")
+    } else {
+        format_ident!("val")
+    };
+    let updates = aggregations
+        .iter()
+        .enumerate()
+        .map(|(i, (aggregation, _))| {
+            let i = syn::Index::from(i);
+            let (init, combine): (syn::Expr, syn::Expr) = match aggregation {
+                Aggregation::Min(..) => (
+                    syn::parse_quote!(val.#i),
+                    syn::parse_quote!(std::cmp::min(prev, val.#i)),
+                ),
+                Aggregation::Max(..) => (
+                    syn::parse_quote!(val.#i),
+                    syn::parse_quote!(std::cmp::max(prev, val.#i)),
+                ),
+                Aggregation::Sum(..) => {
+                    (syn::parse_quote!(val.#i), syn::parse_quote!(prev + val.#i))
+                }
+                Aggregation::Count(..) => (syn::parse_quote!(1), syn::parse_quote!(prev + 1)),
+                // choose = select any 1 element from the relation, by default the 1st.
+                Aggregation::Choose(..) => (syn::parse_quote!(val.#i), syn::parse_quote!(prev)),
+                Aggregation::CountUnique(..) | Aggregation::CollectVec(..) => {
+                    unreachable!("checked in `check_supported`")
+                }
+            };
+            quote! {
+                acc.#i = Some(match acc.#i.take() {
+                    None => #init,
+                    Some(prev) => #combine,
+                });
+            }
+        });
+
+    let key_vars = (0..keys.len())
+        .map(|i| format_ident!("g{}", i))
+        .collect::<Vec<_>>();
+    let agg_vars = (0..aggregations.len())
+        .map(|i| format_ident!("a{}", i))
+        .collect::<Vec<_>>();
+    let fields = columns.iter().map(|&(is_agg, i)| -> syn::Expr {
+        if is_agg {
+            let a = &agg_vars[i];
+            syn::parse_quote!(#a.unwrap())
+        } else {
+            let g = &key_vars[i];
+            syn::parse_quote!(#g)
+        }
+    });
+    let row = match member {
+        Some(m) => {
+            let member = &key_vars[m];
+            quote!((#member, (#(#fields,)*)))
+        }
+        None => quote!((#(#fields,)*)),
+    };
+
+    let mut rows = quote! {
+        #stream.map(#root::q!(|(#(#head_pattern,)*)| ((#(#keys,)*), (#(#agg_values,)*))))
+    };
+    if let Some(tick) = persist_rows {
+        rows = quote! {
+            {
+                let (__rows_complete, __rows_previous) = #tick.cycle::<#root::Stream<_, _, _, #root::NoOrder>>();
+                let __rows = #rows.union(__rows_previous);
+                __rows_complete.complete_next_tick(__rows.clone());
+                __rows
+            }
+        };
+    }
+
+    quote! {
+        #rows
+            .fold_keyed_commutative(
+                #root::q!(|| (#(#initial,)*)),
+                #root::q!(|acc: &mut (#(#acc_type,)*), #val: (#(#val_type,)*)| {
+                    #(#updates)*
+                })
+            )
+            .map(#root::q!(|((#(#key_vars,)*), (#(#agg_vars,)*))| #row))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use quote::quote;
+    use syn::parse_quote;
+
+    use super::gen_hydro_program;
+
+    macro_rules! test_snapshots {
+        ($program:literal) => {
+            let program = gen_hydro_program(parse_quote!($program), quote!(hydro_lang)).unwrap();
+
+            let file: syn::File = parse_quote! {
+                fn main() {
+                    #program
+                }
+            };
+            insta::with_settings!({snapshot_suffix => "hydro"}, {
+                insta::assert_snapshot!(prettyplease::unparse(&file));
+            });
+        };
+    }
+
+    #[test]
+    fn minimal_program() {
+        test_snapshots!(
+            r#"
+            .process p `p`
+            .input input@p `input`
+            .output out `for_each(q!(|v| println!("{:?}", v)))`
+
+            out(y, x) :- input(x, y).
+            "#
+        );
+    }
+
+    #[test]
+    fn transitive_closure() {
+        test_snapshots!(
+            r#"
+            .process p `p`
+            .input edges@p `edges`
+            .output path `for_each(q!(|v| println!("{:?}", v)))`
+
+            path(x, y) :- edges(x, y).
+            path(x, z) :- path(x, y), edges(y, z).
+            "#
+        );
+    }
+
+    #[test]
+    fn send_aggregates_to_cluster_members() {
+        test_snapshots!(
+            r#"
+            .process leader `leader`
+            .cluster workers `workers`
+            .input scores@leader `scores`
+            .output totals `for_each(q!(|v| println!("{:?}", v)))`
+            .persist best
+
+            best(k, max(v)) :- scores(k, v), !banned(k).
+            banned(k) :- scores(k, v), ( v < 0 ).
+            totals@workers[k](k, v) :~ best(k, v).
+            "#
+        );
+    }
+}
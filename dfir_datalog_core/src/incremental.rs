@@ -0,0 +1,109 @@
+use std::collections::{BTreeSet, HashSet};
+
+use dfir_lang::diagnostic::{Diagnostic, Level};
+use proc_macro2::Span;
+use rust_sitter::Spanned;
+
+use crate::MAGIC_RELATIONS;
+use crate::grammar::datalog::{Atom, Rule, RuleType, TargetExpr};
+use crate::stratify::Stratification;
+
+/// Computes the relations of an `.incremental` program which only grow across ticks, so that they
+/// can be maintained from the rows added in each tick instead of being recomputed: persisted and
+/// static relations, and relations whose rules only join, filter and project growing relations
+/// within the same tick. Every other relation may shrink between ticks.
+///
+/// `sources` are the relations which receive rows from `.input` or `.async` declarations. Rows
+/// are never retracted through recursion, so recursive relations must grow.
+pub fn growing_relations(
+    rules: &[&Spanned<Rule>],
+    sources: &HashSet<String>,
+    statics: &HashSet<String>,
+    persists: &HashSet<String>,
+    stratification: &Stratification,
+    diagnostics: &mut Vec<Diagnostic>,
+    get_span: &impl Fn((usize, usize)) -> Span,
+) -> BTreeSet<String> {
+    let mut growing = rules
+        .iter()
+        .map(|rule| rule.target.name.name.clone())
+        .chain(statics.iter().cloned())
+        .filter(|relation| !sources.contains(relation))
+        .chain(persists.iter().cloned())
+        .collect::<BTreeSet<_>>();
+
+    for rule in rules {
+        let target = &rule.target.name.name;
+        if !persists.contains(target) && !is_monotone(rule) {
+            growing.remove(target);
+        }
+    }
+    for target in stratification.recursive_aggregations.keys() {
+        growing.remove(target);
+    }
+
+    loop {
+        let shrinking = rules.iter().find(|rule| {
+            let target = &rule.target.name.name;
+            growing.contains(target)
+                && !persists.contains(target)
+                && rule.sources.iter().any(|atom| match atom {
+                    Atom::PosRelation(e) => {
+                        !MAGIC_RELATIONS.contains(&e.name.name.as_str())
+                            && !growing.contains(&e.name.name)
+                    }
+                    Atom::NegRelation(..) | Atom::Predicate(_) => false,
+                })
+        });
+        let Some(rule) = shrinking else {
+            break;
+        };
+        growing.remove(&rule.target.name.name);
+    }
+
+    let mut reported = HashSet::new();
+    for rule in rules {
+        let target = &rule.target.name.name;
+        let recursive = matches!(rule.rule_type.value, RuleType::Sync(_))
+            && rule.sources.iter().any(|atom| match atom {
+                Atom::PosRelation(e) => stratification.is_recursive(target, &e.name.name),
+                Atom::NegRelation(..) | Atom::Predicate(_) => false,
+            });
+        if !recursive || growing.contains(target) || !reported.insert(target) {
+            continue;
+        }
+
+        let message = if stratification.recursive_aggregations.contains_key(target) {
+            format!(
+                "`{}` is aggregated through recursion, which is not supported in incremental mode",
+                target
+            )
+        } else {
+            format!(
+                "`{}` is recursive, so in incremental mode it may only be derived from persisted or static relations without negation or aggregation",
+                target
+            )
+        };
+        diagnostics.push(Diagnostic::spanned(
+            get_span(rule.target.name.span),
+            Level::Error,
+            message,
+        ));
+    }
+
+    growing
+}
+
+/// If the results of `rule` can only grow as the relations it reads grow.
+fn is_monotone(rule: &Rule) -> bool {
+    matches!(rule.rule_type.value, RuleType::Sync(_))
+        && rule
+            .sources
+            .iter()
+            .all(|atom| !matches!(atom, Atom::NegRelation(..)))
+        && rule
+            .target
+            .fields
+            .iter()
+            .all(|field| matches!(field.value, TargetExpr::Expr(_)))
+}
@@ -8,14 +8,15 @@ use proc_macro2::Span;
 use rust_sitter::Spanned;
 use syn::{parse_quote, parse_quote_spanned};
 
-use crate::grammar::datalog::{BoolExpr, BoolOp, ExtractExpr, InputRelationExpr, IntExpr};
+use crate::grammar::datalog::{BoolExpr, ExtractExpr, InputRelationExpr, IntExpr};
 use crate::util::{Counter, repeat_tuple};
 
 /// Captures the tree of joins used to compute contributions from a single rule.
 pub enum JoinPlan<'a> {
     /// A single relation without any joins, leaves of the tree.
-    /// Second element is whether this is a persisted relation.
-    Source(&'a Spanned<InputRelationExpr>, bool),
+    /// Second element is whether this is a persisted relation, third is whether to read the
+    /// intermediate rows of a relation aggregated through recursion.
+    Source(&'a Spanned<InputRelationExpr>, bool, bool),
     /// A join between two subtrees.
     Join(Box<JoinPlan<'a>>, Box<JoinPlan<'a>>),
     AntiJoin(Box<JoinPlan<'a>>, Box<JoinPlan<'a>>),
@@ -167,6 +168,7 @@ fn build_local_constraint_conditions(constraints: &BTreeMap<String, Vec<usize>>)
 fn gen_predicate_value_expr(
     expr: &IntExpr,
     variable_mapping: &BTreeMap<String, usize>,
+    functions: &HashMap<String, syn::Expr>,
     diagnostics: &mut Vec<Diagnostic>,
     get_span: &dyn Fn((usize, usize)) -> Span,
 ) -> syn::Expr {
@@ -185,6 +187,7 @@ fn gen_predicate_value_expr(
                 parse_quote!(())
             }
         },
+        functions,
         get_span,
     )
 }
@@ -328,6 +331,7 @@ fn process_extract(
 }
 
 /// Generates a DFIR pipeline that computes the output to a given [`JoinPlan`].
+#[expect(clippy::too_many_arguments, reason = "internal code")]
 pub fn expand_join_plan(
     // The plan we are converting to a DFIR pipeline.
     plan: &JoinPlan,
@@ -336,19 +340,26 @@ pub fn expand_join_plan(
     tee_counter: &mut HashMap<String, Counter>,
     next_join_idx: &mut Counter,
     rule_span: (usize, usize),
+    functions: &HashMap<String, syn::Expr>,
     diagnostics: &mut Vec<Diagnostic>,
     get_span: &impl Fn((usize, usize)) -> Span,
 ) -> IntermediateJoinNode {
     match plan {
-        JoinPlan::Source(target, persisted) => {
+        JoinPlan::Source(target, persisted, intermediate) => {
+            let relation_name = if *intermediate {
+                format!("{}__rec", target.name.name)
+            } else {
+                target.name.name.clone()
+            };
+
             // Because this is a node corresponding to some Datalog relation, we need to tee from it.
             let tee_index = tee_counter
-                .entry(target.name.name.clone())
+                .entry(relation_name.clone())
                 .or_insert_with(|| 0..)
                 .next()
                 .expect("Out of tee indices");
 
-            let relation_node = syn::Ident::new(&target.name.name, get_span(target.name.span));
+            let relation_node = syn::Ident::new(&relation_name, get_span(target.name.span));
             let relation_idx = syn::LitInt::new(&tee_index.to_string(), Span::call_site());
 
             let source_node = syn::Ident::new(
@@ -416,6 +427,7 @@ pub fn expand_join_plan(
                 tee_counter,
                 next_join_idx,
                 rule_span,
+                functions,
                 diagnostics,
                 get_span,
             );
@@ -425,6 +437,7 @@ pub fn expand_join_plan(
                 tee_counter,
                 next_join_idx,
                 rule_span,
+                functions,
                 diagnostics,
                 get_span,
             );
@@ -636,6 +649,7 @@ pub fn expand_join_plan(
                 tee_counter,
                 next_join_idx,
                 rule_span,
+                functions,
                 diagnostics,
                 get_span,
             );
@@ -646,19 +660,22 @@ pub fn expand_join_plan(
             let conditions = predicates
                 .iter()
                 .map(|p| {
-                    let l =
-                        gen_predicate_value_expr(&p.left, variable_mapping, diagnostics, get_span);
-                    let r =
-                        gen_predicate_value_expr(&p.right, variable_mapping, diagnostics, get_span);
-
-                    match &p.op {
-                        BoolOp::Lt(_) => parse_quote_spanned!(get_span(p.span)=> #l < #r),
-                        BoolOp::LtEq(_) => parse_quote_spanned!(get_span(p.span)=> #l <= #r),
-                        BoolOp::Gt(_) => parse_quote_spanned!(get_span(p.span)=> #l > #r),
-                        BoolOp::GtEq(_) => parse_quote_spanned!(get_span(p.span)=> #l >= #r),
-                        BoolOp::Eq(_) => parse_quote_spanned!(get_span(p.span)=> #l == #r),
-                        BoolOp::Neq(_) => parse_quote_spanned!(get_span(p.span)=> #l != #r),
-                    }
+                    let l = gen_predicate_value_expr(
+                        &p.left,
+                        variable_mapping,
+                        functions,
+                        diagnostics,
+                        get_span,
+                    );
+                    let r = gen_predicate_value_expr(
+                        &p.right,
+                        variable_mapping,
+                        functions,
+                        diagnostics,
+                        get_span,
+                    );
+
+                    crate::gen_comparison(&l, &p.op, &r, get_span(p.span))
                 })
                 .reduce(|a: syn::Expr, b| parse_quote!(#a && #b))
                 .unwrap();
@@ -700,6 +717,7 @@ pub fn expand_join_plan(
                 tee_counter,
                 next_join_idx,
                 rule_span,
+                functions,
                 diagnostics,
                 get_span,
             );
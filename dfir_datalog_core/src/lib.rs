@@ -1,4 +1,4 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::ops::Deref;
 
 pub use dfir_lang::diagnostic;
@@ -12,17 +12,34 @@ use rust_sitter::errors::{ParseError, ParseErrorReason};
 use syn::{Token, parse_quote, parse_quote_spanned};
 
 mod grammar;
+mod hydro;
+mod incremental;
 mod join_plan;
+mod stratify;
 mod util;
 
 use grammar::datalog::{
-    Aggregation, Atom, Declaration, Ident, IntExpr, Program, Rule, RuleType, TargetExpr,
+    Aggregation, Atom, BoolOp, Declaration, Ident, IntExpr, Program, Rule, RuleType, TargetExpr,
 };
+pub use hydro::gen_hydro_program;
 use join_plan::{IntermediateJoinNode, JoinPlan};
+use stratify::Stratification;
 use util::{Counter, repeat_tuple};
 
 static MAGIC_RELATIONS: [&str; 1] = ["less_than"];
 
+/// Functions which may be called in expressions without a `.function` declaration, and the number
+/// of arguments they take.
+static BUILTIN_FUNCTIONS: [(&str, usize); 7] = [
+    ("concat", 2),
+    ("contains", 2),
+    ("strlen", 1),
+    ("substr", 3),
+    ("to_lower", 1),
+    ("to_string", 1),
+    ("to_upper", 1),
+];
+
 pub fn parse_pipeline(
     code_str: &rust_sitter::Spanned<String>,
     get_span: &impl Fn((usize, usize)) -> Span,
@@ -53,7 +70,26 @@ pub fn parse_static(
         })
 }
 
-pub fn gen_dfir_graph(literal: proc_macro2::Literal) -> Result<DfirGraph, Vec<Diagnostic>> {
+pub fn parse_function(
+    code_str: &rust_sitter::Spanned<String>,
+    get_span: &impl Fn((usize, usize)) -> Span,
+) -> Result<syn::Expr, Vec<Diagnostic>> {
+    syn::LitStr::new(&code_str.value, get_span(code_str.span))
+        .parse()
+        .map_err(|err| {
+            vec![Diagnostic {
+                span: err.span(),
+                level: Level::Error,
+                message: format!("Failed to parse function expression: {}", err),
+            }]
+        })
+}
+
+/// Returns a function which maps spans within the Datalog code in a raw string literal to spans
+/// within the literal.
+fn literal_span_getter(
+    literal: &proc_macro2::Literal,
+) -> Result<impl Fn((usize, usize)) -> Span + '_, Vec<Diagnostic>> {
     let offset = {
         // This includes the quotes, i.e. 'r#"my test"#' or '"hello\nworld"'.
         let source_str = literal.to_string();
@@ -71,15 +107,79 @@ pub fn gen_dfir_graph(literal: proc_macro2::Literal) -> Result<DfirGraph, Vec<Di
         2 + hashes
     };
 
-    let get_span = |(start, end): (usize, usize)| {
+    Ok(move |(start, end): (usize, usize)| {
         let subspan = literal.subspan(start + offset..end + offset);
         subspan.unwrap_or(Span::call_site())
-    };
+    })
+}
 
+/// Parses a raw string literal of Datalog code.
+fn parse_program(
+    literal: &proc_macro2::Literal,
+    get_span: &impl Fn((usize, usize)) -> Span,
+) -> Result<Program, Vec<Diagnostic>> {
     let str_node: syn::LitStr = parse_quote!(#literal);
     let actual_str = str_node.value();
-    let program: Program =
-        grammar::datalog::parse(&actual_str).map_err(|e| handle_errors(e, &get_span))?;
+    grammar::datalog::parse(&actual_str).map_err(|e| handle_errors(e, get_span))
+}
+
+/// Checks that every function called in `rules` is either declared with `.function` or builtin,
+/// and that builtin functions are given the right number of arguments.
+fn check_calls(
+    rules: &[&rust_sitter::Spanned<Rule>],
+    functions: &HashMap<String, syn::Expr>,
+    diagnostics: &mut Vec<Diagnostic>,
+    get_span: &impl Fn((usize, usize)) -> Span,
+) {
+    for rule in rules {
+        let mut calls = vec![];
+        for field in rule
+            .target
+            .fields
+            .iter()
+            .chain(rule.target.at_node.iter().map(|n| &n.node))
+        {
+            calls.extend(field.calls());
+        }
+        for atom in &rule.sources {
+            if let Atom::Predicate(p) = atom {
+                calls.extend(p.left.calls());
+                calls.extend(p.right.calls());
+            }
+        }
+
+        for (name, num_args) in calls {
+            if functions.contains_key(&name.name) {
+                continue;
+            }
+            match BUILTIN_FUNCTIONS.iter().find(|(f, _)| *f == name.name) {
+                None => diagnostics.push(Diagnostic::spanned(
+                    get_span(name.span),
+                    Level::Error,
+                    format!(
+                        "Unknown function `{}`, user functions must be declared with `.function`",
+                        name.name
+                    ),
+                )),
+                Some((_, expected)) if *expected != num_args => {
+                    diagnostics.push(Diagnostic::spanned(
+                        get_span(name.span),
+                        Level::Error,
+                        format!(
+                            "Function `{}` takes {} argument(s) but {} were given",
+                            name.name, expected, num_args
+                        ),
+                    ))
+                }
+                Some(_) => {}
+            }
+        }
+    }
+}
+
+pub fn gen_dfir_graph(literal: proc_macro2::Literal) -> Result<DfirGraph, Vec<Diagnostic>> {
+    let get_span = literal_span_getter(&literal)?;
+    let program = parse_program(&literal, &get_span)?;
 
     let mut inputs = Vec::new();
     let mut outputs = Vec::new();
@@ -87,11 +187,17 @@ pub fn gen_dfir_graph(literal: proc_macro2::Literal) -> Result<DfirGraph, Vec<Di
     let mut asyncs = Vec::new();
     let mut rules = Vec::new();
     let mut statics = Vec::new();
+    let mut functions = HashMap::new();
+    let mut incremental = false;
+    let mut diagnostics = Vec::new();
 
     for stmt in &program.rules {
         match stmt {
-            Declaration::Input(_, ident, hf_code) => {
+            Declaration::Input(_, ident, at_location, hf_code) => {
                 assert!(!MAGIC_RELATIONS.contains(&ident.name.as_str()));
+                if let Some(at_location) = at_location {
+                    diagnostics.push(hydro_only(&at_location.location, &get_span));
+                }
                 inputs.push((ident, hf_code))
             }
             Declaration::Output(_, ident, hf_code) => {
@@ -113,9 +219,60 @@ pub fn gen_dfir_graph(literal: proc_macro2::Literal) -> Result<DfirGraph, Vec<Di
                 assert!(!MAGIC_RELATIONS.contains(&ident.name.as_str()));
                 statics.push((ident, hf_code));
             }
+            Declaration::Function(_, ident, hf_code) => {
+                let function = parse_function(&hf_code.code, &get_span)?;
+                if functions.insert(ident.name.clone(), function).is_some() {
+                    diagnostics.push(Diagnostic::spanned(
+                        get_span(ident.span),
+                        Level::Error,
+                        format!("Function `{}` is declared more than once", ident.name),
+                    ));
+                }
+            }
+            Declaration::Process(_, ident, _) | Declaration::Cluster(_, ident, _) => {
+                diagnostics.push(hydro_only(ident, &get_span));
+            }
+            Declaration::Incremental(_) => incremental = true,
         }
     }
 
+    check_calls(&rules, &functions, &mut diagnostics, &get_span);
+
+    let stratification = stratify::stratify(&rules, &persists, &mut diagnostics, &get_span);
+    if !diagnostics.is_empty() {
+        return Err(diagnostics);
+    }
+
+    // In incremental mode, relations which only grow are persisted, so that each tick only
+    // propagates the rows added to them.
+    let growing = if incremental {
+        let sources = inputs
+            .iter()
+            .map(|(ident, _)| ident.name.clone())
+            .chain(asyncs.iter().map(|(ident, _, _)| ident.name.clone()))
+            .collect();
+        let statics = statics
+            .iter()
+            .map(|(ident, _)| ident.name.clone())
+            .collect();
+        let growing = incremental::growing_relations(
+            &rules,
+            &sources,
+            &statics,
+            &persists,
+            &stratification,
+            &mut diagnostics,
+            &get_span,
+        );
+        if !diagnostics.is_empty() {
+            return Err(diagnostics);
+        }
+        persists.extend(growing.iter().cloned());
+        growing
+    } else {
+        BTreeSet::new()
+    };
+
     let mut flat_graph_builder = FlatGraphBuilder::new();
     let mut tee_counter = HashMap::new();
     let mut union_counter = HashMap::new();
@@ -123,12 +280,18 @@ pub fn gen_dfir_graph(literal: proc_macro2::Literal) -> Result<DfirGraph, Vec<Di
     let mut created_rules = HashSet::new();
     for decl in &program.rules {
         let target_ident = match decl {
-            Declaration::Input(_, ident, _) => ident.clone(),
+            Declaration::Input(_, ident, _, _) => ident.clone(),
             Declaration::Output(_, ident, _) => ident.clone(),
             Declaration::Persist(_, ident) => ident.clone(),
             Declaration::Async(_, ident, _, _) => ident.clone(),
             Declaration::Rule(rule) => rule.target.name.clone(),
             Declaration::Static(_, ident, _) => ident.clone(),
+            Declaration::Function(..)
+            | Declaration::Process(..)
+            | Declaration::Cluster(..)
+            | Declaration::Incremental(..) => {
+                continue;
+            }
         };
 
         if !created_rules.contains(&target_ident.value) {
@@ -139,7 +302,52 @@ pub fn gen_dfir_graph(literal: proc_macro2::Literal) -> Result<DfirGraph, Vec<Di
             );
             let read_name = syn::Ident::new(&target_ident.name, get_span(target_ident.span));
 
-            if persists.contains(&target_ident.value.name) {
+            if let Some(aggregation) = stratification
+                .recursive_aggregations
+                .get(&target_ident.name)
+            {
+                // Only rows which improve on the current aggregate of their group are propagated
+                // through the recursion (which reads `__rec`), the final aggregates are computed
+                // once it reaches a fixpoint.
+                let rec_name = syn::Ident::new(
+                    &format!("{}__rec", target_ident.name),
+                    get_span(target_ident.span),
+                );
+                let row_type =
+                    repeat_tuple::<syn::Type, syn::Type>(|| parse_quote!(_), aggregation.arity);
+                let key_type =
+                    repeat_tuple::<syn::Type, syn::Type>(|| parse_quote!(_), aggregation.arity - 1);
+                let key_indices = (0..aggregation.arity)
+                    .filter(|&i| i != aggregation.column)
+                    .map(syn::Index::from)
+                    .collect::<Vec<_>>();
+                let agg_index = syn::Index::from(aggregation.column);
+                let mut after_group_lookups = (0..aggregation.arity - 1)
+                    .map(|i| -> syn::Expr {
+                        let i = syn::Index::from(i);
+                        parse_quote!(g.#i)
+                    })
+                    .collect::<Vec<_>>();
+                after_group_lookups.insert(aggregation.column, parse_quote!(a));
+                let (lattice, improves): (syn::Path, syn::Expr) = if aggregation.is_max {
+                    (
+                        parse_quote!(dfir_rs::lattices::Max),
+                        parse_quote!(*acc < val),
+                    )
+                } else {
+                    (
+                        parse_quote!(dfir_rs::lattices::Min),
+                        parse_quote!(val < *acc),
+                    )
+                };
+
+                flat_graph_builder
+                    .add_statement(parse_quote_spanned!{get_span(target_ident.span)=> #insert_name = union() -> state_by::<'tick, dfir_rs::lattices::map_union::MapUnionHashMap<_, #lattice<_>>>(|row: #row_type| dfir_rs::lattices::map_union::MapUnionSingletonMap::new_from(((#(row.#key_indices, )*), #lattice::new(row.#agg_index))), std::default::Default::default); });
+                flat_graph_builder
+                    .add_statement(parse_quote_spanned!{get_span(target_ident.span)=> #rec_name = #insert_name -> tee(); });
+                flat_graph_builder
+                    .add_statement(parse_quote_spanned!{get_span(target_ident.span)=> #read_name = #rec_name -> map(|row: #row_type| ((#(row.#key_indices, )*), row.#agg_index)) -> reduce_keyed::<'tick>(|acc: &mut _, val| if #improves { *acc = val; }) -> map(|(g, a): (#key_type, _)| (#(#after_group_lookups, )*)) -> tee(); });
+            } else if persists.contains(&target_ident.value.name) {
                 // read outputs the *new* values for this tick
                 flat_graph_builder
                     .add_statement(parse_quote_spanned!{get_span(target_ident.span)=> #insert_name = union() -> unique::<'tick>(); });
@@ -188,7 +396,48 @@ pub fn gen_dfir_graph(literal: proc_macro2::Literal) -> Result<DfirGraph, Vec<Di
         let target_ident = syn::Ident::new(&target.name, get_span(target.span));
 
         let output_pipeline: Pipeline = parse_pipeline(&hf_code.code, &get_span)?;
-        let output_pipeline = if persists.contains(&target.name) {
+        if incremental && !growing.contains(&target.name) {
+            // The relation may shrink, so it is compared with its value in the previous tick.
+            let span = get_span(target.span);
+            let current = syn::Ident::new(&format!("{}__current", target.name), span);
+            let previous = syn::Ident::new(&format!("{}__previous", target.name), span);
+            let inserted = syn::Ident::new(&format!("{}__inserted", target.name), span);
+            let retracted = syn::Ident::new(&format!("{}__retracted", target.name), span);
+            let changes = syn::Ident::new(&format!("{}__changes", target.name), span);
+            flat_graph_builder.add_statement(parse_quote_spanned! {span=>
+                #current = #target_ident [#my_tee_index_lit] -> tee();
+            });
+            flat_graph_builder.add_statement(parse_quote_spanned! {span=>
+                #previous = #current -> defer_tick() -> tee();
+            });
+            flat_graph_builder.add_statement(parse_quote_spanned! {span=>
+                #changes = union() -> #output_pipeline;
+            });
+            flat_graph_builder.add_statement(parse_quote_spanned! {span=>
+                #inserted = difference::<'tick, 'tick>() -> map(|row| (row, 1_isize)) -> #changes;
+            });
+            flat_graph_builder.add_statement(parse_quote_spanned! {span=>
+                #retracted = difference::<'tick, 'tick>() -> map(|row| (row, -1_isize)) -> #changes;
+            });
+            flat_graph_builder.add_statement(parse_quote_spanned! {span=>
+                #current -> [pos] #inserted;
+            });
+            flat_graph_builder.add_statement(parse_quote_spanned! {span=>
+                #previous -> [neg] #inserted;
+            });
+            flat_graph_builder.add_statement(parse_quote_spanned! {span=>
+                #previous -> [pos] #retracted;
+            });
+            flat_graph_builder.add_statement(parse_quote_spanned! {span=>
+                #current -> [neg] #retracted;
+            });
+            continue;
+        }
+
+        let output_pipeline = if incremental {
+            // Only the rows added to the relation in this tick are read.
+            parse_quote_spanned! {get_span(target.span)=> map(|row| (row, 1_isize)) -> #output_pipeline}
+        } else if persists.contains(&target.name) {
             parse_quote_spanned! {get_span(target.span)=> persist::<'static>() -> #output_pipeline}
         } else {
             output_pipeline
@@ -239,15 +488,23 @@ pub fn gen_dfir_graph(literal: proc_macro2::Literal) -> Result<DfirGraph, Vec<Di
 
         let static_expression: syn::Expr = parse_static(&hf_code.code, &get_span)?;
 
-        flat_graph_builder.add_statement(parse_quote_spanned! {get_span(target.span)=>
-            source_iter(#static_expression) -> persist::<'static>() -> [#my_union_index_lit] #name;
-        });
+        if incremental && persists.contains(&target.name) {
+            // The relation is persisted, so its rows only need to be inserted once.
+            flat_graph_builder.add_statement(parse_quote_spanned! {get_span(target.span)=>
+                source_iter(#static_expression) -> [#my_union_index_lit] #name;
+            });
+        } else {
+            flat_graph_builder.add_statement(parse_quote_spanned! {get_span(target.span)=>
+                source_iter(#static_expression) -> persist::<'static>() -> [#my_union_index_lit] #name;
+            });
+        }
     }
 
     let mut next_join_idx = 0..;
-    let mut diagnostics = Vec::new();
     for rule in rules {
-        let plan = compute_join_plan(&rule.sources, &persists);
+        let plan = compute_join_plan(&rule.sources, &persists, &|source| {
+            stratification.reads_intermediate(&rule.target.name.name, source)
+        });
         generate_rule(
             plan,
             rule,
@@ -256,6 +513,8 @@ pub fn gen_dfir_graph(literal: proc_macro2::Literal) -> Result<DfirGraph, Vec<Di
             &mut union_counter,
             &mut next_join_idx,
             &persists,
+            &stratification,
+            &functions,
             &mut diagnostics,
             &get_span,
         );
@@ -281,6 +540,20 @@ pub fn gen_dfir_graph(literal: proc_macro2::Literal) -> Result<DfirGraph, Vec<Di
     Ok(flat_graph)
 }
 
+fn hydro_only(
+    location: &rust_sitter::Spanned<Ident>,
+    get_span: &impl Fn((usize, usize)) -> Span,
+) -> Diagnostic {
+    Diagnostic::spanned(
+        get_span(location.span),
+        Level::Error,
+        format!(
+            "Location `{}` can only be used when compiling to Hydro",
+            location.name
+        ),
+    )
+}
+
 fn handle_errors(
     errors: Vec<ParseError>,
     get_span: &impl Fn((usize, usize)) -> Span,
@@ -345,6 +618,8 @@ fn generate_rule(
     union_counter: &mut HashMap<String, Counter>,
     next_join_idx: &mut Counter,
     persists: &HashSet<String>,
+    stratification: &Stratification,
+    functions: &HashMap<String, syn::Expr>,
     diagnostics: &mut Vec<Diagnostic>,
     get_span: &impl Fn((usize, usize)) -> Span,
 ) {
@@ -357,6 +632,7 @@ fn generate_rule(
         tee_counter,
         next_join_idx,
         rule.span,
+        functions,
         diagnostics,
         get_span,
     );
@@ -365,6 +641,10 @@ fn generate_rule(
         rule,
         &out_expanded,
         persists.contains(&target.name),
+        stratification
+            .recursive_aggregations
+            .contains_key(&target.name),
+        functions,
         diagnostics,
         get_span,
     );
@@ -393,8 +673,15 @@ fn generate_rule(
             parse_quote_spanned!(get_span(rule.rule_type.span)=> #after_join -> defer_tick() -> [#my_union_index_lit] #target_ident)
         }
         RuleType::Async(_) => {
-            if rule.target.at_node.is_none() {
+            let Some(at_node) = &rule.target.at_node else {
                 panic!("Async rules are only for sending data to other nodes")
+            };
+            if let Some(member) = &at_node.member {
+                diagnostics.push(Diagnostic::spanned(
+                    get_span(member.span),
+                    Level::Error,
+                    "Sending to a cluster member can only be used when compiling to Hydro",
+                ));
             }
 
             let exprs_get_data = rule
@@ -443,14 +730,22 @@ fn generate_rule(
     }));
 }
 
-fn compute_join_plan<'a>(sources: &'a [Atom], persisted_rules: &HashSet<String>) -> JoinPlan<'a> {
+fn compute_join_plan<'a>(
+    sources: &'a [Atom],
+    persisted_rules: &HashSet<String>,
+    reads_intermediate: &dyn Fn(&str) -> bool,
+) -> JoinPlan<'a> {
     // TODO(shadaj): smarter plans
     let mut plan: JoinPlan = sources
         .iter()
         .filter_map(|x| match x {
             Atom::PosRelation(e) => {
                 if !MAGIC_RELATIONS.contains(&e.name.name.as_str()) {
-                    Some(JoinPlan::Source(e, persisted_rules.contains(&e.name.name)))
+                    Some(JoinPlan::Source(
+                        e,
+                        persisted_rules.contains(&e.name.name),
+                        reads_intermediate(&e.name.name),
+                    ))
                 } else {
                     None
                 }
@@ -463,9 +758,11 @@ fn compute_join_plan<'a>(sources: &'a [Atom], persisted_rules: &HashSet<String>)
     plan = sources
         .iter()
         .filter_map(|x| match x {
-            Atom::NegRelation(_, e) => {
-                Some(JoinPlan::Source(e, persisted_rules.contains(&e.name.name)))
-            }
+            Atom::NegRelation(_, e) => Some(JoinPlan::Source(
+                e,
+                persisted_rules.contains(&e.name.name),
+                reads_intermediate(&e.name.name),
+            )),
             _ => None,
         })
         .fold(plan, |a, b| JoinPlan::AntiJoin(Box::new(a), Box::new(b)));
@@ -506,6 +803,7 @@ fn compute_join_plan<'a>(sources: &'a [Atom], persisted_rules: &HashSet<String>)
 pub(crate) fn gen_value_expr(
     expr: &IntExpr,
     lookup_ident: &mut impl FnMut(&rust_sitter::Spanned<Ident>) -> syn::Expr,
+    functions: &HashMap<String, syn::Expr>,
     get_span: &dyn Fn((usize, usize)) -> Span,
 ) -> syn::Expr {
     match expr {
@@ -514,46 +812,125 @@ pub(crate) fn gen_value_expr(
             attrs: Vec::new(),
             lit: syn::Lit::Int(syn::LitInt::new(&i.to_string(), get_span(i.span))),
         }),
+        IntExpr::String(s) => syn::Expr::Lit(syn::ExprLit {
+            attrs: Vec::new(),
+            lit: syn::Lit::Str(syn::LitStr::new(s, get_span(s.span))),
+        }),
         IntExpr::Parenthesized(_, e, _) => {
-            let inner = gen_value_expr(e, lookup_ident, get_span);
+            let inner = gen_value_expr(e, lookup_ident, functions, get_span);
             parse_quote!((#inner))
         }
         IntExpr::Add(l, _, r) => {
-            let l = gen_value_expr(l, lookup_ident, get_span);
-            let r = gen_value_expr(r, lookup_ident, get_span);
+            let l = gen_value_expr(l, lookup_ident, functions, get_span);
+            let r = gen_value_expr(r, lookup_ident, functions, get_span);
             parse_quote!(#l + #r)
         }
         IntExpr::Sub(l, _, r) => {
-            let l = gen_value_expr(l, lookup_ident, get_span);
-            let r = gen_value_expr(r, lookup_ident, get_span);
+            let l = gen_value_expr(l, lookup_ident, functions, get_span);
+            let r = gen_value_expr(r, lookup_ident, functions, get_span);
             parse_quote!(#l - #r)
         }
         IntExpr::Mul(l, _, r) => {
-            let l = gen_value_expr(l, lookup_ident, get_span);
-            let r = gen_value_expr(r, lookup_ident, get_span);
+            let l = gen_value_expr(l, lookup_ident, functions, get_span);
+            let r = gen_value_expr(r, lookup_ident, functions, get_span);
             parse_quote!(#l * #r)
         }
+        IntExpr::Div(l, _, r) => {
+            let l = gen_value_expr(l, lookup_ident, functions, get_span);
+            let r = gen_value_expr(r, lookup_ident, functions, get_span);
+            parse_quote!(#l / #r)
+        }
         IntExpr::Mod(l, _, r) => {
-            let l = gen_value_expr(l, lookup_ident, get_span);
-            let r = gen_value_expr(r, lookup_ident, get_span);
+            let l = gen_value_expr(l, lookup_ident, functions, get_span);
+            let r = gen_value_expr(r, lookup_ident, functions, get_span);
             parse_quote!(#l % #r)
         }
+        IntExpr::Cmp(l, op, r) => {
+            let l = gen_value_expr(l, lookup_ident, functions, get_span);
+            let r = gen_value_expr(r, lookup_ident, functions, get_span);
+            gen_comparison(&l, op, &r, Span::call_site())
+        }
+        IntExpr::Call(name, _, args, _) => {
+            let mut args = args
+                .iter()
+                .map(|arg| gen_value_expr(arg, lookup_ident, functions, get_span))
+                .collect::<Vec<_>>();
+            let span = get_span(name.span);
+            if let Some(function) = functions.get(&name.name) {
+                return parse_quote_spanned!(span=> (#function)(#(#args),*));
+            }
+
+            // Parenthesize compound arguments used as method receivers.
+            for arg in args.iter_mut() {
+                if matches!(arg, syn::Expr::Binary(_)) {
+                    *arg = parse_quote!((#arg));
+                }
+            }
+            match (name.name.as_str(), args.as_slice()) {
+                ("concat", [a, b]) => parse_quote_spanned!(span=> format!("{}{}", #a, #b)),
+                ("contains", [s, pattern]) => {
+                    parse_quote_spanned!(span=> #s.contains(&*#pattern))
+                }
+                ("strlen", [s]) => parse_quote_spanned!(span=> #s.len()),
+                ("substr", [s, start, len]) => {
+                    let start = cast_usize(start);
+                    let len = cast_usize(len);
+                    parse_quote_spanned! {span=>
+                        #s.chars().skip(#start).take(#len).collect::<String>()
+                    }
+                }
+                ("to_lower", [s]) => parse_quote_spanned!(span=> #s.to_lowercase()),
+                ("to_string", [v]) => parse_quote_spanned!(span=> #v.to_string()),
+                ("to_upper", [s]) => parse_quote_spanned!(span=> #s.to_uppercase()),
+                _ => unreachable!("function calls are checked in `gen_dfir_graph`"),
+            }
+        }
+    }
+}
+
+/// Casts `expr` to a `usize`, unless it is an integer literal which can be inferred as one.
+fn cast_usize(expr: &syn::Expr) -> syn::Expr {
+    match expr {
+        syn::Expr::Lit(syn::ExprLit {
+            lit: syn::Lit::Int(_),
+            ..
+        }) => expr.clone(),
+        _ => parse_quote!(#expr as usize),
+    }
+}
+
+pub(crate) fn gen_comparison(l: &syn::Expr, op: &BoolOp, r: &syn::Expr, span: Span) -> syn::Expr {
+    match op {
+        BoolOp::Lt(_) => parse_quote_spanned!(span=> #l < #r),
+        BoolOp::LtEq(_) => parse_quote_spanned!(span=> #l <= #r),
+        BoolOp::Gt(_) => parse_quote_spanned!(span=> #l > #r),
+        BoolOp::GtEq(_) => parse_quote_spanned!(span=> #l >= #r),
+        BoolOp::Eq(_) => parse_quote_spanned!(span=> #l == #r),
+        BoolOp::Neq(_) => parse_quote_spanned!(span=> #l != #r),
     }
 }
 
 fn gen_target_expr(
     expr: &TargetExpr,
     lookup_ident: &mut impl FnMut(&rust_sitter::Spanned<Ident>) -> syn::Expr,
+    functions: &HashMap<String, syn::Expr>,
     get_span: &dyn Fn((usize, usize)) -> Span,
 ) -> syn::Expr {
     match expr {
-        TargetExpr::Expr(expr) => gen_value_expr(expr, lookup_ident, get_span),
+        TargetExpr::Expr(expr) => gen_value_expr(expr, lookup_ident, functions, get_span),
         TargetExpr::Aggregation(Aggregation::Count(_)) => parse_quote!(()),
         TargetExpr::Aggregation(Aggregation::CountUnique(_, _, keys, _))
         | TargetExpr::Aggregation(Aggregation::CollectVec(_, _, keys, _)) => {
             let keys = keys
                 .iter()
-                .map(|k| gen_value_expr(&IntExpr::Ident(k.clone()), lookup_ident, get_span))
+                .map(|k| {
+                    gen_value_expr(
+                        &IntExpr::Ident(k.clone()),
+                        lookup_ident,
+                        functions,
+                        get_span,
+                    )
+                })
                 .collect::<Vec<_>>();
             parse_quote!((#(#keys),*))
         }
@@ -562,7 +939,7 @@ fn gen_target_expr(
             | Aggregation::Max(_, _, a, _)
             | Aggregation::Sum(_, _, a, _)
             | Aggregation::Choose(_, _, a, _),
-        ) => gen_value_expr(&IntExpr::Ident(a.clone()), lookup_ident, get_span),
+        ) => gen_value_expr(a, lookup_ident, functions, get_span),
         TargetExpr::Index(_, _, _) => unreachable!(),
     }
 }
@@ -571,6 +948,8 @@ fn apply_aggregations(
     rule: &Rule,
     out_expanded: &IntermediateJoinNode,
     consumer_is_persist: bool,
+    recursive_aggregation: bool,
+    functions: &HashMap<String, syn::Expr>,
     diagnostics: &mut Vec<Diagnostic>,
     get_span: &impl Fn((usize, usize)) -> Span,
 ) -> Pipeline {
@@ -640,20 +1019,12 @@ fn apply_aggregations(
                         parse_quote!(())
                     }
                 },
+                functions,
                 get_span,
             );
 
             match &field.value {
-                TargetExpr::Expr(_) => {
-                    fold_keyed_exprs.push(expr);
-
-                    let idx = syn::Index::from(group_key_idx);
-                    after_group_lookups.push(parse_quote_spanned!(get_span(field.span)=> g.#idx));
-                    copy_group_key_lookups
-                        .push(parse_quote_spanned!(get_span(field.span)=> g.#idx));
-                    group_key_idx += 1;
-                }
-                TargetExpr::Aggregation(a) => {
+                TargetExpr::Aggregation(a) if !recursive_aggregation => {
                     aggregations.push(a.clone());
                     agg_exprs.push(expr);
 
@@ -679,6 +1050,16 @@ fn apply_aggregations(
                         }
                     }
                 }
+                // Through recursion, `min` and `max` are aggregated by the target relation itself.
+                TargetExpr::Expr(_) | TargetExpr::Aggregation(_) => {
+                    fold_keyed_exprs.push(expr);
+
+                    let idx = syn::Index::from(group_key_idx);
+                    after_group_lookups.push(parse_quote_spanned!(get_span(field.span)=> g.#idx));
+                    copy_group_key_lookups
+                        .push(parse_quote_spanned!(get_span(field.span)=> g.#idx));
+                    group_key_idx += 1;
+                }
                 TargetExpr::Index(_, _, _) => unreachable!(),
             }
         }
@@ -842,6 +1223,24 @@ mod tests {
         );
     }
 
+    #[test]
+    fn incremental() {
+        test_snapshots!(
+            r#"
+            .incremental
+            .input edges `source_stream(edges)`
+            .persist edges
+            .input removed `source_stream(removed)`
+            .output path `for_each(|v| path.send(v).unwrap())`
+            .output live `for_each(|v| live.send(v).unwrap())`
+
+            path(x, y) :- edges(x, y).
+            path(x, z) :- path(x, y), edges(y, z).
+            live(x, y) :- path(x, y), !removed(x).
+            "#
+        );
+    }
+
     #[test]
     fn join_with_self() {
         test_snapshots!(
@@ -1090,6 +1489,33 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_expr_div_cmp_and_calls() {
+        test_snapshots!(
+            r#"
+            .function dist `|a: u32, b: u32| a.abs_diff(b)`
+            .input ints `source_stream(ints)`
+            .output result `for_each(|v| result.send(v).unwrap())`
+
+            result(a / 2, a >= b, dist(a, b), concat(to_string(a), "!")) :- ints(a, b), (strlen(to_string(a + b)) > 1)
+            "#
+        );
+    }
+
+    #[test]
+    fn test_recursive_min() {
+        test_snapshots!(
+            r#"
+            .input edges `source_stream(edges)`
+            .output result `for_each(|v| result.send(v).unwrap())`
+
+            shortest(x, y, min(d)) :- edges(x, y, d)
+            shortest(x, z, min(d1 + d2)) :- shortest(x, y, d1), edges(y, z, d2)
+            result(x, y, d) :- shortest(x, y, d)
+            "#
+        );
+    }
+
     #[test]
     fn test_persist() {
         test_snapshots!(
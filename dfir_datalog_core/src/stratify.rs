@@ -0,0 +1,271 @@
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use dfir_lang::diagnostic::{Diagnostic, Level};
+use proc_macro2::Span;
+use rust_sitter::Spanned;
+
+use crate::MAGIC_RELATIONS;
+use crate::grammar::datalog::{Aggregation, Atom, Rule, RuleType, TargetExpr};
+
+/// A relation which is aggregated with `min` or `max` through recursion.
+///
+/// Within a tick, rows of such a relation are only propagated around the recursion if they improve
+/// on the current aggregate for their group, and the final aggregate is computed once the
+/// recursion reaches a fixpoint.
+pub struct RecursiveAggregation {
+    /// The number of columns in the relation.
+    pub arity: usize,
+    /// The index of the aggregated column.
+    pub column: usize,
+    /// If the aggregation is `max` rather than `min`.
+    pub is_max: bool,
+}
+
+/// The recursive structure of a program, see [`stratify`].
+pub struct Stratification {
+    /// The strongly connected component of the same-tick dependency graph each relation is in.
+    components: HashMap<String, usize>,
+    /// Relations which are aggregated with `min` or `max` through recursion.
+    pub recursive_aggregations: BTreeMap<String, RecursiveAggregation>,
+}
+
+impl Stratification {
+    /// If `target` and `source` depend on each other within a tick.
+    pub fn is_recursive(&self, target: &str, source: &str) -> bool {
+        self.components.contains_key(target)
+            && self.components.get(target) == self.components.get(source)
+    }
+
+    /// If a rule for `target` must read the intermediate rows of `source`, rather than its final
+    /// aggregates, because `source` is aggregated through recursion with `target`.
+    pub fn reads_intermediate(&self, target: &str, source: &str) -> bool {
+        self.recursive_aggregations.contains_key(source) && self.is_recursive(target, source)
+    }
+}
+
+/// Computes the strongly connected components of the same-tick dependency graph of `rules`, and
+/// checks that the program can be stratified: no relation may be negated in a rule for a relation
+/// it depends on, and only a single `min` or `max` aggregation may be computed through recursion.
+/// Rules with `:+` or `:~` do not add dependencies, since their results arrive in a later tick.
+pub fn stratify(
+    rules: &[&Spanned<Rule>],
+    persists: &HashSet<String>,
+    diagnostics: &mut Vec<Diagnostic>,
+    get_span: &impl Fn((usize, usize)) -> Span,
+) -> Stratification {
+    let mut graph = BTreeMap::<&str, BTreeSet<&str>>::new();
+    for rule in rules {
+        let target = rule.target.name.name.as_str();
+        graph.entry(target).or_default();
+        if !matches!(rule.rule_type.value, RuleType::Sync(_)) {
+            continue;
+        }
+        for atom in &rule.sources {
+            match atom {
+                Atom::PosRelation(e) | Atom::NegRelation(_, e) => {
+                    if !MAGIC_RELATIONS.contains(&e.name.name.as_str()) {
+                        graph.entry(&e.name.name).or_default().insert(target);
+                    }
+                }
+                Atom::Predicate(_) => {}
+            }
+        }
+    }
+
+    let mut stratification = Stratification {
+        components: strongly_connected_components(&graph),
+        recursive_aggregations: BTreeMap::new(),
+    };
+
+    for rule in rules {
+        if !matches!(rule.rule_type.value, RuleType::Sync(_)) {
+            continue;
+        }
+        let target = &rule.target.name.name;
+
+        let mut recursive_source = None;
+        for atom in &rule.sources {
+            match atom {
+                Atom::NegRelation(_, e) if stratification.is_recursive(target, &e.name.name) => {
+                    diagnostics.push(Diagnostic::spanned(
+                        get_span(e.span),
+                        Level::Error,
+                        format!(
+                            "Negation of `{}` cannot be stratified, since `{}` depends on `{}`",
+                            e.name.name, e.name.name, target
+                        ),
+                    ));
+                }
+                Atom::PosRelation(e) if stratification.is_recursive(target, &e.name.name) => {
+                    recursive_source.get_or_insert(&e.name.name);
+                }
+                _ => {}
+            }
+        }
+        let Some(source) = recursive_source else {
+            continue;
+        };
+
+        let aggregations = aggregated_columns(rule);
+        if let &[(column, Some(is_max))] = aggregations.as_slice() {
+            // Rules which disagree with the first are reported below.
+            if let Entry::Vacant(entry) =
+                stratification.recursive_aggregations.entry(target.clone())
+            {
+                if persists.contains(target) {
+                    diagnostics.push(Diagnostic::spanned(
+                        get_span(rule.target.name.span),
+                        Level::Error,
+                        format!(
+                            "`{}` is aggregated through recursion, so it cannot be persisted",
+                            target
+                        ),
+                    ));
+                }
+                entry.insert(RecursiveAggregation {
+                    arity: rule.target.fields.len(),
+                    column,
+                    is_max,
+                });
+            }
+            continue;
+        }
+        for (column, _) in aggregations {
+            diagnostics.push(Diagnostic::spanned(
+                get_span(rule.target.fields[column].span),
+                Level::Error,
+                format!(
+                    "Aggregation cannot be stratified, since `{}` depends on `{}`. Only a single `min` or `max` may be aggregated through recursion",
+                    source, target
+                ),
+            ));
+        }
+    }
+
+    for rule in rules {
+        let target = &rule.target.name.name;
+        if let Some(aggregation) = stratification.recursive_aggregations.get(target) {
+            let consistent = rule.target.fields.len() == aggregation.arity
+                && aggregated_columns(rule) == [(aggregation.column, Some(aggregation.is_max))];
+            if !consistent && !matches!(rule.rule_type.value, RuleType::Async(_)) {
+                diagnostics.push(inconsistent_aggregation(rule, aggregation, get_span));
+            }
+        } else if let Some((other, _)) = stratification
+            .recursive_aggregations
+            .iter()
+            .find(|(other, _)| stratification.is_recursive(target, other))
+        {
+            diagnostics.push(Diagnostic::spanned(
+                get_span(rule.target.name.span),
+                Level::Error,
+                format!(
+                    "`{}` depends on `{}`, which is aggregated through recursion, so `{}` must also be aggregated with `min` or `max`",
+                    target, other, target
+                ),
+            ));
+        }
+    }
+
+    stratification
+}
+
+/// The index of each aggregated column in the head of `rule`, and for `min` and `max`
+/// aggregations, if the aggregation is `max`.
+fn aggregated_columns(rule: &Rule) -> Vec<(usize, Option<bool>)> {
+    rule.target
+        .fields
+        .iter()
+        .enumerate()
+        .filter_map(|(i, field)| match &field.value {
+            TargetExpr::Aggregation(Aggregation::Min(..)) => Some((i, Some(false))),
+            TargetExpr::Aggregation(Aggregation::Max(..)) => Some((i, Some(true))),
+            TargetExpr::Aggregation(_) => Some((i, None)),
+            TargetExpr::Expr(_) | TargetExpr::Index(..) => None,
+        })
+        .collect()
+}
+
+fn inconsistent_aggregation(
+    rule: &Spanned<Rule>,
+    aggregation: &RecursiveAggregation,
+    get_span: &impl Fn((usize, usize)) -> Span,
+) -> Diagnostic {
+    Diagnostic::spanned(
+        get_span(rule.target.name.span),
+        Level::Error,
+        format!(
+            "`{}` is aggregated through recursion, so every rule for it must have {} columns and aggregate only column {} with `{}`",
+            rule.target.name.name,
+            aggregation.arity,
+            aggregation.column,
+            if aggregation.is_max { "max" } else { "min" }
+        ),
+    )
+}
+
+/// Tarjan's algorithm, returns the index of the component each node is in.
+fn strongly_connected_components(graph: &BTreeMap<&str, BTreeSet<&str>>) -> HashMap<String, usize> {
+    struct Tarjan<'a, 'g> {
+        graph: &'g BTreeMap<&'a str, BTreeSet<&'a str>>,
+        next_index: usize,
+        indices: HashMap<&'a str, (usize, usize)>,
+        stack: Vec<&'a str>,
+        on_stack: HashSet<&'a str>,
+        components: HashMap<String, usize>,
+        next_component: usize,
+    }
+
+    impl<'a> Tarjan<'a, '_> {
+        fn visit(&mut self, node: &'a str) {
+            let index = self.next_index;
+            self.next_index += 1;
+            self.indices.insert(node, (index, index));
+            self.stack.push(node);
+            self.on_stack.insert(node);
+
+            for &succ in self.graph.get(node).into_iter().flatten() {
+                let low = if !self.indices.contains_key(succ) {
+                    self.visit(succ);
+                    self.indices[succ].1
+                } else if self.on_stack.contains(succ) {
+                    self.indices[succ].0
+                } else {
+                    continue;
+                };
+                let entry = self.indices.get_mut(node).unwrap();
+                entry.1 = entry.1.min(low);
+            }
+
+            let (index, low) = self.indices[node];
+            if index == low {
+                loop {
+                    let member = self.stack.pop().unwrap();
+                    self.on_stack.remove(member);
+                    self.components
+                        .insert(member.to_owned(), self.next_component);
+                    if member == node {
+                        break;
+                    }
+                }
+                self.next_component += 1;
+            }
+        }
+    }
+
+    let mut tarjan = Tarjan {
+        graph,
+        next_index: 0,
+        indices: HashMap::new(),
+        stack: Vec::new(),
+        on_stack: HashSet::new(),
+        components: HashMap::new(),
+        next_component: 0,
+    };
+    for &node in graph.keys() {
+        if !tarjan.indices.contains_key(node) {
+            tarjan.visit(node);
+        }
+    }
+    tarjan.components
+}
@@ -15,8 +15,8 @@ use syn::spanned::Spanned;
 
 use super::graph_write::{Dot, GraphWrite, Mermaid};
 use super::ops::{
-    DelayType, OPERATORS, OperatorWriteOutput, WriteContextArgs, find_op_op_constraints,
-    null_write_iterator_fn,
+    DURABLE_OPERATORS, DelayType, OPERATORS, OperatorWriteOutput, Persistence, WriteContextArgs,
+    find_op_op_constraints, null_write_iterator_fn,
 };
 use super::{
     CONTEXT, Color, DiMulGraph, GRAPH, GraphEdgeId, GraphLoopId, GraphNode, GraphNodeId,
@@ -244,6 +244,7 @@ impl DfirGraph {
     /// Assign all operator instances if not set. Write diagnostic messages/errors into `diagnostics`.
     pub fn insert_node_op_insts_all(&mut self, diagnostics: &mut Vec<Diagnostic>) {
         let mut op_insts = Vec::new();
+        let mut durable_varnames = BTreeSet::new();
         for (node_id, node) in self.nodes() {
             let GraphNode::Operator(operator) = node else {
                 continue;
@@ -318,6 +319,48 @@ impl DfirGraph {
                         ),
                     ));
                 }
+                if generics.durable {
+                    if !DURABLE_OPERATORS.contains(&op_constraints.name) {
+                        diagnostics.push(Diagnostic::spanned(
+                            generics_span,
+                            Level::Error,
+                            format!(
+                                "`{}` does not support `'durable` state.",
+                                op_constraints.name
+                            ),
+                        ));
+                    } else if !matches!(
+                        generics.persistence_args[..],
+                        [Persistence::Static] | [Persistence::Mutable]
+                    ) {
+                        diagnostics.push(Diagnostic::spanned(
+                            generics_span,
+                            Level::Error,
+                            "`'durable` state must also be `'static` or `'mutable`.",
+                        ));
+                    } else if let Some(varname) = self.node_varname(node_id) {
+                        // The variable name keys the state in checkpoints, so must be unique.
+                        if !durable_varnames.insert(varname.to_string()) {
+                            diagnostics.push(Diagnostic::spanned(
+                                generics_span,
+                                Level::Error,
+                                format!(
+                                    "Multiple `'durable` operators are assigned to `{}`, each must have its own name.",
+                                    varname
+                                ),
+                            ));
+                        }
+                    } else {
+                        diagnostics.push(Diagnostic::spanned(
+                            generics_span,
+                            Level::Error,
+                            format!(
+                                "`'durable` operators must be assigned to a variable, e.g. `my_state = {}::<..>(..)`, whose name identifies their checkpointed state.",
+                                op_constraints.name
+                            ),
+                        ));
+                    }
+                }
                 if !op_constraints.type_args.contains(&generics.type_args.len()) {
                     diagnostics.push(Diagnostic::spanned(
                         generics_span,
@@ -1058,6 +1101,7 @@ impl DfirGraph {
                                 loop_id,
                                 op_span,
                                 op_tag: self.operator_tag.get(node_id).cloned(),
+                                varname: self.node_varname(node_id),
                                 work_fn: &fn_ident,
                                 ident: &ident,
                                 is_pull,
@@ -208,31 +208,37 @@ pub struct OpInstGenerics {
     pub persistence_args: Vec<Persistence>,
     /// Type persistence arguments. Corersponds to a (suffix) of [`Self::generic_args`].
     pub type_args: Vec<Type>,
+    /// If the operator's state is marked `'durable`, i.e. checkpointed and restored across
+    /// restarts.
+    pub durable: bool,
 }
 
 /// Gets the generic arguments for the operator.
 ///
 /// This helper method is useful due to the special handling of persistence lifetimes (`'static`,
-/// `'tick`, `'mutable`) which must come before other generic parameters.
+/// `'tick`, `'mutable`) which must come before other generic parameters. The `'durable` lifetime
+/// is not a persistence, it marks the operator's state as durable.
 pub fn get_operator_generics(
     diagnostics: &mut Vec<Diagnostic>,
     operator: &Operator,
 ) -> OpInstGenerics {
     // Generic arguments.
     let generic_args = operator.type_arguments().cloned();
-    let persistence_args = generic_args.iter().flatten().map_while(|generic_arg| match generic_arg {
+    // `None` for `'durable`, which is not a persistence.
+    let lifetime_args = generic_args.iter().flatten().map_while(|generic_arg| match generic_arg {
             GenericArgument::Lifetime(lifetime) => {
                 match &*lifetime.ident.to_string() {
-                    "none" => Some(Persistence::None),
-                    "loop" => Some(Persistence::Loop),
-                    "tick" => Some(Persistence::Tick),
-                    "static" => Some(Persistence::Static),
-                    "mutable" => Some(Persistence::Mutable),
+                    "none" => Some(Some(Persistence::None)),
+                    "loop" => Some(Some(Persistence::Loop)),
+                    "tick" => Some(Some(Persistence::Tick)),
+                    "static" => Some(Some(Persistence::Static)),
+                    "mutable" => Some(Some(Persistence::Mutable)),
+                    "durable" => Some(None),
                     _ => {
                         diagnostics.push(Diagnostic::spanned(
                             generic_arg.span(),
                             Level::Error,
-                            format!("Unknown lifetime generic argument `'{}`, expected `'none`, `'loop`, `'tick`, `'static`, or `'mutable`.", lifetime.ident),
+                            format!("Unknown lifetime generic argument `'{}`, expected `'none`, `'loop`, `'tick`, `'static`, `'mutable`, or `'durable`.", lifetime.ident),
                         ));
                         // TODO(mingwei): should really keep going and not short circuit?
                         None
@@ -241,10 +247,12 @@ pub fn get_operator_generics(
             },
             _ => None,
         }).collect::<Vec<_>>();
+    let durable = lifetime_args.contains(&None);
+    let persistence_args = lifetime_args.iter().copied().flatten().collect::<Vec<_>>();
     let type_args = generic_args
         .iter()
         .flatten()
-        .skip(persistence_args.len())
+        .skip(lifetime_args.len())
         .map_while(|generic_arg| match generic_arg {
             GenericArgument::Type(typ) => Some(typ),
             _ => None,
@@ -256,6 +264,7 @@ pub fn get_operator_generics(
         generic_args,
         persistence_args,
         type_args,
+        durable,
     }
 }
 
@@ -62,8 +62,16 @@ pub const ANTI_JOIN: OperatorConstraints = OperatorConstraints {
             let borrow_ident = wc.make_ident(format!("antijoindata_{}_borrow", side));
             let lifespan = wc.persistence_as_state_lifespan(persistence);
             (
-                quote_spanned! {op_span=>
-                    let #antijoindata_ident = #df_ident.add_state(std::cell::RefCell::new(#root::rustc_hash::FxHashSet::default()));
+                {
+                    let size_fn = wc.state_size_fn(
+                        persistence,
+                        &antijoindata_ident,
+                        quote_spanned! {op_span=> |rcell| rcell.borrow().len() },
+                    );
+                    quote_spanned! {op_span=>
+                        let #antijoindata_ident = #df_ident.add_state(std::cell::RefCell::new(#root::rustc_hash::FxHashSet::default()));
+                        #size_fn
+                    }
                 },
                 lifespan.map(|lifespan| quote_spanned! {op_span=>
                     #df_ident.set_state_lifespan_hook(#antijoindata_ident, #lifespan, |rcell| { rcell.take(); });
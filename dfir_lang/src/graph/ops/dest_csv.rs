@@ -0,0 +1,70 @@
+use quote::quote_spanned;
+
+use super::{OperatorCategory, OperatorConstraints, RANGE_0, RANGE_1};
+
+/// > 1 input stream, 0 or 1 output streams
+///
+/// > Arguments: (1) An [`AsRef`](https://doc.rust-lang.org/std/convert/trait.AsRef.html)`<`[`Path`](https://doc.rust-lang.org/nightly/std/path/struct.Path.html)`>`
+/// > for a file to write to, and (2) a bool `append`.
+///
+/// Consumes records implementing [`Serialize`](https://docs.rs/serde/latest/serde/ser/trait.Serialize.html)
+/// by writing them as rows of a CSV file. The file will be created if it doesn't exist. Records
+/// will be appended to the file if `append` is true, otherwise the file will be truncated before
+/// records are written. A header row naming the record's fields is written first, unless records
+/// are appended to a file that is not empty.
+///
+/// Like [`dest_jsonl`](#dest_jsonl), records that fail to serialize are emitted on the optional
+/// output as `(record, RecordError)` pairs, or logged as errors if the output is not connected.
+///
+/// Note this operator must be used within a Tokio runtime.
+///
+/// ```rustbook
+/// # #[dfir_rs::main]
+/// # async fn main() {
+/// #[derive(serde::Serialize)]
+/// struct Order {
+///     item: &'static str,
+///     quantity: u32,
+/// }
+///
+/// let file = tempfile::NamedTempFile::new().unwrap();
+/// let mut flow = dfir_rs::dfir_syntax! {
+///     source_iter([("apple", 3), ("melon, large", 1)])
+///         -> map(|(item, quantity)| Order { item, quantity })
+///         -> dest_csv(file.path(), false);
+/// };
+/// tokio::time::timeout(std::time::Duration::from_secs(1), flow.run_async())
+///     .await
+///     .expect_err("Expected time out");
+///
+/// assert_eq!(
+///     "item,quantity\napple,3\n\"melon, large\",1\n",
+///     std::fs::read_to_string(file.path()).unwrap(),
+/// );
+/// # }
+/// ```
+pub const DEST_CSV: OperatorConstraints = OperatorConstraints {
+    name: "dest_csv",
+    categories: &[OperatorCategory::Sink],
+    hard_range_inn: RANGE_1,
+    soft_range_inn: RANGE_1,
+    hard_range_out: &(0..=1),
+    soft_range_out: &(0..=1),
+    num_args: 2,
+    persistence_args: RANGE_0,
+    type_args: RANGE_0,
+    is_external_input: false,
+    has_singleton_output: false,
+    flo_type: None,
+    ports_inn: None,
+    ports_out: None,
+    input_delaytype_fn: |_| None,
+    write_fn: |wc, diagnostics| {
+        let root = wc.root;
+        super::dest_jsonl::write_record_sink(
+            wc,
+            diagnostics,
+            quote_spanned!(wc.op_span=> #root::util::records::CsvEncoder::new(file_is_empty)),
+        )
+    },
+};
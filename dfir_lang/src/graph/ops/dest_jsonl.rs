@@ -0,0 +1,190 @@
+use proc_macro2::TokenStream;
+use quote::quote_spanned;
+use syn::parse_quote_spanned;
+
+use super::{
+    OperatorCategory, OperatorConstraints, OperatorWriteOutput, RANGE_0, RANGE_1,
+    WriteContextArgs, make_missing_runtime_msg,
+};
+use crate::diagnostic::Diagnostic;
+
+/// > 1 input stream, 0 or 1 output streams
+///
+/// > Arguments: (1) An [`AsRef`](https://doc.rust-lang.org/std/convert/trait.AsRef.html)`<`[`Path`](https://doc.rust-lang.org/nightly/std/path/struct.Path.html)`>`
+/// > for a file to write to, and (2) a bool `append`.
+///
+/// Consumes records implementing [`Serialize`](https://docs.rs/serde/latest/serde/ser/trait.Serialize.html)
+/// by writing them to a [JSON Lines](https://jsonlines.org/) file, one JSON value per line. The
+/// file will be created if it doesn't exist. Records will be appended to the file if `append` is
+/// true, otherwise the file will be truncated before records are written.
+///
+/// Records that fail to serialize are not written. They are emitted on the optional output as
+/// `(record, RecordError)` pairs, or logged as errors if the output is not connected.
+///
+/// Note this operator must be used within a Tokio runtime.
+///
+/// ```rustbook
+/// # #[dfir_rs::main]
+/// # async fn main() {
+/// #[derive(serde::Serialize)]
+/// struct Reading {
+///     sensor: &'static str,
+///     value: f64,
+/// }
+///
+/// let file = tempfile::NamedTempFile::new().unwrap();
+/// let mut flow = dfir_rs::dfir_syntax! {
+///     source_iter([("a", 1.5), ("b", -2.0)])
+///         -> map(|(sensor, value)| Reading { sensor, value })
+///         -> dest_jsonl(file.path(), false);
+/// };
+/// tokio::time::timeout(std::time::Duration::from_secs(1), flow.run_async())
+///     .await
+///     .expect_err("Expected time out");
+///
+/// assert_eq!(
+///     "{\"sensor\":\"a\",\"value\":1.5}\n{\"sensor\":\"b\",\"value\":-2.0}\n",
+///     std::fs::read_to_string(file.path()).unwrap(),
+/// );
+/// # }
+/// ```
+pub const DEST_JSONL: OperatorConstraints = OperatorConstraints {
+    name: "dest_jsonl",
+    categories: &[OperatorCategory::Sink],
+    hard_range_inn: RANGE_1,
+    soft_range_inn: RANGE_1,
+    hard_range_out: &(0..=1),
+    soft_range_out: &(0..=1),
+    num_args: 2,
+    persistence_args: RANGE_0,
+    type_args: RANGE_0,
+    is_external_input: false,
+    has_singleton_output: false,
+    flo_type: None,
+    ports_inn: None,
+    ports_out: None,
+    input_delaytype_fn: |_| None,
+    write_fn: |wc, diagnostics| {
+        let root = wc.root;
+        write_record_sink(
+            wc,
+            diagnostics,
+            quote_spanned!(wc.op_span=> #root::util::records::JsonlEncoder::new()),
+        )
+    },
+};
+
+/// Shared by `dest_jsonl` and `dest_csv`: serializes each record with the encoder built by
+/// `encoder`, and writes the result as a line. `encoder` may refer to `file_is_empty`, which is
+/// true if nothing has been written to the file yet.
+pub(crate) fn write_record_sink(
+    wc @ &WriteContextArgs {
+        root,
+        op_span,
+        op_name,
+        ident,
+        inputs,
+        outputs,
+        is_pull,
+        arguments,
+        ..
+    }: &WriteContextArgs<'_>,
+    diagnostics: &mut Vec<Diagnostic>,
+    encoder: TokenStream,
+) -> Result<OperatorWriteOutput, ()> {
+    let filename_arg = &arguments[0];
+    let append_arg = &arguments[1];
+
+    let ident_filesink = wc.make_ident("filesink");
+    let ident_encoder = wc.make_ident("encoder");
+    let ident_lines = wc.make_ident("lines");
+
+    let missing_runtime_msg = make_missing_runtime_msg(op_name);
+
+    let write_prologue = quote_spanned! {op_span=>
+        let (#ident_filesink, #ident_encoder) = {
+            let append = #append_arg;
+            let file = ::std::fs::OpenOptions::new()
+                .create(true)
+                .write(true)
+                .append(append)
+                .truncate(!append)
+                .open(#filename_arg)
+                .expect("Failed to open file for writing");
+            #[allow(unused_variables, reason = "only used by some encoders")]
+            let file_is_empty = file.metadata().map_or(true, |metadata| metadata.len() == 0);
+            let encoder = #encoder;
+            let file = #root::tokio::fs::File::from_std(file);
+            let bufwrite = #root::tokio::io::BufWriter::new(file);
+            let codec = #root::tokio_util::codec::LinesCodec::new();
+            (#root::tokio_util::codec::FramedWrite::new(bufwrite, codec), encoder)
+        };
+    };
+    // The lines sink is always a pusherator, even if this operator is pulled from.
+    let wc = WriteContextArgs {
+        ident: &ident_lines,
+        is_pull: false,
+        arguments: &parse_quote_spanned!(op_span=> #ident_filesink),
+        ..wc.clone()
+    };
+
+    let OperatorWriteOutput {
+        write_prologue: write_prologue_sink,
+        write_prologue_after,
+        write_iterator: write_iterator_sink,
+        write_iterator_after,
+    } = (super::dest_sink::DEST_SINK.write_fn)(&wc, diagnostics)?;
+
+    let write_prologue = quote_spanned! {op_span=>
+        #write_prologue
+        #write_prologue_sink
+    };
+
+    // Writes the record, returning it along with the error if it could not be serialized.
+    let write_record = quote_spanned! {op_span=>
+        |record| match #ident_encoder.encode(&record) {
+            ::std::result::Result::Ok(line) => {
+                #root::pusherator::Pusherator::give(&mut #ident_lines, line);
+                ::std::option::Option::None
+            }
+            ::std::result::Result::Err(error) => ::std::option::Option::Some((record, error)),
+        }
+    };
+    let write_iterator = if is_pull {
+        let input = &inputs[0];
+        quote_spanned! {op_span=>
+            let #ident = #input.filter_map(#write_record);
+        }
+    } else if outputs.is_empty() {
+        quote_spanned! {op_span=>
+            let #ident = #root::pusherator::for_each::ForEach::new(|record| {
+                match #ident_encoder.encode(&record) {
+                    ::std::result::Result::Ok(line) => {
+                        #root::pusherator::Pusherator::give(&mut #ident_lines, line);
+                    }
+                    ::std::result::Result::Err(error) => {
+                        #root::tracing::error!("{}: failed to write record: {}", #op_name, error);
+                    }
+                }
+            });
+        }
+    } else {
+        let output = &outputs[0];
+        quote_spanned! {op_span=>
+            let #ident = #root::pusherator::filter_map::FilterMap::new(#write_record, #output);
+        }
+    };
+    let write_iterator = quote_spanned! {op_span=>
+        ::std::debug_assert!(#root::tokio::runtime::Handle::try_current().is_ok(), #missing_runtime_msg);
+        #write_iterator_sink
+        let mut #ident_lines = #ident_lines;
+        #write_iterator
+    };
+
+    Ok(OperatorWriteOutput {
+        write_prologue,
+        write_prologue_after,
+        write_iterator,
+        write_iterator_after,
+    })
+}
@@ -0,0 +1,116 @@
+use quote::quote_spanned;
+
+use super::{
+    OperatorCategory, OperatorConstraints, OperatorWriteOutput, RANGE_0, RANGE_1, WriteContextArgs,
+};
+
+/// > Arguments: An [async `Sink`](https://docs.rs/futures/latest/futures/sink/trait.Sink.html)
+/// > and a [`BufferConfig`](https://hydro.run/rustdoc/dfir_rs/util/buffered_sink/struct.BufferConfig.html)
+///
+/// Like [`dest_sink`](#dest_sink), but holds items in a bounded buffer while the sink catches up,
+/// instead of an unbounded one. The buffer is limited by item count and/or total byte size (items
+/// must implement [`BufferedItem`](https://hydro.run/rustdoc/dfir_rs/util/buffered_sink/trait.BufferedItem.html)),
+/// and the config's [`Overflow`](https://hydro.run/rustdoc/dfir_rs/util/buffered_sink/enum.Overflow.html)
+/// policy decides what happens when it is full:
+/// * `Overflow::Block` blocks the subgraph until the sink has drained enough of the buffer. The
+///   sink must be able to make progress without the current thread, such as a socket on a
+///   multi-threaded Tokio runtime.
+/// * `Overflow::DropOldest` evicts the oldest buffered items.
+/// * `Overflow::DropNewest` rejects the incoming item.
+///
+/// Items dropped by the policy are emitted on the optional output, and discarded otherwise.
+///
+/// Note this operator must be used within a Tokio runtime, and the DFIR program must be launched with `run_async`.
+///
+/// ```rustbook
+/// # #[dfir_rs::main]
+/// # async fn main() {
+/// use bytes::Bytes;
+/// use dfir_rs::util::buffered_sink::{BufferConfig, Overflow};
+///
+/// let (send, mut recv) = futures::channel::mpsc::unbounded::<Bytes>();
+/// let (dropped_send, mut dropped_recv) = dfir_rs::util::unbounded_channel::<Bytes>();
+///
+/// let mut flow = dfir_rs::dfir_syntax! {
+///     source_iter(["a", "b", "c"])
+///         -> map(|s: &'static str| Bytes::from(s))
+///         -> dest_sink_buffered(send, BufferConfig::new(Overflow::DropNewest).max_items(2))
+///         -> for_each(|item| dropped_send.send(item).unwrap());
+/// };
+/// tokio::time::timeout(std::time::Duration::from_secs(1), flow.run_async())
+///     .await
+///     .expect_err("Expected time out");
+///
+/// // The third item did not fit in the buffer before the sink was polled.
+/// let sent: Vec<_> = dfir_rs::util::ready_iter(&mut recv).collect();
+/// assert_eq!(&[Bytes::from_static(b"a"), Bytes::from_static(b"b")], &*sent);
+/// let dropped: Vec<_> = dfir_rs::util::ready_iter(&mut dropped_recv).collect();
+/// assert_eq!(&[Bytes::from_static(b"c")], &*dropped);
+/// # }
+/// ```
+pub const DEST_SINK_BUFFERED: OperatorConstraints = OperatorConstraints {
+    name: "dest_sink_buffered",
+    categories: &[OperatorCategory::Sink],
+    hard_range_inn: RANGE_1,
+    soft_range_inn: RANGE_1,
+    hard_range_out: &(0..=1),
+    soft_range_out: &(0..=1),
+    num_args: 2,
+    persistence_args: RANGE_0,
+    type_args: RANGE_0,
+    is_external_input: false,
+    has_singleton_output: false,
+    flo_type: None,
+    ports_inn: None,
+    ports_out: None,
+    input_delaytype_fn: |_| None,
+    write_fn: |wc @ &WriteContextArgs {
+                   root,
+                   df_ident,
+                   op_span,
+                   ident,
+                   inputs,
+                   outputs,
+                   is_pull,
+                   arguments,
+                   ..
+               },
+               _| {
+        let sink_arg = &arguments[0];
+        let config_arg = &arguments[1];
+
+        let buffer_ident = wc.make_ident("buffer");
+
+        let write_prologue = quote_spanned! {op_span=>
+            let #buffer_ident = #root::util::buffered_sink::BufferedSink::new(#sink_arg, #config_arg);
+            #df_ident.request_task(#buffer_ident.clone().drain());
+        };
+
+        let write_iterator = if is_pull {
+            let input = &inputs[0];
+            quote_spanned! {op_span=>
+                let #ident = #input.flat_map(|item| #buffer_ident.push(item));
+            }
+        } else if outputs.is_empty() {
+            quote_spanned! {op_span=>
+                let #ident = #root::pusherator::for_each::ForEach::new(|item| {
+                    #buffer_ident.push(item);
+                });
+            }
+        } else {
+            let output = &outputs[0];
+            quote_spanned! {op_span=>
+                let #ident = #root::pusherator::map::Map::new(
+                    |item| #buffer_ident.push(item),
+                    #root::pusherator::flatten::Flatten::new(#output)
+                );
+            }
+        };
+
+        Ok(OperatorWriteOutput {
+            write_prologue,
+            write_iterator,
+            ..Default::default()
+        })
+    },
+};
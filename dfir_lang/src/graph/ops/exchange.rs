@@ -0,0 +1,127 @@
+use proc_macro2::TokenStream;
+use quote::quote_spanned;
+
+use super::{
+    OperatorCategory, OperatorConstraints, OperatorWriteOutput, RANGE_0, RANGE_1,
+    WriteContextArgs,
+};
+
+/// > 1 input stream, 1 output stream
+///
+/// > Arguments: A closure `FnMut(&Item) -> Key`, where `Key` implements [`Hash`](https://doc.rust-lang.org/std/hash/trait.Hash.html).
+///
+/// Hash-partitions items between the worker threads of a
+/// [parallel graph](https://hydro.run/rustdoc/dfir_rs/scheduled/parallel/index.html): each item
+/// is moved to the worker owning its key, and items sent by other workers are emitted here.
+/// Items exchanged within a stratum arrive before the stratum ends on any worker, so blocking
+/// operators downstream see every item for their keys.
+///
+/// Outside of a parallel run there is only one worker, and items pass through unchanged. Items
+/// must implement [`Send`](https://doc.rust-lang.org/std/marker/trait.Send.html).
+///
+/// See also [`exchange_keyed`](#exchange_keyed), which partitions `(key, value)` pairs by key.
+///
+/// ```rustbook
+/// let (result_send, mut result_recv) = dfir_rs::util::unbounded_channel();
+/// dfir_rs::scheduled::parallel::run_available(4, |worker| {
+///     let result_send = result_send.clone();
+///     dfir_rs::dfir_syntax! {
+///         // Each worker reads a different slice of the input.
+///         source_iter(0..20)
+///             -> exchange(|n| n % 3)
+///             -> map(move |n| (n % 3, worker.index))
+///             -> for_each(|pair| result_send.send(pair).unwrap());
+///     }
+/// });
+///
+/// // Every item with the same key was handled by the same worker.
+/// let mut owners = std::collections::HashMap::new();
+/// for (key, worker) in dfir_rs::util::collect_ready::<Vec<_>, _>(&mut result_recv) {
+///     assert_eq!(worker, *owners.entry(key).or_insert(worker));
+/// }
+/// assert_eq!(3, owners.len());
+/// ```
+pub const EXCHANGE: OperatorConstraints = OperatorConstraints {
+    name: "exchange",
+    categories: &[OperatorCategory::Control],
+    hard_range_inn: RANGE_1,
+    soft_range_inn: RANGE_1,
+    hard_range_out: RANGE_1,
+    soft_range_out: RANGE_1,
+    num_args: 1,
+    persistence_args: RANGE_0,
+    type_args: RANGE_0,
+    is_external_input: false,
+    has_singleton_output: false,
+    flo_type: None,
+    ports_inn: None,
+    ports_out: None,
+    input_delaytype_fn: |_| None,
+    write_fn: |wc @ &WriteContextArgs { op_span, arguments, .. }, _| {
+        let key_fn = &arguments[0];
+        Ok(write_exchange(
+            wc,
+            quote_spanned!(op_span=> item),
+            quote_spanned!(op_span=> (#key_fn)(&item)),
+        ))
+    },
+};
+
+/// Shared by `exchange` and `exchange_keyed`: routes each `item`, bound by the closure parameter
+/// `item_param`, by the key computed by `key`.
+pub(crate) fn write_exchange(
+    wc @ &WriteContextArgs {
+        root,
+        context,
+        op_span,
+        ident,
+        inputs,
+        outputs,
+        is_pull,
+        ..
+    }: &WriteContextArgs<'_>,
+    item_param: TokenStream,
+    key: TokenStream,
+) -> OperatorWriteOutput {
+    let ident_exchange = wc.make_ident("exchange");
+    let ident_waker = wc.make_ident("waker");
+
+    let write_prologue = quote_spanned! {op_span=>
+        let #ident_exchange = #root::scheduled::parallel::Exchange::next();
+    };
+
+    let route = quote_spanned! {op_span=>
+        |#item_param| {
+            #[allow(clippy::redundant_closure_call)]
+            let owner = #ident_exchange.owner_of(&#key);
+            #ident_exchange.route(item, owner)
+        }
+    };
+    let write_iterator = if is_pull {
+        let input = &inputs[0];
+        quote_spanned! {op_span=>
+            let #ident_waker = #context.waker();
+            let #ident = #input
+                .filter_map(#route)
+                .chain(::std::iter::from_fn(|| #ident_exchange.try_recv(&#ident_waker)));
+        }
+    } else {
+        let output = &outputs[0];
+        quote_spanned! {op_span=>
+            let #ident = {
+                let #ident_waker = #context.waker();
+                let mut output = #output;
+                while let ::std::option::Option::Some(item) = #ident_exchange.try_recv(&#ident_waker) {
+                    #root::pusherator::Pusherator::give(&mut output, item);
+                }
+                #root::pusherator::filter_map::FilterMap::new(#route, output)
+            };
+        }
+    };
+
+    OperatorWriteOutput {
+        write_prologue,
+        write_iterator,
+        ..Default::default()
+    }
+}
@@ -0,0 +1,52 @@
+use quote::quote_spanned;
+
+use super::{OperatorCategory, OperatorConstraints, RANGE_0, RANGE_1};
+
+/// > 1 input stream of type `(K, V)`, 1 output stream of type `(K, V)`
+///
+/// Like [`exchange`](#exchange), but partitions `(key, value)` pairs by their key. Placing
+/// `exchange_keyed()` before each input of a keyed operator such as [`join`](#join) or
+/// [`fold_keyed`](#fold_keyed) lets the workers of a
+/// [parallel graph](https://hydro.run/rustdoc/dfir_rs/scheduled/parallel/index.html) split the
+/// keyspace between them, without changing the operator's output.
+///
+/// ```rustbook
+/// let (result_send, mut result_recv) = dfir_rs::util::unbounded_channel();
+/// dfir_rs::scheduled::parallel::run_available(4, |worker| {
+///     let result_send = result_send.clone();
+///     dfir_rs::dfir_syntax! {
+///         orders = source_iter(0..100) -> map(|n: u32| (n % 10, n)) -> exchange_keyed();
+///         totals = orders -> fold_keyed(|| 0, |total: &mut u32, n| *total += n);
+///         totals -> for_each(|pair| result_send.send(pair).unwrap());
+///     }
+/// });
+///
+/// let mut totals = dfir_rs::util::collect_ready::<Vec<_>, _>(&mut result_recv);
+/// totals.sort();
+/// let expected: Vec<_> = (0..10).map(|k| (k, (0..10).map(|i| 10 * i + k).sum())).collect();
+/// assert_eq!(expected, totals);
+/// ```
+pub const EXCHANGE_KEYED: OperatorConstraints = OperatorConstraints {
+    name: "exchange_keyed",
+    categories: &[OperatorCategory::Control],
+    hard_range_inn: RANGE_1,
+    soft_range_inn: RANGE_1,
+    hard_range_out: RANGE_1,
+    soft_range_out: RANGE_1,
+    num_args: 0,
+    persistence_args: RANGE_0,
+    type_args: RANGE_0,
+    is_external_input: false,
+    has_singleton_output: false,
+    flo_type: None,
+    ports_inn: None,
+    ports_out: None,
+    input_delaytype_fn: |_| None,
+    write_fn: |wc, _| {
+        Ok(super::exchange::write_exchange(
+            wc,
+            quote_spanned!(wc.op_span=> item: (_, _)),
+            quote_spanned!(wc.op_span=> item.0),
+        ))
+    },
+};
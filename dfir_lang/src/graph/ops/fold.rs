@@ -24,6 +24,11 @@ use super::{
 /// will be aggregated with items arriving in later ticks. When not explicitly specified
 /// persistence defaults to `'tick`.
 ///
+/// A `'static` accumulator can additionally be marked `'durable`, i.e. `fold::<'static, 'durable>(..)`,
+/// to include it in the checkpoints written to the directory set with `Dfir::set_checkpoint_dir`.
+/// It is then restored when the flow restarts. The accumulator must implement `Serialize` and
+/// `DeserializeOwned`.
+///
 /// ```dfir
 /// // should print `Reassembled vector [1,2,3,4,5]`
 /// source_iter([1,2,3,4,5])
@@ -76,12 +81,15 @@ pub const FOLD: OperatorConstraints = OperatorConstraints {
         let accumulator_ident = wc.make_ident("accumulator");
         let iterator_item_ident = wc.make_ident("iterator_item");
 
+        let add_state = wc.add_state(quote_spanned! {op_span=>
+            ::std::cell::RefCell::new(#init)
+        });
         let write_prologue = quote_spanned! {op_span=>
             #[allow(unused_mut, reason = "for if `Fn` instead of `FnMut`.")]
             let mut #initializer_func_ident = #init_fn;
 
             #[allow(clippy::redundant_closure_call)]
-            let #singleton_output_ident = #df_ident.add_state(::std::cell::RefCell::new(#init));
+            let #singleton_output_ident = #add_state;
         };
         let write_prologue_after = wc
             .persistence_as_state_lifespan(persistence)
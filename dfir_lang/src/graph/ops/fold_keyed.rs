@@ -131,8 +131,14 @@ pub const FOLD_KEYED: OperatorConstraints = OperatorConstraints {
 
         let hashtable_ident = wc.make_ident("hashtable");
 
+        let size_fn = wc.state_size_fn(
+            persistence,
+            singleton_output_ident,
+            quote_spanned! {op_span=> |rcell| rcell.borrow().len() },
+        );
         let write_prologue = quote_spanned! {op_span=>
             let #singleton_output_ident = #df_ident.add_state(::std::cell::RefCell::new(#root::rustc_hash::FxHashMap::<#( #generic_type_args ),*>::default()));
+            #size_fn
         };
         let write_prologue_after =wc
             .persistence_as_state_lifespan(persistence)
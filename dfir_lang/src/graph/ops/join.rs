@@ -144,10 +144,18 @@ pub const JOIN: OperatorConstraints = OperatorConstraints {
                 #df_ident.set_state_lifespan_hook(#joindata_ident, #lifespan, |rcell| (#work_fn)(|| #root::util::clear::Clear::clear(::std::cell::RefCell::get_mut(rcell))));
             }).unwrap_or_default();
 
+            let size_fn = wc.state_size_fn(
+                persistence,
+                &joindata_ident,
+                quote_spanned! {op_span=>
+                    |rcell| #root::compiled::pull::HalfJoinState::len(&*rcell.borrow())
+                },
+            );
             let prologue = quote_spanned! {op_span=>
                 let #joindata_ident = #df_ident.add_state(::std::cell::RefCell::new(
                     #join_type::default()
                 ));
+                #size_fn
             };
             let borrow = quote_spanned! {op_span=>
                 unsafe {
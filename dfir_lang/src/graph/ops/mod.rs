@@ -262,12 +262,17 @@ declare_ops![
     cross_singleton::CROSS_SINGLETON,
     demux::DEMUX,
     demux_enum::DEMUX_ENUM,
+    dest_csv::DEST_CSV,
     dest_file::DEST_FILE,
+    dest_jsonl::DEST_JSONL,
     dest_sink::DEST_SINK,
+    dest_sink_buffered::DEST_SINK_BUFFERED,
     dest_sink_serde::DEST_SINK_SERDE,
     difference::DIFFERENCE,
     difference_multiset::DIFFERENCE_MULTISET,
     enumerate::ENUMERATE,
+    exchange::EXCHANGE,
+    exchange_keyed::EXCHANGE_KEYED,
     filter::FILTER,
     filter_map::FILTER_MAP,
     flat_map::FLAT_MAP,
@@ -312,10 +317,12 @@ declare_ops![
     spin::SPIN,
     sort::SORT,
     sort_by_key::SORT_BY_KEY,
+    source_csv::SOURCE_CSV,
     source_file::SOURCE_FILE,
     source_interval::SOURCE_INTERVAL,
     source_iter::SOURCE_ITER,
     source_json::SOURCE_JSON,
+    source_jsonl::SOURCE_JSONL,
     source_stdin::SOURCE_STDIN,
     source_stream::SOURCE_STREAM,
     source_stream_serde::SOURCE_STREAM_SERDE,
@@ -328,6 +335,17 @@ declare_ops![
     zip_longest::ZIP_LONGEST,
 ];
 
+/// Names of the operators whose state can be marked `'durable`, to be checkpointed and restored
+/// across restarts.
+pub const DURABLE_OPERATORS: &[&str] = &[
+    "fold",
+    "persist",
+    "persist_mut",
+    "persist_mut_keyed",
+    "state",
+    "state_by",
+];
+
 /// Get the operator lookup table, generating it if needed.
 pub fn operator_lookup() -> &'static HashMap<&'static str, &'static OperatorConstraints> {
     pub static OPERATOR_LOOKUP: OnceLock<HashMap<&'static str, &'static OperatorConstraints>> =
@@ -370,6 +388,8 @@ pub struct WriteContextArgs<'a> {
     pub op_span: Span,
     /// Tag for this operator appended to the generated identifier.
     pub op_tag: Option<String>,
+    /// The variable name this operator is assigned to, if any.
+    pub varname: Option<Ident>,
     /// Identifier for a function to call when doing work outside the iterator.
     pub work_fn: &'a Ident,
 
@@ -415,6 +435,49 @@ impl WriteContextArgs<'_> {
         )
     }
 
+    /// Returns the code which adds `state` to the instance, returning its `StateHandle`. If the
+    /// operator is marked `'durable`, the state is added as durable state, keyed by the name of
+    /// the variable the operator is assigned to.
+    pub fn add_state(&self, state: TokenStream) -> TokenStream {
+        let df_ident = self.df_ident;
+        if self.op_inst.generics.durable {
+            // Unnamed durable operators are reported in `DfirGraph::insert_node_op_insts_all`.
+            let key = self
+                .varname
+                .as_ref()
+                .map(ToString::to_string)
+                .unwrap_or_default();
+            quote_spanned! {self.op_span=>
+                #df_ident.add_durable_state(#key, #state)
+            }
+        } else {
+            quote_spanned! {self.op_span=>
+                #df_ident.add_state(#state)
+            }
+        }
+    }
+
+    /// Returns the code which sets `size_fn` as the size function of the state `state_ident`, so
+    /// that its size is reported by `Dfir::snapshot`. Only state which persists across ticks
+    /// (`'static`, `'mutable`, or `'durable`) is reported, otherwise this returns no code.
+    pub fn state_size_fn(
+        &self,
+        persistence: Persistence,
+        state_ident: &Ident,
+        size_fn: TokenStream,
+    ) -> TokenStream {
+        if !matches!(persistence, Persistence::Static | Persistence::Mutable)
+            && !self.op_inst.generics.durable
+        {
+            return TokenStream::new();
+        }
+        let df_ident = self.df_ident;
+        let name = format!("{} {:?}", self.op_name, self.node_id);
+        quote_spanned! {self.op_span=>
+            #df_ident.set_state_size_fn(#state_ident, #name, #size_fn);
+        }
+    }
+
     /// Returns `#root::scheduled::graph::StateLifespan::#variant` corresponding to the given
     /// peristence.
     pub fn persistence_as_state_lifespan(&self, persistence: Persistence) -> Option<TokenStream> {
@@ -54,7 +54,6 @@ pub const PERSIST: OperatorConstraints = OperatorConstraints {
     write_fn: |wc @ &WriteContextArgs {
                    root,
                    context,
-                   df_ident,
                    op_span,
                    ident,
                    is_pull,
@@ -84,10 +83,17 @@ pub const PERSIST: OperatorConstraints = OperatorConstraints {
 
         let persistdata_ident = singleton_output_ident;
         let vec_ident = wc.make_ident("persistvec");
+        let add_state = wc.add_state(quote_spanned! {op_span=>
+            ::std::cell::RefCell::new(::std::vec::Vec::new())
+        });
+        let size_fn = wc.state_size_fn(
+            Persistence::Static,
+            persistdata_ident,
+            quote_spanned! {op_span=> |rcell| rcell.borrow().len() },
+        );
         let write_prologue = quote_spanned! {op_span=>
-            let #persistdata_ident = #df_ident.add_state(::std::cell::RefCell::new(
-                ::std::vec::Vec::new(),
-            ));
+            let #persistdata_ident = #add_state;
+            #size_fn
         };
 
         let write_iterator = if is_pull {
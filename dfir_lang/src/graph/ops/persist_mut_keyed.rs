@@ -46,7 +46,6 @@ pub const PERSIST_MUT_KEYED: OperatorConstraints = OperatorConstraints {
     write_fn: |wc @ &WriteContextArgs {
                    root,
                    context,
-                   df_ident,
                    op_span,
                    ident,
                    inputs,
@@ -79,10 +78,19 @@ pub const PERSIST_MUT_KEYED: OperatorConstraints = OperatorConstraints {
 
         let persistdata_ident = wc.make_ident("persistdata");
         let vec_ident = wc.make_ident("persistvec");
-        let write_prologue = quote_spanned! {op_span=>
-            let #persistdata_ident = #df_ident.add_state(::std::cell::RefCell::new(
+        let add_state = wc.add_state(quote_spanned! {op_span=>
+            ::std::cell::RefCell::new(
                 #root::rustc_hash::FxHashMap::<_, #root::util::sparse_vec::SparseVec<_>>::default()
-            ));
+            )
+        });
+        let size_fn = wc.state_size_fn(
+            Persistence::Mutable,
+            &persistdata_ident,
+            quote_spanned! {op_span=> |rcell| rcell.borrow().len() },
+        );
+        let write_prologue = quote_spanned! {op_span=>
+            let #persistdata_ident = #add_state;
+            #size_fn
         };
 
         let write_iterator = {
@@ -0,0 +1,80 @@
+use quote::quote_spanned;
+
+use super::{FloType, OperatorCategory, OperatorConstraints, RANGE_0, RANGE_1};
+
+/// > 0 input streams, 1 output stream
+///
+/// > Arguments: An [`AsRef`](https://doc.rust-lang.org/std/convert/trait.AsRef.html)`<`[`Path`](https://doc.rust-lang.org/nightly/std/path/struct.Path.html)`>`
+/// > or a [`FileSource`](https://hydro.run/rustdoc/dfir_rs/util/records/struct.FileSource.html)
+/// > for a CSV file to read.
+///
+/// Reads the referenced CSV file one record at a time. The first record is the header row, whose
+/// names are matched to the fields of the record type. `source_csv` may take one generic type
+/// argument, the type of the records, which must implement
+/// [`Deserialize`](https://docs.rs/serde/latest/serde/de/trait.Deserialize.html). Quoted fields
+/// may span multiple lines.
+///
+/// Like [`source_jsonl`](#source_jsonl), emits a `Result<T, RecordError>` per record, so
+/// malformed records are reported with their line number instead of panicking, and supports
+/// following the file with `FileSource::new(path).follow(poll_interval)`.
+///
+/// Note this operator must be used within a Tokio runtime.
+///
+/// ```rustbook
+/// # #[dfir_rs::main]
+/// # async fn main() {
+/// use dfir_rs::util::records::RecordError;
+///
+/// #[derive(serde::Deserialize)]
+/// struct Order {
+///     item: String,
+///     quantity: u32,
+/// }
+///
+/// let file = tempfile::NamedTempFile::new().unwrap();
+/// std::fs::write(file.path(), "item,quantity\napple,3\npear,many\n\"melon, large\",1\n").unwrap();
+///
+/// let (result_send, mut result_recv) = dfir_rs::util::unbounded_channel();
+/// let (error_send, mut error_recv) = dfir_rs::util::unbounded_channel();
+/// let mut flow = dfir_rs::dfir_syntax! {
+///     source_csv::<Order>(file.path()) -> for_each(|record: Result<Order, RecordError>| match record {
+///         Ok(order) => result_send.send((order.item, order.quantity)).unwrap(),
+///         Err(error) => error_send.send(error.line).unwrap(),
+///     });
+/// };
+/// tokio::time::timeout(std::time::Duration::from_secs(1), flow.run_async())
+///     .await
+///     .expect_err("Expected time out");
+///
+/// let orders: Vec<_> = dfir_rs::util::ready_iter(&mut result_recv).collect();
+/// assert_eq!(&[("apple".to_owned(), 3), ("melon, large".to_owned(), 1)], &*orders);
+/// // The third line has a quantity that is not a number.
+/// let errors: Vec<_> = dfir_rs::util::ready_iter(&mut error_recv).collect();
+/// assert_eq!(&[3], &*errors);
+/// # }
+/// ```
+pub const SOURCE_CSV: OperatorConstraints = OperatorConstraints {
+    name: "source_csv",
+    categories: &[OperatorCategory::Source],
+    hard_range_inn: RANGE_0,
+    soft_range_inn: RANGE_0,
+    hard_range_out: RANGE_1,
+    soft_range_out: RANGE_1,
+    num_args: 1,
+    persistence_args: RANGE_0,
+    type_args: &(0..=1),
+    is_external_input: true,
+    has_singleton_output: false,
+    flo_type: Some(FloType::Source),
+    ports_inn: None,
+    ports_out: None,
+    input_delaytype_fn: |_| None,
+    write_fn: |wc, diagnostics| {
+        let root = wc.root;
+        super::source_jsonl::write_record_source(
+            wc,
+            diagnostics,
+            quote_spanned!(wc.op_span=> #root::util::records::csv_stream),
+        )
+    },
+};
@@ -13,6 +13,8 @@ use super::{
 /// Emits units `()` on a repeated interval. The first tick completes immediately. Missed ticks will
 /// be scheduled as soon as possible.
 ///
+/// The ticks are recorded or replayed by the recorder attached with `Dfir::set_recorder`, if any.
+///
 /// Note that this requires the dfir instance be run within a [Tokio `Runtime`](https://docs.rs/tokio/1/tokio/runtime/struct.Runtime.html).
 /// The easiest way to do this is with a [`#[dfir_rs::main]`](https://hydro.run/rustdoc/dfir_rs/attr.main)
 /// annotation on `async fn main() { ... }` as in the example below.
@@ -60,18 +62,23 @@ pub const SOURCE_INTERVAL: OperatorConstraints = OperatorConstraints {
     input_delaytype_fn: |_| None,
     write_fn: |wc @ &WriteContextArgs {
                    root,
+                   df_ident,
                    op_span,
                    arguments,
                    ..
                },
                diagnostics| {
         let ident_intervalstream = wc.make_ident("intervalstream");
+        // Recorded under the stream's identifier, which is stable for a given graph.
+        let record_name = ident_intervalstream.to_string();
         let mut write_prologue = quote_spanned! {op_span=>
-            let #ident_intervalstream =
+            let #ident_intervalstream = #df_ident.recorded_source(
+                #record_name,
                 #root::tokio_stream::StreamExt::map(
                     #root::tokio_stream::wrappers::IntervalStream::new(#root::tokio::time::interval(#arguments)),
                     |_| {  }
-                );
+                ),
+            );
         };
         let wc = WriteContextArgs {
             arguments: &parse_quote_spanned!(op_span=> #ident_intervalstream),
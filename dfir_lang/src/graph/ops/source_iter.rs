@@ -11,7 +11,9 @@ use super::{
 /// Takes the iterable object and delivers its elements downstream
 /// one by one.
 ///
-/// Note that all elements are emitted during the first tick.
+/// Note that all elements are emitted during the first tick. In a
+/// [parallel graph](https://hydro.run/rustdoc/dfir_rs/scheduled/parallel/index.html), the
+/// elements are split round-robin between the workers, so each element is emitted once.
 ///
 /// ```dfir
 ///     source_iter(vec!["Hello", "World"])
@@ -34,6 +36,7 @@ pub const SOURCE_ITER: OperatorConstraints = OperatorConstraints {
     ports_out: None,
     input_delaytype_fn: |_| None,
     write_fn: |wc @ &WriteContextArgs {
+                   root,
                    op_span,
                    ident,
                    arguments,
@@ -46,7 +49,7 @@ pub const SOURCE_ITER: OperatorConstraints = OperatorConstraints {
             let mut #iter_ident = {
                 #[inline(always)]
                 fn check_iter<IntoIter: ::std::iter::IntoIterator<Item = Item>, Item>(into_iter: IntoIter) -> impl ::std::iter::Iterator<Item = Item> {
-                    ::std::iter::IntoIterator::into_iter(into_iter)
+                    #root::util::partition_source(::std::iter::IntoIterator::into_iter(into_iter))
                 }
                 check_iter(#iter)
             };
@@ -0,0 +1,147 @@
+use proc_macro2::TokenStream;
+use quote::quote_spanned;
+use syn::parse_quote_spanned;
+
+use super::{
+    FloType, OpInstGenerics, OperatorCategory, OperatorConstraints, OperatorInstance,
+    OperatorWriteOutput, RANGE_0, RANGE_1, WriteContextArgs, make_missing_runtime_msg,
+};
+use crate::diagnostic::Diagnostic;
+
+/// > 0 input streams, 1 output stream
+///
+/// > Arguments: An [`AsRef`](https://doc.rust-lang.org/std/convert/trait.AsRef.html)`<`[`Path`](https://doc.rust-lang.org/nightly/std/path/struct.Path.html)`>`
+/// > or a [`FileSource`](https://hydro.run/rustdoc/dfir_rs/util/records/struct.FileSource.html)
+/// > for a [JSON Lines](https://jsonlines.org/) file to read.
+///
+/// Reads the referenced file one line at a time, deserializing each non-blank line as a JSON value.
+/// `source_jsonl` may take one generic type argument, the type of the records, which must
+/// implement [`Deserialize`](https://docs.rs/serde/latest/serde/de/trait.Deserialize.html).
+///
+/// Emits a `Result<T, RecordError>` per record rather than panicking, so a malformed line (or a
+/// file that cannot be opened) is reported as a [`RecordError`](https://hydro.run/rustdoc/dfir_rs/util/records/struct.RecordError.html)
+/// holding the line number, and reading continues with the next line.
+///
+/// Passing `FileSource::new(path).follow(poll_interval)` keeps reading lines as they are appended
+/// to the file, like `tail -f`, instead of ending at the end of the file.
+///
+/// Note this operator must be used within a Tokio runtime.
+///
+/// ```rustbook
+/// # #[dfir_rs::main]
+/// # async fn main() {
+/// use dfir_rs::util::records::RecordError;
+///
+/// #[derive(serde::Deserialize)]
+/// struct Reading {
+///     sensor: String,
+///     value: f64,
+/// }
+///
+/// let file = tempfile::NamedTempFile::new().unwrap();
+/// std::fs::write(
+///     file.path(),
+///     "{\"sensor\":\"a\",\"value\":1.5}\n{\"sensor\":\"b\"}\n{\"sensor\":\"c\",\"value\":-2}\n",
+/// )
+/// .unwrap();
+///
+/// let (result_send, mut result_recv) = dfir_rs::util::unbounded_channel();
+/// let (error_send, mut error_recv) = dfir_rs::util::unbounded_channel();
+/// let mut flow = dfir_rs::dfir_syntax! {
+///     source_jsonl::<Reading>(file.path()) -> for_each(|record: Result<Reading, RecordError>| match record {
+///         Ok(reading) => result_send.send(reading.sensor).unwrap(),
+///         Err(error) => error_send.send(error.line).unwrap(),
+///     });
+/// };
+/// tokio::time::timeout(std::time::Duration::from_secs(1), flow.run_async())
+///     .await
+///     .expect_err("Expected time out");
+///
+/// let sensors: Vec<_> = dfir_rs::util::ready_iter(&mut result_recv).collect();
+/// assert_eq!(&["a", "c"], &*sensors);
+/// // The second line is missing its `value`.
+/// let errors: Vec<_> = dfir_rs::util::ready_iter(&mut error_recv).collect();
+/// assert_eq!(&[2], &*errors);
+/// # }
+/// ```
+pub const SOURCE_JSONL: OperatorConstraints = OperatorConstraints {
+    name: "source_jsonl",
+    categories: &[OperatorCategory::Source],
+    hard_range_inn: RANGE_0,
+    soft_range_inn: RANGE_0,
+    hard_range_out: RANGE_1,
+    soft_range_out: RANGE_1,
+    num_args: 1,
+    persistence_args: RANGE_0,
+    type_args: &(0..=1),
+    is_external_input: true,
+    has_singleton_output: false,
+    flo_type: Some(FloType::Source),
+    ports_inn: None,
+    ports_out: None,
+    input_delaytype_fn: |_| None,
+    write_fn: |wc, diagnostics| {
+        let root = wc.root;
+        write_record_source(wc, diagnostics, quote_spanned!(wc.op_span=> #root::util::records::jsonl_stream))
+    },
+};
+
+/// Shared by `source_jsonl` and `source_csv`: emits the records of the stream returned by calling
+/// `stream_fn` on the file argument.
+pub(crate) fn write_record_source(
+    wc @ &WriteContextArgs {
+        root,
+        op_span,
+        op_name,
+        op_inst:
+            OperatorInstance {
+                generics: OpInstGenerics { type_args, .. },
+                ..
+            },
+        arguments,
+        ..
+    }: &WriteContextArgs<'_>,
+    diagnostics: &mut Vec<Diagnostic>,
+    stream_fn: TokenStream,
+) -> Result<OperatorWriteOutput, ()> {
+    let source_arg = &arguments[0];
+    let record_type = type_args
+        .first()
+        .map(|ty| quote_spanned!(op_span=> #ty))
+        .unwrap_or_else(|| quote_spanned!(op_span=> _));
+
+    let ident_records = wc.make_ident("records");
+
+    let missing_runtime_msg = make_missing_runtime_msg(op_name);
+
+    let write_prologue = quote_spanned! {op_span=>
+        let #ident_records = #stream_fn::<#record_type>(#source_arg);
+    };
+    let wc = WriteContextArgs {
+        arguments: &parse_quote_spanned!(op_span=> #ident_records),
+        ..wc.clone()
+    };
+
+    let OperatorWriteOutput {
+        write_prologue: write_prologue_stream,
+        write_prologue_after,
+        write_iterator,
+        write_iterator_after,
+    } = (super::source_stream::SOURCE_STREAM.write_fn)(&wc, diagnostics)?;
+
+    let write_prologue = quote_spanned! {op_span=>
+        #write_prologue
+        #write_prologue_stream
+    };
+    let write_iterator = quote_spanned! {op_span=>
+        ::std::debug_assert!(#root::tokio::runtime::Handle::try_current().is_ok(), #missing_runtime_msg);
+        #write_iterator
+    };
+
+    Ok(OperatorWriteOutput {
+        write_prologue,
+        write_prologue_after,
+        write_iterator,
+        write_iterator_after,
+    })
+}
@@ -102,11 +102,14 @@ pub const STATE_BY: OperatorConstraints = OperatorConstraints {
         let state_ident = singleton_output_ident;
         let factory_fn = &arguments[1];
 
+        let add_state = wc.add_state(quote_spanned! {op_span=>
+            ::std::cell::RefCell::new(data_struct)
+        });
         let write_prologue = quote_spanned! {op_span=>
             let #state_ident = {
                 let data_struct: #lattice_type = (#factory_fn)();
-                ::std::debug_assert!(::lattices::IsBot::is_bot(&data_struct));
-                #df_ident.add_state(::std::cell::RefCell::new(data_struct))
+                ::std::debug_assert!(#root::lattices::IsBot::is_bot(&data_struct));
+                #add_state
             };
         };
         let write_prologue_after = wc
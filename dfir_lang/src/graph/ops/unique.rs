@@ -75,8 +75,14 @@ pub const UNIQUE: OperatorConstraints = OperatorConstraints {
 
         let uniquedata_ident = wc.make_ident("uniquedata");
 
+        let size_fn = wc.state_size_fn(
+            persistence,
+            &uniquedata_ident,
+            quote_spanned! {op_span=> |rcell| rcell.borrow().len() },
+        );
         let write_prologue = quote_spanned! {op_span=>
             let #uniquedata_ident = #df_ident.add_state(::std::cell::RefCell::new(#root::rustc_hash::FxHashSet::default()));
+            #size_fn
         };
         let write_prologue_after = wc
             .persistence_as_state_lifespan(persistence)
@@ -0,0 +1,120 @@
+//! Module for [`CheckpointDir`], which stores checkpoints of durable state on disk.
+//!
+//! Durable state is added with [`Dfir::add_durable_state`](super::graph::Dfir::add_durable_state),
+//! or by marking an operator's state `'durable` in the surface syntax, i.e.
+//! `my_sum = fold::<'static, 'durable>(..)`. Durable operators must be assigned to a variable,
+//! whose name keys their state in the checkpoint.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use web_time::Instant;
+
+const CHECKPOINT_FILE: &str = "checkpoint.bin";
+const CHECKPOINT_TMP_FILE: &str = "checkpoint.bin.tmp";
+
+/// The serialized value of each durable state, by key.
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub(crate) struct Checkpoint {
+    pub(crate) states: BTreeMap<String, Vec<u8>>,
+}
+
+/// A directory holding the latest checkpoint of a [`Dfir`](super::graph::Dfir) instance's
+/// durable state.
+///
+/// Each checkpoint is written to a temporary file which is synced and then atomically renamed
+/// over the previous checkpoint, so a crash during a checkpoint leaves the previous one intact.
+///
+/// Checkpoints are written automatically at the end of a tick once the [interval](Self::interval)
+/// has passed since the last one, so up to an interval's worth of state changes can be lost on a
+/// crash.
+#[derive(Debug)]
+pub struct CheckpointDir {
+    path: PathBuf,
+    interval: Duration,
+    /// When the last checkpoint was written, or `None` if none has been written yet.
+    last_write: Option<Instant>,
+    /// Digest of the last checkpoint written or read, to skip writing unchanged checkpoints.
+    last_digest: Option<u64>,
+}
+
+impl CheckpointDir {
+    /// The default [`Self::interval`].
+    pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(1);
+
+    /// Opens the checkpoint directory at `path`, creating it if it does not exist.
+    pub fn open(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+        std::fs::create_dir_all(&path)?;
+        Ok(Self {
+            path,
+            interval: Self::DEFAULT_INTERVAL,
+            last_write: None,
+            last_digest: None,
+        })
+    }
+
+    /// Sets the minimum time between automatic checkpoints. With [`Duration::ZERO`], a
+    /// checkpoint is written at the end of every tick.
+    pub fn interval(self, interval: Duration) -> Self {
+        Self { interval, ..self }
+    }
+
+    /// The path of the directory.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether the interval has passed since the last checkpoint was written.
+    pub(crate) fn is_due(&self) -> bool {
+        self.last_write
+            .is_none_or(|last_write| self.interval <= last_write.elapsed())
+    }
+
+    /// Reads the latest checkpoint, if one has been written.
+    pub(crate) fn read(&mut self) -> io::Result<Option<Checkpoint>> {
+        let bytes = match std::fs::read(self.path.join(CHECKPOINT_FILE)) {
+            Ok(bytes) => bytes,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        let checkpoint = bincode::deserialize(&bytes).map_err(io::Error::other)?;
+        self.last_digest = Some(digest(&bytes));
+        Ok(Some(checkpoint))
+    }
+
+    /// Replaces the latest checkpoint with `checkpoint`, unless they are identical.
+    pub(crate) fn write(&mut self, checkpoint: &Checkpoint) -> io::Result<()> {
+        let bytes = bincode::serialize(checkpoint).map_err(io::Error::other)?;
+        let bytes_digest = digest(&bytes);
+        if self.last_digest == Some(bytes_digest) {
+            self.last_write = Some(Instant::now());
+            return Ok(());
+        }
+
+        let tmp_path = self.path.join(CHECKPOINT_TMP_FILE);
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(&bytes)?;
+        file.sync_all()?;
+        std::fs::rename(&tmp_path, self.path.join(CHECKPOINT_FILE))?;
+        // Make sure the rename itself is durable.
+        #[cfg(unix)]
+        File::open(&self.path)?.sync_all()?;
+
+        self.last_digest = Some(bytes_digest);
+        self.last_write = Some(Instant::now());
+        Ok(())
+    }
+}
+
+/// Digest of a serialized checkpoint, only compared within a single process.
+fn digest(bytes: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    bytes.hash(&mut hasher);
+    hasher.finish()
+}
@@ -3,17 +3,21 @@
 //! Provides APIs for state and scheduling.
 
 use std::any::Any;
+use std::borrow::Cow;
 use std::cell::Cell;
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, VecDeque};
 use std::future::Future;
 use std::marker::PhantomData;
 use std::ops::DerefMut;
 use std::pin::Pin;
 
+use serde::Serialize;
+use serde::de::DeserializeOwned;
 use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
 use tokio::task::JoinHandle;
 use web_time::SystemTime;
 
+use super::checkpoint::Checkpoint;
 use super::graph::StateLifespan;
 use super::state::StateHandle;
 use super::{LoopId, LoopTag, StateId, StateTag, SubgraphId, SubgraphTag};
@@ -29,6 +33,8 @@ use crate::util::slot_vec::{SecondarySlotVec, SlotVec};
 pub struct Context {
     /// Storage for the user-facing State API.
     states: SlotVec<StateTag, StateData>,
+    /// States which are saved in and restored from checkpoints.
+    durable_states: Vec<DurableStateData>,
 
     /// Priority stack for handling strata within loops. Prioritized by loop depth.
     pub(super) stratum_stack: PriorityStack<usize>,
@@ -219,6 +225,7 @@ impl Context {
             state: Box::new(state),
             lifespan_hook_fn: None,
             lifespan: None,
+            size_fn: None,
         };
         let state_id = self.states.insert(state_data);
 
@@ -228,6 +235,32 @@ impl Context {
         }
     }
 
+    /// Adds durable state to the context and returns the handle. Durable state is saved under
+    /// `key` in each checkpoint, and replaced by the saved value when a checkpoint is restored.
+    pub fn add_durable_state<T>(&mut self, key: impl Into<String>, state: T) -> StateHandle<T>
+    where
+        T: Any + Serialize + DeserializeOwned,
+    {
+        let key = key.into();
+        assert!(
+            self.durable_states.iter().all(|durable| durable.key != key),
+            "Durable state `{}` was added twice.",
+            key
+        );
+
+        let handle = self.add_state(state);
+        self.durable_states.push(DurableStateData {
+            key,
+            state_id: handle.state_id,
+            save_fn: |state| bincode::serialize(state.downcast_ref::<T>().unwrap()),
+            restore_fn: |state, bytes| {
+                *state.downcast_mut::<T>().unwrap() = bincode::deserialize(bytes)?;
+                Ok(())
+            },
+        });
+        handle
+    }
+
     /// Sets a hook to modify the state at the end of each tick, using the supplied closure.
     pub fn set_state_lifespan_hook<T>(
         &mut self,
@@ -266,6 +299,38 @@ impl Context {
         }
     }
 
+    /// Sets a function to measure the size of the state (for example its number of items), which
+    /// is reported under `name` by [`Dfir::snapshot`](super::graph::Dfir::snapshot).
+    pub fn set_state_size_fn<T>(
+        &mut self,
+        handle: StateHandle<T>,
+        name: impl Into<Cow<'static, str>>,
+        size_fn: impl 'static + Fn(&T) -> usize,
+    ) where
+        T: Any,
+    {
+        let state_data = self
+            .states
+            .get_mut(handle.state_id)
+            .expect("Failed to find state with given handle.");
+        state_data.size_fn = Some((
+            name.into(),
+            Box::new(move |state| (size_fn)(state.downcast_ref::<T>().unwrap())),
+        ));
+    }
+
+    /// Returns the name, current size, and durability of each state with a size function.
+    pub(super) fn state_sizes(&self) -> impl '_ + Iterator<Item = (&'_ str, usize, bool)> {
+        self.states.iter().filter_map(|(state_id, state_data)| {
+            let (name, size_fn) = state_data.size_fn.as_ref()?;
+            let durable = self
+                .durable_states
+                .iter()
+                .any(|durable| durable.state_id == state_id);
+            Some((&**name, (size_fn)(state_data.state.as_ref()), durable))
+        })
+    }
+
     /// Prepares an async task to be launched by [`Self::spawn_tasks`].
     pub fn request_task<Fut>(&mut self, future: Fut)
     where
@@ -303,6 +368,7 @@ impl Default for Context {
         let (stratum_stack, loop_depth) = Default::default();
         Self {
             states: SlotVec::new(),
+            durable_states: Vec::new(),
 
             stratum_stack,
 
@@ -350,6 +416,31 @@ impl Context {
         }
     }
 
+    /// Serializes all durable state into a checkpoint.
+    pub(super) fn save_durable_states(&self) -> bincode::Result<Checkpoint> {
+        let mut states = BTreeMap::new();
+        for durable in self.durable_states.iter() {
+            let state = &self.states[durable.state_id].state;
+            states.insert(durable.key.clone(), (durable.save_fn)(state.as_ref())?);
+        }
+        Ok(Checkpoint { states })
+    }
+
+    /// Replaces all durable state saved in `checkpoint` with the saved values. Durable state
+    /// missing from the checkpoint keeps its current value.
+    pub(super) fn restore_durable_states(
+        &mut self,
+        checkpoint: &Checkpoint,
+    ) -> bincode::Result<()> {
+        for durable in self.durable_states.iter() {
+            if let Some(bytes) = checkpoint.states.get(&durable.key) {
+                let state = &mut self.states[durable.state_id].state;
+                (durable.restore_fn)(state.as_mut(), bytes)?;
+            }
+        }
+        Ok(())
+    }
+
     /// Call this at the end of a tick,
     pub(super) fn run_state_hooks_tick(&mut self) {
         tracing::trace!("Running state hooks for tick.");
@@ -358,6 +449,7 @@ impl Context {
                 state,
                 lifespan_hook_fn: Some(lifespan_hook_fn),
                 lifespan: Some(StateLifespan::Tick),
+                ..
             } = state_data
             else {
                 continue;
@@ -372,7 +464,7 @@ impl Context {
             let StateData {
                 state,
                 lifespan_hook_fn,
-                lifespan: _,
+                ..
             } = self
                 .states
                 .get_mut(*state_id)
@@ -395,7 +487,7 @@ impl Context {
             let StateData {
                 state,
                 lifespan_hook_fn,
-                lifespan: _,
+                ..
             } = self
                 .states
                 .get_mut(*state_id)
@@ -414,5 +506,16 @@ struct StateData {
     lifespan_hook_fn: Option<LifespanResetFn>, // TODO(mingwei): replace with trait?
     /// `None` for static.
     lifespan: Option<StateLifespan>,
+    /// Name and size function, see [`Context::set_state_size_fn`].
+    size_fn: Option<(Cow<'static, str>, StateSizeFn)>,
 }
 type LifespanResetFn = Box<dyn FnMut(&mut dyn Any)>;
+type StateSizeFn = Box<dyn Fn(&dyn Any) -> usize>;
+
+/// Internal struct for serializing and deserializing a durable state.
+struct DurableStateData {
+    key: String,
+    state_id: StateId,
+    save_fn: fn(&dyn Any) -> bincode::Result<Vec<u8>>,
+    restore_fn: fn(&mut dyn Any, &[u8]) -> bincode::Result<()>,
+}
@@ -2,22 +2,33 @@
 
 use std::any::Any;
 use std::borrow::Cow;
-use std::cell::Cell;
+use std::cell::{Cell, RefCell};
 use std::cmp::Ordering;
 use std::future::Future;
+use std::io;
 use std::marker::PhantomData;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
 
 #[cfg(feature = "meta")]
 use dfir_lang::diagnostic::{Diagnostic, SerdeSpan};
 #[cfg(feature = "meta")]
 use dfir_lang::graph::DfirGraph;
+use futures::Stream;
 use ref_cast::RefCast;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
 use smallvec::SmallVec;
-use web_time::SystemTime;
+use web_time::{Instant, SystemTime};
 
+use super::checkpoint::CheckpointDir;
 use super::context::Context;
 use super::handoff::handoff_list::PortList;
 use super::handoff::{Handoff, HandoffMeta, TeeingHandoff};
+use super::introspect::{GraphSnapshot, HandoffSnapshot, StateSnapshot, SubgraphSnapshot};
+use super::metrics::DfirMetrics;
+#[cfg(not(target_arch = "wasm32"))]
+use super::parallel::Lockstep;
 use super::port::{RECV, RecvCtx, RecvPort, SEND, SendCtx, SendPort};
 use super::reactor::Reactor;
 use super::state::StateHandle;
@@ -25,6 +36,7 @@ use super::subgraph::Subgraph;
 use super::{HandoffId, HandoffTag, LoopId, LoopTag, SubgraphId, SubgraphTag};
 use crate::Never;
 use crate::scheduled::ticks::{TickDuration, TickInstant};
+use crate::util::replay::{NetworkItem, RecordedStream, Recorder, RecorderSlot};
 use crate::util::slot_vec::{SecondarySlotVec, SlotVec};
 
 /// A DFIR graph. Owns, schedules, and runs the compiled subgraphs.
@@ -38,6 +50,18 @@ pub struct Dfir<'a> {
 
     handoffs: SlotVec<HandoffTag, HandoffData>,
 
+    /// See [`Self::set_checkpoint_dir()`].
+    checkpoint_dir: Option<CheckpointDir>,
+
+    /// See [`Self::enable_metrics()`].
+    metrics: Option<Rc<RefCell<DfirMetrics>>>,
+
+    /// Snapshot refreshed at subgraph boundaries, see [`Self::serve_introspection()`].
+    introspection: Option<Arc<Mutex<GraphSnapshot>>>,
+
+    /// See [`Self::set_recorder()`].
+    recorder: RecorderSlot,
+
     #[cfg(feature = "meta")]
     /// See [`Self::meta_graph()`].
     meta_graph: Option<DfirGraph>,
@@ -209,6 +233,153 @@ impl<'a> Dfir<'a> {
         self.context.current_stratum
     }
 
+    /// Starts collecting runtime metrics, see [`metrics`](super::metrics). Metrics are off by
+    /// default, as timing every subgraph run has a cost.
+    pub fn enable_metrics(&mut self) {
+        self.metrics.get_or_insert_with(Default::default);
+    }
+
+    /// Returns a snapshot of the runtime metrics collected so far, or `None` if they have not
+    /// been enabled with [`Self::enable_metrics`].
+    pub fn metrics(&self) -> Option<DfirMetrics> {
+        self.metrics
+            .as_ref()
+            .map(|metrics| metrics.borrow().clone())
+    }
+
+    /// Serves the runtime metrics in the OpenMetrics text format at `http://<addr>/metrics`,
+    /// for scraping by Prometheus. Returns the bound address, which is useful if `addr` has port
+    /// zero.
+    ///
+    /// Enables metrics if they are not already, see [`Self::enable_metrics`]. The server runs as
+    /// a task of this graph, so only responds while the graph is running.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn serve_metrics(
+        &mut self,
+        addr: impl std::net::ToSocketAddrs,
+    ) -> io::Result<std::net::SocketAddr> {
+        let listener = std::net::TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        let local_addr = listener.local_addr()?;
+        let metrics = self.metrics.get_or_insert_with(Default::default).clone();
+        self.request_task(super::metrics::serve(listener, metrics));
+        Ok(local_addr)
+    }
+
+    /// Returns a snapshot of the live data of this graph, see [`introspect`](super::introspect).
+    pub fn snapshot(&self) -> GraphSnapshot {
+        let metrics = self.metrics.as_ref().map(|metrics| metrics.borrow());
+        GraphSnapshot {
+            tick: u64::from(self.context.current_tick),
+            stratum: self.context.current_stratum,
+            subgraphs: self
+                .subgraphs
+                .iter()
+                .map(|(sg_id, sg_data)| {
+                    let sg_metrics = metrics.as_ref().and_then(|metrics| metrics.subgraph(sg_id));
+                    SubgraphSnapshot {
+                        id: sg_id.to_string(),
+                        name: sg_data.name.clone(),
+                        stratum: sg_data.stratum,
+                        is_scheduled: sg_data.is_scheduled.get(),
+                        runs: sg_metrics.map_or(0, |m| m.runs),
+                        run_time_us: sg_metrics.map_or(0, |m| m.run_time.as_micros() as u64),
+                    }
+                })
+                .collect(),
+            handoffs: self
+                .handoffs
+                .iter()
+                .map(|(hoff_id, hoff_data)| HandoffSnapshot {
+                    id: hoff_id.to_string(),
+                    name: hoff_data.name.clone(),
+                    preds: hoff_data.preds.iter().map(ToString::to_string).collect(),
+                    succs: hoff_data.succs.iter().map(ToString::to_string).collect(),
+                    items: hoff_data.handoff.item_count(),
+                    items_given: hoff_data.handoff.items_given(),
+                })
+                .collect(),
+            states: self
+                .context
+                .state_sizes()
+                .map(|(name, size, durable)| StateSnapshot {
+                    name: name.to_owned(),
+                    size,
+                    durable,
+                })
+                .collect(),
+        }
+    }
+
+    /// Replaces the snapshot served by [`Self::serve_introspection`], if any.
+    fn refresh_introspection(&self) {
+        if let Some(introspection) = &self.introspection {
+            *introspection.lock().unwrap() = self.snapshot();
+        }
+    }
+
+    /// Serves the [`meta_graph`](Self::meta_graph) (if any) annotated with the live data of this
+    /// graph as JSON at `http://<addr>/graph`, in the form `{"graph": <meta graph>, "live":
+    /// <snapshot>}` (see [`Self::snapshot`]). Returns the bound address, which is useful if
+    /// `addr` has port zero.
+    ///
+    /// The live data is refreshed before each subgraph runs, after each stratum, and at the end
+    /// of each tick. The server runs on its own thread, so it responds even while the graph is
+    /// busy or stuck, and responds with `404 Not Found` once this graph has been dropped.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn serve_introspection(
+        &mut self,
+        addr: impl std::net::ToSocketAddrs,
+    ) -> io::Result<std::net::SocketAddr> {
+        #[cfg(feature = "meta")]
+        let meta_graph_json = serde_json::to_string(&self.meta_graph)?;
+        #[cfg(not(feature = "meta"))]
+        let meta_graph_json = "null".to_owned();
+
+        let listener = std::net::TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        let local_addr = listener.local_addr()?;
+        let snapshot = Arc::new(Mutex::new(self.snapshot()));
+        let snapshot = self.introspection.get_or_insert(snapshot);
+        super::introspect::spawn_server(listener, meta_graph_json, Arc::downgrade(snapshot))?;
+        Ok(local_addr)
+    }
+
+    /// Attaches `recorder`, so that it records or replays the items of this graph's
+    /// `source_interval`s and network sources, and of any sources wrapped with
+    /// [`Recorder::source`], by this graph's tick. See [`replay`](crate::util::replay).
+    pub fn set_recorder(&mut self, recorder: Recorder) {
+        recorder
+            .set_tick(u64::from(self.context.current_tick))
+            .expect("Failed to flush recording.");
+        *self.recorder.borrow_mut() = Some(recorder);
+    }
+
+    /// Wraps `stream`, which is recorded or replayed under `name` by the recorder attached to this
+    /// graph, if any. Unlike [`Recorder::source`], the recorder may be attached after the source
+    /// is created. Used by `source_interval`.
+    pub fn recorded_source<S>(&self, name: impl Into<String>, stream: S) -> RecordedStream<S>
+    where
+        S: Stream + Unpin,
+        S::Item: Serialize + DeserializeOwned,
+    {
+        RecordedStream::source(self.recorder.clone(), name.into(), stream)
+    }
+
+    /// Wraps a network source `stream`, which is recorded or replayed under `name` by the recorder
+    /// attached to this graph, if any. Used by Hydro network sources.
+    pub fn recorded_network_source<S>(
+        &self,
+        name: impl Into<String>,
+        stream: S,
+    ) -> RecordedStream<S>
+    where
+        S: Stream + Unpin,
+        S::Item: NetworkItem,
+    {
+        RecordedStream::network_source(self.recorder.clone(), name.into(), stream)
+    }
+
     /// Runs the dataflow until the next tick begins.
     /// Returns true if any work was done.
     #[tracing::instrument(level = "trace", skip(self), ret)]
@@ -260,6 +431,69 @@ impl<'a> Dfir<'a> {
         work_done
     }
 
+    /// Runs one tick in lockstep with the other workers of a parallel graph, see
+    /// [`parallel`](super::parallel). Every stratum is run on every worker, and a stratum only
+    /// ends once no worker has work left in it.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn run_tick_lockstep(&mut self, lockstep: &Lockstep) -> bool {
+        debug_assert_eq!(0, self.context.current_stratum);
+
+        let mut work_done = false;
+        for stratum in 0..self.context.stratum_queues.len() {
+            self.context.current_stratum = stratum;
+            loop {
+                work_done |= self.run_stratum();
+                // Wait for all workers' sends, then pick up the subgraphs they woke.
+                lockstep.wait();
+                self.try_recv_events();
+                if !lockstep.any(!self.context.stratum_queues[stratum].is_empty()) {
+                    break;
+                }
+            }
+        }
+        self.end_tick();
+        self.context.can_start_tick = false;
+        work_done
+    }
+
+    /// Returns true if any subgraph is scheduled to run.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn has_work(&self) -> bool {
+        self.context
+            .stratum_queues
+            .iter()
+            .any(|queue| !queue.is_empty())
+    }
+
+    /// Runs end-of-tick hooks and moves to stratum 0 of the next tick.
+    fn end_tick(&mut self) {
+        if let Some(metrics) = &self.metrics {
+            metrics.borrow_mut().ticks += 1;
+        }
+        self.context.run_state_hooks_tick();
+        if self
+            .checkpoint_dir
+            .as_ref()
+            .is_some_and(CheckpointDir::is_due)
+        {
+            if let Err(err) = self.checkpoint() {
+                // The next tick retries, the previous checkpoint is still intact.
+                tracing::error!(error = %err, "Failed to write checkpoint.");
+            }
+        }
+
+        self.context.current_stratum = 0;
+        self.context.current_tick += TickDuration::SINGLE_TICK;
+        self.context.events_received_tick = false;
+
+        self.refresh_introspection();
+        if let Some(recorder) = &*self.recorder.borrow() {
+            recorder
+                .set_tick(u64::from(self.context.current_tick))
+                .expect("Failed to flush recording.");
+        }
+    }
+
     /// Runs the current stratum of the dataflow until no more local work is available (does not receive events).
     /// Returns true if any work was done.
     #[tracing::instrument(level = "trace", skip(self), fields(tick = u64::from(self.context.current_tick), stratum = self.context.current_stratum), ret)]
@@ -277,6 +511,10 @@ impl<'a> Dfir<'a> {
                 let sg_data = &mut self.subgraphs[sg_id];
                 // This must be true for the subgraph to be enqueued.
                 assert!(sg_data.is_scheduled.take());
+            }
+            self.refresh_introspection();
+            {
+                let sg_data = &mut self.subgraphs[sg_id];
 
                 let _enter = tracing::info_span!(
                     "run-subgraph",
@@ -372,7 +610,18 @@ impl<'a> Dfir<'a> {
                 self.context.run_state_hooks_subgraph(sg_id);
 
                 tracing::info!("Running subgraph.");
+                let run_start = self.metrics.is_some().then(Instant::now);
                 sg_data.subgraph.run(&mut self.context, &mut self.handoffs);
+                if let Some((metrics, run_start)) = self.metrics.as_ref().zip(run_start) {
+                    metrics.borrow_mut().record_run(
+                        sg_id,
+                        &sg_data.name,
+                        run_start.elapsed(),
+                        &sg_data.preds,
+                        &sg_data.succs,
+                        &self.handoffs,
+                    );
+                }
 
                 sg_data.last_tick_run_in = Some(self.context.current_tick);
             }
@@ -423,6 +672,9 @@ impl<'a> Dfir<'a> {
 
             work_done = true;
         }
+        if work_done {
+            self.refresh_introspection();
+        }
         work_done
     }
 
@@ -513,11 +765,7 @@ impl<'a> Dfir<'a> {
                         self.context.current_tick,
                         self.context.current_tick + TickDuration::SINGLE_TICK,
                     );
-                    self.context.run_state_hooks_tick();
-
-                    self.context.current_stratum = 0;
-                    self.context.current_tick += TickDuration::SINGLE_TICK;
-                    self.context.events_received_tick = false;
+                    self.end_tick();
 
                     if current_tick_only {
                         tracing::trace!(
@@ -954,6 +1202,53 @@ impl<'a> Dfir<'a> {
         self.context.add_state(state)
     }
 
+    /// Adds referenceable state which survives restarts into this instance. The state is saved
+    /// under `key` in each checkpoint (see [`Self::set_checkpoint_dir`]), and `key` must be the
+    /// same across restarts for the state to be restored.
+    ///
+    /// This is part of the "state API".
+    pub fn add_durable_state<T>(&mut self, key: impl Into<String>, state: T) -> StateHandle<T>
+    where
+        T: Any + Serialize + DeserializeOwned,
+    {
+        self.context.add_durable_state(key, state)
+    }
+
+    /// Restores all durable state from the latest checkpoint in `dir`, if there is one. Then at
+    /// the end of each tick where the directory's [interval](CheckpointDir::interval) has passed,
+    /// replaces the checkpoint with the current durable state.
+    ///
+    /// Must be called before the first tick is run.
+    pub fn set_checkpoint_dir(&mut self, mut dir: CheckpointDir) -> io::Result<()> {
+        assert_eq!(
+            TickInstant::default(),
+            self.context.current_tick,
+            "Checkpoints must be restored before the first tick."
+        );
+
+        if let Some(checkpoint) = dir.read()? {
+            self.context
+                .restore_durable_states(&checkpoint)
+                .map_err(io::Error::other)?;
+        }
+        self.checkpoint_dir = Some(dir);
+        Ok(())
+    }
+
+    /// Immediately replaces the checkpoint in the directory set by [`Self::set_checkpoint_dir`]
+    /// with the current durable state. Checkpoints are also written automatically at the end of
+    /// ticks, see [`CheckpointDir::interval`].
+    pub fn checkpoint(&mut self) -> io::Result<()> {
+        let checkpoint = self
+            .context
+            .save_durable_states()
+            .map_err(io::Error::other)?;
+        self.checkpoint_dir
+            .as_mut()
+            .expect("No checkpoint directory set, see `Dfir::set_checkpoint_dir`.")
+            .write(&checkpoint)
+    }
+
     /// Sets a hook to modify the state at the end of each tick, using the supplied closure.
     ///
     /// This is part of the "state API".
@@ -969,6 +1264,21 @@ impl<'a> Dfir<'a> {
             .set_state_lifespan_hook(handle, lifespan, hook_fn)
     }
 
+    /// Sets a function to measure the size of the state, which is reported by
+    /// [`Self::snapshot`].
+    ///
+    /// This is part of the "state API".
+    pub fn set_state_size_fn<T>(
+        &mut self,
+        handle: StateHandle<T>,
+        name: impl Into<Cow<'static, str>>,
+        size_fn: impl 'static + Fn(&T) -> usize,
+    ) where
+        T: Any,
+    {
+        self.context.set_state_size_fn(handle, name, size_fn)
+    }
+
     /// Gets a exclusive (mut) ref to the internal context, setting the subgraph ID.
     pub fn context_mut(&mut self, sg_id: SubgraphId) -> &mut Context {
         self.context.subgraph_id = sg_id;
@@ -1089,7 +1399,6 @@ pub(super) struct SubgraphData<'a> {
     /// The actual execution code of the subgraph.
     subgraph: Box<dyn Subgraph + 'a>,
 
-    #[expect(dead_code, reason = "may be useful in the future")]
     preds: Vec<HandoffId>,
     succs: Vec<HandoffId>,
 
@@ -36,6 +36,18 @@ pub trait HandoffMeta: Any {
     // TODO(justin): more fine-grained info here.
     /// Return if the handoff is empty.
     fn is_bottom(&self) -> bool;
+
+    /// Return the number of items currently buffered in the handoff, or zero if the handoff does
+    /// not count its items.
+    fn item_count(&self) -> usize {
+        0
+    }
+
+    /// Return the total number of items ever given to the handoff, or zero if the handoff does
+    /// not count its items.
+    fn items_given(&self) -> u64 {
+        0
+    }
 }
 
 impl<H> HandoffMeta for Rc<RefCell<H>>
@@ -49,6 +61,14 @@ where
     fn is_bottom(&self) -> bool {
         self.borrow().is_bottom()
     }
+
+    fn item_count(&self) -> usize {
+        self.borrow().item_count()
+    }
+
+    fn items_given(&self) -> u64 {
+        self.borrow().items_given()
+    }
 }
 
 /// Trait for handoffs to implement.
@@ -9,12 +9,15 @@ use super::{CanReceive, Handoff, HandoffMeta};
 
 struct ReaderHandoff<T> {
     contents: VecDeque<Vec<T>>,
+    /// Total number of items given to this reader, for metrics.
+    given: u64,
 }
 
 impl<T> Default for ReaderHandoff<T> {
     fn default() -> Self {
         Self {
             contents: Default::default(),
+            given: 0,
         }
     }
 }
@@ -83,6 +86,19 @@ impl<T> HandoffMeta for TeeingHandoff<T> {
             .iter()
             .all(Vec::is_empty)
     }
+
+    fn item_count(&self) -> usize {
+        self.internal.borrow().readers[self.read_from]
+            .1
+            .contents
+            .iter()
+            .map(Vec::len)
+            .sum()
+    }
+
+    fn items_given(&self) -> u64 {
+        self.internal.borrow().readers[self.read_from].1.given
+    }
 }
 
 impl<T> Handoff for TeeingHandoff<T> {
@@ -110,10 +126,12 @@ where
         if let Some((last, rest)) = readers.split_last_mut() {
             for reader in rest {
                 if reader.0 {
+                    reader.1.given += vec.len() as u64;
                     reader.1.contents.push_back(vec.clone());
                 }
             }
             if last.0 {
+                last.1.given += vec.len() as u64;
                 last.1.contents.push_back(vec);
             }
         }
@@ -1,5 +1,5 @@
 use std::any::Any;
-use std::cell::{RefCell, RefMut};
+use std::cell::{Cell, RefCell, RefMut};
 use std::rc::Rc;
 
 use super::{CanReceive, Handoff, HandoffMeta, Iter};
@@ -11,6 +11,8 @@ where
 {
     pub(crate) input: Rc<RefCell<Vec<T>>>,
     pub(crate) output: Rc<RefCell<Vec<T>>>,
+    /// Total number of items given, for metrics.
+    given: Cell<u64>,
 }
 impl<T> Default for VecHandoff<T>
 where
@@ -20,6 +22,7 @@ where
         Self {
             input: Default::default(),
             output: Default::default(),
+            given: Cell::new(0),
         }
     }
 }
@@ -43,7 +46,8 @@ impl<T> Handoff for VecHandoff<T> {
 impl<T> CanReceive<Option<T>> for VecHandoff<T> {
     fn give(&self, mut item: Option<T>) -> Option<T> {
         if let Some(item) = item.take() {
-            (*self.input).borrow_mut().push(item)
+            (*self.input).borrow_mut().push(item);
+            self.given.set(self.given.get() + 1);
         }
         None
     }
@@ -53,12 +57,17 @@ where
     I: Iterator<Item = T>,
 {
     fn give(&self, mut iter: Iter<I>) -> Iter<I> {
-        (*self.input).borrow_mut().extend(&mut iter.0);
+        let mut input = (*self.input).borrow_mut();
+        let len_before = input.len();
+        input.extend(&mut iter.0);
+        self.given
+            .set(self.given.get() + (input.len() - len_before) as u64);
         iter
     }
 }
 impl<T> CanReceive<Vec<T>> for VecHandoff<T> {
     fn give(&self, mut vec: Vec<T>) -> Vec<T> {
+        self.given.set(self.given.get() + vec.len() as u64);
         (*self.input).borrow_mut().extend(vec.drain(..));
         vec
     }
@@ -72,4 +81,12 @@ impl<T> HandoffMeta for VecHandoff<T> {
     fn is_bottom(&self) -> bool {
         (*self.input).borrow_mut().is_empty()
     }
+
+    fn item_count(&self) -> usize {
+        self.input.borrow().len() + self.output.borrow().len()
+    }
+
+    fn items_given(&self) -> u64 {
+        self.given.get()
+    }
 }
@@ -0,0 +1,52 @@
+//! Minimal HTTP/1.1 server for the debug endpoints of a [`Dfir`](super::graph::Dfir) instance,
+//! see [`Dfir::serve_metrics`](super::graph::Dfir::serve_metrics) and
+//! [`Dfir::serve_introspection`](super::graph::Dfir::serve_introspection).
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+/// Serves HTTP `GET` requests received by `listener`, forever. `route` maps a request path to
+/// the response's content type and body, or `None` to respond with `404 Not Found`.
+///
+/// Must be run within a [`tokio::task::LocalSet`].
+pub(super) async fn serve(
+    listener: std::net::TcpListener,
+    route: impl 'static + Fn(&[u8]) -> Option<(&'static str, String)>,
+) {
+    let route = std::rc::Rc::new(route);
+    let listener =
+        tokio::net::TcpListener::from_std(listener).expect("Failed to register HTTP listener.");
+    loop {
+        let Ok((mut stream, _addr)) = listener.accept().await else {
+            continue;
+        };
+        let route = route.clone();
+        tokio::task::spawn_local(async move {
+            // Read until the end of the request headers, the body (if any) is ignored.
+            let mut request = Vec::new();
+            let mut buf = [0; 1024];
+            while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                match stream.read(&mut buf).await {
+                    Ok(0) | Err(_) => return,
+                    Ok(n) => request.extend_from_slice(&buf[..n]),
+                }
+                if 16 * 1024 < request.len() {
+                    return;
+                }
+            }
+            let request_line = request.split(|&b| b == b'\r').next().unwrap_or_default();
+            let mut parts = request_line.split(|&b| b == b' ');
+            let response = match (parts.next(), parts.next().and_then(|path| (route)(path))) {
+                (Some(b"GET"), Some((content_type, body))) => format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    content_type,
+                    body.len(),
+                    body
+                ),
+                _ => "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                    .to_owned(),
+            };
+            let _ = stream.write_all(response.as_bytes()).await;
+            let _ = stream.shutdown().await;
+        });
+    }
+}
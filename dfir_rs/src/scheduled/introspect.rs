@@ -0,0 +1,105 @@
+//! Live introspection of a running [`Dfir`](super::graph::Dfir) instance, see
+//! [`Dfir::snapshot`](super::graph::Dfir::snapshot).
+//!
+//! A [`GraphSnapshot`] records the scheduler position and the live data of every subgraph,
+//! handoff, and measured state. It can be served as JSON over HTTP, together with the graph's
+//! [`meta_graph`](super::graph::Dfir::meta_graph), by
+//! [`Dfir::serve_introspection`](super::graph::Dfir::serve_introspection), to diagnose a stuck or
+//! ballooning graph while it is running.
+
+use std::borrow::Cow;
+
+use serde::Serialize;
+
+/// A snapshot of the live data of a [`Dfir`](super::graph::Dfir) instance.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct GraphSnapshot {
+    /// The current tick.
+    pub tick: u64,
+    /// The current stratum.
+    pub stratum: usize,
+    /// Every subgraph, in the order they were added.
+    pub subgraphs: Vec<SubgraphSnapshot>,
+    /// Every handoff, in the order they were added.
+    pub handoffs: Vec<HandoffSnapshot>,
+    /// Every state with a size function, see
+    /// [`Context::set_state_size_fn`](super::context::Context::set_state_size_fn).
+    pub states: Vec<StateSnapshot>,
+}
+
+/// Live data of a single subgraph.
+#[derive(Clone, Debug, Serialize)]
+pub struct SubgraphSnapshot {
+    /// The subgraph's ID.
+    pub id: String,
+    /// The subgraph's name.
+    pub name: Cow<'static, str>,
+    /// The subgraph's stratum.
+    pub stratum: usize,
+    /// If the subgraph is currently scheduled to run.
+    pub is_scheduled: bool,
+    /// Number of times the subgraph has run, or zero if metrics are not enabled, see
+    /// [`Dfir::enable_metrics`](super::graph::Dfir::enable_metrics).
+    pub runs: u64,
+    /// Total time spent running the subgraph, in microseconds, or zero if metrics are not enabled.
+    pub run_time_us: u64,
+}
+
+/// Live data of a single handoff.
+#[derive(Clone, Debug, Serialize)]
+pub struct HandoffSnapshot {
+    /// The handoff's ID.
+    pub id: String,
+    /// The handoff's name.
+    pub name: Cow<'static, str>,
+    /// IDs of the subgraphs which send into the handoff.
+    pub preds: Vec<String>,
+    /// IDs of the subgraphs which receive from the handoff.
+    pub succs: Vec<String>,
+    /// Number of items currently buffered in the handoff.
+    pub items: usize,
+    /// Number of items ever given to the handoff.
+    pub items_given: u64,
+}
+
+/// The size of a single state.
+#[derive(Clone, Debug, Serialize)]
+pub struct StateSnapshot {
+    /// The name given to the state's size function.
+    pub name: String,
+    /// The state's size, usually its number of items.
+    pub size: usize,
+    /// If the state is `'durable`, i.e. checkpointed and restored across restarts.
+    pub durable: bool,
+}
+
+/// Serves `{"graph": <meta_graph_json>, "live": <snapshot>}` to HTTP `GET /graph` requests
+/// received by `listener`, forever, from a new thread. Responds with `404 Not Found` once
+/// `snapshot` has been dropped.
+#[cfg(not(target_arch = "wasm32"))]
+pub(super) fn spawn_server(
+    listener: std::net::TcpListener,
+    meta_graph_json: String,
+    snapshot: std::sync::Weak<std::sync::Mutex<GraphSnapshot>>,
+) -> std::io::Result<()> {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_io()
+        .build()?;
+    let route = move |path: &[u8]| match path {
+        b"/graph" => {
+            let snapshot = snapshot.upgrade()?;
+            let live = serde_json::to_string(&*snapshot.lock().unwrap()).unwrap();
+            Some((
+                "application/json",
+                format!(r#"{{"graph":{},"live":{}}}"#, meta_graph_json, live),
+            ))
+        }
+        _ => None,
+    };
+    std::thread::Builder::new()
+        .name("dfir-introspection".to_owned())
+        .spawn(move || {
+            tokio::task::LocalSet::new().block_on(&runtime, super::http::serve(listener, route));
+        })?;
+    Ok(())
+}
@@ -0,0 +1,316 @@
+//! Runtime metrics for a [`Dfir`](super::graph::Dfir) instance, see
+//! [`Dfir::metrics`](super::graph::Dfir::metrics).
+//!
+//! Metrics are off by default. Once enabled with
+//! [`Dfir::enable_metrics`](super::graph::Dfir::enable_metrics), they are collected for every
+//! subgraph and handoff as the graph runs. They can be read as a
+//! [`DfirMetrics`] snapshot, written in the [OpenMetrics](https://openmetrics.io/) text format
+//! with [`DfirMetrics::write_openmetrics`], or scraped over HTTP from the endpoint started by
+//! [`Dfir::serve_metrics`](super::graph::Dfir::serve_metrics).
+
+use std::borrow::Cow;
+use std::fmt::{self, Write};
+use std::time::Duration;
+
+use super::graph::HandoffData;
+use super::handoff::HandoffMeta;
+use super::{HandoffId, HandoffTag, SubgraphId, SubgraphTag};
+use crate::util::slot_vec::{SecondarySlotVec, SlotVec};
+
+/// Metrics for a whole [`Dfir`](super::graph::Dfir) instance.
+#[derive(Clone, Default)]
+pub struct DfirMetrics {
+    /// Number of ticks completed.
+    pub ticks: u64,
+    subgraphs: SecondarySlotVec<SubgraphTag, SubgraphMetrics>,
+    handoffs: SecondarySlotVec<HandoffTag, HandoffMetrics>,
+}
+
+/// Metrics for a single subgraph.
+#[derive(Clone, Debug)]
+pub struct SubgraphMetrics {
+    /// The subgraph's name.
+    pub name: Cow<'static, str>,
+    /// Number of times the subgraph has run.
+    pub runs: u64,
+    /// Total time spent running the subgraph.
+    pub run_time: Duration,
+    /// Number of items the subgraph has taken from its input handoffs.
+    pub items_in: u64,
+    /// Number of items the subgraph has given to its output handoffs.
+    pub items_out: u64,
+}
+
+/// Metrics for a single handoff.
+#[derive(Clone, Debug)]
+pub struct HandoffMetrics {
+    /// The handoff's name.
+    pub name: Cow<'static, str>,
+    /// Number of items currently buffered in the handoff.
+    pub items: usize,
+    /// Number of items ever given to the handoff.
+    pub items_given: u64,
+}
+
+impl HandoffMetrics {
+    fn new(name: Cow<'static, str>) -> Self {
+        Self {
+            name,
+            items: 0,
+            items_given: 0,
+        }
+    }
+
+    /// Number of items ever taken from the handoff.
+    pub fn items_taken(&self) -> u64 {
+        self.items_given - self.items as u64
+    }
+
+    fn update(&mut self, handoff: &dyn HandoffMeta) {
+        self.items = handoff.item_count();
+        self.items_given = handoff.items_given();
+    }
+}
+
+impl DfirMetrics {
+    /// Metrics for each subgraph which has run at least once.
+    pub fn subgraphs(&self) -> impl '_ + Iterator<Item = (SubgraphId, &'_ SubgraphMetrics)> {
+        self.subgraphs.iter()
+    }
+
+    /// Metrics for each handoff connected to a subgraph which has run at least once.
+    pub fn handoffs(&self) -> impl '_ + Iterator<Item = (HandoffId, &'_ HandoffMetrics)> {
+        self.handoffs.iter()
+    }
+
+    /// Metrics for the given subgraph, if it has run.
+    pub fn subgraph(&self, sg_id: SubgraphId) -> Option<&SubgraphMetrics> {
+        self.subgraphs.get(sg_id)
+    }
+
+    /// Metrics for the given handoff, if a subgraph connected to it has run.
+    pub fn handoff(&self, hoff_id: HandoffId) -> Option<&HandoffMetrics> {
+        self.handoffs.get(hoff_id)
+    }
+
+    /// Records a run of subgraph `sg_id` which took `run_time`, with input handoffs `preds` and
+    /// output handoffs `succs`.
+    pub(super) fn record_run(
+        &mut self,
+        sg_id: SubgraphId,
+        sg_name: &str,
+        run_time: Duration,
+        preds: &[HandoffId],
+        succs: &[HandoffId],
+        handoffs: &SlotVec<HandoffTag, HandoffData>,
+    ) {
+        let mut items_in = 0;
+        for &hoff_id in preds {
+            let hoff_metrics = self.handoff_metrics(hoff_id, handoffs);
+            let taken_before = hoff_metrics.items_taken();
+            hoff_metrics.update(&*handoffs[hoff_id].handoff);
+            items_in += hoff_metrics.items_taken() - taken_before;
+        }
+        let mut items_out = 0;
+        for &hoff_id in succs {
+            let hoff_metrics = self.handoff_metrics(hoff_id, handoffs);
+            let given_before = hoff_metrics.items_given;
+            hoff_metrics.update(&*handoffs[hoff_id].handoff);
+            items_out += hoff_metrics.items_given - given_before;
+        }
+
+        let sg_metrics = self
+            .subgraphs
+            .get_or_insert_with(sg_id, || SubgraphMetrics {
+                name: Cow::Owned(sg_name.to_owned()),
+                runs: 0,
+                run_time: Duration::ZERO,
+                items_in: 0,
+                items_out: 0,
+            });
+        sg_metrics.runs += 1;
+        sg_metrics.run_time += run_time;
+        sg_metrics.items_in += items_in;
+        sg_metrics.items_out += items_out;
+    }
+
+    fn handoff_metrics(
+        &mut self,
+        hoff_id: HandoffId,
+        handoffs: &SlotVec<HandoffTag, HandoffData>,
+    ) -> &mut HandoffMetrics {
+        self.handoffs.get_or_insert_with(hoff_id, || {
+            HandoffMetrics::new(handoffs[hoff_id].name.clone())
+        })
+    }
+
+    /// Writes all metrics in the [OpenMetrics](https://openmetrics.io/) text format, ending with
+    /// `# EOF`.
+    pub fn write_openmetrics(&self, out: &mut impl Write) -> fmt::Result {
+        write_header(out, "dfir_ticks", "counter", "Number of ticks completed.")?;
+        writeln!(out, "dfir_ticks_total {}", self.ticks)?;
+
+        let subgraphs = || {
+            self.subgraphs().map(|(sg_id, metrics)| {
+                let labels = format!(
+                    "subgraph=\"{}\",name=\"{}\"",
+                    sg_id,
+                    EscapeLabel(&metrics.name)
+                );
+                (labels, metrics)
+            })
+        };
+        write_header(
+            out,
+            "dfir_subgraph_runs",
+            "counter",
+            "Number of times the subgraph has run.",
+        )?;
+        for (labels, metrics) in subgraphs() {
+            writeln!(
+                out,
+                "dfir_subgraph_runs_total{{{}}} {}",
+                labels, metrics.runs
+            )?;
+        }
+        write_header(
+            out,
+            "dfir_subgraph_run_seconds",
+            "counter",
+            "Total time spent running the subgraph.",
+        )?;
+        for (labels, metrics) in subgraphs() {
+            writeln!(
+                out,
+                "dfir_subgraph_run_seconds_total{{{}}} {}",
+                labels,
+                metrics.run_time.as_secs_f64()
+            )?;
+        }
+        write_header(
+            out,
+            "dfir_subgraph_items_in",
+            "counter",
+            "Number of items taken from the subgraph's input handoffs.",
+        )?;
+        for (labels, metrics) in subgraphs() {
+            writeln!(
+                out,
+                "dfir_subgraph_items_in_total{{{}}} {}",
+                labels, metrics.items_in
+            )?;
+        }
+        write_header(
+            out,
+            "dfir_subgraph_items_out",
+            "counter",
+            "Number of items given to the subgraph's output handoffs.",
+        )?;
+        for (labels, metrics) in subgraphs() {
+            writeln!(
+                out,
+                "dfir_subgraph_items_out_total{{{}}} {}",
+                labels, metrics.items_out
+            )?;
+        }
+
+        let handoffs = || {
+            self.handoffs().map(|(hoff_id, metrics)| {
+                let labels = format!(
+                    "handoff=\"{}\",name=\"{}\"",
+                    hoff_id,
+                    EscapeLabel(&metrics.name)
+                );
+                (labels, metrics)
+            })
+        };
+        write_header(
+            out,
+            "dfir_handoff_items",
+            "gauge",
+            "Number of items currently buffered in the handoff.",
+        )?;
+        for (labels, metrics) in handoffs() {
+            writeln!(out, "dfir_handoff_items{{{}}} {}", labels, metrics.items)?;
+        }
+        write_header(
+            out,
+            "dfir_handoff_items_given",
+            "counter",
+            "Number of items given to the handoff.",
+        )?;
+        for (labels, metrics) in handoffs() {
+            writeln!(
+                out,
+                "dfir_handoff_items_given_total{{{}}} {}",
+                labels, metrics.items_given
+            )?;
+        }
+
+        writeln!(out, "# EOF")
+    }
+
+    /// Returns all metrics in the [OpenMetrics](https://openmetrics.io/) text format, see
+    /// [`Self::write_openmetrics`].
+    pub fn to_openmetrics(&self) -> String {
+        let mut out = String::new();
+        self.write_openmetrics(&mut out).unwrap();
+        out
+    }
+}
+
+fn write_header(out: &mut impl Write, family: &str, kind: &str, help: &str) -> fmt::Result {
+    writeln!(out, "# TYPE {} {}", family, kind)?;
+    writeln!(out, "# HELP {} {}", family, help)
+}
+
+/// Escapes a label value, see the OpenMetrics `escaped-string` rule.
+struct EscapeLabel<'a>(&'a str);
+impl fmt::Display for EscapeLabel<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for c in self.0.chars() {
+            match c {
+                '\\' => f.write_str("\\\\")?,
+                '"' => f.write_str("\\\"")?,
+                '\n' => f.write_str("\\n")?,
+                c => f.write_char(c)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Serves `metrics` in the OpenMetrics text format to HTTP `GET /metrics` requests received by
+/// `listener`, forever.
+#[cfg(not(target_arch = "wasm32"))]
+pub(super) async fn serve(
+    listener: std::net::TcpListener,
+    metrics: std::rc::Rc<std::cell::RefCell<DfirMetrics>>,
+) {
+    super::http::serve(listener, move |path| match path {
+        b"/metrics" => Some((
+            "application/openmetrics-text; version=1.0.0; charset=utf-8",
+            metrics.borrow().to_openmetrics(),
+        )),
+        _ => None,
+    })
+    .await
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn escape_label() {
+        assert_eq!(r#"a\"b\\c\nd"#, EscapeLabel("a\"b\\c\nd").to_string());
+    }
+
+    #[test]
+    fn empty_openmetrics() {
+        let text = DfirMetrics::default().to_openmetrics();
+        assert!(text.starts_with("# TYPE dfir_ticks counter\n"));
+        assert!(text.contains("\ndfir_ticks_total 0\n"));
+        assert!(text.ends_with("\n# EOF\n"));
+    }
+}
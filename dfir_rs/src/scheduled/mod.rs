@@ -5,12 +5,19 @@
 
 use crate::util::slot_vec::Key;
 
+pub mod checkpoint;
 pub mod context;
 pub mod graph;
 pub mod graph_ext;
 pub mod handoff;
+#[cfg(not(target_arch = "wasm32"))]
+mod http;
 pub mod input;
+pub mod introspect;
+pub mod metrics;
 pub mod net;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod parallel;
 pub mod port;
 pub mod query;
 pub mod reactor;
@@ -0,0 +1,403 @@
+//! Multi-core execution of a single DFIR graph.
+//!
+//! [`run_available`] and [`run`] build one copy of a graph per worker thread and run the copies in
+//! lockstep: every worker runs the same stratum at the same time, and no worker moves on to the
+//! next stratum (or tick) until all workers have finished the current one. Data is moved between
+//! workers by the `exchange` and `exchange_keyed` operators, which hash-partition their input so
+//! that every item with a given key is handled by the same worker. Placing an exchange before
+//! each input of a keyed operator such as `join` or `fold_keyed` therefore lets the workers split
+//! the keyspace between them without changing the program's output.
+//!
+//! Because a stratum only ends once no worker has work left in it, items exchanged within a
+//! stratum are always delivered within that stratum, before any blocking operator downstream
+//! runs. Outside of a parallel run the exchange operators pass items through unchanged, so the
+//! same graph runs as before on a single thread.
+//!
+//! Each worker's `source_iter` only yields its share of the items, split round-robin between the
+//! workers, so building the same graph on every worker does not duplicate the input. If a worker
+//! panics, the other workers stop at the next stratum boundary and the panic is propagated to the
+//! caller.
+//!
+//! Loop blocks are not supported within parallel graphs.
+
+use std::any::Any;
+use std::cell::{Cell, RefCell};
+use std::hash::{BuildHasher, BuildHasherDefault, Hash};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::task::{Poll, Waker};
+
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use tokio::sync::watch;
+
+use super::graph::Dfir;
+use crate::Never;
+
+/// Identifies one of the worker threads running a parallel graph.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Worker {
+    /// Index of this worker, from zero to `num_workers - 1`.
+    pub index: usize,
+    /// Total number of workers.
+    pub num_workers: usize,
+}
+
+impl Worker {
+    /// The worker handling the given key, the same on every worker.
+    pub fn owner_of<K>(&self, key: &K) -> usize
+    where
+        K: ?Sized + Hash,
+    {
+        // `DefaultHasher::new()` is unseeded, so all workers agree on the hash.
+        let hash = BuildHasherDefault::<std::collections::hash_map::DefaultHasher>::default()
+            .hash_one(key);
+        (hash % self.num_workers as u64) as usize
+    }
+}
+
+/// A barrier which is poisoned when a worker panics, so that the other workers panic instead of
+/// waiting for it forever.
+struct PoisonBarrier {
+    num_workers: usize,
+    state: Mutex<BarrierState>,
+    condvar: Condvar,
+}
+
+#[derive(Default)]
+struct BarrierState {
+    /// Number of workers waiting in the current generation.
+    waiting: usize,
+    generation: u64,
+    poisoned: bool,
+}
+
+/// The panic payload of workers unwinding because another worker panicked.
+struct OtherWorkerPanicked;
+
+impl PoisonBarrier {
+    fn new(num_workers: usize) -> Self {
+        Self {
+            num_workers,
+            state: Mutex::default(),
+            condvar: Condvar::new(),
+        }
+    }
+
+    /// Waits until all workers reach the barrier, unwinding if a worker panicked.
+    fn wait(&self) {
+        let mut state = self.state.lock().unwrap();
+        let generation = state.generation;
+        state.waiting += 1;
+        if state.waiting == self.num_workers {
+            state.waiting = 0;
+            state.generation += 1;
+            self.condvar.notify_all();
+        } else {
+            state = self
+                .condvar
+                .wait_while(state, |state| {
+                    !state.poisoned && state.generation == generation
+                })
+                .unwrap();
+        }
+
+        if state.poisoned {
+            drop(state);
+            std::panic::resume_unwind(Box::new(OtherWorkerPanicked));
+        }
+    }
+
+    fn poison(&self) {
+        self.state.lock().unwrap().poisoned = true;
+        self.condvar.notify_all();
+    }
+}
+
+/// State shared by all workers.
+struct Shared {
+    barrier: PoisonBarrier,
+    /// The latest [`Lockstep::any`] round in which some worker voted `true`.
+    any_round: AtomicU64,
+    /// Channels of each exchange operator, in the order the operators were built.
+    exchanges: Mutex<Vec<Box<dyn Any + Send>>>,
+    /// Bumped when an idle worker receives an event, to wake up the other idle workers.
+    wake: watch::Sender<u64>,
+}
+
+/// A worker's handle to the lockstep barrier.
+pub(crate) struct Lockstep {
+    worker: Worker,
+    shared: Arc<Shared>,
+    /// Number of [`Self::any`] rounds so far, the same on all workers.
+    round: Cell<u64>,
+    next_exchange: Cell<usize>,
+}
+
+impl Lockstep {
+    /// Waits until all workers reach this point.
+    pub(crate) fn wait(&self) {
+        self.shared.barrier.wait();
+    }
+
+    /// Waits until all workers reach this point, returning true if any of them passed `true`.
+    pub(crate) fn any(&self, local: bool) -> bool {
+        let round = self.round.get() + 1;
+        self.round.set(round);
+        if local {
+            self.shared.any_round.fetch_max(round, Ordering::AcqRel);
+        }
+        self.wait();
+        round == self.shared.any_round.load(Ordering::Acquire)
+    }
+}
+
+thread_local! {
+    static CURRENT: RefCell<Option<Rc<Lockstep>>> = const { RefCell::new(None) };
+}
+
+/// The worker running on this thread, if any.
+pub fn current_worker() -> Option<Worker> {
+    CURRENT.with_borrow(|current| current.as_ref().map(|lockstep| lockstep.worker))
+}
+
+/// One worker's end of an exchange: senders to every other worker and a receiver for items sent
+/// to this worker. Used by the `exchange` and `exchange_keyed` operators.
+pub struct Exchange<T> {
+    worker: Worker,
+    senders: Vec<UnboundedSender<T>>,
+    receiver: Option<RefCell<UnboundedReceiver<T>>>,
+}
+
+struct ExchangeChannels<T> {
+    senders: Vec<UnboundedSender<T>>,
+    receivers: Vec<Option<UnboundedReceiver<T>>>,
+}
+
+impl<T> Exchange<T>
+where
+    T: 'static + Send,
+{
+    /// Connects to the next exchange of the worker running on this thread. Every worker builds
+    /// the same graph, so the `n`th exchange built on each worker shares the same channels.
+    ///
+    /// Outside of a parallel run, returns an exchange with a single worker which passes items
+    /// through.
+    pub fn next() -> Self {
+        let Some(lockstep) = CURRENT.with_borrow(Clone::clone) else {
+            return Self {
+                worker: Worker {
+                    index: 0,
+                    num_workers: 1,
+                },
+                senders: Vec::new(),
+                receiver: None,
+            };
+        };
+        let worker = lockstep.worker;
+        let exchange_index = lockstep.next_exchange.get();
+        lockstep.next_exchange.set(exchange_index + 1);
+
+        let mut exchanges = lockstep.shared.exchanges.lock().unwrap();
+        if exchanges.len() == exchange_index {
+            let (senders, receivers) = (0..worker.num_workers)
+                .map(|_| {
+                    let (send, recv) = tokio::sync::mpsc::unbounded_channel();
+                    (send, Some(recv))
+                })
+                .unzip();
+            exchanges.push(Box::new(ExchangeChannels::<T> { senders, receivers }));
+        }
+        let channels = exchanges[exchange_index]
+            .downcast_mut::<ExchangeChannels<T>>()
+            .expect("Workers built different graphs, exchange item types do not match.");
+        Self {
+            worker,
+            senders: channels.senders.clone(),
+            receiver: Some(RefCell::new(
+                channels.receivers[worker.index]
+                    .take()
+                    .expect("Exchange was connected twice by the same worker."),
+            )),
+        }
+    }
+
+    /// The worker handling the given key, see [`Worker::owner_of`].
+    pub fn owner_of<K>(&self, key: &K) -> usize
+    where
+        K: ?Sized + Hash,
+    {
+        self.worker.owner_of(key)
+    }
+
+    /// Returns `item` if `owner` is this worker, otherwise sends it to worker `owner`.
+    pub fn route(&self, item: T, owner: usize) -> Option<T> {
+        if owner == self.worker.index {
+            Some(item)
+        } else {
+            self.senders[owner]
+                .send(item)
+                .expect("Exchange receiver dropped, did a worker exit?");
+            None
+        }
+    }
+
+    /// Returns an item sent to this worker by another worker, if any. Once none are left,
+    /// `waker` is woken when the next one arrives.
+    pub fn try_recv(&self, waker: &Waker) -> Option<T> {
+        let receiver = self.receiver.as_ref()?;
+        match receiver
+            .borrow_mut()
+            .poll_recv(&mut std::task::Context::from_waker(waker))
+        {
+            Poll::Ready(item) => item,
+            Poll::Pending => None,
+        }
+    }
+}
+
+/// Runs `num_workers` copies of the graph returned by `build`, one per thread, until no worker
+/// has work available. See the [module docs](self).
+pub fn run_available<'a, F>(num_workers: usize, build: F)
+where
+    F: Fn(Worker) -> Dfir<'a> + Sync,
+{
+    run_workers(num_workers, build, true);
+}
+
+/// Runs `num_workers` copies of the graph returned by `build`, one per thread, forever. See the
+/// [module docs](self).
+pub fn run<'a, F>(num_workers: usize, build: F) -> Option<Never>
+where
+    F: Fn(Worker) -> Dfir<'a> + Sync,
+{
+    run_workers(num_workers, build, false);
+    None
+}
+
+fn run_workers<'a, F>(num_workers: usize, build: F, until_idle: bool)
+where
+    F: Fn(Worker) -> Dfir<'a> + Sync,
+{
+    assert!(0 < num_workers, "Must have at least one worker.");
+
+    let shared = Arc::new(Shared {
+        barrier: PoisonBarrier::new(num_workers),
+        any_round: AtomicU64::new(0),
+        exchanges: Mutex::new(Vec::new()),
+        wake: watch::Sender::new(0),
+    });
+    let panic = std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..num_workers)
+            .map(|index| {
+                let shared = shared.clone();
+                let build = &build;
+                std::thread::Builder::new()
+                    .name(format!("dfir-worker-{}", index))
+                    .spawn_scoped(scope, move || {
+                        let _poison_on_panic = PoisonOnPanic(shared.clone());
+                        let lockstep = Rc::new(Lockstep {
+                            worker: Worker { index, num_workers },
+                            shared,
+                            round: Cell::new(0),
+                            next_exchange: Cell::new(0),
+                        });
+                        let runtime = tokio::runtime::Builder::new_current_thread()
+                            .enable_all()
+                            .build()
+                            .expect("Failed to build worker runtime.");
+                        tokio::task::LocalSet::new()
+                            .block_on(&runtime, run_worker(lockstep, build, until_idle));
+                    })
+                    .expect("Failed to spawn worker thread.")
+            })
+            .collect();
+
+        // Join every worker, keeping the panic of the worker that failed first rather than those
+        // of the workers it brought down.
+        workers
+            .into_iter()
+            .filter_map(|worker| worker.join().err())
+            .reduce(|first, next| {
+                if first.is::<OtherWorkerPanicked>() {
+                    next
+                } else {
+                    first
+                }
+            })
+    });
+
+    if let Some(panic) = panic {
+        std::panic::resume_unwind(panic);
+    }
+}
+
+/// Poisons the barrier and wakes idle workers if the worker thread panics.
+struct PoisonOnPanic(Arc<Shared>);
+
+impl Drop for PoisonOnPanic {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            self.0.barrier.poison();
+            self.0.wake.send_modify(|epoch| *epoch += 1);
+        }
+    }
+}
+
+async fn run_worker<'a>(
+    lockstep: Rc<Lockstep>,
+    build: &(impl Fn(Worker) -> Dfir<'a> + Sync),
+    until_idle: bool,
+) {
+    CURRENT.with_borrow_mut(|current| *current = Some(lockstep.clone()));
+    let mut df = build(lockstep.worker);
+    CURRENT.with_borrow_mut(|current| *current = None);
+
+    let mut wake = lockstep.shared.wake.subscribe();
+    loop {
+        // Mark wakes as seen before voting, so none sent after the vote are missed.
+        wake.borrow_and_update();
+        df.try_recv_events();
+        if lockstep.any(df.has_work()) {
+            // Exchange receives would otherwise use up Tokio's coop budget partway through the
+            // tick, and then wake themselves forever.
+            tokio::task::unconstrained(async { df.run_tick_lockstep(&lockstep) }).await;
+            // Let tasks spawned by the graph make progress.
+            tokio::task::yield_now().await;
+            continue;
+        }
+        if until_idle {
+            break;
+        }
+        tokio::select! {
+            _ = df.recv_events_async() => {
+                lockstep.shared.wake.send_modify(|epoch| *epoch += 1);
+            }
+            _ = wake.changed() => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn owner_is_consistent() {
+        let workers = (0..4).map(|index| Worker {
+            index,
+            num_workers: 4,
+        });
+        for key in 0..100 {
+            let owners: Vec<_> = workers.clone().map(|w| w.owner_of(&key)).collect();
+            assert!(owners.iter().all(|&owner| owner == owners[0] && owner < 4));
+        }
+    }
+
+    #[test]
+    fn exchange_passes_through_without_workers() {
+        let exchange = Exchange::<u32>::next();
+        assert_eq!(Some(5), exchange.route(5, exchange.owner_of(&5)));
+        assert_eq!(None, exchange.try_recv(futures::task::noop_waker_ref()));
+    }
+}
@@ -0,0 +1,417 @@
+//! Bounded buffering in front of an async [`Sink`], used by the `dest_sink_buffered` operator.
+//!
+//! [`BufferedSink`] holds items in a queue limited by item count and/or byte size, and a
+//! background task (see [`BufferedSink::drain`]) feeds them into the wrapped sink. When the queue
+//! is full the configured [`Overflow`] policy decides what happens to the next item.
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll, Waker, ready};
+
+use bytes::{Bytes, BytesMut};
+use futures::Sink;
+
+/// What a [`BufferedSink`] does with an item that does not fit in its buffer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Overflow {
+    /// Block the pushing subgraph until the sink has drained enough of the buffer.
+    ///
+    /// Blocking drives the sink on the current thread, which is only possible on a
+    /// multi-threaded Tokio runtime. Elsewhere, such as on a current-thread runtime, the sink is
+    /// only drained as far as it is ready and the rest of the items are buffered past the limits,
+    /// since waiting for the sink there would deadlock.
+    Block,
+    /// Evict the oldest buffered items to make room for the new item, dropping the evicted
+    /// items.
+    DropOldest,
+    /// Reject the new item, leaving the buffer unchanged.
+    DropNewest,
+}
+
+/// Limits and overflow policy for a [`BufferedSink`].
+///
+/// A limit of `None` is unbounded. An item larger than `max_bytes` on its own is still accepted
+/// when the buffer is empty, so that [`Overflow::Block`] cannot wait forever.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct BufferConfig {
+    /// Maximum number of buffered items.
+    pub max_items: Option<usize>,
+    /// Maximum total [`BufferedItem::buffered_size`] of buffered items.
+    pub max_bytes: Option<usize>,
+    /// What to do with items that do not fit.
+    pub overflow: Overflow,
+}
+
+impl BufferConfig {
+    /// An unbounded buffer using the given overflow policy once limits are added.
+    pub const fn new(overflow: Overflow) -> Self {
+        Self {
+            max_items: None,
+            max_bytes: None,
+            overflow,
+        }
+    }
+
+    /// Limits the buffer to `max_items` items.
+    pub const fn max_items(mut self, max_items: usize) -> Self {
+        self.max_items = Some(max_items);
+        self
+    }
+
+    /// Limits the buffer to `max_bytes` bytes.
+    pub const fn max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+}
+
+/// Items that can be held in a [`BufferedSink`], which counts their size against
+/// [`BufferConfig::max_bytes`].
+pub trait BufferedItem {
+    /// The number of bytes this item occupies in the buffer.
+    fn buffered_size(&self) -> usize;
+}
+
+impl BufferedItem for Bytes {
+    fn buffered_size(&self) -> usize {
+        self.len()
+    }
+}
+
+impl BufferedItem for BytesMut {
+    fn buffered_size(&self) -> usize {
+        self.len()
+    }
+}
+
+impl BufferedItem for Vec<u8> {
+    fn buffered_size(&self) -> usize {
+        self.len()
+    }
+}
+
+impl BufferedItem for String {
+    fn buffered_size(&self) -> usize {
+        self.len()
+    }
+}
+
+/// Tagged items, such as `(member_id, payload)` pairs sent to a demux sink, are sized by their
+/// payload.
+impl<K, T> BufferedItem for (K, T)
+where
+    T: BufferedItem,
+{
+    fn buffered_size(&self) -> usize {
+        self.1.buffered_size()
+    }
+}
+
+struct Inner<T, S> {
+    sink: S,
+    config: BufferConfig,
+    queue: VecDeque<T>,
+    bytes: usize,
+    /// Whether items have been fed to the sink since it was last flushed.
+    unflushed: bool,
+    /// Waker of the [`BufferedSink::drain`] task, if it is waiting for new items.
+    drain_waker: Option<Waker>,
+}
+
+impl<T, S> Inner<T, S>
+where
+    T: BufferedItem,
+    S: Sink<T> + Unpin,
+    S::Error: std::fmt::Debug,
+{
+    fn fits(&self, size: usize) -> bool {
+        if self.queue.is_empty() {
+            return self.config.max_items != Some(0);
+        }
+        self.config
+            .max_items
+            .is_none_or(|max_items| self.queue.len() < max_items)
+            && self
+                .config
+                .max_bytes
+                .is_none_or(|max_bytes| self.bytes + size <= max_bytes)
+    }
+
+    fn pop_front(&mut self) -> Option<T> {
+        let item = self.queue.pop_front()?;
+        self.bytes -= item.buffered_size();
+        Some(item)
+    }
+
+    /// Feeds queued items into the sink until `done` holds or the sink is not ready, flushing
+    /// once the queue is empty.
+    fn poll_drain(&mut self, cx: &mut Context<'_>, done: impl Fn(&Self) -> bool) -> Poll<()> {
+        while !done(self) {
+            if self.queue.is_empty() {
+                break;
+            }
+            ready!(Pin::new(&mut self.sink).poll_ready(cx))
+                .expect("Error processing async sink item");
+            let item = self.pop_front().unwrap();
+            Pin::new(&mut self.sink)
+                .start_send(item)
+                .expect("Error processing async sink item");
+            self.unflushed = true;
+        }
+        if self.queue.is_empty() && self.unflushed {
+            ready!(Pin::new(&mut self.sink).poll_flush(cx)).expect("Failed to flush sink");
+            self.unflushed = false;
+        }
+        Poll::Ready(())
+    }
+}
+
+/// A bounded queue in front of an async [`Sink`].
+///
+/// Items are added synchronously with [`Self::push`] and moved into the sink by the future
+/// returned from [`Self::drain`], which must be spawned as a task.
+pub struct BufferedSink<T, S> {
+    inner: Rc<RefCell<Inner<T, S>>>,
+}
+
+impl<T, S> Clone for BufferedSink<T, S> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T, S> BufferedSink<T, S>
+where
+    T: BufferedItem,
+    S: Sink<T> + Unpin,
+    S::Error: std::fmt::Debug,
+{
+    /// Wraps `sink` in a buffer with the given limits.
+    pub fn new(sink: S, config: BufferConfig) -> Self {
+        Self {
+            inner: Rc::new(RefCell::new(Inner {
+                sink,
+                config,
+                queue: VecDeque::new(),
+                bytes: 0,
+                unflushed: false,
+                drain_waker: None,
+            })),
+        }
+    }
+
+    /// The number of items currently buffered.
+    pub fn len(&self) -> usize {
+        self.inner.borrow().queue.len()
+    }
+
+    /// Whether the buffer is empty.
+    pub fn is_empty(&self) -> bool {
+        self.inner.borrow().queue.is_empty()
+    }
+
+    /// The total size of the items currently buffered, in bytes.
+    pub fn buffered_bytes(&self) -> usize {
+        self.inner.borrow().bytes
+    }
+
+    /// Adds `item` to the buffer, applying the overflow policy if it does not fit. Returns the
+    /// items dropped by the policy: the evicted items for [`Overflow::DropOldest`], `item`
+    /// itself for [`Overflow::DropNewest`], and nothing for [`Overflow::Block`].
+    pub fn push(&self, item: T) -> Vec<T> {
+        let size = item.buffered_size();
+        let mut dropped = Vec::new();
+
+        let mut inner = self.inner.borrow_mut();
+        // Whether the item is buffered even though it does not fit.
+        let mut over_limit = false;
+        if !inner.fits(size) {
+            match inner.config.overflow {
+                Overflow::Block => {
+                    drop(inner);
+                    self.drain_until_fits(size);
+                    inner = self.inner.borrow_mut();
+                    // The sink could not be waited on from this thread.
+                    over_limit = inner.config.max_items != Some(0);
+                }
+                Overflow::DropOldest => {
+                    while !inner.fits(size) {
+                        match inner.pop_front() {
+                            Some(evicted) => dropped.push(evicted),
+                            None => break,
+                        }
+                    }
+                }
+                Overflow::DropNewest => {
+                    dropped.push(item);
+                    return dropped;
+                }
+            }
+        }
+
+        if over_limit || inner.fits(size) {
+            inner.bytes += size;
+            inner.queue.push_back(item);
+        } else {
+            // Only reachable with a zero-item limit.
+            dropped.push(item);
+        }
+
+        if let Some(waker) = inner.drain_waker.take() {
+            waker.wake();
+        }
+        dropped
+    }
+
+    /// Feeds buffered items into the sink until an item of `size` bytes fits, blocking the
+    /// thread if it is a worker of a multi-threaded Tokio runtime. Otherwise only feeds the
+    /// items that the sink is ready for. The buffer is only borrowed while the sink is polled.
+    fn drain_until_fits(&self, size: usize) {
+        let mut drain = std::pin::pin!(futures::future::poll_fn(|cx| {
+            self.inner
+                .borrow_mut()
+                .poll_drain(cx, |inner| inner.fits(size))
+        }));
+
+        match tokio::runtime::Handle::try_current() {
+            Ok(handle) if handle.runtime_flavor() == tokio::runtime::RuntimeFlavor::MultiThread => {
+                tokio::task::block_in_place(|| handle.block_on(drain));
+            }
+            _ => {
+                let _ = drain
+                    .as_mut()
+                    .poll(&mut Context::from_waker(futures::task::noop_waker_ref()));
+            }
+        }
+    }
+
+    /// Moves buffered items into the sink as it becomes ready, flushing whenever the buffer
+    /// empties. Never completes; spawn it as a task alongside the flow.
+    pub async fn drain(self) {
+        futures::future::poll_fn(|cx| {
+            let mut inner = self.inner.borrow_mut();
+            if inner.poll_drain(cx, |_| false).is_ready() {
+                inner.drain_waker = Some(cx.waker().clone());
+            }
+            Poll::<()>::Pending
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use futures::SinkExt;
+    use futures::channel::mpsc;
+
+    use super::*;
+
+    fn bytes(s: &'static str) -> Bytes {
+        Bytes::from_static(s.as_bytes())
+    }
+
+    #[test]
+    fn drop_oldest_evicts_by_items_and_bytes() {
+        let (send, _recv) = mpsc::unbounded::<Bytes>();
+        let buf = BufferedSink::new(
+            send,
+            BufferConfig::new(Overflow::DropOldest)
+                .max_items(3)
+                .max_bytes(6),
+        );
+
+        assert!(buf.push(bytes("a")).is_empty());
+        assert!(buf.push(bytes("b")).is_empty());
+        assert!(buf.push(bytes("c")).is_empty());
+        assert_eq!(vec![bytes("a")], buf.push(bytes("d")));
+        assert_eq!(vec![bytes("b"), bytes("c")], buf.push(bytes("eeeee")));
+        assert_eq!(2, buf.len());
+        assert_eq!(6, buf.buffered_bytes());
+    }
+
+    #[test]
+    fn drop_newest_rejects_incoming() {
+        let (send, _recv) = mpsc::unbounded::<Bytes>();
+        let buf = BufferedSink::new(send, BufferConfig::new(Overflow::DropNewest).max_items(1));
+
+        assert!(buf.push(bytes("a")).is_empty());
+        assert_eq!(vec![bytes("b")], buf.push(bytes("b")));
+        assert_eq!(1, buf.len());
+    }
+
+    #[test]
+    fn oversized_item_accepted_when_empty() {
+        let (send, _recv) = mpsc::unbounded::<Bytes>();
+        let buf = BufferedSink::new(send, BufferConfig::new(Overflow::DropNewest).max_bytes(2));
+
+        assert!(buf.push(bytes("hello")).is_empty());
+        assert_eq!(vec![bytes("x")], buf.push(bytes("x")));
+    }
+
+    #[test]
+    fn block_drains_into_sink() {
+        let (send, mut recv) = mpsc::unbounded::<Bytes>();
+        let buf = BufferedSink::new(send, BufferConfig::new(Overflow::Block).max_items(2));
+
+        for s in ["a", "b", "c", "d"] {
+            assert!(buf.push(bytes(s)).is_empty());
+        }
+        assert_eq!(2, buf.len());
+        assert_eq!(Ok(Some(bytes("a"))), recv.try_next().map_err(|_| ()));
+        assert_eq!(Ok(Some(bytes("b"))), recv.try_next().map_err(|_| ()));
+        assert!(recv.try_next().is_err());
+    }
+
+    #[crate::test]
+    async fn block_does_not_wait_on_current_thread_runtime() {
+        // the receiver is never polled, so the sink stops being ready after one item
+        let (send, _recv) = mpsc::channel::<Bytes>(0);
+        let buf = BufferedSink::new(send, BufferConfig::new(Overflow::Block).max_items(1));
+
+        for s in ["a", "b", "c", "d"] {
+            assert!(buf.push(bytes(s)).is_empty());
+        }
+        assert_eq!(3, buf.len());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn block_waits_on_multi_thread_runtime() {
+        let (send, mut recv) = mpsc::channel::<Bytes>(0);
+        // a slow receiver on another worker thread
+        tokio::spawn(async move {
+            while futures::StreamExt::next(&mut recv).await.is_some() {
+                tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+            }
+        });
+
+        let buf = BufferedSink::new(send, BufferConfig::new(Overflow::Block).max_items(1));
+        for s in ["a", "b", "c", "d"] {
+            assert!(buf.push(bytes(s)).is_empty());
+            assert!(buf.len() <= 1);
+        }
+    }
+
+    #[crate::test]
+    async fn drain_flushes_everything() {
+        let (send, mut recv) = mpsc::unbounded::<Bytes>();
+        let buf = BufferedSink::new(
+            send.sink_map_err(|_| ()),
+            BufferConfig::new(Overflow::Block),
+        );
+        let task = tokio::task::spawn_local(buf.clone().drain());
+
+        buf.push(bytes("a"));
+        buf.push(bytes("b"));
+        tokio::task::yield_now().await;
+
+        let out: Vec<_> = crate::util::ready_iter(&mut recv).collect();
+        assert_eq!(vec![bytes("a"), bytes("b")], out);
+        assert!(buf.is_empty());
+        task.abort();
+    }
+}
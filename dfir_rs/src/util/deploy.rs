@@ -8,6 +8,9 @@ pub use hydro_deploy_integration::*;
 use serde::de::DeserializeOwned;
 
 use crate::scheduled::graph::Dfir;
+use crate::util::replay::Recorder;
+#[cfg(doc)]
+use crate::util::replay::{RECORD_ENV, REPLAY_ENV};
 
 #[macro_export]
 macro_rules! launch {
@@ -25,24 +28,72 @@ macro_rules! launch {
 
 pub use crate::launch;
 
-pub async fn launch_flow(mut flow: Dfir<'_>) {
-    let stop = tokio::sync::oneshot::channel();
-    tokio::task::spawn_blocking(|| {
-        let mut line = String::new();
-        std::io::stdin().read_line(&mut line).unwrap();
-        if line.starts_with("stop") {
-            stop.0.send(()).unwrap();
-        } else {
-            eprintln!("Unexpected stdin input: {:?}", line);
+pub async fn launch_flow(flow: Dfir<'_>) {
+    launch_flow_with_commands(flow, |command| {
+        eprintln!("Unexpected stdin input: {:?}", command);
+    })
+    .await
+}
+
+/// Runs the flow until it completes or a `stop` line is received on stdin. Every other line
+/// received on stdin is passed to `on_command`, without its trailing newline.
+///
+/// If the `DFIR_METRICS_ADDR` environment variable is set, runtime metrics are served at that
+/// address, see [`Dfir::serve_metrics`]. Likewise, if `DFIR_INTROSPECTION_ADDR` is set, the live
+/// graph is served at that address, see [`Dfir::serve_introspection`]. If the [`RECORD_ENV`] or
+/// [`REPLAY_ENV`] environment variable is set, the flow's network sources and `source_interval`s
+/// are recorded to or replayed from that file, see [`replay`](crate::util::replay).
+pub async fn launch_flow_with_commands(mut flow: Dfir<'_>, mut on_command: impl FnMut(&str)) {
+    let (command_send, mut command_recv) = tokio::sync::mpsc::unbounded_channel::<String>();
+    tokio::task::spawn_blocking(move || {
+        loop {
+            let mut line = String::new();
+            if std::io::stdin().read_line(&mut line).unwrap() == 0 {
+                break;
+            }
+
+            // stop reading after a stop command, so that the runtime can shut down
+            let is_stop = line.starts_with("stop");
+            if command_send.send(line).is_err() || is_stop {
+                break;
+            }
         }
     });
 
+    if let Ok(metrics_addr) = std::env::var("DFIR_METRICS_ADDR") {
+        let addr = flow
+            .serve_metrics(&*metrics_addr)
+            .expect("Failed to bind `DFIR_METRICS_ADDR`.");
+        eprintln!("Serving DFIR metrics at http://{}/metrics", addr);
+    }
+    if let Ok(introspection_addr) = std::env::var("DFIR_INTROSPECTION_ADDR") {
+        let addr = flow
+            .serve_introspection(&*introspection_addr)
+            .expect("Failed to bind `DFIR_INTROSPECTION_ADDR`.");
+        eprintln!("Serving DFIR graph at http://{}/graph", addr);
+    }
+
+    if let Some(recorder) = Recorder::from_env().expect("Failed to open recording.") {
+        flow.set_recorder(recorder);
+    }
+
     let local_set = tokio::task::LocalSet::new();
     let flow = local_set.run_until(flow.run_async());
-
-    tokio::select! {
-        _ = stop.1 => {},
-        _ = flow => {}
+    tokio::pin!(flow);
+
+    loop {
+        tokio::select! {
+            command = command_recv.recv() => match command {
+                Some(line) if line.starts_with("stop") => break,
+                Some(line) => on_command(line.trim_end()),
+                None => {
+                    // stdin was closed, so no stop command can arrive
+                    (&mut flow).await;
+                    break;
+                }
+            },
+            _ = &mut flow => break,
+        }
     }
 }
 
@@ -1,12 +1,16 @@
 #![warn(missing_docs)]
 //! Helper utilities for the DFIR syntax.
 
+pub mod buffered_sink;
 pub mod clear;
 #[cfg(feature = "dfir_macro")]
 #[cfg_attr(docsrs, doc(cfg(feature = "dfir_macro")))]
 pub mod demux_enum;
 pub mod multiset;
 pub mod priority_stack;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod records;
+pub mod replay;
 pub mod slot_vec;
 pub mod sparse_vec;
 pub mod unsync;
@@ -93,6 +97,22 @@ where
     })
 }
 
+/// Splits the items of a `source_iter` round-robin between the workers of a
+/// [parallel graph](crate::scheduled::parallel), so that each item is emitted by exactly one
+/// worker. Outside of a parallel run, all items are kept.
+pub fn partition_source<I>(iter: I) -> std::iter::StepBy<std::iter::Skip<I>>
+where
+    I: Iterator,
+{
+    #[cfg(not(target_arch = "wasm32"))]
+    let (index, num_workers) = crate::scheduled::parallel::current_worker()
+        .map_or((0, 1), |worker| (worker.index, worker.num_workers));
+    #[cfg(target_arch = "wasm32")]
+    let (index, num_workers) = (0, 1);
+
+    iter.skip(index).step_by(num_workers)
+}
+
 /// Collects the immediately available items from the `Stream` into a `FromIterator` collection.
 ///
 /// This consumes the stream, use [`futures::StreamExt::by_ref()`] (or just `&mut ...`) if you want
@@ -0,0 +1,470 @@
+//! Typed record files, used by the `source_jsonl`, `source_csv`, `dest_jsonl`, and `dest_csv`
+//! operators.
+//!
+//! Sources read a [`FileSource`] line by line and emit one `Result<T, RecordError>` per record,
+//! so a malformed record is reported alongside its line number instead of ending the flow. Sinks
+//! serialize each record with [`JsonlEncoder`] or [`CsvEncoder`] before writing it as a line.
+
+use std::cell::{Cell, RefCell};
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::time::Duration;
+
+use futures::Stream;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+/// A file to read records from, and whether to keep following it once its end is reached.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileSource {
+    /// Path of the file.
+    pub path: PathBuf,
+    /// If set, the source does not end at the end of the file. It instead checks for appended
+    /// data at this interval, like `tail -f`.
+    pub follow: Option<Duration>,
+}
+
+impl FileSource {
+    /// Reads the file at `path` once, to its end.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            follow: None,
+        }
+    }
+
+    /// Keeps reading records appended to the file after its end is reached, checking for new
+    /// data every `poll_interval`.
+    pub fn follow(mut self, poll_interval: Duration) -> Self {
+        self.follow = Some(poll_interval);
+        self
+    }
+}
+
+impl<P> From<P> for FileSource
+where
+    P: AsRef<Path>,
+{
+    fn from(path: P) -> Self {
+        Self::new(path.as_ref())
+    }
+}
+
+/// An error reading, parsing, or serializing a single record.
+#[derive(Debug)]
+pub struct RecordError {
+    /// The line the record starts on, counting from 1. For sinks, this is the line the record
+    /// would have been written to, counting only lines written by the operator.
+    pub line: u64,
+    /// What went wrong.
+    pub kind: RecordErrorKind,
+}
+
+/// The cause of a [`RecordError`].
+#[derive(Debug)]
+pub enum RecordErrorKind {
+    /// The file could not be opened or read.
+    Io(std::io::Error),
+    /// The record is not valid JSON for the record type.
+    Json(serde_json::Error),
+    /// The record is not valid CSV for the record type.
+    Csv(csv::Error),
+}
+
+impl Display for RecordError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.kind {
+            RecordErrorKind::Io(err) => write!(f, "line {}: {}", self.line, err),
+            RecordErrorKind::Json(err) => write!(f, "line {}: {}", self.line, err),
+            RecordErrorKind::Csv(err) => write!(f, "line {}: {}", self.line, err),
+        }
+    }
+}
+
+impl std::error::Error for RecordError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            RecordErrorKind::Io(err) => Some(err),
+            RecordErrorKind::Json(err) => Some(err),
+            RecordErrorKind::Csv(err) => Some(err),
+        }
+    }
+}
+
+/// A boxed stream of records, as produced by the source functions in this module.
+pub type RecordStream<T> = Pin<Box<dyn Stream<Item = T>>>;
+
+struct LineReader {
+    reader: Option<BufReader<tokio::fs::File>>,
+    follow: Option<Duration>,
+    /// Number of complete lines read so far.
+    line: u64,
+    buf: String,
+}
+
+impl LineReader {
+    /// Reads the next line without its line ending, along with its line number. Invalid UTF-8
+    /// is reported for the offending line; any other IO error ends the stream.
+    async fn next_line(&mut self) -> Option<Result<(u64, String), RecordError>> {
+        loop {
+            let reader = self.reader.as_mut()?;
+            match reader.read_line(&mut self.buf).await {
+                Ok(0) => match self.follow {
+                    Some(poll_interval) => tokio::time::sleep(poll_interval).await,
+                    None => {
+                        self.reader = None;
+                        if self.buf.is_empty() {
+                            return None;
+                        }
+                        self.line += 1;
+                        return Some(Ok((self.line, std::mem::take(&mut self.buf))));
+                    }
+                },
+                Ok(_) => {
+                    if let Some(line) = self.buf.strip_suffix('\n') {
+                        let line = line.strip_suffix('\r').unwrap_or(line).to_owned();
+                        self.buf.clear();
+                        self.line += 1;
+                        return Some(Ok((self.line, line)));
+                    }
+                    // A partial line at the end of the file, keep reading.
+                }
+                Err(err) => {
+                    self.buf.clear();
+                    self.line += 1;
+                    if err.kind() != std::io::ErrorKind::InvalidData {
+                        self.reader = None;
+                    }
+                    return Some(Err(RecordError {
+                        line: self.line,
+                        kind: RecordErrorKind::Io(err),
+                    }));
+                }
+            }
+        }
+    }
+}
+
+/// Streams the lines of `source`, without line endings, along with their line numbers.
+///
+/// Must be polled within a Tokio runtime.
+pub fn lines_stream(
+    source: impl Into<FileSource>,
+) -> RecordStream<Result<(u64, String), RecordError>> {
+    let FileSource { path, follow } = source.into();
+    let reader = match std::fs::File::open(path) {
+        Ok(file) => LineReader {
+            reader: Some(BufReader::new(tokio::fs::File::from_std(file))),
+            follow,
+            line: 0,
+            buf: String::new(),
+        },
+        Err(err) => {
+            let err = RecordError {
+                line: 0,
+                kind: RecordErrorKind::Io(err),
+            };
+            return Box::pin(futures::stream::once(async { Err(err) }));
+        }
+    };
+    Box::pin(futures::stream::unfold(reader, |mut reader| async move {
+        let line = reader.next_line().await?;
+        Some((line, reader))
+    }))
+}
+
+/// Streams the records of a [JSON Lines](https://jsonlines.org/) file, one JSON value per line.
+/// Blank lines are skipped.
+pub fn jsonl_stream<T>(source: impl Into<FileSource>) -> RecordStream<Result<T, RecordError>>
+where
+    T: DeserializeOwned + 'static,
+{
+    use futures::StreamExt;
+
+    Box::pin(lines_stream(source).filter_map(|line| async move {
+        match line {
+            Ok((_, line)) if line.trim().is_empty() => None,
+            Ok((line_no, line)) => Some(serde_json::from_str(&line).map_err(|err| RecordError {
+                line: line_no,
+                kind: RecordErrorKind::Json(err),
+            })),
+            Err(err) => Some(Err(err)),
+        }
+    }))
+}
+
+/// Parses a single CSV record, which may span several lines.
+fn parse_csv_record(record: &str) -> Result<csv::StringRecord, csv::Error> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .from_reader(record.as_bytes());
+    let mut parsed = csv::StringRecord::new();
+    reader.read_record(&mut parsed)?;
+    Ok(parsed)
+}
+
+struct CsvState {
+    headers: Option<csv::StringRecord>,
+    /// A record with an unterminated quoted field, and the line it starts on.
+    partial: Option<(u64, String)>,
+}
+
+impl CsvState {
+    fn next<T>(
+        &mut self,
+        line: Result<(u64, String), RecordError>,
+    ) -> Option<Result<T, RecordError>>
+    where
+        T: DeserializeOwned,
+    {
+        let (line_no, line) = match line {
+            Ok(line) => line,
+            Err(err) => return Some(Err(err)),
+        };
+        let (start, record) = match self.partial.take() {
+            Some((start, mut record)) => {
+                record.push('\n');
+                record.push_str(&line);
+                (start, record)
+            }
+            None if line.is_empty() => return None,
+            None => (line_no, line),
+        };
+        // Quotes are escaped by doubling them, so an odd count means a quoted field continues
+        // onto the next line.
+        if record.matches('"').count() % 2 == 1 {
+            self.partial = Some((start, record));
+            return None;
+        }
+
+        let error = |err| RecordError {
+            line: start,
+            kind: RecordErrorKind::Csv(err),
+        };
+        let parsed = match parse_csv_record(&record) {
+            Ok(parsed) => parsed,
+            Err(err) => return Some(Err(error(err))),
+        };
+        match &self.headers {
+            None => {
+                self.headers = Some(parsed);
+                None
+            }
+            Some(headers) => Some(parsed.deserialize(Some(headers)).map_err(error)),
+        }
+    }
+}
+
+/// Streams the records of a CSV file. The first record is the header row, which names the fields
+/// each following record is deserialized into. Blank lines are skipped.
+pub fn csv_stream<T>(source: impl Into<FileSource>) -> RecordStream<Result<T, RecordError>>
+where
+    T: DeserializeOwned + 'static,
+{
+    use futures::StreamExt;
+
+    let mut state = CsvState {
+        headers: None,
+        partial: None,
+    };
+    Box::pin(lines_stream(source).filter_map(move |line| futures::future::ready(state.next(line))))
+}
+
+/// Serializes records as [JSON Lines](https://jsonlines.org/).
+#[derive(Default)]
+pub struct JsonlEncoder {
+    line: Cell<u64>,
+}
+
+impl JsonlEncoder {
+    /// Creates an encoder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Serializes `record` as a single line of JSON, without the line ending.
+    pub fn encode<T>(&self, record: &T) -> Result<String, RecordError>
+    where
+        T: Serialize,
+    {
+        self.line.set(self.line.get() + 1);
+        serde_json::to_string(record).map_err(|err| RecordError {
+            line: self.line.get(),
+            kind: RecordErrorKind::Json(err),
+        })
+    }
+}
+
+/// The output buffer of a [`CsvEncoder`], shared with its `csv::Writer`.
+#[derive(Clone, Default)]
+struct SharedBuf(Rc<RefCell<Vec<u8>>>);
+
+impl std::io::Write for SharedBuf {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Serializes records as CSV rows, preceded by a header row if requested.
+pub struct CsvEncoder {
+    writer: RefCell<csv::Writer<SharedBuf>>,
+    written: SharedBuf,
+    line: Cell<u64>,
+}
+
+impl CsvEncoder {
+    /// Creates an encoder. If `write_headers` is true, the header row is emitted along with the
+    /// first record, using the field names of the record type.
+    pub fn new(write_headers: bool) -> Self {
+        let written = SharedBuf::default();
+        Self {
+            writer: RefCell::new(
+                csv::WriterBuilder::new()
+                    .has_headers(write_headers)
+                    .from_writer(written.clone()),
+            ),
+            written,
+            line: Cell::new(0),
+        }
+    }
+
+    /// Serializes `record` as a CSV row, without the final line ending. The first row may be
+    /// preceded by the header row.
+    pub fn encode<T>(&self, record: &T) -> Result<String, RecordError>
+    where
+        T: Serialize,
+    {
+        let mut writer = self.writer.borrow_mut();
+        let result = writer.serialize(record).and_then(|()| {
+            writer.flush()?;
+            Ok(())
+        });
+        let written =
+            String::from_utf8(self.written.0.take()).expect("CSV writer produced invalid UTF-8");
+        match result {
+            Ok(()) => {
+                let row = written.strip_suffix('\n').unwrap_or(&written);
+                self.line
+                    .set(self.line.get() + 1 + row.matches('\n').count() as u64);
+                Ok(row.to_owned())
+            }
+            Err(err) => Err(RecordError {
+                line: self.line.get() + 1,
+                kind: RecordErrorKind::Csv(err),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Write;
+
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+    struct Row {
+        name: String,
+        count: u32,
+    }
+
+    fn row(name: &str, count: u32) -> Row {
+        Row {
+            name: name.to_owned(),
+            count,
+        }
+    }
+
+    fn write_file(contents: &str) -> tempfile::NamedTempFile {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(contents.as_bytes()).unwrap();
+        file
+    }
+
+    #[crate::test]
+    async fn jsonl_reports_bad_records() {
+        use futures::StreamExt;
+
+        let file =
+            write_file("{\"name\":\"a\",\"count\":1}\n\nnot json\n{\"name\":\"b\",\"count\":2}");
+        let records: Vec<_> = jsonl_stream::<Row>(file.path()).collect().await;
+        assert_eq!(3, records.len());
+        assert_eq!(&row("a", 1), records[0].as_ref().unwrap());
+        let err = records[1].as_ref().unwrap_err();
+        assert_eq!(3, err.line);
+        assert!(matches!(err.kind, RecordErrorKind::Json(_)));
+        assert_eq!(&row("b", 2), records[2].as_ref().unwrap());
+    }
+
+    #[crate::test]
+    async fn csv_headers_and_quoted_newlines() {
+        use futures::StreamExt;
+
+        let file = write_file("count,name\r\n1,a\r\n2,\"b\nc\"\r\nx,d\r\n3,\"e\"\"\"\r\n");
+        let records: Vec<_> = csv_stream::<Row>(file.path()).collect().await;
+        assert_eq!(4, records.len());
+        assert_eq!(&row("a", 1), records[0].as_ref().unwrap());
+        assert_eq!(&row("b\nc", 2), records[1].as_ref().unwrap());
+        assert_eq!(5, records[2].as_ref().unwrap_err().line);
+        assert_eq!(&row("e\"", 3), records[3].as_ref().unwrap());
+    }
+
+    #[crate::test]
+    async fn missing_file_is_an_error() {
+        use futures::StreamExt;
+
+        let records: Vec<_> = jsonl_stream::<Row>("/nonexistent/records.jsonl")
+            .collect()
+            .await;
+        assert_eq!(1, records.len());
+        assert!(matches!(
+            records[0].as_ref().unwrap_err().kind,
+            RecordErrorKind::Io(_)
+        ));
+    }
+
+    #[crate::test]
+    async fn follow_reads_appended_lines() {
+        use futures::StreamExt;
+
+        let mut file = write_file("{\"name\":\"a\",\"count\":1}\n{\"name\":");
+        let mut records =
+            jsonl_stream::<Row>(FileSource::new(file.path()).follow(Duration::from_millis(1)));
+        assert_eq!(row("a", 1), records.next().await.unwrap().unwrap());
+
+        file.write_all(b"\"b\",\"count\":2}\n").unwrap();
+        assert_eq!(row("b", 2), records.next().await.unwrap().unwrap());
+    }
+
+    #[test]
+    fn csv_encoder_writes_headers_once() {
+        let encoder = CsvEncoder::new(true);
+        assert_eq!("name,count\na,1", encoder.encode(&row("a", 1)).unwrap());
+        assert_eq!("\"b\nc\",2", encoder.encode(&row("b\nc", 2)).unwrap());
+        assert_eq!(4, encoder.line.get());
+
+        let encoder = CsvEncoder::new(false);
+        assert_eq!("a,1", encoder.encode(&row("a", 1)).unwrap());
+    }
+
+    #[test]
+    fn jsonl_encoder_reports_bad_records() {
+        let encoder = JsonlEncoder::new();
+        assert_eq!(
+            "{\"name\":\"a\",\"count\":1}",
+            encoder.encode(&row("a", 1)).unwrap()
+        );
+        let bad = std::collections::HashMap::from([((1, 2), 3)]);
+        assert_eq!(2, encoder.encode(&bad).unwrap_err().line);
+    }
+}
@@ -0,0 +1,302 @@
+//! Recording and deterministic replay of the external inputs of a [`Dfir`] instance.
+//!
+//! A [`Recorder`] is attached to a graph with [`Dfir::set_recorder`], or from the [`RECORD_ENV`]
+//! and [`REPLAY_ENV`] environment variables by [`launch_flow`](crate::util::deploy::launch_flow).
+//! The graph's `source_interval`s and Hydro Deploy network sources are recorded automatically,
+//! other source streams can be wrapped explicitly, for example
+//! `source_stream(recorder.source("requests", requests))`. In [record mode](Recorder::record),
+//! every item received by a recorded source is appended to a log file along with the tick in which
+//! it was received. In [replay mode](Recorder::replay), the underlying streams are never polled (so
+//! no real network or timer input is received) and each logged item is instead delivered in the
+//! same tick it was originally received in. Stepping through a replay with [`Dfir::run_tick`]
+//! reproduces the original execution tick by tick.
+//!
+//! The log is flushed at the end of every tick, so it survives the process being killed. A log
+//! which ends in a partially written item is replayed up to that item.
+
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use bytes::BytesMut;
+use futures::Stream;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+#[cfg(doc)]
+use crate::scheduled::graph::Dfir;
+
+/// Environment variable holding the log path for [`Recorder::from_env`] to record to.
+pub const RECORD_ENV: &str = "DFIR_RECORD";
+/// Environment variable holding the log path for [`Recorder::from_env`] to replay from.
+pub const REPLAY_ENV: &str = "DFIR_REPLAY";
+
+/// A single logged item.
+#[derive(Serialize, Deserialize)]
+struct LogEntry {
+    tick: u64,
+    source: String,
+    item: Vec<u8>,
+}
+
+enum Mode {
+    Record(BufWriter<File>),
+    Replay(HashMap<String, VecDeque<(u64, Vec<u8>)>>),
+}
+
+/// The recorder attached to a graph, if any, shared with the sources it records.
+pub(crate) type RecorderSlot = Rc<RefCell<Option<Recorder>>>;
+
+struct RecorderState {
+    /// The current tick of the attached graph.
+    tick: u64,
+    mode: Mode,
+}
+
+/// Records the items received by source streams to a log file, or replays them from one. See the
+/// [module docs](self).
+///
+/// Cloning a `Recorder` returns a handle to the same log.
+#[derive(Clone)]
+pub struct Recorder {
+    state: Rc<RefCell<RecorderState>>,
+}
+
+impl Recorder {
+    /// Creates a recorder which records to a new log file at `path`, replacing any existing file.
+    pub fn record(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = BufWriter::new(File::create(path)?);
+        Ok(Self::new(Mode::Record(file)))
+    }
+
+    /// Creates a recorder which replays the log file at `path`.
+    pub fn replay(path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut file = BufReader::new(File::open(path)?);
+        let mut sources = HashMap::<_, VecDeque<_>>::new();
+        loop {
+            match bincode::deserialize_from::<_, LogEntry>(&mut file) {
+                Ok(entry) => sources
+                    .entry(entry.source)
+                    .or_default()
+                    .push_back((entry.tick, entry.item)),
+                Err(err) => match *err {
+                    // The log ends at an entry boundary, or in a partial entry if the recording
+                    // process was killed mid-write.
+                    bincode::ErrorKind::Io(err) if io::ErrorKind::UnexpectedEof == err.kind() => {
+                        break;
+                    }
+                    _ => return Err(io::Error::new(io::ErrorKind::InvalidData, err)),
+                },
+            }
+        }
+        Ok(Self::new(Mode::Replay(sources)))
+    }
+
+    /// Creates a recorder from the [`RECORD_ENV`] or [`REPLAY_ENV`] environment variable, or
+    /// returns `None` if neither is set.
+    pub fn from_env() -> io::Result<Option<Self>> {
+        match (std::env::var_os(RECORD_ENV), std::env::var_os(REPLAY_ENV)) {
+            (None, None) => Ok(None),
+            (Some(path), None) => Self::record(path).map(Some),
+            (None, Some(path)) => Self::replay(path).map(Some),
+            (Some(_), Some(_)) => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Only one of `{}` and `{}` may be set.",
+                    RECORD_ENV, REPLAY_ENV
+                ),
+            )),
+        }
+    }
+
+    fn new(mode: Mode) -> Self {
+        Self {
+            state: Rc::new(RefCell::new(RecorderState { tick: 0, mode })),
+        }
+    }
+
+    /// If this recorder is replaying a log.
+    pub fn is_replay(&self) -> bool {
+        matches!(self.state.borrow().mode, Mode::Replay(_))
+    }
+
+    /// Wraps `stream`, which is recorded or replayed under `name`. Each source of a graph must
+    /// have a distinct name.
+    pub fn source<S>(&self, name: impl Into<String>, stream: S) -> RecordedStream<S>
+    where
+        S: Stream + Unpin,
+        S::Item: Serialize + DeserializeOwned,
+    {
+        RecordedStream::source(self.slot(), name.into(), stream)
+    }
+
+    /// Wraps a network source `stream`, which is recorded or replayed under `name`. Unlike
+    /// [`Self::source`], the received items may be I/O errors.
+    pub fn network_source<S>(&self, name: impl Into<String>, stream: S) -> RecordedStream<S>
+    where
+        S: Stream + Unpin,
+        S::Item: NetworkItem,
+    {
+        RecordedStream::network_source(self.slot(), name.into(), stream)
+    }
+
+    /// A recorded or replayed `()` stream, which in record mode yields at every `period`. For use
+    /// in place of `source_interval(period)`.
+    pub fn interval(
+        &self,
+        name: impl Into<String>,
+        period: Duration,
+    ) -> RecordedStream<impl Stream<Item = ()> + Unpin> {
+        let interval = tokio_stream::wrappers::IntervalStream::new(tokio::time::interval(period));
+        self.source(name, tokio_stream::StreamExt::map(interval, |_| ()))
+    }
+
+    fn slot(&self) -> RecorderSlot {
+        Rc::new(RefCell::new(Some(self.clone())))
+    }
+
+    /// Sets the current tick, and flushes the log.
+    pub(crate) fn set_tick(&self, tick: u64) -> io::Result<()> {
+        let mut state = self.state.borrow_mut();
+        state.tick = tick;
+        match &mut state.mode {
+            Mode::Record(file) => file.flush(),
+            Mode::Replay(_) => Ok(()),
+        }
+    }
+}
+
+/// A network source item, which is recorded through its serializable representation.
+pub trait NetworkItem: Sized {
+    /// The serializable representation.
+    type Repr: Serialize + DeserializeOwned;
+    /// Converts to the serializable representation.
+    fn to_repr(&self) -> Self::Repr;
+    /// Converts from the serializable representation.
+    fn from_repr(repr: Self::Repr) -> Self;
+}
+
+impl NetworkItem for io::Result<BytesMut> {
+    type Repr = Result<Vec<u8>, String>;
+
+    fn to_repr(&self) -> Self::Repr {
+        match self {
+            Ok(bytes) => Ok(bytes.to_vec()),
+            Err(err) => Err(err.to_string()),
+        }
+    }
+
+    fn from_repr(repr: Self::Repr) -> Self {
+        repr.map(|bytes| BytesMut::from(&*bytes))
+            .map_err(io::Error::other)
+    }
+}
+
+impl NetworkItem for io::Result<(u32, BytesMut)> {
+    type Repr = Result<(u32, Vec<u8>), String>;
+
+    fn to_repr(&self) -> Self::Repr {
+        match self {
+            Ok((id, bytes)) => Ok((*id, bytes.to_vec())),
+            Err(err) => Err(err.to_string()),
+        }
+    }
+
+    fn from_repr(repr: Self::Repr) -> Self {
+        repr.map(|(id, bytes)| (id, BytesMut::from(&*bytes)))
+            .map_err(io::Error::other)
+    }
+}
+
+/// A source stream wrapped by a [`Recorder`].
+pub struct RecordedStream<S: Stream> {
+    /// The stream is passed through unrecorded while no recorder is attached.
+    recorder: RecorderSlot,
+    name: String,
+    /// Not polled when replaying.
+    stream: S,
+    encode: fn(&S::Item) -> bincode::Result<Vec<u8>>,
+    decode: fn(&[u8]) -> bincode::Result<S::Item>,
+}
+
+impl<S: Stream> RecordedStream<S> {
+    pub(crate) fn source(recorder: RecorderSlot, name: String, stream: S) -> Self
+    where
+        S::Item: Serialize + DeserializeOwned,
+    {
+        Self {
+            recorder,
+            name,
+            stream,
+            encode: bincode::serialize,
+            decode: |bytes| bincode::deserialize(bytes),
+        }
+    }
+
+    pub(crate) fn network_source(recorder: RecorderSlot, name: String, stream: S) -> Self
+    where
+        S::Item: NetworkItem,
+    {
+        Self {
+            recorder,
+            name,
+            stream,
+            encode: |item| bincode::serialize(&item.to_repr()),
+            decode: |bytes| bincode::deserialize(bytes).map(NetworkItem::from_repr),
+        }
+    }
+}
+
+impl<S> Stream for RecordedStream<S>
+where
+    S: Stream + Unpin,
+{
+    type Item = S::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let Some(recorder) = this.recorder.borrow().clone() else {
+            return Pin::new(&mut this.stream).poll_next(cx);
+        };
+        let mut state = recorder.state.borrow_mut();
+        let tick = state.tick;
+        match &mut state.mode {
+            Mode::Record(file) => {
+                let poll = Pin::new(&mut this.stream).poll_next(cx);
+                if let Poll::Ready(Some(item)) = &poll {
+                    let entry = LogEntry {
+                        tick,
+                        source: this.name.clone(),
+                        item: (this.encode)(item).expect("Failed to serialize recorded item."),
+                    };
+                    bincode::serialize_into(file, &entry).expect("Failed to write recorded item.");
+                }
+                poll
+            }
+            Mode::Replay(sources) => {
+                let Some(queue) = sources.get_mut(&this.name) else {
+                    return Poll::Pending;
+                };
+                match queue.front() {
+                    Some(&(item_tick, _)) if item_tick <= tick => {
+                        let (_, bytes) = queue.pop_front().unwrap();
+                        let item = (this.decode)(&bytes).expect("Failed to deserialize item.");
+                        Poll::Ready(Some(item))
+                    }
+                    Some(_) => {
+                        // Keep running ticks until the item's tick is reached.
+                        cx.waker().wake_by_ref();
+                        Poll::Pending
+                    }
+                    None => Poll::Pending,
+                }
+            }
+        }
+    }
+}
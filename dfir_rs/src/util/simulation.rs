@@ -59,6 +59,21 @@
 //! destination hostname and the interface name on that host to which the message should be
 //! delivered.
 //!
+//! ### Network Policies
+//! Every message picked up from an outbox is handed to the fleet's [`NetworkPolicy`], which decides
+//! whether the message is dropped, how many copies of it are delivered and how many network rounds
+//! each copy spends in flight. The policy can also permute the messages that are delivered in a
+//! single round. The default policy, [`ReliableNetwork`], delivers every message exactly once in
+//! the round in which it was sent. [`FaultyNetwork`] injects drops, delays, duplicates and
+//! reordering driven by a seeded random number generator, so a failing run can be reproduced by
+//! re-running the test with the same seed.
+//!
+//! ### Partitions
+//! Independently of the policy, the unit test can open named partitions on the fleet with
+//! [`Fleet::partition`] and heal them with [`Fleet::heal`]. While a partition is open, messages
+//! between the two sides of the partition are dropped, including messages that were already in
+//! flight when the partition was opened.
+//!
 //! ## Progress of Time in the Simulation
 //! The single-threaded unit test can drive time forward on every host by invoking the `run_tick`
 //! method on the host. This ultimately runs a single tick on the process. The unit test is
@@ -68,13 +83,17 @@
 //! ## Examples
 //! Check the tests module for examples on how to use the simulation framework.
 use std::any::Any;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::convert::Infallible;
 use std::fmt::Debug;
 use std::future::ready;
+use std::ops::RangeInclusive;
 use std::pin::Pin;
 
 use futures::{Sink, SinkExt, StreamExt, sink};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
 use serde::{Deserialize, Serialize};
 use tokio::sync::mpsc::UnboundedSender;
 use tokio_stream::Stream;
@@ -103,6 +122,16 @@ impl Address {
     pub fn new(host: Hostname, interface: InterfaceName) -> Self {
         Address { host, interface }
     }
+
+    /// The hostname part of the address.
+    pub fn host(&self) -> &Hostname {
+        &self.host
+    }
+
+    /// The interface name part of the address.
+    pub fn interface(&self) -> &str {
+        &self.interface
+    }
 }
 
 /// A message sender is used to send messages to an inbox on a host.
@@ -136,6 +165,17 @@ pub struct Inbox {
 /// on their host.
 pub struct Outbox {
     receiver: Pin<Box<dyn Stream<Item = MessageWithAddress>>>,
+    duplicate: fn(&dyn Any) -> Box<dyn Any>,
+}
+
+/// Clones a type-erased message of type `T`. Used to duplicate messages in flight.
+fn duplicate_message<T: Clone + 'static>(message: &dyn Any) -> Box<dyn Any> {
+    Box::new(
+        message
+            .downcast_ref::<T>()
+            .expect("Message in outbox has unexpected type")
+            .clone(),
+    )
 }
 
 /// A host is a single Hydro/DFIR process running in the simulation. It has a unique hostname
@@ -145,7 +185,7 @@ pub struct Host {
     name: Hostname,
     process: Dfir<'static>,
     inputs: HashMap<InterfaceName, Inbox>,
-    output: HashMap<InterfaceName, Outbox>,
+    output: BTreeMap<InterfaceName, Outbox>,
 }
 
 impl Host {
@@ -161,13 +201,13 @@ pub struct HostBuilder {
     name: Hostname,
     process: Option<Dfir<'static>>,
     inboxes: HashMap<InterfaceName, Inbox>,
-    outboxes: HashMap<InterfaceName, Outbox>,
+    outboxes: BTreeMap<InterfaceName, Outbox>,
 }
 
 /// Used in conjunction with the `HostBuilder` to construct a host in the simulation.
 pub struct ProcessBuilderContext<'context> {
     inboxes: &'context mut HashMap<InterfaceName, Inbox>,
-    outboxes: &'context mut HashMap<InterfaceName, Outbox>,
+    outboxes: &'context mut BTreeMap<InterfaceName, Outbox>,
 }
 
 fn sink_from_fn<T>(mut f: impl FnMut(T)) -> impl Sink<T, Error = Infallible> {
@@ -196,7 +236,7 @@ impl ProcessBuilderContext<'_> {
 
     /// Creates a new outbox on the host with the given interface name. Returns a sink that can
     /// be written to by the process using the dest_sink dfir operator.
-    pub fn new_outbox<T: 'static>(
+    pub fn new_outbox<T: Clone + 'static>(
         &mut self,
         interface: InterfaceName,
     ) -> impl use<T> + Sink<(T, Address), Error = Infallible> {
@@ -208,6 +248,7 @@ impl ProcessBuilderContext<'_> {
             interface,
             Outbox {
                 receiver: Box::pin(receiver),
+                duplicate: duplicate_message::<T>,
             },
         );
 
@@ -255,20 +296,198 @@ impl HostBuilder {
     }
 }
 
+/// A message travelling through the virtual network, from the outbox of the source host to the
+/// inbox of the destination host.
+pub struct Envelope {
+    src: Address,
+    dst: Address,
+    message: Box<dyn Any>,
+    deliver_at: u64,
+}
+
+impl Envelope {
+    /// The address of the outbox the message was sent from.
+    pub fn src(&self) -> &Address {
+        &self.src
+    }
+
+    /// The address of the inbox the message is addressed to.
+    pub fn dst(&self) -> &Address {
+        &self.dst
+    }
+}
+
+/// A network policy decides what happens to messages as they cross the virtual network. It is the
+/// point at which a unit test injects faults such as drops, delays, duplicates and reordering.
+///
+/// All randomness used by a policy should come from a seeded source so that simulation runs are
+/// reproducible.
+pub trait NetworkPolicy {
+    /// Called once for every message sent from `src` to `dst`. Returns the delay, in network
+    /// rounds, of every copy of the message that should be delivered. An empty result drops the
+    /// message, a delay of `0` delivers the copy in the current round.
+    fn schedule(&mut self, src: &Address, dst: &Address) -> Vec<u64>;
+
+    /// Called with all the messages that are about to be delivered in the current round, in the
+    /// order in which they would be delivered. The policy may permute them.
+    fn reorder(&mut self, _deliveries: &mut [Envelope]) {}
+}
+
+/// A network policy that delivers every message exactly once, in order, in the round in which it
+/// was sent.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ReliableNetwork;
+
+impl NetworkPolicy for ReliableNetwork {
+    fn schedule(&mut self, _src: &Address, _dst: &Address) -> Vec<u64> {
+        vec![0]
+    }
+}
+
+/// A network policy that randomly drops, delays, duplicates and reorders messages. All decisions
+/// are made by a random number generator seeded at construction, so two simulations running the
+/// same test with the same seed observe exactly the same network behavior.
+#[derive(Debug, Clone)]
+pub struct FaultyNetwork {
+    rng: StdRng,
+    drop_rate: f64,
+    duplicate_rate: f64,
+    delay: RangeInclusive<u64>,
+    link_delays: HashMap<(Hostname, Hostname), RangeInclusive<u64>>,
+    reorder: bool,
+}
+
+impl FaultyNetwork {
+    /// Creates a new policy seeded with `seed`. Until configured otherwise, the policy behaves like
+    /// [`ReliableNetwork`].
+    pub fn new(seed: u64) -> Self {
+        FaultyNetwork {
+            rng: StdRng::seed_from_u64(seed),
+            drop_rate: 0.0,
+            duplicate_rate: 0.0,
+            delay: 0..=0,
+            link_delays: HashMap::new(),
+            reorder: false,
+        }
+    }
+
+    /// Sets the probability, in `[0, 1]`, that a message is dropped.
+    pub fn drop_rate(mut self, drop_rate: f64) -> Self {
+        assert!(
+            (0.0..=1.0).contains(&drop_rate),
+            "Drop rate must be between 0 and 1"
+        );
+        self.drop_rate = drop_rate;
+        self
+    }
+
+    /// Sets the probability, in `[0, 1]`, that a delivered message is delivered an additional
+    /// time. Duplicates are themselves subject to duplication, so a message may be delivered many
+    /// times.
+    pub fn duplicate_rate(mut self, duplicate_rate: f64) -> Self {
+        assert!(
+            (0.0..1.0).contains(&duplicate_rate),
+            "Duplicate rate must be at least 0 and less than 1"
+        );
+        self.duplicate_rate = duplicate_rate;
+        self
+    }
+
+    /// Sets the range, in network rounds, from which the delay of every message is uniformly
+    /// sampled. Links configured with [`Self::link_delay`] use their own range instead.
+    pub fn delay(mut self, delay: RangeInclusive<u64>) -> Self {
+        assert!(!delay.is_empty(), "Delay range must not be empty");
+        self.delay = delay;
+        self
+    }
+
+    /// Sets the range, in network rounds, from which the delay of messages sent from host `src` to
+    /// host `dst` is uniformly sampled. The link is directional.
+    pub fn link_delay(mut self, src: Hostname, dst: Hostname, delay: RangeInclusive<u64>) -> Self {
+        assert!(!delay.is_empty(), "Delay range must not be empty");
+        self.link_delays.insert((src, dst), delay);
+        self
+    }
+
+    /// If `true`, the messages delivered in a single round are delivered in a random order.
+    pub fn reorder(mut self, reorder: bool) -> Self {
+        self.reorder = reorder;
+        self
+    }
+}
+
+impl NetworkPolicy for FaultyNetwork {
+    fn schedule(&mut self, src: &Address, dst: &Address) -> Vec<u64> {
+        if self.rng.gen_bool(self.drop_rate) {
+            return Vec::new();
+        }
+
+        let delay = self
+            .link_delays
+            .get(&(src.host.clone(), dst.host.clone()))
+            .unwrap_or(&self.delay)
+            .clone();
+
+        let mut delays = vec![self.rng.gen_range(delay.clone())];
+        while self.rng.gen_bool(self.duplicate_rate) {
+            delays.push(self.rng.gen_range(delay.clone()));
+        }
+        delays
+    }
+
+    fn reorder(&mut self, deliveries: &mut [Envelope]) {
+        if self.reorder {
+            deliveries.shuffle(&mut self.rng);
+        }
+    }
+}
+
+/// A named network partition between two groups of hosts. Messages between a host on one side and
+/// a host on the other side are dropped, in both directions.
+struct Partition {
+    side_a: BTreeSet<Hostname>,
+    side_b: BTreeSet<Hostname>,
+}
+
+impl Partition {
+    fn separates(&self, src: &Hostname, dst: &Hostname) -> bool {
+        (self.side_a.contains(src) && self.side_b.contains(dst))
+            || (self.side_b.contains(src) && self.side_a.contains(dst))
+    }
+}
+
 /// A fleet is a collection of hosts in the simulation. It is responsible for running the
 /// simulation and processing network messages.
 pub struct Fleet {
-    hosts: HashMap<String, Host>,
+    hosts: BTreeMap<String, Host>,
+    network: Box<dyn NetworkPolicy>,
+    partitions: BTreeMap<String, Partition>,
+    in_flight: Vec<Envelope>,
+    round: u64,
 }
 
 impl Fleet {
-    /// Creates a new instance of Fleet.
+    /// Creates a new instance of Fleet with a [`ReliableNetwork`].
     pub fn new() -> Self {
+        Self::with_network_policy(ReliableNetwork)
+    }
+
+    /// Creates a new instance of Fleet whose virtual network is governed by `policy`.
+    pub fn with_network_policy(policy: impl NetworkPolicy + 'static) -> Self {
         Fleet {
-            hosts: HashMap::new(),
+            hosts: BTreeMap::new(),
+            network: Box::new(policy),
+            partitions: BTreeMap::new(),
+            in_flight: Vec::new(),
+            round: 0,
         }
     }
 
+    /// Replaces the network policy. Messages already in flight keep their scheduled delivery round.
+    pub fn set_network_policy(&mut self, policy: impl NetworkPolicy + 'static) {
+        self.network = Box::new(policy);
+    }
+
     /// Adds a new host to the fleet with the given name and process.
     pub fn add_host<F>(&mut self, name: String, process_builder: F) -> &Host
     where
@@ -295,10 +514,46 @@ impl Fleet {
         self.hosts.get_mut(name)
     }
 
+    /// Opens a partition named `name` between the hosts in `side_a` and the hosts in `side_b`.
+    /// Hosts on the same side of the partition can still communicate. Panics if a partition with
+    /// the same name is already open.
+    pub fn partition(
+        &mut self,
+        name: impl Into<String>,
+        side_a: impl IntoIterator<Item = Hostname>,
+        side_b: impl IntoIterator<Item = Hostname>,
+    ) {
+        let name = name.into();
+        let partition = Partition {
+            side_a: side_a.into_iter().collect(),
+            side_b: side_b.into_iter().collect(),
+        };
+        assert!(
+            self.partitions.insert(name.clone(), partition).is_none(),
+            "Partition with name {} already exists",
+            name
+        );
+    }
+
+    /// Heals the partition named `name`. Returns false if no such partition is open.
+    pub fn heal(&mut self, name: &str) -> bool {
+        self.partitions.remove(name).is_some()
+    }
+
+    /// Heals all open partitions.
+    pub fn heal_all(&mut self) {
+        self.partitions.clear();
+    }
+
+    /// The number of messages that have been sent but not yet delivered (or dropped).
+    pub fn messages_in_flight(&self) -> usize {
+        self.in_flight.len()
+    }
+
     /// Advance time on all hosts by a single tick. Returns true if any work was done by any of the
     /// hosts. After ticking once on all the hosts, the method also processes network messages.
     ///
-    /// The order in which the ticks are processed is not guaranteed.
+    /// Hosts are ticked in order of their hostnames.
     pub async fn run_single_tick_all_hosts(&mut self) -> bool {
         let mut work_done: bool = false;
 
@@ -312,50 +567,94 @@ impl Fleet {
         work_done
     }
 
-    /// Process all network messages in the simulation. This method picks up all messages from all
-    /// outboxes on all hosts and delivers them to the corresponding inboxes on the destination.
+    /// Process all network messages in the simulation for one network round. This method picks up
+    /// all messages from all outboxes on all hosts, hands them to the network policy, and delivers
+    /// every message whose delay has elapsed to the corresponding inbox on the destination.
     ///
-    /// The order in which the messages are processed is not guaranteed.
+    /// Messages are collected in order of their source hostname and interface name, so the
+    /// network behavior is fully determined by the network policy.
     pub async fn process_network(&mut self) {
-        let mut all_messages: Vec<(Address, MessageWithAddress)> = Vec::new();
-
         // Collect all messages from all outboxes on all hosts.
         for (name, host) in self.hosts.iter_mut() {
             for (interface, output) in host.output.iter_mut() {
                 let src_address = Address::new(name.clone(), interface.clone());
                 let all_messages_on_interface: Vec<_> =
                     collect_ready_async(&mut output.receiver).await;
-                for message_on_interface in all_messages_on_interface {
-                    all_messages.push((src_address.clone(), message_on_interface));
+                for (msg, dst_address) in all_messages_on_interface {
+                    let delays = self.network.schedule(&src_address, &dst_address);
+                    let Some((&delay, duplicate_delays)) = delays.split_last() else {
+                        trace!(
+                            "Network policy dropped message {:?} from {:?} to {:?}.",
+                            msg, src_address, dst_address
+                        );
+                        continue;
+                    };
+                    for &duplicate_delay in duplicate_delays {
+                        self.in_flight.push(Envelope {
+                            src: src_address.clone(),
+                            dst: dst_address.clone(),
+                            message: (output.duplicate)(&*msg),
+                            deliver_at: self.round + duplicate_delay,
+                        });
+                    }
+                    self.in_flight.push(Envelope {
+                        src: src_address.clone(),
+                        dst: dst_address,
+                        message: msg,
+                        deliver_at: self.round + delay,
+                    });
                 }
             }
         }
 
-        // Deliver all messages to the corresponding inboxes on the destination hosts.
-        for (src_address, (msg, addr)) in all_messages {
-            if let Some(destination_host) = self.hosts.get(&addr.host) {
-                if let Some(input) = destination_host.inputs.get(&addr.interface) {
-                    input.sender.send((msg, src_address.clone()));
+        // Pick out the messages that are due in this round.
+        let round = self.round;
+        let (mut deliveries, in_flight): (Vec<_>, Vec<_>) = std::mem::take(&mut self.in_flight)
+            .into_iter()
+            .partition(|envelope| envelope.deliver_at <= round);
+        self.in_flight = in_flight;
+        self.round += 1;
+
+        self.network.reorder(&mut deliveries);
+
+        // Deliver the messages to the corresponding inboxes on the destination hosts.
+        for Envelope {
+            src, dst, message, ..
+        } in deliveries
+        {
+            if let Some((partition_name, _)) = self
+                .partitions
+                .iter()
+                .find(|(_, partition)| partition.separates(&src.host, &dst.host))
+            {
+                trace!(
+                    "Partition {:?} separates {:?} from {:?}. Dropping message {:?}.",
+                    partition_name, src.host, dst.host, message
+                );
+            } else if let Some(destination_host) = self.hosts.get(&dst.host) {
+                if let Some(input) = destination_host.inputs.get(&dst.interface) {
+                    input.sender.send((message, src));
                 } else {
                     trace!(
                         "No interface named {:?} found on host {:?}. Dropping message {:?}.",
-                        addr.interface, addr.host, msg
+                        dst.interface, dst.host, message
                     );
                 }
             } else {
                 trace!(
                     "No host named {:?} found. Dropping message {:?}.",
-                    addr.host, msg
+                    dst.host, message
                 );
             }
         }
     }
 
-    /// Tick all hosts until all hosts are quiescent (i.e. no new work is done by any host). Ticking
-    /// is done in "rounds". At each round, all hosts are ticked once and then network messages are
-    /// processed. The process continues until no work is done by any host in a round.
+    /// Tick all hosts until all hosts are quiescent (i.e. no new work is done by any host) and no
+    /// messages are in flight. Ticking is done in "rounds". At each round, all hosts are ticked
+    /// once and then network messages are processed. The process continues until no work is done
+    /// by any host in a round and the network has delivered or dropped every message.
     pub async fn run_until_quiescent(&mut self) {
-        while self.run_single_tick_all_hosts().await {}
+        while self.run_single_tick_all_hosts().await || !self.in_flight.is_empty() {}
     }
 }
 
@@ -369,9 +668,11 @@ impl Default for Fleet {
 mod tests {
     use dfir_macro::{dfir_syntax, dfir_test};
     use futures::StreamExt;
+    use tokio::sync::mpsc::UnboundedSender;
+    use tokio_stream::wrappers::UnboundedReceiverStream;
 
-    use crate::util::simulation::{Address, Fleet, Hostname};
-    use crate::util::unbounded_channel;
+    use crate::util::simulation::{Address, FaultyNetwork, Fleet, Hostname, NetworkPolicy};
+    use crate::util::{collect_ready_async, unbounded_channel};
 
     /// A simple test to demonstrate use of the simulation framework. Implements an echo server
     /// and client.
@@ -434,4 +735,122 @@ mod tests {
         let response = client_response_rx.next().await.unwrap();
         assert_eq!(response, "Hello, world!");
     }
+
+    /// Builds a fleet running an echo server and a client using the given network policy. Returns
+    /// the fleet, a channel to trigger client requests and a stream of the responses the client
+    /// received.
+    fn echo_fleet(
+        policy: impl NetworkPolicy + 'static,
+    ) -> (
+        Fleet,
+        UnboundedSender<String>,
+        UnboundedReceiverStream<String>,
+    ) {
+        let mut fleet = Fleet::with_network_policy(policy);
+        let interface: String = "echo".to_string();
+        let server_address = Address::new("server".to_string(), interface.clone());
+
+        fleet.add_host("server".to_string(), |ctx| {
+            let network_input = ctx.new_inbox::<String>(interface.clone());
+            let network_output = ctx.new_outbox::<String>(interface.clone());
+            dfir_syntax! {
+                source_stream(network_input) -> dest_sink(network_output);
+            }
+        });
+
+        let (client_trigger_tx, client_trigger_rx) = unbounded_channel::<String>();
+        let (client_response_tx, client_response_rx) = unbounded_channel::<String>();
+
+        fleet.add_host("client".to_string(), |ctx| {
+            let network_out = ctx.new_outbox::<String>(interface.clone());
+            let network_in = ctx.new_inbox::<String>(interface.clone());
+            dfir_syntax! {
+                source_stream(client_trigger_rx)
+                    -> map(|msg| (msg, server_address.clone()))
+                    -> dest_sink(network_out);
+
+                source_stream(network_in)
+                    -> for_each(|(msg, _addr)| client_response_tx.send(msg).unwrap());
+            }
+        });
+
+        (fleet, client_trigger_tx, client_response_rx)
+    }
+
+    #[dfir_test]
+    async fn test_drop_all() {
+        let (mut fleet, trigger_tx, mut response_rx) =
+            echo_fleet(FaultyNetwork::new(0).drop_rate(1.0));
+
+        trigger_tx.send("Hello, world!".to_string()).unwrap();
+        fleet.run_until_quiescent().await;
+
+        let responses: Vec<String> = collect_ready_async(&mut response_rx).await;
+        assert!(responses.is_empty());
+    }
+
+    #[dfir_test]
+    async fn test_link_delay() {
+        let (mut fleet, trigger_tx, mut response_rx) = echo_fleet(
+            FaultyNetwork::new(0).link_delay("client".to_string(), "server".to_string(), 3..=3),
+        );
+
+        trigger_tx.send("Hello, world!".to_string()).unwrap();
+
+        // Without delay, a round trip takes three rounds (client sends, server echoes, client
+        // receives). The request spends three additional rounds in flight.
+        let mut rounds = 0;
+        loop {
+            fleet.run_single_tick_all_hosts().await;
+            rounds += 1;
+            let responses: Vec<String> = collect_ready_async(&mut response_rx).await;
+            if !responses.is_empty() {
+                assert_eq!(responses, &["Hello, world!"]);
+                break;
+            }
+            assert!(rounds < 10, "Response never arrived");
+        }
+        assert_eq!(rounds, 6);
+    }
+
+    #[dfir_test]
+    async fn test_partition_heal() {
+        let (mut fleet, trigger_tx, mut response_rx) = echo_fleet(FaultyNetwork::new(0));
+
+        fleet.partition("split", ["client".to_string()], ["server".to_string()]);
+        trigger_tx.send("lost".to_string()).unwrap();
+        fleet.run_until_quiescent().await;
+        let responses: Vec<String> = collect_ready_async(&mut response_rx).await;
+        assert!(responses.is_empty());
+
+        assert!(fleet.heal("split"));
+        assert!(!fleet.heal("split"));
+        trigger_tx.send("delivered".to_string()).unwrap();
+        fleet.run_until_quiescent().await;
+        let responses: Vec<String> = collect_ready_async(&mut response_rx).await;
+        assert_eq!(responses, &["delivered"]);
+    }
+
+    #[dfir_test]
+    async fn test_seed_reproducible() {
+        async fn run(seed: u64) -> Vec<String> {
+            let (mut fleet, trigger_tx, mut response_rx) = echo_fleet(
+                FaultyNetwork::new(seed)
+                    .drop_rate(0.2)
+                    .duplicate_rate(0.3)
+                    .delay(0..=2)
+                    .reorder(true),
+            );
+            for i in 0..20 {
+                trigger_tx.send(i.to_string()).unwrap();
+                fleet.run_single_tick_all_hosts().await;
+            }
+            fleet.run_until_quiescent().await;
+            collect_ready_async(&mut response_rx).await
+        }
+
+        let first = run(42).await;
+        assert_eq!(first, run(42).await);
+        assert_ne!(first, run(43).await);
+    }
 }
@@ -260,6 +260,14 @@ impl<Tag: ?Sized, Val> Default for SecondarySlotVec<Tag, Val> {
         Self::new()
     }
 }
+impl<Tag: ?Sized, Val: Clone> Clone for SecondarySlotVec<Tag, Val> {
+    fn clone(&self) -> Self {
+        Self {
+            slots: self.slots.clone(),
+            _phantom: PhantomData,
+        }
+    }
+}
 impl<Tag: ?Sized, Val> Index<Key<Tag>> for SecondarySlotVec<Tag, Val> {
     type Output = Val;
 
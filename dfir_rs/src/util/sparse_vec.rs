@@ -3,6 +3,9 @@ use std::collections::HashMap;
 use std::hash::Hash;
 use std::iter::FusedIterator;
 
+use serde::de::{Deserialize, Deserializer};
+use serde::ser::{Serialize, SerializeSeq, Serializer};
+
 /// A vector that supports efficient deletion without reordering all subsequent items.
 pub struct SparseVec<T> {
     items: Vec<Option<T>>,
@@ -43,6 +46,28 @@ impl<T: Clone + Eq + Hash> SparseVec<T> {
     }
 }
 
+/// Serializes only the remaining items, so deleted items are compacted away.
+impl<T: Serialize> Serialize for SparseVec<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let items = self.items.iter().flatten();
+        let mut seq = serializer.serialize_seq(Some(items.clone().count()))?;
+        for item in items {
+            seq.serialize_element(item)?;
+        }
+        seq.end()
+    }
+}
+
+impl<'de, T: Clone + Eq + Hash + Deserialize<'de>> Deserialize<'de> for SparseVec<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut sparse_vec = SparseVec::default();
+        for item in Vec::<T>::deserialize(deserializer)? {
+            sparse_vec.push(item);
+        }
+        Ok(sparse_vec)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -73,4 +98,18 @@ mod test {
         assert_eq!(x.items.len(), 0);
         assert_eq!(x.item_locs.len(), 0);
     }
+
+    #[test]
+    fn serde_round_trip() {
+        let mut x = SparseVec::default();
+
+        x.push(0);
+        x.push(1);
+        x.push(2);
+        x.delete(&1);
+
+        let y: SparseVec<i32> = bincode::deserialize(&bincode::serialize(&x).unwrap()).unwrap();
+        assert_eq!(collect(&y), vec![0, 2]);
+        assert_eq!(y.items.len(), 2);
+    }
 }
@@ -0,0 +1,11 @@
+use dfir_rs::datalog;
+
+fn main() {
+    let mut df = datalog!(r#"
+        .process leader `leader`
+        .input in1@leader `source_iter(0..10) -> map(|x| (x, x))`
+        .output out `null::<(u32,)>()`
+        out(a) :- in1(a, b)
+    "#);
+    df.run_available();
+}
@@ -0,0 +1,13 @@
+use dfir_rs::datalog;
+
+fn main() {
+    let mut df = datalog!(r#"
+        .incremental
+        .input edges `source_iter([(1, 2), (2, 3)])`
+        .output out `null::<((u32, u32), isize)>()`
+        path(x, y) :- edges(x, y)
+        path(x, z) :- path(x, y), edges(y, z)
+        out(x, y) :- path(x, y)
+    "#);
+    df.run_available();
+}
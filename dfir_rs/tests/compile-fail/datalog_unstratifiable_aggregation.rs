@@ -0,0 +1,12 @@
+use dfir_rs::datalog;
+
+fn main() {
+    let mut df = datalog!(r#"
+        .input edges `source_iter([(1, 2), (2, 3)])`
+        .output out `null::<(u32, usize)>()`
+        degree(x, count(y)) :- edges(x, y)
+        degree(y, count(d)) :- degree(x, d), edges(x, y)
+        out(x, d) :- degree(x, d)
+    "#);
+    df.run_available();
+}
@@ -0,0 +1,12 @@
+use dfir_rs::datalog;
+
+fn main() {
+    let mut df = datalog!(r#"
+        .input edges `source_iter([(1, 2), (2, 3)])`
+        .output out `null::<(u32,)>()`
+        reachable(y) :- edges(x, y), !unreachable(x)
+        unreachable(x) :- edges(x, _), !reachable(x)
+        out(x) :- reachable(x)
+    "#);
+    df.run_available();
+}
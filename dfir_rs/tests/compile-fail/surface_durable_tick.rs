@@ -0,0 +1,8 @@
+use dfir_rs::dfir_syntax;
+
+fn main() {
+    let mut df = dfir_syntax! {
+        source_iter(0..10) -> fold::<'tick, 'durable>(|| 0, |acc: &mut u32, x| *acc += x) -> for_each(std::mem::drop);
+    };
+    df.run_available();
+}
@@ -1290,6 +1290,154 @@ fn test_detuple_then_flat() {
     );
 }
 
+#[multiplatform_test]
+fn test_recursive_min() {
+    let (edges_send, edges) = dfir_rs::util::unbounded_channel::<(usize, usize, usize)>();
+    let (result, mut result_recv) = dfir_rs::util::unbounded_channel::<(usize, usize, usize)>();
+
+    let mut flow = datalog!(
+        r#"
+        .input edges `source_stream(edges)`
+        .output result `for_each(|v| result.send(v).unwrap())`
+
+        shortest(x, y, min(d)) :- edges(x, y, d)
+        shortest(x, z, min(d1 + d2)) :- shortest(x, y, d1), edges(y, z, d2)
+        result(x, y, d) :- shortest(x, y, d), (x == 1)
+        "#
+    );
+
+    edges_send.send((1, 2, 1)).unwrap();
+    edges_send.send((2, 3, 1)).unwrap();
+    edges_send.send((1, 3, 5)).unwrap();
+    edges_send.send((3, 1, 1)).unwrap();
+    edges_send.send((3, 4, 2)).unwrap();
+
+    flow.run_tick();
+
+    assert_eq!(
+        collect_ready::<HashMultiSet<_>, _>(&mut result_recv),
+        HashMultiSet::from_iter([(1, 1, 3), (1, 2, 1), (1, 3, 2), (1, 4, 4)])
+    );
+}
+
+#[multiplatform_test]
+fn test_recursive_max() {
+    let (edges_send, edges) = dfir_rs::util::unbounded_channel::<(usize, usize, usize)>();
+    let (result, mut result_recv) = dfir_rs::util::unbounded_channel::<(usize, usize)>();
+
+    let mut flow = datalog!(
+        r#"
+        .input edges `source_stream(edges)`
+        .output result `for_each(|v| result.send(v).unwrap())`
+
+        .function lesser `std::cmp::min`
+
+        widest(x, y, max(w)) :- edges(x, y, w)
+        widest(x, z, max(lesser(w1, w2))) :- widest(x, y, w1), edges(y, z, w2)
+        result(y, w) :- widest(x, y, w), (x == 1)
+        "#
+    );
+
+    edges_send.send((1, 2, 3)).unwrap();
+    edges_send.send((2, 4, 5)).unwrap();
+    edges_send.send((1, 3, 7)).unwrap();
+    edges_send.send((3, 4, 6)).unwrap();
+
+    flow.run_tick();
+
+    assert_eq!(
+        collect_ready::<HashMultiSet<_>, _>(&mut result_recv),
+        HashMultiSet::from_iter([(2, 3), (3, 7), (4, 6)])
+    );
+}
+
+#[multiplatform_test]
+fn test_div_and_comparison() {
+    let (ints_send, ints) = dfir_rs::util::unbounded_channel::<(i64, i64)>();
+    let (result, mut result_recv) = dfir_rs::util::unbounded_channel::<(i64, bool)>();
+
+    let mut flow = datalog!(
+        r#"
+        .input ints `source_stream(ints)`
+        .output result `for_each(|v| result.send(v).unwrap())`
+
+        result(a / b, a % b == 0) :- ints(a, b), (b != 0)
+        "#
+    );
+
+    ints_send.send((7, 2)).unwrap();
+    ints_send.send((9, 3)).unwrap();
+    ints_send.send((1, 0)).unwrap();
+
+    flow.run_tick();
+
+    assert_eq!(
+        collect_ready::<HashMultiSet<_>, _>(&mut result_recv),
+        HashMultiSet::from_iter([(3, false), (3, true)])
+    );
+}
+
+#[multiplatform_test]
+fn test_string_functions() {
+    let (names_send, names) = dfir_rs::util::unbounded_channel::<(String, String)>();
+    let (result, mut result_recv) =
+        dfir_rs::util::unbounded_channel::<(String, usize, String, bool)>();
+
+    let mut flow = datalog!(
+        r#"
+        .input names `source_stream(names)`
+        .output result `for_each(|v| result.send(v).unwrap())`
+
+        result(concat(first, to_upper(last)), strlen(first), substr(last, 0, 2), contains(last, "ring")) :- names(first, last)
+        "#
+    );
+
+    names_send
+        .send(("ada".to_owned(), "lovelace".to_owned()))
+        .unwrap();
+    names_send
+        .send(("alan".to_owned(), "turing".to_owned()))
+        .unwrap();
+
+    flow.run_tick();
+
+    assert_eq!(
+        collect_ready::<HashMultiSet<_>, _>(&mut result_recv),
+        HashMultiSet::from_iter([
+            ("adaLOVELACE".to_owned(), 3, "lo".to_owned(), false),
+            ("alanTURING".to_owned(), 4, "tu".to_owned(), true)
+        ])
+    );
+}
+
+#[multiplatform_test]
+fn test_user_function() {
+    let (ints_send, ints) = dfir_rs::util::unbounded_channel::<(u32, u32)>();
+    let (result, mut result_recv) = dfir_rs::util::unbounded_channel::<(u32, u32)>();
+
+    let mut flow = datalog!(
+        r#"
+        .function dist `|a: u32, b: u32| a.abs_diff(b)`
+        .function double `|a: u32| 2 * a`
+        .input ints `source_stream(ints)`
+        .output result `for_each(|v| result.send(v).unwrap())`
+
+        result(a, double(dist(a, b))) :- ints(a, b), (dist(a, b) > 1)
+        "#
+    );
+
+    ints_send.send((1, 5)).unwrap();
+    ints_send.send((4, 3)).unwrap();
+    ints_send.send((9, 2)).unwrap();
+
+    flow.run_tick();
+
+    assert_eq!(
+        collect_ready::<HashMultiSet<_>, _>(&mut result_recv),
+        HashMultiSet::from_iter([(1, 8), (9, 14)])
+    );
+}
+
 // #[ignore] doesn't seem to work for #[multiplatform_test]
 // #[ignore] // This test depends on the ordering of specific tuples which is undefined.
 // #[multiplatform_test]
@@ -1405,3 +1553,124 @@ fn test_detuple_then_flat() {
 //         HashMultiSet::from_iter([(1, 1, 0), (1, 2, 1), (2, 1, 2), (3, 1, 3)])
 //     );
 // }
+
+#[multiplatform_test]
+fn test_incremental_transitive_closure() {
+    let (edges_send, edges) = dfir_rs::util::unbounded_channel::<(usize, usize)>();
+    let (out, mut out_recv) = dfir_rs::util::unbounded_channel::<((usize, usize), isize)>();
+
+    let mut flow = datalog!(
+        r#"
+        .incremental
+
+        .input edges `source_stream(edges)`
+        .persist edges
+        .output path `for_each(|v| out.send(v).unwrap())`
+
+        path(x, y) :- edges(x, y).
+        path(x, z) :- path(x, y), edges(y, z).
+        "#
+    );
+
+    edges_send.send((1, 2)).unwrap();
+    edges_send.send((2, 3)).unwrap();
+    flow.run_tick();
+
+    assert_eq!(
+        collect_ready::<HashMultiSet<_>, _>(&mut out_recv),
+        HashMultiSet::from_iter([((1, 2), 1), ((2, 3), 1), ((1, 3), 1)])
+    );
+
+    // Only the paths through the new edge are derived.
+    edges_send.send((3, 4)).unwrap();
+    flow.run_tick();
+
+    assert_eq!(
+        collect_ready::<HashMultiSet<_>, _>(&mut out_recv),
+        HashMultiSet::from_iter([((3, 4), 1), ((2, 4), 1), ((1, 4), 1)])
+    );
+
+    flow.run_tick();
+
+    assert_eq!(&*collect_ready::<Vec<_>, _>(&mut out_recv), &[]);
+}
+
+#[multiplatform_test]
+fn test_incremental_retractions() {
+    let (seen_send, seen) = dfir_rs::util::unbounded_channel::<(usize,)>();
+    let (removed_send, removed) = dfir_rs::util::unbounded_channel::<(usize,)>();
+    let (live_out, mut live_recv) = dfir_rs::util::unbounded_channel::<((usize,), isize)>();
+    let (total_out, mut total_recv) = dfir_rs::util::unbounded_channel::<((i32,), isize)>();
+
+    let mut flow = datalog!(
+        r#"
+        .incremental
+
+        .input seen `source_stream(seen)`
+        .persist seen
+        .input removed `source_stream(removed)`
+        .persist removed
+        .output live `for_each(|v| live_out.send(v).unwrap())`
+        .output total `for_each(|v| total_out.send(v).unwrap())`
+
+        live(x) :- seen(x), !removed(x).
+        total(count(*)) :- live(_).
+        "#
+    );
+
+    seen_send.send((1,)).unwrap();
+    seen_send.send((2,)).unwrap();
+    flow.run_tick();
+
+    assert_eq!(
+        collect_ready::<HashMultiSet<_>, _>(&mut live_recv),
+        HashMultiSet::from_iter([((1,), 1), ((2,), 1)])
+    );
+    assert_eq!(&*collect_ready::<Vec<_>, _>(&mut total_recv), &[((2,), 1)]);
+
+    removed_send.send((1,)).unwrap();
+    flow.run_tick();
+
+    assert_eq!(&*collect_ready::<Vec<_>, _>(&mut live_recv), &[((1,), -1)]);
+    assert_eq!(
+        collect_ready::<HashMultiSet<_>, _>(&mut total_recv),
+        HashMultiSet::from_iter([((2,), -1), ((1,), 1)])
+    );
+
+    flow.run_tick();
+
+    assert_eq!(&*collect_ready::<Vec<_>, _>(&mut live_recv), &[]);
+    assert_eq!(&*collect_ready::<Vec<_>, _>(&mut total_recv), &[]);
+}
+
+#[multiplatform_test]
+fn test_incremental_transient_input() {
+    let (input_send, input) = dfir_rs::util::unbounded_channel::<(usize,)>();
+    let (out, mut out_recv) = dfir_rs::util::unbounded_channel::<((usize,), isize)>();
+
+    let mut flow = datalog!(
+        r#"
+        .incremental
+
+        .input input `source_stream(input)`
+        .static offsets `[(10,)]`
+        .output shifted `for_each(|v| out.send(v).unwrap())`
+
+        shifted(x + o) :- input(x), offsets(o).
+        "#
+    );
+
+    input_send.send((1,)).unwrap();
+    flow.run_tick();
+
+    assert_eq!(&*collect_ready::<Vec<_>, _>(&mut out_recv), &[((11,), 1)]);
+
+    // Rows of a relation which is not persisted are retracted in the next tick.
+    input_send.send((2,)).unwrap();
+    flow.run_tick();
+
+    assert_eq!(
+        collect_ready::<HashMultiSet<_>, _>(&mut out_recv),
+        HashMultiSet::from_iter([((12,), 1), ((11,), -1)])
+    );
+}
@@ -0,0 +1,225 @@
+use std::cell::RefCell;
+use std::time::Duration;
+
+use dfir_rs::dfir_syntax;
+use dfir_rs::scheduled::checkpoint::CheckpointDir;
+use dfir_rs::scheduled::graph::Dfir;
+use dfir_rs::util::{Persistence, PersistenceKeyed, collect_ready};
+use lattices::set_union::{SetUnionHashSet, SetUnionSingletonSet};
+use tokio::sync::mpsc::UnboundedSender;
+use tokio_stream::wrappers::UnboundedReceiverStream;
+
+/// Runs a fresh instance of `make_flow` restored from `dir`, with `inputs` sent one tick at a
+/// time, and returns everything it output. Simulates a process restart between calls.
+fn run_restarted<In, Out>(
+    dir: &tempfile::TempDir,
+    inputs: impl IntoIterator<Item = In>,
+    make_flow: impl FnOnce(UnboundedReceiverStream<In>, UnboundedSender<Out>) -> Dfir<'static>,
+) -> Vec<Out> {
+    let (input_send, input_recv) = dfir_rs::util::unbounded_channel::<In>();
+    let (output_send, mut output_recv) = dfir_rs::util::unbounded_channel::<Out>();
+
+    let mut df = make_flow(input_recv, output_send);
+    df.set_checkpoint_dir(
+        CheckpointDir::open(dir.path())
+            .unwrap()
+            .interval(Duration::ZERO),
+    )
+    .unwrap();
+    df.run_available();
+    for input in inputs {
+        input_send.send(input).unwrap();
+        df.run_available();
+    }
+    collect_ready(&mut output_recv)
+}
+
+#[test]
+pub fn test_durable_fold() {
+    let dir = tempfile::tempdir().unwrap();
+    let make_flow = |input_recv: UnboundedReceiverStream<u32>,
+                     output_send: UnboundedSender<u32>| {
+        dfir_syntax! {
+            sum = source_stream(input_recv)
+                -> fold::<'static, 'durable>(|| 0, |acc: &mut u32, x| *acc += x)
+                -> for_each(|sum| output_send.send(sum).unwrap());
+        }
+    };
+
+    assert_eq!(&[0, 1, 3], &*run_restarted(&dir, [1, 2], make_flow));
+    assert_eq!(&[3, 7], &*run_restarted(&dir, [4], make_flow));
+    assert_eq!(&[7], &*run_restarted(&dir, [], make_flow));
+}
+
+#[test]
+pub fn test_durable_persist() {
+    let dir = tempfile::tempdir().unwrap();
+    let make_flow = |input_recv: UnboundedReceiverStream<String>,
+                     output_send: UnboundedSender<Vec<String>>| {
+        dfir_syntax! {
+            all = source_stream(input_recv)
+                -> persist::<'static, 'durable>()
+                -> fold(Vec::new, Vec::push)
+                -> for_each(|all| output_send.send(all).unwrap());
+        }
+    };
+
+    assert_eq!(
+        vec!["a", "b"],
+        run_restarted(&dir, ["a", "b"].map(String::from), make_flow)
+            .pop()
+            .unwrap()
+    );
+    assert_eq!(
+        vec!["a", "b", "c"],
+        run_restarted(&dir, ["c".to_owned()], make_flow)
+            .pop()
+            .unwrap()
+    );
+}
+
+#[test]
+pub fn test_durable_persist_mut_keyed() {
+    let dir = tempfile::tempdir().unwrap();
+    let make_flow = |input_recv: UnboundedReceiverStream<PersistenceKeyed<u32, char>>,
+                     output_send: UnboundedSender<Vec<(u32, char)>>| {
+        dfir_syntax! {
+            all = source_stream(input_recv)
+                -> persist_mut_keyed::<'mutable, 'durable>()
+                -> fold(Vec::new, Vec::push)
+                -> map(|mut all| { all.sort(); all })
+                -> for_each(|all| output_send.send(all).unwrap());
+        }
+    };
+
+    let inputs = [
+        PersistenceKeyed::Persist(1, 'a'),
+        PersistenceKeyed::Persist(2, 'b'),
+        PersistenceKeyed::Delete(1),
+    ];
+    assert_eq!(
+        vec![(2, 'b')],
+        run_restarted(&dir, inputs, make_flow).pop().unwrap()
+    );
+    assert_eq!(
+        vec![(2, 'b'), (3, 'c')],
+        run_restarted(&dir, [PersistenceKeyed::Persist(3, 'c')], make_flow)
+            .pop()
+            .unwrap()
+    );
+}
+
+#[test]
+pub fn test_durable_persist_mut() {
+    let dir = tempfile::tempdir().unwrap();
+    let make_flow = |input_recv: UnboundedReceiverStream<Persistence<char>>,
+                     output_send: UnboundedSender<Vec<char>>| {
+        dfir_syntax! {
+            all = source_stream(input_recv)
+                -> persist_mut::<'mutable, 'durable>()
+                -> fold(Vec::new, Vec::push)
+                -> map(|mut all| { all.sort(); all })
+                -> for_each(|all| output_send.send(all).unwrap());
+        }
+    };
+
+    let inputs = [
+        Persistence::Persist('a'),
+        Persistence::Persist('b'),
+        Persistence::Delete('a'),
+    ];
+    assert_eq!(
+        vec!['b'],
+        run_restarted(&dir, inputs, make_flow).pop().unwrap()
+    );
+    assert_eq!(
+        vec!['b', 'c'],
+        run_restarted(&dir, [Persistence::Persist('c')], make_flow)
+            .pop()
+            .unwrap()
+    );
+}
+
+#[test]
+pub fn test_durable_checkpoint_interval() {
+    let dir = tempfile::tempdir().unwrap();
+    let (input_send, input_recv) = dfir_rs::util::unbounded_channel::<u32>();
+    let (output_send, mut output_recv) = dfir_rs::util::unbounded_channel::<u32>();
+
+    let mut df = dfir_syntax! {
+        sum = source_stream(input_recv)
+            -> fold::<'static, 'durable>(|| 0, |acc: &mut u32, x| *acc += x)
+            -> for_each(|sum| output_send.send(sum).unwrap());
+    };
+    df.set_checkpoint_dir(
+        CheckpointDir::open(dir.path())
+            .unwrap()
+            .interval(Duration::from_secs(3600)),
+    )
+    .unwrap();
+    // The first tick writes a checkpoint, later ones wait for the interval.
+    input_send.send(1).unwrap();
+    df.run_available();
+    input_send.send(2).unwrap();
+    df.run_available();
+    assert_eq!(&[1, 3], &*collect_ready::<Vec<_>, _>(&mut output_recv));
+    drop(df);
+
+    let (_input_send, input_recv) = dfir_rs::util::unbounded_channel::<u32>();
+    let (output_send, mut output_recv) = dfir_rs::util::unbounded_channel::<u32>();
+    let mut df = dfir_syntax! {
+        sum = source_stream(input_recv)
+            -> fold::<'static, 'durable>(|| 0, |acc: &mut u32, x| *acc += x)
+            -> for_each(|sum| output_send.send(sum).unwrap());
+    };
+    df.set_checkpoint_dir(CheckpointDir::open(dir.path()).unwrap())
+        .unwrap();
+    df.run_available();
+    assert_eq!(&[1], &*collect_ready::<Vec<_>, _>(&mut output_recv));
+}
+
+#[test]
+pub fn test_durable_state() {
+    let dir = tempfile::tempdir().unwrap();
+    let make_flow = |input_recv: UnboundedReceiverStream<u32>,
+                     output_send: UnboundedSender<usize>| {
+        dfir_syntax! {
+            my_state = source_stream(input_recv)
+                -> map(SetUnionSingletonSet::new_from)
+                -> state::<'static, 'durable, SetUnionHashSet<u32>>();
+
+            source_iter([()])
+                -> persist::<'static>()
+                -> for_each(|()| output_send.send(#my_state.as_reveal_ref().len()).unwrap());
+        }
+    };
+
+    assert_eq!(Some(2), run_restarted(&dir, [1, 2, 2], make_flow).pop());
+    assert_eq!(Some(3), run_restarted(&dir, [3], make_flow).pop());
+}
+
+#[test]
+pub fn test_durable_state_api() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let mut df = Dfir::new();
+    let handle = df.add_durable_state("counter", RefCell::new(0));
+    df.set_checkpoint_dir(CheckpointDir::open(dir.path()).unwrap())
+        .unwrap();
+    *df.context_mut(dfir_rs::scheduled::SubgraphId::from_raw(0))
+        .state_ref(handle)
+        .borrow_mut() = 5;
+    df.checkpoint().unwrap();
+    drop(df);
+
+    let mut df = Dfir::new();
+    let handle = df.add_durable_state("counter", RefCell::new(0));
+    df.set_checkpoint_dir(CheckpointDir::open(dir.path()).unwrap())
+        .unwrap();
+    assert_eq!(
+        5,
+        *df.context_mut(dfir_rs::scheduled::SubgraphId::from_raw(0))
+            .state_ref(handle)
+            .borrow()
+    );
+}
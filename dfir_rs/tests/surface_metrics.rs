@@ -0,0 +1,174 @@
+use std::time::Duration;
+
+use dfir_rs::dfir_syntax;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+#[test]
+pub fn test_metrics_counts() {
+    let mut df = dfir_syntax! {
+        source_iter(0..10)
+            -> filter(|n| n % 2 == 0)
+            -> sort()
+            -> for_each(|_| ());
+    };
+    assert!(df.metrics().is_none());
+    df.enable_metrics();
+    df.run_available();
+
+    let metrics = df.metrics().unwrap();
+    assert!(1 <= metrics.ticks);
+
+    // The handoff into `sort()` received all five even numbers, and they were all taken out.
+    let handoffs: Vec<_> = metrics.handoffs().map(|(_, m)| m).collect();
+    assert_eq!(1, handoffs.len());
+    assert_eq!(5, handoffs[0].items_given);
+    assert_eq!(0, handoffs[0].items);
+    assert_eq!(5, handoffs[0].items_taken());
+
+    let subgraphs: Vec<_> = metrics.subgraphs().map(|(_, m)| m).collect();
+    assert_eq!(2, subgraphs.len());
+    assert!(subgraphs.iter().all(|m| 1 == m.runs));
+    assert_eq!(5, subgraphs.iter().map(|m| m.items_in).sum::<u64>());
+    assert_eq!(5, subgraphs.iter().map(|m| m.items_out).sum::<u64>());
+
+    let text = metrics.to_openmetrics();
+    assert!(text.contains("# TYPE dfir_subgraph_runs counter\n"));
+    assert!(text.contains("\ndfir_handoff_items_given_total{handoff=\""));
+    assert!(text.ends_with("# EOF\n"));
+}
+
+#[dfir_rs::test]
+async fn test_serve_metrics() {
+    let mut df = dfir_syntax! {
+        source_iter(0..3) -> defer_tick() -> for_each(|_| ());
+    };
+    let addr = df.serve_metrics("127.0.0.1:0").unwrap();
+
+    let scrape = async move {
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    };
+    let response = tokio::select! {
+        response = scrape => response,
+        _ = df.run_async() => unreachable!(),
+        _ = tokio::time::sleep(Duration::from_secs(5)) => panic!("Timed out scraping metrics."),
+    };
+
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(response.contains("application/openmetrics-text"));
+    assert!(response.contains("\ndfir_ticks_total "));
+    assert!(response.contains("\ndfir_handoff_items{handoff=\""));
+    assert!(response.ends_with("# EOF\n"));
+}
+
+#[test]
+pub fn test_snapshot_state_sizes() {
+    let (input_send, input_recv) = dfir_rs::util::unbounded_channel::<u32>();
+    let mut df = dfir_syntax! {
+        kept = source_stream(input_recv)
+            -> unique::<'static>()
+            -> persist::<'static, 'durable>()
+            -> for_each(|_| ());
+    };
+    df.enable_metrics();
+
+    for n in [1, 2, 2, 3] {
+        input_send.send(n).unwrap();
+    }
+    df.run_available();
+
+    let snapshot = df.snapshot();
+    assert_eq!(u64::from(df.current_tick()), snapshot.tick);
+    let mut states: Vec<_> = snapshot
+        .states
+        .iter()
+        .map(|state| {
+            (
+                state.name.split(' ').next().unwrap(),
+                state.size,
+                state.durable,
+            )
+        })
+        .collect();
+    states.sort_unstable();
+    assert_eq!(vec![("persist", 3, true), ("unique", 3, false)], states);
+    assert!(snapshot.subgraphs.iter().any(|sg| 1 <= sg.runs));
+}
+
+#[dfir_rs::test]
+async fn test_serve_introspection() {
+    let mut df = dfir_syntax! {
+        source_iter(0..3) -> persist::<'static>() -> defer_tick() -> for_each(|_| ());
+    };
+    let addr = df.serve_introspection("127.0.0.1:0").unwrap();
+    // `persist` replays into the next tick forever, so only run two ticks.
+    df.run_tick();
+    df.run_tick();
+
+    // The server runs on its own thread, so responds while the graph is not running.
+    let scrape = async move {
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(b"GET /graph HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    };
+    let response = tokio::time::timeout(Duration::from_secs(5), scrape)
+        .await
+        .expect("Timed out fetching graph.");
+
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(head.contains("application/json"));
+    let json: serde_json::Value = serde_json::from_str(body).unwrap();
+    assert!(json["graph"].is_object());
+    assert_eq!(3, json["live"]["states"][0]["size"]);
+    assert!(1 <= json["live"]["tick"].as_u64().unwrap());
+}
+
+#[test]
+pub fn test_introspection_during_subgraph() {
+    fn get_graph(addr: std::net::SocketAddr) -> String {
+        use std::io::{Read, Write};
+
+        let mut stream = std::net::TcpStream::connect(addr).unwrap();
+        stream
+            .write_all(b"GET /graph HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    // The server runs on its own thread, so the graph can query it while running a subgraph.
+    let addr = std::rc::Rc::new(std::cell::OnceCell::new());
+    let response = std::rc::Rc::new(std::cell::RefCell::new(String::new()));
+    let mut df = {
+        let (addr, response) = (addr.clone(), response.clone());
+        dfir_syntax! {
+            source_iter([()])
+                -> next_stratum()
+                -> for_each(|()| *response.borrow_mut() = get_graph(*addr.get().unwrap()));
+        }
+    };
+    let addr = *addr.get_or_init(|| df.serve_introspection("127.0.0.1:0").unwrap());
+    df.run_available();
+
+    let response = response.take();
+    let (_head, body) = response.split_once("\r\n\r\n").unwrap();
+    let json: serde_json::Value = serde_json::from_str(body).unwrap();
+    assert_eq!(0, json["live"]["tick"]);
+    assert_eq!(1, json["live"]["stratum"]);
+
+    drop(df);
+    assert!(get_graph(addr).starts_with("HTTP/1.1 404 Not Found\r\n"));
+}
@@ -0,0 +1,116 @@
+use std::collections::HashSet;
+
+use dfir_rs::dfir_syntax;
+use dfir_rs::scheduled::graph::Dfir;
+use dfir_rs::scheduled::parallel;
+use dfir_rs::util::collect_ready;
+use tokio::sync::mpsc::UnboundedSender;
+
+/// Joins two keyed inputs, then counts the joined pairs per `value % 7`, re-partitioning between
+/// the two keyed operators. The inputs are split between the workers by `source_iter`.
+fn join_then_count(output_send: UnboundedSender<(u32, usize, String)>) -> Dfir<'static> {
+    dfir_syntax! {
+        lhs = source_iter(0..200) -> map(|n: u32| (n % 50, n)) -> exchange_keyed();
+        rhs = source_iter(0..100) -> map(|n: u32| (n % 50, n * 1000)) -> exchange_keyed();
+        joined = join::<'tick, 'tick>() -> map(|(_k, (l, r)): (u32, (u32, u32))| ((l + r) % 7, ()));
+        lhs -> [0]joined;
+        rhs -> [1]joined;
+        joined
+            -> exchange_keyed()
+            -> fold_keyed(|| 0, |count: &mut usize, ()| *count += 1)
+            -> for_each(|(k, count)| {
+                let thread = std::thread::current().name().unwrap_or_default().to_owned();
+                output_send.send((k, count, thread)).unwrap();
+            });
+    }
+}
+
+#[test]
+pub fn test_parallel_matches_single_thread() {
+    let (output_send, mut output_recv) = dfir_rs::util::unbounded_channel();
+    let mut df = join_then_count(output_send);
+    df.run_available();
+    let mut expected: Vec<_> = collect_ready::<Vec<_>, _>(&mut output_recv)
+        .into_iter()
+        .map(|(k, count, _thread)| (k, count))
+        .collect();
+    expected.sort();
+    assert!(!expected.is_empty());
+
+    let (output_send, mut output_recv) = dfir_rs::util::unbounded_channel();
+    parallel::run_available(4, |_worker| join_then_count(output_send.clone()));
+    let output = collect_ready::<Vec<_>, _>(&mut output_recv);
+
+    let mut counts: Vec<_> = output.iter().map(|(k, count, _)| (*k, *count)).collect();
+    counts.sort();
+    assert_eq!(expected, counts);
+
+    // Each key is folded once, on a worker thread.
+    let threads: HashSet<_> = output.iter().map(|(_, _, thread)| &**thread).collect();
+    assert!(threads.iter().all(|name| name.starts_with("dfir-worker-")));
+}
+
+#[test]
+pub fn test_parallel_ticks_in_lockstep() {
+    let (output_send, mut output_recv) = dfir_rs::util::unbounded_channel();
+    parallel::run_available(3, |worker| {
+        let output_send = output_send.clone();
+        dfir_syntax! {
+            // Each worker counts up for three ticks, sending every count to the worker owning it.
+            // `source_iter` splits its items between the workers, so each worker gets one zero.
+            counter = union() -> tee();
+            source_iter(vec![0; worker.num_workers]) -> counter;
+            counter -> filter(|&n| n < 2) -> map(|n| n + 1) -> defer_tick() -> counter;
+            counter
+                -> map(move |n| (n, worker.index))
+                -> exchange_keyed()
+                -> map(|(n, _from)| (n, context.current_tick().0))
+                -> fold_keyed(HashSet::new, |ticks: &mut HashSet<u64>, tick| {
+                    ticks.insert(tick);
+                })
+                -> for_each(|pair| output_send.send(pair).unwrap());
+        }
+    });
+
+    let mut output = collect_ready::<Vec<_>, _>(&mut output_recv);
+    output.sort_by_key(|(n, _)| *n);
+    // All workers send count `n` in tick `n`, and it arrives in the same tick.
+    assert_eq!(
+        vec![
+            (0, HashSet::from([0])),
+            (1, HashSet::from([1])),
+            (2, HashSet::from([2]))
+        ],
+        output
+    );
+}
+
+#[test]
+#[should_panic(expected = "worker 1 failed")]
+pub fn test_parallel_worker_panic_propagates() {
+    parallel::run_available(3, |worker| {
+        dfir_syntax! {
+            source_iter(vec![0; worker.num_workers])
+                -> inspect(|_| assert_ne!(1, worker.index, "worker 1 failed"))
+                -> map(|n: u32| (n, ()))
+                -> exchange_keyed()
+                -> null();
+        }
+    });
+}
+
+#[test]
+pub fn test_exchange_passes_through_single_threaded() {
+    let (output_send, mut output_recv) = dfir_rs::util::unbounded_channel();
+    let mut df = dfir_syntax! {
+        source_iter([(1, 'a'), (2, 'b')])
+            -> exchange_keyed()
+            -> exchange(|(_k, c): &(u32, char)| *c)
+            -> for_each(|pair| output_send.send(pair).unwrap());
+    };
+    df.run_available();
+    assert_eq!(
+        &[(1, 'a'), (2, 'b')],
+        &*collect_ready::<Vec<_>, _>(&mut output_recv)
+    );
+}
@@ -0,0 +1,116 @@
+use std::io::Write;
+use std::time::Duration;
+
+use dfir_rs::dfir_syntax;
+use dfir_rs::util::collect_ready_async;
+use dfir_rs::util::records::{FileSource, RecordError};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+struct Order {
+    item: String,
+    quantity: u32,
+}
+
+fn order(item: &str, quantity: u32) -> Order {
+    Order {
+        item: item.to_owned(),
+        quantity,
+    }
+}
+
+/// Runs `flow` until it has been idle long enough for file IO to complete.
+async fn run_for(flow: &mut dfir_rs::scheduled::graph::Dfir<'_>, duration: Duration) {
+    tokio::time::timeout(duration, flow.run_async())
+        .await
+        .expect_err("Expected time out");
+}
+
+#[dfir_rs::test]
+async fn test_csv_roundtrip_append_writes_headers_once() {
+    let file = tempfile::NamedTempFile::new().unwrap();
+    let path = file.path().to_owned();
+
+    for batch in [
+        vec![order("apple", 3)],
+        vec![order("pear", 1), order("fig", 2)],
+    ] {
+        let path = path.clone();
+        let mut flow = dfir_syntax! {
+            source_iter(batch) -> dest_csv(&path, true);
+        };
+        run_for(&mut flow, Duration::from_millis(200)).await;
+    }
+    assert_eq!(
+        "item,quantity\napple,3\npear,1\nfig,2\n",
+        std::fs::read_to_string(&path).unwrap()
+    );
+
+    let (result_send, mut result_recv) = dfir_rs::util::unbounded_channel();
+    let mut flow = dfir_syntax! {
+        source_csv::<Order>(&path) -> map(Result::unwrap) -> for_each(|order| result_send.send(order).unwrap());
+    };
+    run_for(&mut flow, Duration::from_millis(200)).await;
+    assert_eq!(
+        &[order("apple", 3), order("pear", 1), order("fig", 2)],
+        &*collect_ready_async::<Vec<_>, _>(&mut result_recv).await
+    );
+}
+
+/// Fails to serialize when the value is even.
+#[derive(Debug)]
+struct OddOnly(u32);
+
+impl Serialize for OddOnly {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.0 % 2 == 0 {
+            return Err(serde::ser::Error::custom("even"));
+        }
+        serializer.serialize_u32(self.0)
+    }
+}
+
+#[dfir_rs::test]
+async fn test_dest_jsonl_emits_failed_records() {
+    let file = tempfile::NamedTempFile::new().unwrap();
+
+    let (error_send, mut error_recv) = dfir_rs::util::unbounded_channel();
+    let mut flow = dfir_syntax! {
+        source_iter(1..=3)
+            -> map(OddOnly)
+            -> dest_jsonl(file.path(), false)
+            -> for_each(|(record, error): (OddOnly, RecordError)| error_send.send((record.0, error.line)).unwrap());
+    };
+    run_for(&mut flow, Duration::from_millis(200)).await;
+
+    assert_eq!("1\n3\n", std::fs::read_to_string(file.path()).unwrap());
+    assert_eq!(
+        &[(2, 2)],
+        &*collect_ready_async::<Vec<_>, _>(&mut error_recv).await
+    );
+}
+
+#[dfir_rs::test]
+async fn test_source_jsonl_follow() {
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    writeln!(file, "{{\"item\":\"apple\",\"quantity\":3}}").unwrap();
+
+    let (result_send, mut result_recv) = dfir_rs::util::unbounded_channel();
+    let source = FileSource::new(file.path()).follow(Duration::from_millis(10));
+    let mut flow = dfir_syntax! {
+        source_jsonl::<Order>(source) -> for_each(|record: Result<Order, RecordError>| result_send.send(record.map_err(|error| error.line)).unwrap());
+    };
+    run_for(&mut flow, Duration::from_millis(100)).await;
+    assert_eq!(
+        &[Ok(order("apple", 3))],
+        &*collect_ready_async::<Vec<_>, _>(&mut result_recv).await
+    );
+
+    writeln!(file, "{{\"item\":\"pear\"}}").unwrap();
+    writeln!(file, "{{\"item\":\"fig\",\"quantity\":2}}").unwrap();
+    run_for(&mut flow, Duration::from_millis(100)).await;
+    assert_eq!(
+        &[Err(2), Ok(order("fig", 2))],
+        &*collect_ready_async::<Vec<_>, _>(&mut result_recv).await
+    );
+}
@@ -0,0 +1,143 @@
+use std::cell::RefCell;
+use std::io;
+use std::rc::Rc;
+use std::time::Duration;
+
+use bytes::BytesMut;
+use dfir_rs::dfir_syntax;
+use dfir_rs::util::replay::Recorder;
+
+/// Runs a graph which tags each input with the tick it arrived in, sending `inputs[i]` before
+/// tick `i`.
+fn run_tagged(recorder: Recorder, inputs: &[&[u32]]) -> Vec<(u64, u32)> {
+    let (input_send, input_recv) = dfir_rs::util::unbounded_channel::<u32>();
+    let output = Rc::new(RefCell::new(Vec::new()));
+    let output_ref = output.clone();
+    let mut df = dfir_syntax! {
+        source_stream(recorder.source("input", input_recv))
+            -> map(|x| (context.current_tick().0, x))
+            -> for_each(|x| output_ref.borrow_mut().push(x));
+    };
+    df.set_recorder(recorder);
+
+    for tick_inputs in inputs {
+        for &x in tick_inputs.iter() {
+            let _ = input_send.send(x);
+        }
+        df.run_tick();
+    }
+    // Finish replaying any remaining ticks.
+    df.run_available();
+    output.take()
+}
+
+#[test]
+pub fn test_record_replay() {
+    let log = tempfile::NamedTempFile::new().unwrap();
+
+    let recorded = run_tagged(
+        Recorder::record(log.path()).unwrap(),
+        &[&[1, 2], &[], &[3], &[], &[4, 5]],
+    );
+    assert_eq!(vec![(0, 1), (0, 2), (2, 3), (4, 4), (4, 5)], recorded);
+
+    // The live input is ignored when replaying, and each item arrives in its original tick.
+    let replayed = run_tagged(Recorder::replay(log.path()).unwrap(), &[&[100, 200]]);
+    assert_eq!(recorded, replayed);
+}
+
+#[test]
+pub fn test_replay_truncated_log() {
+    let log = tempfile::NamedTempFile::new().unwrap();
+
+    let recorded = run_tagged(Recorder::record(log.path()).unwrap(), &[&[1, 2], &[], &[3]]);
+    assert_eq!(vec![(0, 1), (0, 2), (2, 3)], recorded);
+
+    // As if the recording process was killed while writing the last item.
+    let len = log.as_file().metadata().unwrap().len();
+    log.as_file().set_len(len - 1).unwrap();
+
+    let replayed = run_tagged(Recorder::replay(log.path()).unwrap(), &[]);
+    assert_eq!(vec![(0, 1), (0, 2)], replayed);
+}
+
+/// Runs a graph which tags each network item with the tick it arrived in, sending `inputs[i]`
+/// before tick `i`. The recorder is attached after the graph is built.
+fn run_network(recorder: Recorder, inputs: &[&[&[u8]]]) -> Vec<(u64, Vec<u8>)> {
+    let (input_send, input_recv) = dfir_rs::util::unbounded_channel::<io::Result<BytesMut>>();
+    let output = Rc::new(RefCell::new(Vec::new()));
+    let output_ref = output.clone();
+    let mut df = dfir_syntax! {
+        source_stream(df.recorded_network_source("network", input_recv))
+            -> map(|bytes| (context.current_tick().0, bytes.unwrap().to_vec()))
+            -> for_each(|x| output_ref.borrow_mut().push(x));
+    };
+    df.set_recorder(recorder);
+
+    for tick_inputs in inputs {
+        for &bytes in tick_inputs.iter() {
+            let _ = input_send.send(Ok(BytesMut::from(bytes)));
+        }
+        df.run_tick();
+    }
+    df.run_available();
+    output.take()
+}
+
+#[test]
+pub fn test_record_replay_network() {
+    let log = tempfile::NamedTempFile::new().unwrap();
+
+    let recorded = run_network(
+        Recorder::record(log.path()).unwrap(),
+        &[&[b"a"], &[], &[b"b", b"c"]],
+    );
+    assert_eq!(
+        vec![(0, b"a".to_vec()), (2, b"b".to_vec()), (2, b"c".to_vec())],
+        recorded
+    );
+
+    let replayed = run_network(Recorder::replay(log.path()).unwrap(), &[&[b"live"]]);
+    assert_eq!(recorded, replayed);
+}
+
+/// Runs a graph which collects the ticks in which a `source_interval` of `period` fires, running
+/// `ticks` ticks a millisecond apart.
+async fn run_interval(recorder: Recorder, period: Duration, ticks: usize) -> Vec<u64> {
+    let output = Rc::new(RefCell::new(Vec::new()));
+    let output_ref = output.clone();
+    let mut df = dfir_syntax! {
+        source_interval(period)
+            -> map(|()| context.current_tick().0)
+            -> for_each(|tick| output_ref.borrow_mut().push(tick));
+    };
+    df.set_recorder(recorder);
+
+    for _ in 0..ticks {
+        tokio::time::sleep(Duration::from_millis(1)).await;
+        df.run_tick();
+    }
+    output.take()
+}
+
+#[dfir_rs::test]
+pub async fn test_record_replay_interval() {
+    let log = tempfile::NamedTempFile::new().unwrap();
+
+    let recorded = run_interval(
+        Recorder::record(log.path()).unwrap(),
+        Duration::from_millis(3),
+        10,
+    )
+    .await;
+    assert!(2 <= recorded.len(), "{:?}", recorded);
+
+    // The live interval never fires again when replaying, so only the recorded ticks are seen.
+    let replayed = run_interval(
+        Recorder::replay(log.path()).unwrap(),
+        Duration::from_millis(1),
+        10,
+    )
+    .await;
+    assert_eq!(recorded, replayed);
+}
@@ -33,6 +33,9 @@ pub use custom_service::CustomService;
 
 pub mod terraform;
 
+pub mod trace_collector;
+pub use trace_collector::TraceCollector;
+
 pub mod util;
 
 #[derive(Default)]
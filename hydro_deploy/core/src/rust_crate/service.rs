@@ -153,6 +153,10 @@ impl RustCrateService {
         }
     }
 
+    pub fn stdin(&self) -> mpsc::UnboundedSender<String> {
+        self.launched_binary.as_ref().unwrap().stdin()
+    }
+
     pub fn stdout(&self) -> mpsc::UnboundedReceiver<String> {
         self.launched_binary.as_ref().unwrap().stdout()
     }
@@ -0,0 +1,185 @@
+//! Collects the distributed trace spans printed by launched binaries into a single file.
+
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+pub use hydro_deploy_integration::trace::{SpanKind, SpanRecord, TRACE_PREFIX};
+use serde_json::json;
+use tokio::sync::mpsc::UnboundedReceiver;
+
+/// `(process name, span)` for every span collected so far.
+type CollectedSpans = Arc<Mutex<Vec<(Arc<str>, SpanRecord)>>>;
+
+/// Collects [`SpanRecord`]s from the stdout of any number of launched binaries.
+///
+/// Each binary's stdout must be filtered by [`TRACE_PREFIX`] (for example with
+/// [`crate::rust_crate::RustCrateService::stdout_filter`]) before the binary is started, so that
+/// no spans are missed.
+#[derive(Default)]
+pub struct TraceCollector {
+    spans: CollectedSpans,
+}
+
+impl TraceCollector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Collects the spans in `stdout`, the [`TRACE_PREFIX`]-filtered stdout of a binary, labelled
+    /// as coming from `process`.
+    pub fn collect(&self, process: impl Into<String>, mut stdout: UnboundedReceiver<String>) {
+        let process: Arc<str> = process.into().into();
+        let spans = self.spans.clone();
+        tokio::spawn(async move {
+            while let Some(line) = stdout.recv().await {
+                let Some(json) = line.strip_prefix(TRACE_PREFIX) else {
+                    continue;
+                };
+                match serde_json::from_str::<SpanRecord>(json) {
+                    Ok(span) => spans.lock().unwrap().push((process.clone(), span)),
+                    Err(err) => eprintln!("Invalid trace span from {}: {}", process, err),
+                }
+            }
+        });
+    }
+
+    /// Returns `(process name, span)` for every span collected so far, ordered by start time.
+    pub fn spans(&self) -> Vec<(String, SpanRecord)> {
+        let mut spans: Vec<_> = self
+            .spans
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(process, span)| (process.to_string(), span.clone()))
+            .collect();
+        spans.sort_by_key(|(_, span)| span.start_us);
+        spans
+    }
+
+    /// Writes the spans collected so far in the Chrome trace event format, which can be opened
+    /// in `chrome://tracing` or [Perfetto](https://ui.perfetto.dev/). Each process gets its own
+    /// track, and each network message is drawn as an arrow from its send to its receive.
+    ///
+    /// Spans still buffered in stdout are not included, so this should be called after the
+    /// binaries have been stopped.
+    pub fn write_chrome_trace(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let spans = self.spans();
+
+        let mut processes: Vec<&str> = spans.iter().map(|(process, _)| &**process).collect();
+        processes.sort_unstable();
+        processes.dedup();
+        let pid = |process: &str| processes.binary_search(&process).unwrap();
+
+        let mut events = Vec::new();
+        for process in processes.iter() {
+            events.push(json!({
+                "name": "process_name",
+                "ph": "M",
+                "pid": pid(process),
+                "args": { "name": process },
+            }));
+        }
+        for (process, span) in spans.iter() {
+            let args = json!({
+                "trace_id": format!("{:016x}", span.trace_id),
+                "span_id": format!("{:016x}", span.span_id),
+                "parent_span_id": span.parent_span_id.map(|id| format!("{:016x}", id)),
+            });
+            events.push(json!({
+                "name": span.name,
+                "cat": format!("{:?}", span.kind),
+                "ph": "X",
+                "ts": span.start_us,
+                "dur": span.duration_us,
+                "pid": pid(process),
+                "tid": 0,
+                "args": args,
+            }));
+            // Flow arrows connect a send to the receives which have it as their parent.
+            match (span.kind, span.parent_span_id) {
+                (SpanKind::Send, _) => events.push(json!({
+                    "name": "message",
+                    "cat": "network",
+                    "ph": "s",
+                    "id": format!("{:016x}", span.span_id),
+                    "ts": span.start_us,
+                    "pid": pid(process),
+                    "tid": 0,
+                })),
+                (SpanKind::Recv, Some(parent_span_id)) => events.push(json!({
+                    "name": "message",
+                    "cat": "network",
+                    "ph": "f",
+                    "bp": "e",
+                    "id": format!("{:016x}", parent_span_id),
+                    "ts": span.start_us,
+                    "pid": pid(process),
+                    "tid": 0,
+                })),
+                _ => {}
+            }
+        }
+
+        let mut file = io::BufWriter::new(std::fs::File::create(path)?);
+        serde_json::to_writer(&mut file, &json!({ "traceEvents": events }))?;
+        file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_chrome_trace() {
+        let collector = TraceCollector::new();
+        let (send, recv) = tokio::sync::mpsc::unbounded_channel();
+        collector.collect("proposer", recv);
+
+        let span = |kind, span_id, parent_span_id, start_us| SpanRecord {
+            kind,
+            name: "to acceptors".to_owned(),
+            trace_id: 1,
+            span_id,
+            parent_span_id,
+            start_us,
+            duration_us: 0,
+        };
+        for record in [
+            span(SpanKind::Recv, 3, Some(2), 20),
+            span(SpanKind::Send, 2, None, 10),
+        ] {
+            let line = format!(
+                "{}{}",
+                TRACE_PREFIX,
+                serde_json::to_string(&record).unwrap()
+            );
+            send.send(line).unwrap();
+        }
+        drop(send);
+        while collector.spans().len() < 2 {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(
+            vec![10, 20],
+            collector
+                .spans()
+                .iter()
+                .map(|(_, span)| span.start_us)
+                .collect::<Vec<_>>()
+        );
+
+        let file = tempfile::NamedTempFile::new().unwrap();
+        collector.write_chrome_trace(file.path()).unwrap();
+        let trace: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(file.path()).unwrap()).unwrap();
+        let phases: Vec<_> = trace["traceEvents"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|event| event["ph"].as_str().unwrap())
+            .collect();
+        assert_eq!(vec!["M", "X", "s", "X", "f"], phases);
+    }
+}
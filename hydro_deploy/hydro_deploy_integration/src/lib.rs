@@ -23,6 +23,8 @@ use tokio_stream::StreamExt;
 use tokio_stream::wrappers::TcpListenerStream;
 use tokio_util::codec::{Framed, LengthDelimitedCodec};
 
+pub mod trace;
+
 pub type InitConfig = (HashMap<String, ServerBindConfig>, Option<String>);
 
 /// Contains runtime information passed by Hydro Deploy to a program,
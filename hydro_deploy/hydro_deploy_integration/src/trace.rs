@@ -0,0 +1,36 @@
+//! Span records printed by traced programs and collected by Hydro Deploy.
+//!
+//! A traced program prints each span as a single stdout line: [`TRACE_PREFIX`] followed by the
+//! JSON serialization of a [`SpanRecord`].
+
+use serde::{Deserialize, Serialize};
+
+/// Prefix of the stdout lines which carry a JSON [`SpanRecord`].
+pub const TRACE_PREFIX: &str = "_trace ";
+
+/// What a span represents.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SpanKind {
+    /// A message was sent over a network channel.
+    Send,
+    /// A message was received from a network channel. The parent is the [`SpanKind::Send`] span
+    /// of the sender.
+    Recv,
+    /// An operator processed an item which is part of the trace.
+    Operator,
+}
+
+/// A single span of a distributed trace.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct SpanRecord {
+    pub kind: SpanKind,
+    /// The network channel or operator, for display.
+    pub name: String,
+    /// Shared by all spans caused by the same root message.
+    pub trace_id: u64,
+    pub span_id: u64,
+    pub parent_span_id: Option<u64>,
+    /// Start time, in microseconds since the Unix epoch.
+    pub start_us: u64,
+    pub duration_us: u64,
+}
@@ -216,6 +216,18 @@ impl<'a, D: Deploy<'a>> DeployFlow<'a, D> {
                     .push(syn::parse_quote! {
                         let #other_id_ident = #other_id_expr;
                     });
+
+                let membership_ident = syn::Ident::new(
+                    &format!("__hydro_lang_cluster_membership_{}", c_id),
+                    Span::call_site(),
+                );
+                let membership_expr = D::cluster_membership(env, c_id).splice_untyped();
+                extra_stmts
+                    .entry(*other_location)
+                    .or_default()
+                    .push(syn::parse_quote! {
+                        let #membership_ident = #membership_expr;
+                    });
             }
         }
         extra_stmts
@@ -345,6 +357,12 @@ impl<'a, D: Deploy<'a>> DeployResult<'a, D> {
         self.clusters.get(&id).unwrap()
     }
 
+    pub fn get_all_processes(&self) -> impl Iterator<Item = (LocationId, &D::Process)> {
+        self.processes
+            .iter()
+            .map(|(&id, p)| (LocationId::Process(id), p))
+    }
+
     pub fn get_all_clusters(&self) -> impl Iterator<Item = (LocationId, String, &D::Cluster)> {
         self.clusters.iter().map(|(&id, c)| {
             (
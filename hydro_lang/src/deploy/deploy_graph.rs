@@ -1,5 +1,5 @@
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::future::Future;
 use std::io::Error;
 use std::pin::Pin;
@@ -13,7 +13,8 @@ use hydro_deploy::custom_service::CustomClientPort;
 use hydro_deploy::rust_crate::RustCrateService;
 use hydro_deploy::rust_crate::ports::{DemuxSink, RustCrateSink, RustCrateSource, TaggedSource};
 use hydro_deploy::rust_crate::tracing_options::TracingOptions;
-use hydro_deploy::{CustomService, Deployment, Host, RustCrate, TracingResults};
+use hydro_deploy::trace_collector::TRACE_PREFIX;
+use hydro_deploy::{CustomService, Deployment, Host, RustCrate, TraceCollector, TracingResults};
 use hydro_deploy_integration::{ConnectedSink, ConnectedSource};
 use nameof::name_of;
 use serde::Serialize;
@@ -23,17 +24,29 @@ use tokio::sync::RwLock;
 
 use super::trybuild::{HYDRO_RUNTIME_FEATURES, create_graph_trybuild};
 use super::{ClusterSpec, Deploy, ExternalSpec, IntoProcessSpec, Node, ProcessSpec, RegisterPort};
+use crate::builder::deploy::DeployResult;
 use crate::deploy_runtime::*;
+use crate::location::cluster::ClusterMembership;
+use crate::location::{Cluster, ClusterId, Location, MembershipEvent};
 
 pub struct HydroDeploy {}
 
+/// The members of every cluster in a deployment, which are passed to each deployed location.
+#[derive(Default)]
+pub struct DeployMeta {
+    /// The raw IDs of all deployed members of each cluster.
+    clusters: HashMap<usize, Vec<u32>>,
+    /// The raw IDs of the standby members of each cluster, which start outside its membership.
+    standby: HashMap<usize, Vec<u32>>,
+}
+
 impl<'a> Deploy<'a> for HydroDeploy {
     type InstantiateEnv = Deployment;
     type CompileEnv = ();
     type Process = DeployNode;
     type Cluster = DeployCluster;
     type ExternalProcess = DeployExternal;
-    type Meta = HashMap<usize, Vec<u32>>;
+    type Meta = DeployMeta;
     type GraphId = ();
     type Port = String;
     type ExternalRawPort = CustomClientPort;
@@ -375,6 +388,102 @@ impl<'a> Deploy<'a> for HydroDeploy {
     fn cluster_self_id(_env: &Self::CompileEnv) -> impl QuotedWithContext<'a, u32, ()> + Copy + 'a {
         cluster_self_id(RuntimeData::new("__hydro_lang_trybuild_cli"))
     }
+
+    fn cluster_membership(
+        _env: &Self::CompileEnv,
+        of_cluster: usize,
+    ) -> impl QuotedWithContext<'a, &'a ClusterMembership, ()> + Copy + 'a {
+        cluster_membership(RuntimeData::new("__hydro_lang_trybuild_cli"), of_cluster)
+    }
+}
+
+impl<'a> DeployResult<'a, HydroDeploy> {
+    /// Tells every running location that `member` has joined `cluster`, so that broadcasts and
+    /// round-robins to the cluster include it again. The member must have been deployed as part of
+    /// the cluster, since the network connections to a cluster are fixed at deploy time. New
+    /// members are added with [`Self::allocate_cluster_member`] instead.
+    pub async fn add_cluster_member<C>(&self, cluster: &Cluster<'a, C>, member: ClusterId<C>) {
+        self.announce_membership(cluster, member, MembershipEvent::Joined)
+            .await;
+    }
+
+    /// Grows `cluster` by allocating the ID of its next [standby](TrybuildHost::standby) member
+    /// and telling every running location that it has joined. Returns `None` if every standby
+    /// member has already been allocated.
+    pub async fn allocate_cluster_member<C>(
+        &self,
+        cluster: &Cluster<'a, C>,
+    ) -> Option<ClusterId<C>> {
+        let raw_id = self.get_cluster(cluster).standby.borrow_mut().pop_front()?;
+        let member = ClusterId::from_raw(raw_id);
+        self.announce_membership(cluster, member, MembershipEvent::Joined)
+            .await;
+        Some(member)
+    }
+
+    /// Tells every running location that `member` has left `cluster`, so that broadcasts and
+    /// round-robins to the cluster skip it. The member's process is left running, and can be
+    /// re-added later with [`Self::add_cluster_member`].
+    pub async fn remove_cluster_member<C>(&self, cluster: &Cluster<'a, C>, member: ClusterId<C>) {
+        self.announce_membership(cluster, member, MembershipEvent::Left)
+            .await;
+    }
+
+    /// Collects the trace spans of every location into a [`TraceCollector`]. The IR must have
+    /// been rewritten with [`crate::rewrites::trace_network`], and this must be called before
+    /// the deployment is started so that no spans are missed.
+    pub async fn collect_traces(&self) -> TraceCollector {
+        let collector = TraceCollector::new();
+        for (id, process) in self.get_all_processes() {
+            collector.collect(
+                format!("{:?}", id),
+                process.stdout_filter(TRACE_PREFIX).await,
+            );
+        }
+        for (_, name, cluster) in self.get_all_clusters() {
+            for (idx, member) in cluster.members().iter().enumerate() {
+                collector.collect(
+                    format!("{}/{}", name, idx),
+                    member.stdout_filter(TRACE_PREFIX).await,
+                );
+            }
+        }
+        collector
+    }
+
+    async fn announce_membership<C>(
+        &self,
+        cluster: &Cluster<'a, C>,
+        member: ClusterId<C>,
+        event: MembershipEvent,
+    ) {
+        let deployed = self.get_cluster(cluster);
+        assert!(
+            (member.raw_id as usize) < deployed.members().len(),
+            "{} was not deployed as part of the cluster",
+            member
+        );
+
+        // a standby member that is added explicitly can no longer be allocated
+        deployed
+            .standby
+            .borrow_mut()
+            .retain(|&standby| standby != member.raw_id);
+
+        let command = membership_command(cluster.id().raw_id(), member.raw_id, event);
+        let services = self
+            .get_all_processes()
+            .map(|(_, process)| process.underlying())
+            .chain(self.get_all_clusters().flat_map(|(_, _, cluster)| {
+                cluster.members().into_iter().map(|node| node.underlying())
+            }))
+            .collect::<Vec<_>>();
+
+        for service in services {
+            // the service may have already exited
+            let _ = service.read().await.stdin().send(format!("{command}\n"));
+        }
+    }
 }
 
 pub trait DeployCrateWrapper {
@@ -421,6 +530,7 @@ pub struct TrybuildHost {
     pub tracing: Option<TracingOptions>,
     pub name_hint: Option<String>,
     pub cluster_idx: Option<usize>,
+    pub standby: bool,
 }
 
 impl From<Arc<dyn Host>> for TrybuildHost {
@@ -433,6 +543,7 @@ impl From<Arc<dyn Host>> for TrybuildHost {
             tracing: None,
             name_hint: None,
             cluster_idx: None,
+            standby: false,
         }
     }
 }
@@ -447,6 +558,7 @@ impl<H: Host + 'static> From<Arc<H>> for TrybuildHost {
             tracing: None,
             name_hint: None,
             cluster_idx: None,
+            standby: false,
         }
     }
 }
@@ -461,6 +573,7 @@ impl TrybuildHost {
             tracing: None,
             name_hint: None,
             cluster_idx: None,
+            standby: false,
         }
     }
 
@@ -503,6 +616,17 @@ impl TrybuildHost {
             ..self
         }
     }
+
+    /// Deploys this cluster member as a standby, which runs and is connected to the rest of the
+    /// flow but starts outside the cluster's [`Cluster::membership`], so other locations do not
+    /// send to it. Standby members join as new members of the cluster through
+    /// [`DeployResult::allocate_cluster_member`].
+    pub fn standby(self) -> Self {
+        Self {
+            standby: true,
+            ..self
+        }
+    }
 }
 
 impl IntoProcessSpec<'_, HydroDeploy> for Arc<dyn Host> {
@@ -516,6 +640,7 @@ impl IntoProcessSpec<'_, HydroDeploy> for Arc<dyn Host> {
             tracing: None,
             name_hint: None,
             cluster_idx: None,
+            standby: false,
         }
     }
 }
@@ -531,6 +656,7 @@ impl<H: Host + 'static> IntoProcessSpec<'_, HydroDeploy> for Arc<H> {
             tracing: None,
             name_hint: None,
             cluster_idx: None,
+            standby: false,
         }
     }
 }
@@ -614,7 +740,7 @@ impl<'a> RegisterPort<'a, HydroDeploy> for DeployExternal {
 
 impl Node for DeployExternal {
     type Port = String;
-    type Meta = HashMap<usize, Vec<u32>>;
+    type Meta = DeployMeta;
     type InstantiateEnv = Deployment;
 
     fn next_port(&self) -> Self::Port {
@@ -683,7 +809,7 @@ impl DeployCrateWrapper for DeployNode {
 
 impl Node for DeployNode {
     type Port = String;
-    type Meta = HashMap<usize, Vec<u32>>;
+    type Meta = DeployMeta;
     type InstantiateEnv = Deployment;
 
     fn next_port(&self) -> String {
@@ -696,11 +822,12 @@ impl Node for DeployNode {
     fn update_meta(&mut self, meta: &Self::Meta) {
         let underlying_node = self.underlying.borrow();
         let mut n = underlying_node.as_ref().unwrap().try_write().unwrap();
-        n.update_meta(HydroMeta {
-            clusters: meta.clone(),
-            cluster_id: None,
-            subgraph_id: self.id,
-        });
+        n.update_meta(HydroMeta::new(
+            meta.clusters.clone(),
+            meta.standby.clone(),
+            None,
+            self.id,
+        ));
     }
 
     fn instantiate(
@@ -740,6 +867,8 @@ pub struct DeployCluster {
     next_port: Rc<RefCell<usize>>,
     cluster_spec: Rc<RefCell<Option<Vec<CrateOrTrybuild>>>>,
     members: Rc<RefCell<Vec<DeployClusterNode>>>,
+    /// Raw IDs of the standby members that have not been allocated yet, in ascending order.
+    standby: Rc<RefCell<VecDeque<u32>>>,
     name_hint: Option<String>,
 }
 
@@ -751,7 +880,7 @@ impl DeployCluster {
 
 impl Node for DeployCluster {
     type Port = String;
-    type Meta = HashMap<usize, Vec<u32>>;
+    type Meta = DeployMeta;
     type InstantiateEnv = Deployment;
 
     fn next_port(&self) -> String {
@@ -788,7 +917,12 @@ impl Node for DeployCluster {
             .take()
             .unwrap()
             .into_iter()
-            .map(|spec| {
+            .enumerate()
+            .map(|(idx, spec)| {
+                if matches!(&spec, CrateOrTrybuild::Trybuild(t) if t.standby) {
+                    self.standby.borrow_mut().push_back(idx as u32);
+                }
+
                 let service = match spec {
                     CrateOrTrybuild::Crate(c) => c,
                     CrateOrTrybuild::Trybuild(trybuild) => {
@@ -801,7 +935,10 @@ impl Node for DeployCluster {
                 env.add_service(service)
             })
             .collect::<Vec<_>>();
-        meta.insert(self.id, (0..(cluster_nodes.len() as u32)).collect());
+        meta.clusters
+            .insert(self.id, (0..(cluster_nodes.len() as u32)).collect());
+        meta.standby
+            .insert(self.id, self.standby.borrow().iter().copied().collect());
         *self.members.borrow_mut() = cluster_nodes
             .into_iter()
             .map(|n| DeployClusterNode { underlying: n })
@@ -811,11 +948,12 @@ impl Node for DeployCluster {
     fn update_meta(&mut self, meta: &Self::Meta) {
         for (cluster_id, node) in self.members.borrow().iter().enumerate() {
             let mut n = node.underlying.try_write().unwrap();
-            n.update_meta(HydroMeta {
-                clusters: meta.clone(),
-                cluster_id: Some(cluster_id as u32),
-                subgraph_id: self.id,
-            });
+            n.update_meta(HydroMeta::new(
+                meta.clusters.clone(),
+                meta.standby.clone(),
+                Some(cluster_id as u32),
+                self.id,
+            ));
         }
     }
 }
@@ -870,6 +1008,7 @@ impl ClusterSpec<'_, HydroDeploy> for DeployClusterSpec {
                 self.0.into_iter().map(CrateOrTrybuild::Crate).collect(),
             ))),
             members: Rc::new(RefCell::new(vec![])),
+            standby: Rc::new(RefCell::new(VecDeque::new())),
             name_hint: None,
         }
     }
@@ -893,6 +1032,7 @@ impl<T: Into<TrybuildHost>, I: IntoIterator<Item = T>> ClusterSpec<'_, HydroDepl
                     .collect(),
             ))),
             members: Rc::new(RefCell::new(vec![])),
+            standby: Rc::new(RefCell::new(VecDeque::new())),
             name_hint: Some(name_hint),
         }
     }
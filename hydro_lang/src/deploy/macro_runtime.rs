@@ -11,6 +11,7 @@ use stageleft::{QuotedWithContext, RuntimeData};
 
 use crate::deploy::{ClusterSpec, Deploy, ExternalSpec, Node, ProcessSpec, RegisterPort};
 use crate::deploy_runtime::HydroMeta;
+use crate::location::cluster::ClusterMembership;
 
 pub struct DeployRuntime {}
 
@@ -177,6 +178,13 @@ impl<'a> Deploy<'a> for DeployRuntime {
     fn cluster_self_id(env: &Self::CompileEnv) -> impl QuotedWithContext<'a, u32, ()> + Copy + 'a {
         crate::deploy_runtime::cluster_self_id(*env)
     }
+
+    fn cluster_membership(
+        env: &Self::CompileEnv,
+        of_cluster: usize,
+    ) -> impl QuotedWithContext<'a, &'a ClusterMembership, ()> + Copy + 'a {
+        crate::deploy_runtime::cluster_membership(*env, of_cluster)
+    }
 }
 
 #[derive(Clone)]
@@ -9,9 +9,14 @@ use serde::Serialize;
 use serde::de::DeserializeOwned;
 use stageleft::QuotedWithContext;
 
+use crate::location::cluster::ClusterMembership;
+
 pub mod macro_runtime;
 pub use macro_runtime::*;
 
+pub mod sim;
+pub use sim::*;
+
 #[cfg(feature = "deploy")]
 #[cfg(stageleft_runtime)]
 pub(crate) mod trybuild;
@@ -89,6 +94,14 @@ pub trait Deploy<'a> {
         panic!("No trivial cluster")
     }
 
+    /// Wraps a network sink returned by one of the `*_sink_source` methods into a closure which
+    /// sends each element synchronously, so that sent elements reach the network before the
+    /// sending tick ends. Returns `None` (the default) to feed the sink from a background task
+    /// with `dest_sink`. Buffered channels always use the sink as-is.
+    fn sync_network_sink(_sink: &syn::Expr) -> Option<syn::Expr> {
+        None
+    }
+
     fn allocate_process_port(process: &Self::Process) -> Self::Port;
     fn allocate_cluster_port(cluster: &Self::Cluster) -> Self::Port;
     fn allocate_external_port(external: &Self::ExternalProcess) -> Self::Port;
@@ -182,6 +195,10 @@ pub trait Deploy<'a> {
         of_cluster: usize,
     ) -> impl QuotedWithContext<'a, &'a [u32], ()> + Copy + 'a;
     fn cluster_self_id(env: &Self::CompileEnv) -> impl QuotedWithContext<'a, u32, ()> + Copy + 'a;
+    fn cluster_membership(
+        env: &Self::CompileEnv,
+        of_cluster: usize,
+    ) -> impl QuotedWithContext<'a, &'a ClusterMembership, ()> + Copy + 'a;
 }
 
 impl<
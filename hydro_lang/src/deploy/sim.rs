@@ -0,0 +1,319 @@
+use std::cell::RefCell;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+
+use bytes::Bytes;
+use dfir_lang::graph::DfirGraph;
+use futures::{Sink, Stream};
+use stageleft::{QuotedWithContext, RuntimeData};
+
+use crate::deploy::{ClusterSpec, Deploy, ExternalSpec, Node, ProcessSpec, RegisterPort};
+use crate::location::cluster::ClusterMembership;
+use crate::sim_runtime::SimPorts;
+
+/// Compiles a flow for the deterministic simulator in [`crate::sim_runtime`]. Every network edge
+/// is backed by the in-memory [`crate::sim_runtime::SimNetwork`] that `env` is connected to, and
+/// the graph of each location is selected at runtime with
+/// `with_dynamic_id(q!(env.location_id()))`.
+pub struct SimDeploy {}
+
+impl<'a> Deploy<'a> for SimDeploy {
+    type InstantiateEnv = ();
+    type CompileEnv = RuntimeData<&'a SimPorts>;
+    type Process = SimNode;
+    type Cluster = SimNode;
+    type ExternalProcess = SimNode;
+    type Port = String;
+    type ExternalRawPort = ();
+    type Meta = ();
+    type GraphId = usize;
+
+    fn has_trivial_node() -> bool {
+        true
+    }
+
+    fn trivial_process(id: usize) -> Self::Process {
+        SimNode::new(id)
+    }
+
+    fn trivial_cluster(id: usize) -> Self::Cluster {
+        SimNode::new(id)
+    }
+
+    fn sync_network_sink(sink: &syn::Expr) -> Option<syn::Expr> {
+        Some(syn::parse_quote! {
+            {
+                let mut sink = #sink;
+                move |item| sink.send_now(item)
+            }
+        })
+    }
+
+    fn allocate_process_port(process: &Self::Process) -> Self::Port {
+        process.next_port()
+    }
+
+    fn allocate_cluster_port(cluster: &Self::Cluster) -> Self::Port {
+        cluster.next_port()
+    }
+
+    fn allocate_external_port(external: &Self::ExternalProcess) -> Self::Port {
+        external.next_port()
+    }
+
+    fn o2o_sink_source(
+        env: &Self::CompileEnv,
+        _p1: &Self::Process,
+        _p1_port: &Self::Port,
+        p2: &Self::Process,
+        p2_port: &Self::Port,
+    ) -> (syn::Expr, syn::Expr) {
+        crate::sim_runtime::sim_o2o(*env, p2.id, p2_port.as_str())
+    }
+
+    fn o2o_connect(
+        _p1: &Self::Process,
+        _p1_port: &Self::Port,
+        _p2: &Self::Process,
+        _p2_port: &Self::Port,
+    ) -> Box<dyn FnOnce()> {
+        // Channels are connected through the `SimNetwork` when the members are instantiated.
+        Box::new(|| {})
+    }
+
+    fn o2m_sink_source(
+        env: &Self::CompileEnv,
+        _p1: &Self::Process,
+        _p1_port: &Self::Port,
+        c2: &Self::Cluster,
+        c2_port: &Self::Port,
+    ) -> (syn::Expr, syn::Expr) {
+        crate::sim_runtime::sim_o2m(*env, c2.id, c2_port.as_str())
+    }
+
+    fn o2m_connect(
+        _p1: &Self::Process,
+        _p1_port: &Self::Port,
+        _c2: &Self::Cluster,
+        _c2_port: &Self::Port,
+    ) -> Box<dyn FnOnce()> {
+        // Channels are connected through the `SimNetwork` when the members are instantiated.
+        Box::new(|| {})
+    }
+
+    fn m2o_sink_source(
+        env: &Self::CompileEnv,
+        _c1: &Self::Cluster,
+        _c1_port: &Self::Port,
+        p2: &Self::Process,
+        p2_port: &Self::Port,
+    ) -> (syn::Expr, syn::Expr) {
+        crate::sim_runtime::sim_m2o(*env, p2.id, p2_port.as_str())
+    }
+
+    fn m2o_connect(
+        _c1: &Self::Cluster,
+        _c1_port: &Self::Port,
+        _p2: &Self::Process,
+        _p2_port: &Self::Port,
+    ) -> Box<dyn FnOnce()> {
+        // Channels are connected through the `SimNetwork` when the members are instantiated.
+        Box::new(|| {})
+    }
+
+    fn m2m_sink_source(
+        env: &Self::CompileEnv,
+        _c1: &Self::Cluster,
+        _c1_port: &Self::Port,
+        c2: &Self::Cluster,
+        c2_port: &Self::Port,
+    ) -> (syn::Expr, syn::Expr) {
+        crate::sim_runtime::sim_m2m(*env, c2.id, c2_port.as_str())
+    }
+
+    fn m2m_connect(
+        _c1: &Self::Cluster,
+        _c1_port: &Self::Port,
+        _c2: &Self::Cluster,
+        _c2_port: &Self::Port,
+    ) -> Box<dyn FnOnce()> {
+        // Channels are connected through the `SimNetwork` when the members are instantiated.
+        Box::new(|| {})
+    }
+
+    fn e2o_source(
+        _compile_env: &Self::CompileEnv,
+        _p1: &Self::ExternalProcess,
+        _p1_port: &Self::Port,
+        _p2: &Self::Process,
+        _p2_port: &Self::Port,
+    ) -> syn::Expr {
+        unsupported_external()
+    }
+
+    fn e2o_connect(
+        _p1: &Self::ExternalProcess,
+        _p1_port: &Self::Port,
+        _p2: &Self::Process,
+        _p2_port: &Self::Port,
+    ) -> Box<dyn FnOnce()> {
+        Box::new(|| {})
+    }
+
+    fn o2e_sink(
+        _compile_env: &Self::CompileEnv,
+        _p1: &Self::Process,
+        _p1_port: &Self::Port,
+        _p2: &Self::ExternalProcess,
+        _p2_port: &Self::Port,
+    ) -> syn::Expr {
+        unsupported_external()
+    }
+
+    fn o2e_connect(
+        _p1: &Self::Process,
+        _p1_port: &Self::Port,
+        _p2: &Self::ExternalProcess,
+        _p2_port: &Self::Port,
+    ) -> Box<dyn FnOnce()> {
+        Box::new(|| {})
+    }
+
+    fn cluster_ids(
+        env: &Self::CompileEnv,
+        of_cluster: usize,
+    ) -> impl QuotedWithContext<'a, &'a [u32], ()> + Copy + 'a {
+        crate::sim_runtime::sim_cluster_members(*env, of_cluster)
+    }
+
+    fn cluster_self_id(env: &Self::CompileEnv) -> impl QuotedWithContext<'a, u32, ()> + Copy + 'a {
+        crate::sim_runtime::sim_cluster_self_id(*env)
+    }
+
+    fn cluster_membership(
+        env: &Self::CompileEnv,
+        of_cluster: usize,
+    ) -> impl QuotedWithContext<'a, &'a ClusterMembership, ()> + Copy + 'a {
+        crate::sim_runtime::sim_cluster_membership(*env, of_cluster)
+    }
+}
+
+/// Channels to or from external processes cannot be simulated, so they are compiled to an error
+/// reported when the generated program is built.
+fn unsupported_external() -> syn::Expr {
+    syn::parse_quote!(::core::compile_error!(
+        "External processes are not supported in the simulator"
+    ))
+}
+
+#[derive(Clone)]
+pub struct SimNode {
+    id: usize,
+    next_port: Rc<RefCell<usize>>,
+}
+
+impl SimNode {
+    fn new(id: usize) -> Self {
+        SimNode {
+            id,
+            next_port: Rc::new(RefCell::new(0)),
+        }
+    }
+}
+
+impl<'a> RegisterPort<'a, SimDeploy> for SimNode {
+    fn register(&self, _key: usize, _port: <SimDeploy as Deploy>::Port) {
+        panic!("External processes are not supported in the simulator")
+    }
+
+    fn raw_port(&self, _key: usize) -> <SimDeploy as Deploy>::ExternalRawPort {
+        panic!("External processes are not supported in the simulator")
+    }
+
+    #[expect(
+        clippy::manual_async_fn,
+        reason = "buggy Clippy lint for lifetime bounds"
+    )]
+    fn as_bytes_sink(
+        &self,
+        _key: usize,
+    ) -> impl Future<Output = Pin<Box<dyn Sink<Bytes, Error = std::io::Error>>>> + 'a {
+        async { panic!("External processes are not supported in the simulator") }
+    }
+
+    #[expect(
+        clippy::manual_async_fn,
+        reason = "buggy Clippy lint for lifetime bounds"
+    )]
+    fn as_bincode_sink<T: serde::Serialize + 'static>(
+        &self,
+        _key: usize,
+    ) -> impl Future<Output = Pin<Box<dyn Sink<T, Error = std::io::Error>>>> + 'a {
+        async { panic!("External processes are not supported in the simulator") }
+    }
+
+    #[expect(
+        clippy::manual_async_fn,
+        reason = "buggy Clippy lint for lifetime bounds"
+    )]
+    fn as_bytes_source(
+        &self,
+        _key: usize,
+    ) -> impl Future<Output = Pin<Box<dyn Stream<Item = Bytes>>>> + 'a {
+        async { panic!("External processes are not supported in the simulator") }
+    }
+
+    #[expect(
+        clippy::manual_async_fn,
+        reason = "buggy Clippy lint for lifetime bounds"
+    )]
+    fn as_bincode_source<T: serde::de::DeserializeOwned + 'static>(
+        &self,
+        _key: usize,
+    ) -> impl Future<Output = Pin<Box<dyn Stream<Item = T>>>> + 'a {
+        async { panic!("External processes are not supported in the simulator") }
+    }
+}
+
+impl Node for SimNode {
+    type Port = String;
+    type Meta = ();
+    type InstantiateEnv = ();
+
+    fn next_port(&self) -> String {
+        let next_send_port = *self.next_port.borrow();
+        *self.next_port.borrow_mut() += 1;
+        format!("port_{}", next_send_port)
+    }
+
+    fn update_meta(&mut self, _meta: &Self::Meta) {}
+
+    fn instantiate(
+        &self,
+        _env: &mut Self::InstantiateEnv,
+        _meta: &mut Self::Meta,
+        _graph: DfirGraph,
+        _extra_stmts: Vec<syn::Stmt>,
+    ) {
+        panic!(".deploy() cannot be called on a SimNode, use .compile() instead");
+    }
+}
+
+impl ProcessSpec<'_, SimDeploy> for () {
+    fn build(self, id: usize, _name_hint: &str) -> SimNode {
+        SimNode::new(id)
+    }
+}
+
+impl ClusterSpec<'_, SimDeploy> for () {
+    fn build(self, id: usize, _name_hint: &str) -> SimNode {
+        SimNode::new(id)
+    }
+}
+
+impl ExternalSpec<'_, SimDeploy> for () {
+    fn build(self, id: usize, _name_hint: &str) -> SimNode {
+        SimNode::new(id)
+    }
+}
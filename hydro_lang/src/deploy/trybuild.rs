@@ -153,7 +153,10 @@ pub fn compile_graph_trybuild(
             let flow = __hydro_runtime(&ports);
             println!("ack start");
 
-            hydro_lang::runtime_support::resource_measurement::run(flow).await;
+            hydro_lang::runtime_support::resource_measurement::run(flow, |command| {
+                ports.meta.handle_command(command)
+            })
+            .await;
         }
     };
     source_ast
@@ -1,3 +1,4 @@
+use std::cell::OnceCell;
 use std::collections::HashMap;
 
 use hydro_deploy_integration::{
@@ -6,11 +7,102 @@ use hydro_deploy_integration::{
 use serde::{Deserialize, Serialize};
 use stageleft::{QuotedWithContext, RuntimeData, q};
 
+use crate::internal_constants::MEMBERSHIP_COMMAND_PREFIX;
+use crate::location::cluster::{ClusterMembership, MembershipEvent};
+
+/// Wraps the network source `source` so that it is recorded or replayed under `port` by the
+/// recorder attached to the DFIR graph, see `Dfir::set_recorder`.
+fn recorded_network_source(port: &str, source: syn::Expr) -> syn::Expr {
+    syn::parse_quote!(df.recorded_network_source(#port, #source))
+}
+
 #[derive(Default, Serialize, Deserialize)]
 pub struct HydroMeta {
     pub clusters: HashMap<usize, Vec<u32>>,
+    /// Members of each cluster that were deployed as standbys, and start outside its membership.
+    #[serde(default)]
+    pub standby: HashMap<usize, Vec<u32>>,
     pub cluster_id: Option<u32>,
     pub subgraph_id: usize,
+    #[serde(skip)]
+    memberships: OnceCell<HashMap<usize, ClusterMembership>>,
+    #[serde(skip)]
+    no_members: ClusterMembership,
+}
+
+impl HydroMeta {
+    pub fn new(
+        clusters: HashMap<usize, Vec<u32>>,
+        standby: HashMap<usize, Vec<u32>>,
+        cluster_id: Option<u32>,
+        subgraph_id: usize,
+    ) -> Self {
+        HydroMeta {
+            clusters,
+            standby,
+            cluster_id,
+            subgraph_id,
+            ..Default::default()
+        }
+    }
+
+    /// The live membership of the cluster with the given raw location ID, which initially
+    /// contains every member the cluster was deployed with, except for standby members.
+    pub fn cluster_membership(&self, of_cluster: usize) -> &ClusterMembership {
+        self.memberships
+            .get_or_init(|| {
+                self.clusters
+                    .iter()
+                    .map(|(&id, members)| {
+                        let standby = self.standby.get(&id).map_or(&[][..], Vec::as_slice);
+                        let initial = members.iter().copied().filter(|m| !standby.contains(m));
+                        (id, ClusterMembership::new(initial))
+                    })
+                    .collect()
+            })
+            .get(&of_cluster)
+            .unwrap_or(&self.no_members) // we default to no members because this is the scenario where the cluster is unused in the graph
+    }
+
+    /// Applies a membership change sent by Hydro Deploy on stdin, formatted by
+    /// [`membership_command`].
+    pub fn handle_command(&self, command: &str) {
+        let Some(update) = command.strip_prefix(MEMBERSHIP_COMMAND_PREFIX) else {
+            eprintln!("Unexpected stdin input: {:?}", command);
+            return;
+        };
+
+        let mut parts = update.split_whitespace();
+        let (Some(of_cluster), Some(member), Some(event)) = (
+            parts.next().and_then(|c| c.parse().ok()),
+            parts.next().and_then(|m| m.parse().ok()),
+            parts.next(),
+        ) else {
+            eprintln!("Malformed membership command: {:?}", command);
+            return;
+        };
+
+        let membership = self.cluster_membership(of_cluster);
+        match event {
+            "joined" => {
+                membership.join(member);
+            }
+            "left" => {
+                membership.leave(member);
+            }
+            _ => eprintln!("Malformed membership command: {:?}", command),
+        }
+    }
+}
+
+/// Formats the stdin command that tells a running location about a change to the membership of
+/// the cluster with the given raw location ID.
+pub fn membership_command(of_cluster: usize, member: u32, event: MembershipEvent) -> String {
+    let event = match event {
+        MembershipEvent::Joined => "joined",
+        MembershipEvent::Left => "left",
+    };
+    format!("{MEMBERSHIP_COMMAND_PREFIX}{of_cluster} {member} {event}")
 }
 
 pub fn cluster_members(
@@ -34,6 +126,13 @@ pub fn cluster_self_id(
         .expect("Tried to read Cluster ID on a non-cluster node"))
 }
 
+pub fn cluster_membership(
+    cli: RuntimeData<&DeployPorts<HydroMeta>>,
+    of_cluster: usize,
+) -> impl QuotedWithContext<&ClusterMembership, ()> + Copy {
+    q!(cli.meta.cluster_membership(of_cluster))
+}
+
 pub fn deploy_o2o(
     env: RuntimeData<&DeployPorts<HydroMeta>>,
     p1_port: &str,
@@ -49,12 +148,15 @@ pub fn deploy_o2o(
             .splice_untyped_ctx(&())
         },
         {
-            q!({
-                env.port(p2_port)
-                    .connect_local_blocking::<ConnectedDirect>()
-                    .into_source()
-            })
-            .splice_untyped_ctx(&())
+            recorded_network_source(
+                p2_port,
+                q!({
+                    env.port(p2_port)
+                        .connect_local_blocking::<ConnectedDirect>()
+                        .into_source()
+                })
+                .splice_untyped_ctx(&()),
+            )
         },
     )
 }
@@ -74,12 +176,15 @@ pub fn deploy_o2m(
             .splice_untyped_ctx(&())
         },
         {
-            q!({
-                env.port(c2_port)
-                    .connect_local_blocking::<ConnectedDirect>()
-                    .into_source()
-            })
-            .splice_untyped_ctx(&())
+            recorded_network_source(
+                c2_port,
+                q!({
+                    env.port(c2_port)
+                        .connect_local_blocking::<ConnectedDirect>()
+                        .into_source()
+                })
+                .splice_untyped_ctx(&()),
+            )
         },
     )
 }
@@ -99,12 +204,15 @@ pub fn deploy_m2o(
             .splice_untyped_ctx(&())
         },
         {
-            q!({
-                env.port(p2_port)
-                    .connect_local_blocking::<ConnectedTagged<ConnectedDirect>>()
-                    .into_source()
-            })
-            .splice_untyped_ctx(&())
+            recorded_network_source(
+                p2_port,
+                q!({
+                    env.port(p2_port)
+                        .connect_local_blocking::<ConnectedTagged<ConnectedDirect>>()
+                        .into_source()
+                })
+                .splice_untyped_ctx(&()),
+            )
         },
     )
 }
@@ -124,12 +232,15 @@ pub fn deploy_m2m(
             .splice_untyped_ctx(&())
         },
         {
-            q!({
-                env.port(c2_port)
-                    .connect_local_blocking::<ConnectedTagged<ConnectedDirect>>()
-                    .into_source()
-            })
-            .splice_untyped_ctx(&())
+            recorded_network_source(
+                c2_port,
+                q!({
+                    env.port(c2_port)
+                        .connect_local_blocking::<ConnectedTagged<ConnectedDirect>>()
+                        .into_source()
+                })
+                .splice_untyped_ctx(&()),
+            )
         },
     )
 }
@@ -139,12 +250,15 @@ pub fn deploy_e2o(
     _e1_port: &str,
     p2_port: &str,
 ) -> syn::Expr {
-    q!({
-        env.port(p2_port)
-            .connect_local_blocking::<ConnectedDirect>()
-            .into_source()
-    })
-    .splice_untyped_ctx(&())
+    recorded_network_source(
+        p2_port,
+        q!({
+            env.port(p2_port)
+                .connect_local_blocking::<ConnectedDirect>()
+                .into_source()
+        })
+        .splice_untyped_ctx(&()),
+    )
 }
 
 pub fn deploy_o2e(
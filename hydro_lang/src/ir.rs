@@ -22,6 +22,7 @@ use syn::parse_quote;
 #[cfg(feature = "build")]
 use crate::deploy::{Deploy, RegisterPort};
 use crate::location::LocationId;
+use crate::network_buffer::NetworkBuffer;
 
 /// Debug displays the type's tokens.
 ///
@@ -101,6 +102,9 @@ pub enum DebugInstantiate {
 )]
 pub struct DebugInstantiateFinalized {
     sink: syn::Expr,
+    /// Closure sending elements into `sink` synchronously, see
+    /// [`crate::deploy::Deploy::sync_network_sink`].
+    sync_sink: Option<syn::Expr>,
     source: syn::Expr,
     connect_fn: Option<Box<dyn FnOnce()>>,
 }
@@ -585,6 +589,12 @@ pub enum HydroNode {
         metadata: HydroIrMetadata,
     },
 
+    Union {
+        first: Box<HydroNode>,
+        second: Box<HydroNode>,
+        metadata: HydroIrMetadata,
+    },
+
     CrossProduct {
         left: Box<HydroNode>,
         right: Box<HydroNode>,
@@ -700,10 +710,20 @@ pub enum HydroNode {
         serialize_fn: Option<DebugExpr>,
         instantiate_fn: DebugInstantiate,
         deserialize_fn: Option<DebugExpr>,
+        buffer: Option<Box<HydroNetworkBuffer>>,
         input: Box<HydroNode>,
         metadata: HydroIrMetadata,
     },
 
+    /// Elements dropped by the bounded buffer of the [`HydroNode::Network`] with the same
+    /// `overflow_ident`, deserialized back into their sent type on the sending location.
+    NetworkOverflow {
+        ident: syn::Ident,
+        deserialize_fn: DebugExpr,
+        location_kind: LocationId,
+        metadata: HydroIrMetadata,
+    },
+
     Counter {
         tag: String,
         duration: DebugExpr,
@@ -712,6 +732,15 @@ pub enum HydroNode {
     },
 }
 
+/// The bounded buffer on the sending side of a [`HydroNode::Network`].
+#[derive(Debug, Clone, Hash)]
+pub struct HydroNetworkBuffer {
+    pub config: NetworkBuffer,
+    /// Names the DFIR output carrying elements dropped by the buffer, read by
+    /// [`HydroNode::NetworkOverflow`].
+    pub overflow_ident: syn::Ident,
+}
+
 pub type SeenTees = HashMap<*const RefCell<HydroNode>, Rc<RefCell<HydroNode>>>;
 pub type SeenTeeLocations = HashMap<*const RefCell<HydroNode>, LocationId>;
 
@@ -754,6 +783,7 @@ impl HydroNode {
                     };
 
                     *instantiate_fn = DebugInstantiateFinalized {
+                        sync_sink: D::sync_network_sink(&sink_expr),
                         sink: sink_expr,
                         source: source_expr,
                         connect_fn: Some(connect_fn),
@@ -768,6 +798,7 @@ impl HydroNode {
                         ..
                     }
                     | HydroNode::CycleSource { location_kind, .. }
+                    | HydroNode::NetworkOverflow { location_kind, .. }
                     | HydroNode::Source { location_kind, .. } => {
                         // Unwrap location out of Tick
                         if let LocationId::Tick(_, tick_loc) = location_kind {
@@ -829,7 +860,9 @@ impl HydroNode {
                 panic!();
             }
 
-            HydroNode::Source { .. } | HydroNode::CycleSource { .. } => {}
+            HydroNode::Source { .. }
+            | HydroNode::CycleSource { .. }
+            | HydroNode::NetworkOverflow { .. } => {}
 
             HydroNode::Tee { inner, .. } => {
                 if let Some(transformed) = seen_tees.get(&inner.as_ptr()) {
@@ -850,7 +883,7 @@ impl HydroNode {
                 transform(inner.as_mut(), seen_tees);
             }
 
-            HydroNode::Chain { first, second, .. } => {
+            HydroNode::Chain { first, second, .. } | HydroNode::Union { first, second, .. } => {
                 transform(first.as_mut(), seen_tees);
                 transform(second.as_mut(), seen_tees);
             }
@@ -910,6 +943,17 @@ impl HydroNode {
                 location_kind: location_kind.clone(),
                 metadata: metadata.clone(),
             },
+            HydroNode::NetworkOverflow {
+                ident,
+                deserialize_fn,
+                location_kind,
+                metadata,
+            } => HydroNode::NetworkOverflow {
+                ident: ident.clone(),
+                deserialize_fn: deserialize_fn.clone(),
+                location_kind: location_kind.clone(),
+                metadata: metadata.clone(),
+            },
             HydroNode::Tee { inner, metadata } => {
                 if let Some(transformed) = seen_tees.get(&inner.as_ptr()) {
                     HydroNode::Tee {
@@ -948,6 +992,15 @@ impl HydroNode {
                 second: Box::new(second.deep_clone(seen_tees)),
                 metadata: metadata.clone(),
             },
+            HydroNode::Union {
+                first,
+                second,
+                metadata,
+            } => HydroNode::Union {
+                first: Box::new(first.deep_clone(seen_tees)),
+                second: Box::new(second.deep_clone(seen_tees)),
+                metadata: metadata.clone(),
+            },
             HydroNode::CrossProduct {
                 left,
                 right,
@@ -1080,6 +1133,7 @@ impl HydroNode {
                 serialize_fn,
                 instantiate_fn,
                 deserialize_fn,
+                buffer,
                 input,
                 metadata,
             } => HydroNode::Network {
@@ -1089,6 +1143,7 @@ impl HydroNode {
                 serialize_fn: serialize_fn.clone(),
                 instantiate_fn: instantiate_fn.clone(),
                 deserialize_fn: deserialize_fn.clone(),
+                buffer: buffer.clone(),
                 input: Box::new(input.deep_clone(seen_tees)),
                 metadata: metadata.clone(),
             },
@@ -1268,6 +1323,43 @@ impl HydroNode {
                 (ident, location_id)
             }
 
+            HydroNode::NetworkOverflow {
+                ident,
+                deserialize_fn,
+                location_kind,
+                ..
+            } => {
+                let location_id = *match location_kind.root() {
+                    LocationId::Process(id) => id,
+                    LocationId::Cluster(id) => id,
+                    LocationId::Tick(_, _) => panic!(),
+                    LocationId::ExternalProcess(_) => panic!(),
+                };
+
+                let overflow_stream_ident =
+                    syn::Ident::new(&format!("stream_{}", *next_stmt_id), Span::call_site());
+
+                match builders_or_callback {
+                    BuildersOrCallback::Builders(graph_builders) => {
+                        let builder = graph_builders.entry(location_id).or_default();
+                        builder.add_dfir(
+                            parse_quote! {
+                                #overflow_stream_ident = #ident -> map(#deserialize_fn);
+                            },
+                            None,
+                            Some(&next_stmt_id.to_string()),
+                        );
+                    }
+                    BuildersOrCallback::Callback(_, node_callback) => {
+                        node_callback(self, next_stmt_id);
+                    }
+                }
+
+                *next_stmt_id += 1;
+
+                (overflow_stream_ident, location_id)
+            }
+
             HydroNode::Tee { inner, .. } => {
                 let (ret_ident, inner_location_id) = if let Some((teed_from, inner_location_id)) =
                     built_tees.get(&(inner.0.as_ref() as *const RefCell<HydroNode>))
@@ -1358,6 +1450,43 @@ impl HydroNode {
                 (chain_ident, first_location_id)
             }
 
+            HydroNode::Union { first, second, .. } => {
+                let (first_ident, first_location_id) =
+                    first.emit_core(builders_or_callback, built_tees, next_stmt_id);
+                let (second_ident, second_location_id) =
+                    second.emit_core(builders_or_callback, built_tees, next_stmt_id);
+
+                assert_eq!(
+                    first_location_id, second_location_id,
+                    "union inputs must be in the same location"
+                );
+
+                let union_ident =
+                    syn::Ident::new(&format!("stream_{}", *next_stmt_id), Span::call_site());
+
+                match builders_or_callback {
+                    BuildersOrCallback::Builders(graph_builders) => {
+                        let builder = graph_builders.entry(first_location_id).or_default();
+                        builder.add_dfir(
+                            parse_quote! {
+                                #union_ident = union();
+                                #first_ident -> [0]#union_ident;
+                                #second_ident -> [1]#union_ident;
+                            },
+                            None,
+                            Some(&next_stmt_id.to_string()),
+                        );
+                    }
+                    BuildersOrCallback::Callback(_, node_callback) => {
+                        node_callback(self, next_stmt_id);
+                    }
+                }
+
+                *next_stmt_id += 1;
+
+                (union_ident, first_location_id)
+            }
+
             HydroNode::CrossSingleton { left, right, .. } => {
                 let (left_ident, left_location_id) =
                     left.emit_core(builders_or_callback, built_tees, next_stmt_id);
@@ -1478,6 +1607,11 @@ impl HydroNode {
                         (neg, quote!('tick))
                     };
 
+                // Either input may be produced by a later subgraph, so pin the item types
+                // explicitly to keep the generated operator code inferable.
+                let pos_type = pos.metadata().output_type.as_ref().map(|t| t.0.clone());
+                let neg_type = neg.metadata().output_type.as_ref().map(|t| t.0.clone());
+
                 let (pos_ident, pos_location_id) =
                     pos.emit_core(builders_or_callback, built_tees, next_stmt_id);
                 let (neg_ident, neg_location_id) =
@@ -1494,11 +1628,13 @@ impl HydroNode {
                 match builders_or_callback {
                     BuildersOrCallback::Builders(graph_builders) => {
                         let builder = graph_builders.entry(pos_location_id).or_default();
+                        let pos_hint = pos_type.map(|t| quote!(-> identity::<#t>()));
+                        let neg_hint = neg_type.map(|t| quote!(-> identity::<#t>()));
                         builder.add_dfir(
                             parse_quote! {
                                 #stream_ident = #operator::<'tick, #neg_lifetime>();
-                                #pos_ident -> [pos]#stream_ident;
-                                #neg_ident -> [neg]#stream_ident;
+                                #pos_ident #pos_hint -> [pos]#stream_ident;
+                                #neg_ident #neg_hint -> [neg]#stream_ident;
                             },
                             None,
                             Some(&next_stmt_id.to_string()),
@@ -1934,6 +2070,7 @@ impl HydroNode {
                 serialize_fn: serialize_pipeline,
                 instantiate_fn,
                 deserialize_fn: deserialize_pipeline,
+                buffer,
                 input,
                 ..
             } => {
@@ -1952,22 +2089,45 @@ impl HydroNode {
 
                 match builders_or_callback {
                     BuildersOrCallback::Builders(graph_builders) => {
-                        let (sink_expr, source_expr) = match instantiate_fn {
+                        let (sink_expr, sync_sink_expr, source_expr) = match instantiate_fn {
                             DebugInstantiate::Building => (
                                 syn::parse_quote!(DUMMY_SINK),
+                                None,
                                 syn::parse_quote!(DUMMY_SOURCE),
                             ),
 
-                            DebugInstantiate::Finalized(finalized) => {
-                                (finalized.sink.clone(), finalized.source.clone())
-                            }
+                            DebugInstantiate::Finalized(finalized) => (
+                                finalized.sink.clone(),
+                                finalized.sync_sink.clone(),
+                                finalized.source.clone(),
+                            ),
                         };
 
                         let sender_builder = graph_builders.entry(input_location_id).or_default();
-                        if let Some(serialize_pipeline) = serialize_pipeline {
+                        let serialized: TokenStream =
+                            if let Some(serialize_pipeline) = serialize_pipeline {
+                                quote!(#input_ident -> map(#serialize_pipeline))
+                            } else {
+                                quote!(#input_ident)
+                            };
+
+                        if let Some(buffer) = buffer {
+                            let HydroNetworkBuffer {
+                                config,
+                                overflow_ident,
+                            } = buffer.as_ref();
+                            let config_expr = config.config_expr();
+                            sender_builder.add_dfir(
+                                parse_quote! {
+                                    #overflow_ident = #serialized -> dest_sink_buffered(#sink_expr, #config_expr);
+                                },
+                                None,
+                                Some(&next_stmt_id.to_string()),
+                            );
+                        } else if let Some(sync_sink_expr) = sync_sink_expr {
                             sender_builder.add_dfir(
                                 parse_quote! {
-                                    #input_ident -> map(#serialize_pipeline) -> dest_sink(#sink_expr);
+                                    #serialized -> for_each(#sync_sink_expr);
                                 },
                                 None,
                                 Some(&next_stmt_id.to_string()),
@@ -1975,7 +2135,7 @@ impl HydroNode {
                         } else {
                             sender_builder.add_dfir(
                                 parse_quote! {
-                                    #input_ident -> dest_sink(#sink_expr);
+                                    #serialized -> dest_sink(#sink_expr);
                                 },
                                 None,
                                 Some(&next_stmt_id.to_string()),
@@ -2057,6 +2217,7 @@ impl HydroNode {
             | HydroNode::Unpersist { .. }
             | HydroNode::Delta { .. }
             | HydroNode::Chain { .. }
+            | HydroNode::Union { .. }
             | HydroNode::CrossProduct { .. }
             | HydroNode::CrossSingleton { .. }
             | HydroNode::ResolveFutures { .. }
@@ -2093,6 +2254,9 @@ impl HydroNode {
                     transform(deserialize_fn);
                 }
             }
+            HydroNode::NetworkOverflow { deserialize_fn, .. } => {
+                transform(deserialize_fn);
+            }
             HydroNode::Counter { duration, .. } => {
                 transform(duration);
             }
@@ -2106,11 +2270,13 @@ impl HydroNode {
             }
             HydroNode::Source { metadata, .. } => metadata,
             HydroNode::CycleSource { metadata, .. } => metadata,
+            HydroNode::NetworkOverflow { metadata, .. } => metadata,
             HydroNode::Tee { metadata, .. } => metadata,
             HydroNode::Persist { metadata, .. } => metadata,
             HydroNode::Unpersist { metadata, .. } => metadata,
             HydroNode::Delta { metadata, .. } => metadata,
             HydroNode::Chain { metadata, .. } => metadata,
+            HydroNode::Union { metadata, .. } => metadata,
             HydroNode::CrossProduct { metadata, .. } => metadata,
             HydroNode::CrossSingleton { metadata, .. } => metadata,
             HydroNode::Join { metadata, .. } => metadata,
@@ -2143,11 +2309,13 @@ impl HydroNode {
             }
             HydroNode::Source { metadata, .. } => metadata,
             HydroNode::CycleSource { metadata, .. } => metadata,
+            HydroNode::NetworkOverflow { metadata, .. } => metadata,
             HydroNode::Tee { metadata, .. } => metadata,
             HydroNode::Persist { metadata, .. } => metadata,
             HydroNode::Unpersist { metadata, .. } => metadata,
             HydroNode::Delta { metadata, .. } => metadata,
             HydroNode::Chain { metadata, .. } => metadata,
+            HydroNode::Union { metadata, .. } => metadata,
             HydroNode::CrossProduct { metadata, .. } => metadata,
             HydroNode::CrossSingleton { metadata, .. } => metadata,
             HydroNode::Join { metadata, .. } => metadata,
@@ -2180,6 +2348,7 @@ impl HydroNode {
             }
             HydroNode::Source { source, .. } => format!("Source({:?})", source),
             HydroNode::CycleSource { ident, .. } => format!("CycleSource({})", ident),
+            HydroNode::NetworkOverflow { ident, .. } => format!("NetworkOverflow({})", ident),
             HydroNode::Tee { inner, .. } => format!("Tee({})", inner.0.borrow().print_root()),
             HydroNode::Persist { .. } => "Persist()".to_string(),
             HydroNode::Unpersist { .. } => "Unpersist()".to_string(),
@@ -2187,6 +2356,9 @@ impl HydroNode {
             HydroNode::Chain { first, second, .. } => {
                 format!("Chain({}, {})", first.print_root(), second.print_root())
             }
+            HydroNode::Union { first, second, .. } => {
+                format!("Union({}, {})", first.print_root(), second.print_root())
+            }
             HydroNode::CrossProduct { left, right, .. } => {
                 format!(
                     "CrossProduct({}, {})",
@@ -2416,7 +2588,7 @@ mod test {
 
     #[test]
     fn hydro_node_size() {
-        insta::assert_snapshot!(size_of::<HydroNode>(), @"152");
+        insta::assert_snapshot!(size_of::<HydroNode>(), @"160");
     }
 
     #[test]
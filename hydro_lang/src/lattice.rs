@@ -0,0 +1,529 @@
+//! Unbounded collections that hold a lattice value.
+//!
+//! A [`LatticeValue`] is a single lattice value that only grows over time, represented by the
+//! stream of *deltas* that are merged into it. A [`KeyedLattice`] is the keyed variant, holding
+//! a lattice value for each key like a [`MapUnionHashMap`], represented by a stream of
+//! `(key, delta)` pairs.
+//!
+//! Because [`Merge::merge`] is associative, commutative and idempotent, the value of a lattice
+//! collection does not depend on the order in which its deltas arrive or on how often each
+//! delta is received. This makes it safe to send lattice collections over the network even when
+//! messages are reordered or duplicated, and allows their operators to process deltas in batches
+//! of any size. New values are derived with [`LatticeMorphism`]s, which are applied to each delta
+//! independently, and joins are computed with [`LatticeBimorphism`]s, which are applied to each
+//! new delta on one side and the accumulated value of the other side.
+
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::hash::Hash;
+
+use lattices::map_union::MapUnionHashMap;
+use lattices::{LatticeBimorphism, LatticeMorphism, Merge};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use stageleft::{QuotedWithContext, q, quote_type};
+use syn::parse_quote;
+
+use crate::ir::HydroNode;
+use crate::location::can_send::CanSend;
+use crate::location::tick::NoAtomic;
+use crate::location::{Location, NoTick};
+use crate::staging_util::get_this_crate;
+use crate::stream::MinOrder;
+use crate::{Bounded, Cluster, ClusterId, NoOrder, Optional, Singleton, Stream, Tick, Unbounded};
+
+/// An unbounded lattice value, represented by the stream of deltas merged into it.
+pub struct LatticeValue<Lat, L> {
+    deltas: Stream<Lat, L, Unbounded, NoOrder>,
+}
+
+impl<'a, Lat, L: Location<'a> + NoTick + NoAtomic> LatticeValue<Lat, L> {
+    /// Creates a lattice value from a stream of deltas to merge into it.
+    pub fn from_deltas(deltas: Stream<Lat, L, Unbounded, NoOrder>) -> Self {
+        LatticeValue { deltas }
+    }
+
+    /// The stream of deltas merged into this lattice value. Deltas may be redundant with each
+    /// other, so the same delta may appear several times.
+    pub fn deltas(self) -> Stream<Lat, L, Unbounded, NoOrder> {
+        self.deltas
+    }
+
+    /// Merges two lattice values together.
+    pub fn merge(self, other: LatticeValue<Lat, L>) -> LatticeValue<Lat, L> {
+        LatticeValue::from_deltas(self.deltas.union(other.deltas))
+    }
+
+    /// Transforms the lattice value with a [`LatticeMorphism`]. Since merging distributes over
+    /// a morphism, the morphism is applied to each delta rather than to the accumulated value.
+    pub fn map<M: LatticeMorphism<Lat> + 'a>(
+        self,
+        morphism: impl QuotedWithContext<'a, M, L>,
+    ) -> LatticeValue<M::Output, L> {
+        let root = get_this_crate();
+        let morphism = morphism.splice_untyped_ctx(&self.deltas.location);
+        let lat_type = quote_type::<Lat>();
+        let f: syn::Expr = parse_quote!({
+            let mut morphism = #morphism;
+            move |delta: #lat_type| {
+                #root::runtime_support::dfir_rs::lattices::LatticeMorphism::call(&mut morphism, delta)
+            }
+        });
+
+        LatticeValue::from_deltas(Stream::new(
+            self.deltas.location.clone(),
+            HydroNode::Map {
+                f: f.into(),
+                input: Box::new(self.deltas.ir_node.into_inner()),
+                metadata: self.deltas.location.new_node_metadata::<M::Output>(),
+            },
+        ))
+    }
+
+    /// Combines two lattice values with a [`LatticeBimorphism`]. Each delta on one side is
+    /// combined with the value accumulated so far on the other side, so the result grows as
+    /// either input grows.
+    pub fn join<Other, Bi: LatticeBimorphism<Lat, Other> + 'a>(
+        self,
+        other: LatticeValue<Other, L>,
+        bimorphism: impl QuotedWithContext<'a, Bi, L>,
+    ) -> LatticeValue<Bi::Output, L>
+    where
+        Lat: Merge<Lat> + Clone + 'a,
+        Other: Merge<Other> + Clone + 'a,
+        Bi::Output: Clone,
+    {
+        let left = KeyedLattice::from_deltas(self.deltas.map(q!(|delta| ((), delta))));
+        let right = KeyedLattice::from_deltas(other.deltas.map(q!(|delta| ((), delta))));
+        LatticeValue::from_deltas(
+            left.join(right, bimorphism)
+                .deltas
+                .map(q!(|((), delta)| delta)),
+        )
+    }
+
+    /// Returns a snapshot of the lattice value, with every delta merged into it, inside the
+    /// given tick. The snapshot is null until the first delta has been received.
+    ///
+    /// # Safety
+    /// The deltas that have been received by a given tick are non-deterministic.
+    pub unsafe fn snapshot(self, tick: &Tick<L>) -> Optional<Lat, Tick<L>, Bounded>
+    where
+        Lat: Merge<Lat> + Clone,
+    {
+        unsafe {
+            // SAFETY: propagated to caller
+            self.deltas
+                .reduce_commutative(q!(|value, delta| {
+                    Merge::merge(value, delta);
+                }))
+                .latest_tick(tick)
+        }
+    }
+
+    /// Sends the deltas of the lattice value to another location. Deltas may be reordered
+    /// by the network, which does not change the value received.
+    pub fn send_bincode<L2: Location<'a> + NoTick + NoAtomic>(
+        self,
+        other: &L2,
+    ) -> LatticeValue<Lat, L2>
+    where
+        L::Root: CanSend<'a, L2, In<Lat> = Lat, Out<Lat> = Lat>,
+        NoOrder: MinOrder<<L::Root as CanSend<'a, L2>>::OutStrongestOrder<NoOrder>, Min = NoOrder>,
+        Lat: Serialize + DeserializeOwned,
+    {
+        LatticeValue::from_deltas(self.deltas.send_bincode::<L2, Lat>(other))
+    }
+
+    /// Sends the deltas of the lattice value from each member of a cluster to another
+    /// location, merging the values of all the senders.
+    pub fn send_bincode_anonymous<L2: Location<'a> + NoTick + NoAtomic, Tag>(
+        self,
+        other: &L2,
+    ) -> LatticeValue<Lat, L2>
+    where
+        L::Root: CanSend<'a, L2, In<Lat> = Lat, Out<Lat> = (Tag, Lat)>,
+        NoOrder: MinOrder<<L::Root as CanSend<'a, L2>>::OutStrongestOrder<NoOrder>, Min = NoOrder>,
+        Lat: Serialize + DeserializeOwned,
+    {
+        LatticeValue::from_deltas(self.deltas.send_bincode_anonymous::<L2, Tag, Lat>(other))
+    }
+
+    /// Sends the deltas of the lattice value to every member of a cluster.
+    pub fn broadcast_bincode<C2: 'a>(
+        self,
+        other: &Cluster<'a, C2>,
+    ) -> LatticeValue<Lat, Cluster<'a, C2>>
+    where
+        L::Root: CanSend<'a, Cluster<'a, C2>, In<Lat> = (ClusterId<C2>, Lat), Out<Lat> = Lat>,
+        Lat: Clone + Serialize + DeserializeOwned,
+        NoOrder: MinOrder<
+                <L::Root as CanSend<'a, Cluster<'a, C2>>>::OutStrongestOrder<NoOrder>,
+                Min = NoOrder,
+            >,
+    {
+        LatticeValue::from_deltas(self.deltas.broadcast_bincode(other))
+    }
+
+    /// Sends the deltas of the lattice value from each member of a cluster to every member of
+    /// another cluster, merging the values of all the senders.
+    pub fn broadcast_bincode_anonymous<C2: 'a, Tag>(
+        self,
+        other: &Cluster<'a, C2>,
+    ) -> LatticeValue<Lat, Cluster<'a, C2>>
+    where
+        L::Root:
+            CanSend<'a, Cluster<'a, C2>, In<Lat> = (ClusterId<C2>, Lat), Out<Lat> = (Tag, Lat)>,
+        Lat: Clone + Serialize + DeserializeOwned,
+        NoOrder: MinOrder<
+                <L::Root as CanSend<'a, Cluster<'a, C2>>>::OutStrongestOrder<NoOrder>,
+                Min = NoOrder,
+            >,
+    {
+        LatticeValue::from_deltas(self.deltas.broadcast_bincode_anonymous(other))
+    }
+}
+
+impl<'a, K, V, L: Location<'a> + NoTick + NoAtomic> LatticeValue<MapUnionHashMap<K, V>, L> {
+    /// Splits a map lattice into the lattice values of each of its keys.
+    pub fn into_keyed(self) -> KeyedLattice<K, V, L> {
+        KeyedLattice::from_deltas(self.deltas.flat_map_unordered(q!(|map| map.into_reveal())))
+    }
+}
+
+/// An unbounded lattice value for each key, represented by the stream of `(key, delta)` pairs
+/// merged into the values.
+pub struct KeyedLattice<K, Lat, L> {
+    deltas: Stream<(K, Lat), L, Unbounded, NoOrder>,
+}
+
+impl<'a, K, Lat, L: Location<'a> + NoTick + NoAtomic> KeyedLattice<K, Lat, L> {
+    /// Creates keyed lattice values from a stream of `(key, delta)` pairs to merge into them.
+    pub fn from_deltas(deltas: Stream<(K, Lat), L, Unbounded, NoOrder>) -> Self {
+        KeyedLattice { deltas }
+    }
+
+    /// The stream of `(key, delta)` pairs merged into the lattice values.
+    pub fn deltas(self) -> Stream<(K, Lat), L, Unbounded, NoOrder> {
+        self.deltas
+    }
+
+    /// Merges the lattice values of each key together.
+    pub fn merge(self, other: KeyedLattice<K, Lat, L>) -> KeyedLattice<K, Lat, L> {
+        KeyedLattice::from_deltas(self.deltas.union(other.deltas))
+    }
+
+    /// Combines the keyed lattice values into a single [`MapUnionHashMap`] lattice.
+    pub fn into_lattice(self) -> LatticeValue<MapUnionHashMap<K, Lat>, L>
+    where
+        K: Eq + Hash,
+    {
+        LatticeValue::from_deltas(
+            self.deltas
+                .map(q!(|(key, delta)| MapUnionHashMap::new_from([(key, delta)]))),
+        )
+    }
+
+    /// Transforms the lattice value of each key with a [`LatticeMorphism`].
+    pub fn map_values<M: LatticeMorphism<Lat> + 'a>(
+        self,
+        morphism: impl QuotedWithContext<'a, M, L>,
+    ) -> KeyedLattice<K, M::Output, L> {
+        let root = get_this_crate();
+        let morphism = morphism.splice_untyped_ctx(&self.deltas.location);
+        let lat_type = quote_type::<Lat>();
+        let f: syn::Expr = parse_quote!({
+            let mut morphism = #morphism;
+            move |(key, delta): (_, #lat_type)| {
+                (key, #root::runtime_support::dfir_rs::lattices::LatticeMorphism::call(&mut morphism, delta))
+            }
+        });
+
+        KeyedLattice::from_deltas(Stream::new(
+            self.deltas.location.clone(),
+            HydroNode::Map {
+                f: f.into(),
+                input: Box::new(self.deltas.ir_node.into_inner()),
+                metadata: self.deltas.location.new_node_metadata::<(K, M::Output)>(),
+            },
+        ))
+    }
+
+    /// Combines the lattice values of each key that is present on both sides with a
+    /// [`LatticeBimorphism`]. Each delta on one side is combined with the value accumulated so
+    /// far for the same key on the other side.
+    pub fn join<Other, Bi: LatticeBimorphism<Lat, Other> + 'a>(
+        self,
+        other: KeyedLattice<K, Other, L>,
+        bimorphism: impl QuotedWithContext<'a, Bi, L>,
+    ) -> KeyedLattice<K, Bi::Output, L>
+    where
+        K: Eq + Hash + Clone + 'a,
+        Lat: Merge<Lat> + Clone + 'a,
+        Other: Merge<Other> + Clone + 'a,
+        Bi::Output: Clone,
+    {
+        let location = self.deltas.location.clone();
+        let tick = location.tick();
+        let root = get_this_crate();
+        let bimorphism = bimorphism.splice_untyped_ctx(&location);
+        let state_type = quote_type::<KeyedJoinState<K, Lat, Other>>();
+        let f: syn::Expr = parse_quote!({
+            let mut bimorphism = #bimorphism;
+            move |((mut state, left), right): ((#state_type, _), _)| {
+                let joined = state.apply(left, right, |lat_a, lat_b| {
+                    #root::runtime_support::dfir_rs::lattices::LatticeBimorphism::call(
+                        &mut bimorphism,
+                        lat_a,
+                        lat_b,
+                    )
+                });
+                (state, joined)
+            }
+        });
+
+        let (left, right) = unsafe {
+            // SAFETY: merging is commutative and idempotent, so the joined values do not
+            // depend on how the deltas are batched into ticks
+            (
+                self.deltas.tick_batch(&tick),
+                other.deltas.tick_batch(&tick),
+            )
+        };
+        let left = left.fold_commutative(q!(Vec::new), q!(|batch, delta| batch.push(delta)));
+        let right = right.fold_commutative(q!(Vec::new), q!(|batch, delta| batch.push(delta)));
+
+        let initial: Singleton<KeyedJoinState<K, Lat, Other>, _, _> =
+            tick.singleton(q!(KeyedJoinState::new()));
+        let (state_complete_cycle, state) = tick.cycle_with_initial(initial);
+        let zipped = state.zip(left).zip(right);
+        let applied =
+            Singleton::<(KeyedJoinState<K, Lat, Other>, Vec<(K, Bi::Output)>), _, Bounded>::new(
+                tick.clone(),
+                HydroNode::Map {
+                    f: f.into(),
+                    input: Box::new(zipped.ir_node.into_inner()),
+                    metadata: tick
+                        .new_node_metadata::<(KeyedJoinState<K, Lat, Other>, Vec<(K, Bi::Output)>)>(
+                        ),
+                },
+            );
+        state_complete_cycle.complete_next_tick(applied.clone().map(q!(|(state, _)| state)));
+
+        KeyedLattice::from_deltas(
+            applied
+                .flat_map_unordered(q!(|(_, joined)| joined))
+                .all_ticks()
+                .into(),
+        )
+    }
+
+    /// Returns a snapshot of the lattice value of every key that has received a delta, inside
+    /// the given tick.
+    ///
+    /// # Safety
+    /// The deltas that have been received by a given tick are non-deterministic.
+    pub unsafe fn snapshot(self, tick: &Tick<L>) -> Stream<(K, Lat), Tick<L>, Bounded, NoOrder>
+    where
+        K: Eq + Hash + Clone,
+        Lat: Merge<Lat> + Clone,
+    {
+        unsafe {
+            // SAFETY: propagated to caller
+            self.deltas.tick_batch(tick)
+        }
+        .persist()
+        .reduce_keyed_commutative(q!(|value, delta| {
+            Merge::merge(value, delta);
+        }))
+    }
+
+    /// Sends the `(key, delta)` pairs to another location. Deltas may be reordered by the
+    /// network, which does not change the values received.
+    pub fn send_bincode<L2: Location<'a> + NoTick + NoAtomic>(
+        self,
+        other: &L2,
+    ) -> KeyedLattice<K, Lat, L2>
+    where
+        L::Root: CanSend<'a, L2, In<(K, Lat)> = (K, Lat), Out<(K, Lat)> = (K, Lat)>,
+        NoOrder: MinOrder<<L::Root as CanSend<'a, L2>>::OutStrongestOrder<NoOrder>, Min = NoOrder>,
+        K: Serialize + DeserializeOwned,
+        Lat: Serialize + DeserializeOwned,
+    {
+        KeyedLattice::from_deltas(self.deltas.send_bincode::<L2, (K, Lat)>(other))
+    }
+
+    /// Sends the `(key, delta)` pairs from each member of a cluster to another location,
+    /// merging the values of all the senders.
+    pub fn send_bincode_anonymous<L2: Location<'a> + NoTick + NoAtomic, Tag>(
+        self,
+        other: &L2,
+    ) -> KeyedLattice<K, Lat, L2>
+    where
+        L::Root: CanSend<'a, L2, In<(K, Lat)> = (K, Lat), Out<(K, Lat)> = (Tag, (K, Lat))>,
+        NoOrder: MinOrder<<L::Root as CanSend<'a, L2>>::OutStrongestOrder<NoOrder>, Min = NoOrder>,
+        K: Serialize + DeserializeOwned,
+        Lat: Serialize + DeserializeOwned,
+    {
+        KeyedLattice::from_deltas(
+            self.deltas
+                .send_bincode_anonymous::<L2, Tag, (K, Lat)>(other),
+        )
+    }
+
+    /// Sends the `(key, delta)` pairs to every member of a cluster.
+    pub fn broadcast_bincode<C2: 'a>(
+        self,
+        other: &Cluster<'a, C2>,
+    ) -> KeyedLattice<K, Lat, Cluster<'a, C2>>
+    where
+        L::Root: CanSend<
+                'a,
+                Cluster<'a, C2>,
+                In<(K, Lat)> = (ClusterId<C2>, (K, Lat)),
+                Out<(K, Lat)> = (K, Lat),
+            >,
+        K: Clone + Serialize + DeserializeOwned,
+        Lat: Clone + Serialize + DeserializeOwned,
+        NoOrder: MinOrder<
+                <L::Root as CanSend<'a, Cluster<'a, C2>>>::OutStrongestOrder<NoOrder>,
+                Min = NoOrder,
+            >,
+    {
+        KeyedLattice::from_deltas(self.deltas.broadcast_bincode(other))
+    }
+}
+
+impl<'a, Lat, L: Location<'a> + NoTick + NoAtomic, Order> Stream<Lat, L, Unbounded, Order> {
+    /// Treats the elements of the stream as deltas to merge into a lattice value.
+    pub fn into_lattice(self) -> LatticeValue<Lat, L> {
+        LatticeValue::from_deltas(unsafe {
+            // SAFETY: merging is commutative, so lattice values are unordered
+            self.assume_ordering::<NoOrder>()
+        })
+    }
+}
+
+impl<'a, K, Lat, L: Location<'a> + NoTick + NoAtomic, Order> Stream<(K, Lat), L, Unbounded, Order> {
+    /// Treats the elements of the stream as `(key, delta)` pairs to merge into the lattice
+    /// value of each key.
+    pub fn into_keyed_lattice(self) -> KeyedLattice<K, Lat, L> {
+        KeyedLattice::from_deltas(unsafe {
+            // SAFETY: merging is commutative, so lattice values are unordered
+            self.assume_ordering::<NoOrder>()
+        })
+    }
+}
+
+/// The values accumulated for each key on both sides of a [`KeyedLattice::join`], used by the
+/// generated code.
+#[doc(hidden)]
+#[derive(Clone)]
+pub struct KeyedJoinState<K, A, B> {
+    left: HashMap<K, A>,
+    right: HashMap<K, B>,
+}
+
+impl<K: Eq + Hash + Clone, A: Merge<A> + Clone, B: Merge<B> + Clone> KeyedJoinState<K, A, B> {
+    #[expect(
+        clippy::new_without_default,
+        reason = "only constructed by generated code"
+    )]
+    pub fn new() -> Self {
+        KeyedJoinState {
+            left: HashMap::new(),
+            right: HashMap::new(),
+        }
+    }
+
+    /// Merges a batch of deltas into both sides, returning the results of `bimorphism` for
+    /// each delta and the value of the other side for the same key. Deltas on the left are
+    /// joined with the right side as of the previous batch, and deltas on the right with the
+    /// left side including this batch, so every pair of deltas is joined at least once.
+    pub fn apply<O>(
+        &mut self,
+        left: Vec<(K, A)>,
+        right: Vec<(K, B)>,
+        mut bimorphism: impl FnMut(A, B) -> O,
+    ) -> Vec<(K, O)> {
+        let mut joined = Vec::new();
+        for (key, delta) in left {
+            if let Some(other) = self.right.get(&key) {
+                joined.push((key.clone(), bimorphism(delta.clone(), other.clone())));
+            }
+            merge_entry(self.left.entry(key), delta);
+        }
+        for (key, delta) in right {
+            if let Some(other) = self.left.get(&key) {
+                joined.push((key.clone(), bimorphism(other.clone(), delta.clone())));
+            }
+            merge_entry(self.right.entry(key), delta);
+        }
+        joined
+    }
+}
+
+fn merge_entry<K, Lat: Merge<Lat>>(entry: Entry<'_, K, Lat>, delta: Lat) {
+    match entry {
+        Entry::Occupied(mut value) => {
+            value.get_mut().merge(delta);
+        }
+        Entry::Vacant(vacant) => {
+            vacant.insert(delta);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lattices::{Max, Min, Pair};
+
+    use super::*;
+
+    fn apply_pairs(
+        state: &mut KeyedJoinState<char, Max<i32>, Min<i32>>,
+        left: Vec<(char, i32)>,
+        right: Vec<(char, i32)>,
+    ) -> Vec<(char, (i32, i32))> {
+        let mut joined = state
+            .apply(
+                left.into_iter().map(|(k, v)| (k, Max::new(v))).collect(),
+                right.into_iter().map(|(k, v)| (k, Min::new(v))).collect(),
+                Pair::new,
+            )
+            .into_iter()
+            .map(|(k, pair)| {
+                let (a, b) = pair.into_reveal();
+                (k, (a.into_reveal(), b.into_reveal()))
+            })
+            .collect::<Vec<_>>();
+        joined.sort();
+        joined
+    }
+
+    #[test]
+    fn test_keyed_join_same_batch() {
+        let mut state = KeyedJoinState::new();
+        assert_eq!(
+            apply_pairs(&mut state, vec![('a', 1), ('b', 2)], vec![('a', 5)]),
+            vec![('a', (1, 5))]
+        );
+    }
+
+    #[test]
+    fn test_keyed_join_across_batches() {
+        let mut state = KeyedJoinState::new();
+        assert_eq!(
+            apply_pairs(&mut state, vec![('a', 1), ('a', 4)], vec![]),
+            vec![]
+        );
+        assert_eq!(
+            apply_pairs(&mut state, vec![], vec![('a', 5)]),
+            vec![('a', (4, 5))]
+        );
+        assert_eq!(
+            apply_pairs(&mut state, vec![('a', 2)], vec![('a', 3)]),
+            vec![('a', (2, 5)), ('a', (4, 3))]
+        );
+    }
+}
@@ -11,6 +11,8 @@ pub use stageleft::q;
 pub mod runtime_support {
     pub use {bincode, dfir_rs, stageleft, tokio};
     pub mod resource_measurement;
+    #[cfg(stageleft_runtime)]
+    pub mod trace_context;
 }
 
 #[doc(hidden)]
@@ -18,6 +20,7 @@ pub mod internal_constants {
     pub const CPU_USAGE_PREFIX: &str = "CPU:";
     // Should remain consistent with dfir_lang/src/graph/ops/_counter.rs
     pub const COUNTER_PREFIX: &str = "_counter";
+    pub const MEMBERSHIP_COMMAND_PREFIX: &str = "membership: ";
 }
 
 #[cfg(feature = "dfir_context")]
@@ -39,9 +42,23 @@ pub use singleton::Singleton;
 pub mod optional;
 pub use optional::Optional;
 
+pub mod lattice;
+pub use lattice::{KeyedLattice, LatticeValue};
+
+pub mod window;
+pub use window::{Window, WindowBounds};
+
+pub mod zset;
+pub use zset::ZSet;
+
+pub mod network_buffer;
+pub use network_buffer::NetworkBuffer;
+
 pub mod location;
 pub use location::cluster::CLUSTER_SELF_ID;
-pub use location::{Atomic, Cluster, ClusterId, ExternalProcess, Location, Process, Tick};
+pub use location::{
+    Atomic, Cluster, ClusterId, ExternalProcess, Location, MembershipEvent, Process, Tick,
+};
 
 #[cfg(feature = "build")]
 #[cfg_attr(docsrs, doc(cfg(feature = "build")))]
@@ -49,6 +66,8 @@ pub mod deploy;
 
 pub mod deploy_runtime;
 
+pub mod sim_runtime;
+
 pub mod cycle;
 
 pub mod builder;
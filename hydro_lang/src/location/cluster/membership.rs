@@ -0,0 +1,201 @@
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::rc::Rc;
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::{UnboundedSender, unbounded_channel};
+use tokio_stream::wrappers::UnboundedReceiverStream;
+
+use super::ClusterId;
+
+/// A change to the membership of a cluster.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum MembershipEvent {
+    /// The member joined the cluster, and will receive messages sent to the cluster.
+    Joined,
+    /// The member left the cluster, and will no longer receive messages sent to the cluster.
+    Left,
+}
+
+#[derive(Default)]
+struct MembershipState {
+    members: BTreeSet<u32>,
+    subscribers: Vec<UnboundedSender<(u32, MembershipEvent)>>,
+    unroutable: u64,
+}
+
+/// The live membership of a cluster, as seen by a single running location. Members can only
+/// join if they were provisioned when the cluster was deployed, either as initial members or as
+/// standby members, since the network connections to a cluster are fixed at deploy time.
+///
+/// Cloning a [`ClusterMembership`] produces another handle to the same membership.
+#[derive(Clone, Default)]
+pub struct ClusterMembership {
+    state: Rc<RefCell<MembershipState>>,
+}
+
+impl ClusterMembership {
+    /// Creates a membership that initially contains the given raw member IDs.
+    pub fn new(initial: impl IntoIterator<Item = u32>) -> Self {
+        ClusterMembership {
+            state: Rc::new(RefCell::new(MembershipState {
+                members: initial.into_iter().collect(),
+                subscribers: Vec::new(),
+                unroutable: 0,
+            })),
+        }
+    }
+
+    /// Whether the member with the given raw ID is currently part of the cluster.
+    pub fn contains(&self, member: u32) -> bool {
+        self.state.borrow().members.contains(&member)
+    }
+
+    /// The raw IDs of the current members, in ascending order.
+    pub fn members(&self) -> Vec<u32> {
+        self.state.borrow().members.iter().copied().collect()
+    }
+
+    /// Adds the member to the cluster. Returns false if it was already a member.
+    pub fn join(&self, member: u32) -> bool {
+        self.update(member, MembershipEvent::Joined)
+    }
+
+    /// Removes the member from the cluster. Returns false if it was not a member.
+    pub fn leave(&self, member: u32) -> bool {
+        self.update(member, MembershipEvent::Left)
+    }
+
+    fn update(&self, member: u32, event: MembershipEvent) -> bool {
+        let mut state = self.state.borrow_mut();
+        let changed = match event {
+            MembershipEvent::Joined => state.members.insert(member),
+            MembershipEvent::Left => state.members.remove(&member),
+        };
+
+        if changed {
+            state
+                .subscribers
+                .retain(|subscriber| subscriber.send((member, event)).is_ok());
+        }
+        changed
+    }
+
+    /// Returns a stream of membership changes, starting with a [`MembershipEvent::Joined`] for
+    /// each current member.
+    pub fn events(&self) -> UnboundedReceiverStream<(u32, MembershipEvent)> {
+        let (sender, receiver) = unbounded_channel();
+        let mut state = self.state.borrow_mut();
+        for &member in &state.members {
+            sender.send((member, MembershipEvent::Joined)).unwrap();
+        }
+        state.subscribers.push(sender);
+        UnboundedReceiverStream::new(receiver)
+    }
+
+    /// Picks the `index`-th recipient of a round-robin over `ids`, skipping any that are not
+    /// current members. Returns `None` if none of `ids` is a current member, and counts the
+    /// element as [unroutable](Self::unroutable).
+    pub fn round_robin<C>(&self, ids: &[ClusterId<C>], index: usize) -> Option<ClusterId<C>> {
+        let recipient = (0..ids.len())
+            .map(|offset| ids[(index + offset) % ids.len()])
+            .find(|id| self.contains(id.raw_id));
+
+        if recipient.is_none() {
+            self.state.borrow_mut().unroutable += 1;
+        }
+        recipient
+    }
+
+    /// The number of elements that [`Self::round_robin`] could not route because the cluster had
+    /// no members, and which were dropped.
+    pub fn unroutable(&self) -> u64 {
+        self.state.borrow().unroutable
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::StreamExt;
+    use hydro_deploy::Deployment;
+
+    use crate::*;
+
+    struct Leader {}
+    struct Worker {}
+
+    #[tokio::test]
+    async fn cluster_membership_changes() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let leader = flow.process::<Leader>();
+        let workers = flow.cluster::<Worker>();
+        let external = flow.external_process::<()>();
+
+        let out_port = leader
+            .source_cluster_members(&workers)
+            .map(q!(|(id, event)| (id.raw_id, event)))
+            .send_bincode_external(&external);
+
+        // locations without any dataflow are not deployed, so give the workers something to do
+        workers
+            .source_iter(q!([CLUSTER_SELF_ID]))
+            .send_bincode(&leader)
+            .for_each(q!(|_| {}));
+
+        let nodes = flow
+            .with_process(&leader, deployment.Localhost())
+            .with_cluster(
+                &workers,
+                vec![
+                    deploy::TrybuildHost::from(deployment.Localhost()),
+                    deploy::TrybuildHost::from(deployment.Localhost()),
+                    deploy::TrybuildHost::from(deployment.Localhost()).standby(),
+                ],
+            )
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut external_out = nodes.connect_source_bincode(out_port).await;
+
+        deployment.start().await.unwrap();
+
+        assert_eq!(
+            external_out.next().await.unwrap(),
+            (0, MembershipEvent::Joined)
+        );
+        assert_eq!(
+            external_out.next().await.unwrap(),
+            (1, MembershipEvent::Joined)
+        );
+
+        nodes
+            .remove_cluster_member(&workers, ClusterId::from_raw(1))
+            .await;
+        assert_eq!(
+            external_out.next().await.unwrap(),
+            (1, MembershipEvent::Left)
+        );
+
+        nodes
+            .add_cluster_member(&workers, ClusterId::from_raw(1))
+            .await;
+        assert_eq!(
+            external_out.next().await.unwrap(),
+            (1, MembershipEvent::Joined)
+        );
+
+        assert_eq!(
+            nodes.allocate_cluster_member(&workers).await,
+            Some(ClusterId::from_raw(2))
+        );
+        assert_eq!(
+            external_out.next().await.unwrap(),
+            (2, MembershipEvent::Joined)
+        );
+        assert_eq!(nodes.allocate_cluster_member(&workers).await, None);
+    }
+}
@@ -13,6 +13,9 @@ use crate::staging_util::{Invariant, get_this_crate};
 pub mod cluster_id;
 pub use cluster_id::ClusterId;
 
+pub mod membership;
+pub use membership::{ClusterMembership, MembershipEvent};
+
 pub struct Cluster<'a, C> {
     pub(crate) id: usize,
     pub(crate) flow_state: FlowState,
@@ -33,12 +36,22 @@ impl<C> PartialEq for Cluster<'_, C> {
 }
 
 impl<'a, C> Cluster<'a, C> {
+    /// The IDs of all members the cluster was deployed with. These do not change at runtime;
+    /// use [`Cluster::membership`] to check which of them are currently part of the cluster.
     pub fn members(&self) -> ClusterIds<'a, C> {
         ClusterIds {
             id: self.id,
             _phantom: PhantomData,
         }
     }
+
+    /// The live [`ClusterMembership`] of the cluster, which changes as members join and leave.
+    pub fn membership(&self) -> ClusterMembershipRef<'a, C> {
+        ClusterMembershipRef {
+            id: self.id,
+            _phantom: PhantomData,
+        }
+    }
 }
 
 impl<C> Clone for Cluster<'_, C> {
@@ -109,6 +122,43 @@ impl<'a, C: 'a, Ctx> FreeVariableWithContext<Ctx> for ClusterIds<'a, C> {
 
 impl<'a, C, Ctx> QuotedWithContext<'a, &'a Vec<ClusterId<C>>, Ctx> for ClusterIds<'a, C> {}
 
+pub struct ClusterMembershipRef<'a, C> {
+    pub(crate) id: usize,
+    _phantom: Invariant<'a, C>,
+}
+
+impl<C> Clone for ClusterMembershipRef<'_, C> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<C> Copy for ClusterMembershipRef<'_, C> {}
+
+impl<'a, C: 'a, Ctx> FreeVariableWithContext<Ctx> for ClusterMembershipRef<'a, C> {
+    type O = &'a ClusterMembership;
+
+    fn to_tokens(self, _ctx: &Ctx) -> QuoteTokens
+    where
+        Self: Sized,
+    {
+        let ident = syn::Ident::new(
+            &format!("__hydro_lang_cluster_membership_{}", self.id),
+            Span::call_site(),
+        );
+
+        QuoteTokens {
+            prelude: None,
+            expr: Some(quote! { #ident }),
+        }
+    }
+}
+
+impl<'a, C: 'a, Ctx> QuotedWithContext<'a, &'a ClusterMembership, Ctx>
+    for ClusterMembershipRef<'a, C>
+{
+}
+
 pub trait IsCluster {
     type Tag;
 }
@@ -118,6 +118,7 @@ impl<'a, P> ExternalProcess<'a, P> {
                         serialize_fn: None,
                         instantiate_fn: DebugInstantiate::Building,
                         deserialize_fn: Some(deser_expr.into()),
+                        buffer: None,
                         input: Box::new(HydroNode::Source {
                             source: HydroSource::ExternalNetwork(),
                             location_kind: LocationId::ExternalProcess(self.id),
@@ -158,6 +159,7 @@ impl<'a, P> ExternalProcess<'a, P> {
                         serialize_fn: None,
                         instantiate_fn: DebugInstantiate::Building,
                         deserialize_fn: Some(crate::stream::deserialize_bincode::<T>(None).into()),
+                        buffer: None,
                         input: Box::new(HydroNode::Source {
                             source: HydroSource::ExternalNetwork(),
                             location_kind: LocationId::ExternalProcess(self.id),
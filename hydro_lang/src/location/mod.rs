@@ -18,7 +18,7 @@ pub mod process;
 pub use process::Process;
 
 pub mod cluster;
-pub use cluster::{Cluster, ClusterId};
+pub use cluster::{Cluster, ClusterId, MembershipEvent};
 
 pub mod can_send;
 pub use can_send::CanSend;
@@ -135,6 +135,21 @@ pub trait Location<'a>: Clone {
         )
     }
 
+    /// Returns a stream of the changes to the membership of `cluster`, as observed by this
+    /// location. The stream starts with a [`MembershipEvent::Joined`] for every member at the time
+    /// the program starts, followed by joins and leaves as the deployment changes the membership.
+    fn source_cluster_members<C: 'a>(
+        &self,
+        cluster: &Cluster<'a, C>,
+    ) -> Stream<(ClusterId<C>, MembershipEvent), Self, Unbounded>
+    where
+        Self: Sized + NoTick,
+    {
+        let membership = cluster.membership();
+        self.source_stream(q!(membership.events()))
+            .map(q!(|(id, event)| (ClusterId::from_raw(id), event)))
+    }
+
     fn source_iter<T, E: IntoIterator<Item = T>>(
         &self,
         e: impl QuotedWithContext<'a, E, Self>,
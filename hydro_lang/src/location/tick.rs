@@ -217,6 +217,52 @@ impl<'a, L: Location<'a>> Tick<L> {
         )
     }
 
+    /// Creates a forward reference to a stream within this tick, which is completed later in the
+    /// same tick, for example to express recursion. Unlike [`Self::forward_ref`], the stream type
+    /// is fixed, so that forward references on top-level locations can still infer theirs.
+    #[expect(clippy::type_complexity, reason = "forward reference and stream types")]
+    pub fn forward_ref_stream<T, Order>(
+        &self,
+    ) -> (
+        ForwardRef<'a, Stream<T, Self, Bounded, Order>>,
+        Stream<T, Self, Bounded, Order>,
+    ) {
+        let next_id = {
+            let on_id = match self.l.id() {
+                LocationId::Process(id) => id,
+                LocationId::Cluster(id) => id,
+                LocationId::Tick(_, _) => panic!(),
+                LocationId::ExternalProcess(_) => panic!(),
+            };
+
+            let mut flow_state = self.flow_state().borrow_mut();
+            let next_id_entry = flow_state.cycle_counts.entry(on_id).or_default();
+
+            let id = *next_id_entry;
+            *next_id_entry += 1;
+            id
+        };
+
+        let ident = syn::Ident::new(&format!("cycle_{}", next_id), Span::call_site());
+
+        (
+            ForwardRef {
+                completed: false,
+                ident: ident.clone(),
+                expected_location: self.id(),
+                _phantom: PhantomData,
+            },
+            Stream::new(
+                self.clone(),
+                HydroNode::CycleSource {
+                    ident,
+                    location_kind: self.id(),
+                    metadata: self.new_node_metadata::<T>(),
+                },
+            ),
+        )
+    }
+
     pub fn cycle<S: CycleCollection<'a, TickCycleMarker, Location = Self> + DeferTick>(
         &self,
     ) -> (TickCycle<'a, S>, S)
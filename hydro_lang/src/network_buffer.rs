@@ -0,0 +1,84 @@
+//! Bounded buffering for the sending side of network channels.
+//!
+//! By default, elements sent over the network are queued without limit until the receiver
+//! accepts them, so a slow receiver makes the sender's memory grow. A [`NetworkBuffer`] passed to
+//! [`Stream::send_bincode_buffered`] or [`Stream::broadcast_bincode_buffered`] bounds that queue
+//! by element count and/or serialized size, and picks an [`Overflow`] policy for when it is full.
+//! Elements dropped by the policy are returned to the sender as an overflow stream.
+//!
+//! [`Stream::send_bincode_buffered`]: crate::Stream::send_bincode_buffered
+//! [`Stream::broadcast_bincode_buffered`]: crate::Stream::broadcast_bincode_buffered
+
+/// What a bounded network channel does with an element that does not fit in its buffer.
+pub use dfir_rs::util::buffered_sink::Overflow;
+
+#[cfg(feature = "build")]
+use crate::staging_util::get_this_crate;
+
+/// Limits and overflow policy for a bounded network channel.
+///
+/// Byte limits are measured on the serialized payloads. A single element larger than
+/// `max_bytes` is still sent when the buffer is empty.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct NetworkBuffer {
+    /// Maximum number of buffered elements.
+    pub max_items: Option<usize>,
+    /// Maximum total size of buffered elements, in serialized bytes.
+    pub max_bytes: Option<usize>,
+    /// What to do with elements that do not fit.
+    pub overflow: Overflow,
+}
+
+impl NetworkBuffer {
+    /// A buffer with no limits yet, using `overflow` once limits are added.
+    pub const fn new(overflow: Overflow) -> Self {
+        Self {
+            max_items: None,
+            max_bytes: None,
+            overflow,
+        }
+    }
+
+    /// Limits the buffer to `max_items` elements.
+    pub const fn max_items(mut self, max_items: usize) -> Self {
+        self.max_items = Some(max_items);
+        self
+    }
+
+    /// Limits the buffer to `max_bytes` serialized bytes.
+    pub const fn max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    /// The `BufferConfig` expression passed to the `dest_sink_buffered` DFIR operator.
+    #[cfg(feature = "build")]
+    pub(crate) fn config_expr(&self) -> syn::Expr {
+        let root = get_this_crate();
+
+        let limit = |limit: Option<usize>| -> syn::Expr {
+            match limit {
+                Some(limit) => syn::parse_quote!(::std::option::Option::Some(#limit)),
+                None => syn::parse_quote!(::std::option::Option::None),
+            }
+        };
+        let max_items = limit(self.max_items);
+        let max_bytes = limit(self.max_bytes);
+        let overflow: syn::Ident = syn::Ident::new(
+            match self.overflow {
+                Overflow::Block => "Block",
+                Overflow::DropOldest => "DropOldest",
+                Overflow::DropNewest => "DropNewest",
+            },
+            proc_macro2::Span::call_site(),
+        );
+
+        syn::parse_quote! {
+            #root::runtime_support::dfir_rs::util::buffered_sink::BufferConfig {
+                max_items: #max_items,
+                max_bytes: #max_bytes,
+                overflow: #root::runtime_support::dfir_rs::util::buffered_sink::Overflow::#overflow,
+            }
+        }
+    }
+}
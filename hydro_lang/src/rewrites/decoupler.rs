@@ -59,6 +59,7 @@ fn decouple_node(node: &mut HydroNode, decoupler: &Decoupler, next_stmt_id: &mut
                 output_type,
             ))
             .map(|e| e.into()),
+            buffer: None,
             input: Box::new(mapped_node),
             metadata: network_metadata.clone(),
         };
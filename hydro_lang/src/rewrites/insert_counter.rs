@@ -13,9 +13,11 @@ fn insert_counter_node(node: &mut HydroNode, next_stmt_id: &mut usize, duration:
         }
         HydroNode::Source { metadata, .. }
         | HydroNode::CycleSource { metadata, .. }
+        | HydroNode::NetworkOverflow { metadata, .. }
         | HydroNode::Persist { metadata, .. }
         | HydroNode::Delta { metadata, .. }
         | HydroNode::Chain { metadata, .. } // Can technically be derived by summing parent cardinalities
+        | HydroNode::Union { metadata, .. }
         | HydroNode::CrossSingleton { metadata, .. }
         | HydroNode::CrossProduct { metadata, .. } // Can technically be derived by multiplying parent cardinalities
         | HydroNode::Join { metadata, .. }
@@ -22,3 +22,6 @@ pub mod persist_pullup;
 #[cfg_attr(docsrs, doc(cfg(feature = "build")))]
 pub mod print_id;
 pub mod properties;
+#[cfg(feature = "build")]
+#[cfg_attr(docsrs, doc(cfg(feature = "build")))]
+pub mod trace_network;
@@ -126,6 +126,7 @@ fn persist_pullup_node(
                 serialize_fn,
                 instantiate_fn,
                 deserialize_fn,
+                buffer,
                 input: mb!(* HydroNode::Persist { inner: behind_persist, .. }),
                 metadata,
             } => HydroNode::Persist {
@@ -136,6 +137,7 @@ fn persist_pullup_node(
                     serialize_fn,
                     instantiate_fn,
                     deserialize_fn,
+                    buffer,
                     input: behind_persist,
                     metadata: metadata.clone()
                 }),
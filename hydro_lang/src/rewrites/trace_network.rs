@@ -0,0 +1,215 @@
+use syn::parse_quote;
+
+use crate::ir::*;
+use crate::staging_util::get_this_crate;
+
+fn trace_network_node(node: &mut HydroNode, next_stmt_id: &mut usize) {
+    let root = get_this_crate();
+    match node {
+        HydroNode::Placeholder | HydroNode::Counter { .. } => {
+            std::panic!(
+                "Unexpected {:?} found in trace_network_node",
+                node.print_root()
+            );
+        }
+        HydroNode::Network {
+            to_location,
+            serialize_fn: Some(serialize_fn),
+            deserialize_fn: Some(deserialize_fn),
+            input,
+            metadata,
+            ..
+        } => {
+            let channel = format!(
+                "{}: {:?} -> {:?}",
+                next_stmt_id,
+                input.metadata().location_kind.root(),
+                to_location
+            );
+            let serialize = &serialize_fn.0;
+            *serialize_fn = DebugExpr(parse_quote! {
+                |data| #root::runtime_support::trace_context::send(#channel, (#serialize)(data))
+            });
+            let deserialize = &deserialize_fn.0;
+            *deserialize_fn = DebugExpr(parse_quote! {
+                |res| #root::runtime_support::trace_context::recv(#channel, res, #deserialize)
+            });
+
+            // Handle each received message within its trace.
+            let metadata = metadata.clone();
+            let node_content = std::mem::replace(node, HydroNode::Placeholder);
+            *node = HydroNode::FlatMap {
+                f: DebugExpr(parse_quote!(#root::runtime_support::trace_context::scoped)),
+                input: Box::new(node_content),
+                metadata,
+            };
+
+            // when we emit this IR, the flat_map will bump the stmt id, so simulate that here
+            *next_stmt_id += 1;
+        }
+        HydroNode::NetworkOverflow { deserialize_fn, .. } => {
+            let deserialize = &deserialize_fn.0;
+            *deserialize_fn = DebugExpr(parse_quote! {
+                |data| (#deserialize)(#root::runtime_support::trace_context::strip(data))
+            });
+        }
+        HydroNode::CrossProduct { metadata, .. }
+        | HydroNode::CrossSingleton { metadata, .. }
+        | HydroNode::Join { metadata, .. }
+        | HydroNode::Difference { metadata, .. }
+        | HydroNode::AntiJoin { metadata, .. }
+        | HydroNode::Map { metadata, .. }
+        | HydroNode::FlatMap { metadata, .. }
+        | HydroNode::Filter { metadata, .. }
+        | HydroNode::FilterMap { metadata, .. }
+        | HydroNode::Unique { metadata, .. }
+        | HydroNode::Fold { metadata, .. }
+        | HydroNode::FoldKeyed { metadata, .. }
+        | HydroNode::Reduce { metadata, .. }
+        | HydroNode::ReduceKeyed { metadata, .. } => {
+            let metadata = metadata.clone();
+            let name = format!("{}: {}", next_stmt_id, node.print_root());
+            let node_content = std::mem::replace(node, HydroNode::Placeholder);
+
+            *node = HydroNode::Inspect {
+                f: DebugExpr(parse_quote! {
+                    |_| #root::runtime_support::trace_context::operator(#name)
+                }),
+                input: Box::new(node_content),
+                metadata,
+            };
+
+            // when we emit this IR, the inspect will bump the stmt id, so simulate that here
+            *next_stmt_id += 1;
+        }
+        HydroNode::Source { .. }
+        | HydroNode::CycleSource { .. }
+        | HydroNode::Tee { .. }
+        | HydroNode::Persist { .. }
+        | HydroNode::Unpersist { .. }
+        | HydroNode::Delta { .. }
+        | HydroNode::Chain { .. }
+        | HydroNode::Union { .. }
+        | HydroNode::ResolveFutures { .. }
+        | HydroNode::ResolveFuturesOrdered { .. }
+        | HydroNode::DeferTick { .. }
+        | HydroNode::Enumerate { .. }
+        | HydroNode::Inspect { .. }
+        | HydroNode::Sort { .. }
+        | HydroNode::Network { .. } => {} // Channels without serialization
+    }
+}
+
+/// Propagates a trace context with every message sent over a network channel, and records a
+/// span for each message sent and received and for each operator run on an item of a trace.
+/// See [`trace_context`](crate::runtime_support::trace_context) for how spans are attributed.
+///
+/// Each received message is handled within its trace until it has passed through the operators
+/// in the same subgraph as the channel. Channels without serialization (to or from external
+/// processes) are not traced.
+pub fn trace_network(ir: &mut [HydroLeaf]) {
+    traverse_dfir(
+        ir,
+        |_, _| {},
+        |node, next_stmt_id| {
+            trace_network_node(node, next_stmt_id);
+        },
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures::StreamExt;
+    use hydro_deploy::Deployment;
+    use hydro_deploy::trace_collector::SpanKind;
+    use stageleft::q;
+
+    use crate::FlowBuilder;
+    use crate::location::Location;
+
+    struct P1 {}
+    struct P2 {}
+
+    #[tokio::test]
+    async fn trace_links_spans_across_processes() {
+        let mut deployment = Deployment::new();
+
+        let flow = FlowBuilder::new();
+        let first_node = flow.process::<P1>();
+        let second_node = flow.process::<P2>();
+        let external = flow.external_process::<P2>();
+
+        let out_port = first_node
+            .source_iter(q!(0..3))
+            .send_bincode(&second_node)
+            .map(q!(|n: u32| n * 10))
+            .send_bincode_external(&external);
+
+        let nodes = flow
+            .optimize_with(super::trace_network)
+            .with_process(&first_node, deployment.Localhost())
+            .with_process(&second_node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let traces = nodes.collect_traces().await;
+        let mut external_out = nodes.connect_source_bincode(out_port).await;
+
+        deployment.start().await.unwrap();
+
+        for n in [0, 10, 20] {
+            assert_eq!(n, external_out.next().await.unwrap());
+        }
+
+        // Spans are printed when they close, so wait for the send, receive and `map` span of
+        // each message.
+        let spans = tokio::time::timeout(Duration::from_secs(30), async {
+            loop {
+                let spans = traces.spans();
+                if spans.len() >= 9 {
+                    break spans;
+                }
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        })
+        .await
+        .unwrap();
+        let of_kind = |kind| {
+            spans
+                .iter()
+                .filter(move |(_, span)| span.kind == kind)
+                .collect::<Vec<_>>()
+        };
+        let (sends, recvs, operators) = (
+            of_kind(SpanKind::Send),
+            of_kind(SpanKind::Recv),
+            of_kind(SpanKind::Operator),
+        );
+        assert_eq!((3, 3, 3), (sends.len(), recvs.len(), operators.len()));
+
+        // Each receive is a child of a send on the other process, in the same trace.
+        for (recv_process, recv) in recvs.iter() {
+            let (send_process, send) = sends
+                .iter()
+                .find(|(_, send)| Some(send.span_id) == recv.parent_span_id)
+                .unwrap();
+            assert_ne!(send_process, recv_process);
+            assert_eq!(send.trace_id, recv.trace_id);
+        }
+
+        // The `map` run on each received message is a child of its receive.
+        for (operator_process, operator) in operators.iter() {
+            let (recv_process, recv) = recvs
+                .iter()
+                .find(|(_, recv)| Some(recv.span_id) == operator.parent_span_id)
+                .unwrap();
+            assert_eq!(recv_process, operator_process);
+            assert_eq!(recv.trace_id, operator.trace_id);
+            assert!(operator.name.contains(": Map("), "{}", operator.name);
+        }
+    }
+}
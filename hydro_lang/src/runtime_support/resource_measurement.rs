@@ -9,16 +9,18 @@ use dfir_rs::scheduled::graph::Dfir;
 use procfs::WithCurrentSystemInfo;
 
 #[cfg(not(feature = "runtime_measure"))]
-pub async fn run(flow: Dfir<'_>) {
-    dfir_rs::util::deploy::launch_flow(flow).await;
+pub async fn run(flow: Dfir<'_>, on_command: impl FnMut(&str)) {
+    dfir_rs::util::deploy::launch_flow_with_commands(flow, on_command).await;
 }
 
 #[cfg(feature = "runtime_measure")]
-pub async fn run(flow: Dfir<'_>) {
+pub async fn run(flow: Dfir<'_>, on_command: impl FnMut(&str)) {
     // Make sure to print CPU even if we crash
-    let res = AssertUnwindSafe(dfir_rs::util::deploy::launch_flow(flow))
-        .catch_unwind()
-        .await;
+    let res = AssertUnwindSafe(dfir_rs::util::deploy::launch_flow_with_commands(
+        flow, on_command,
+    ))
+    .catch_unwind()
+    .await;
 
     #[cfg(target_os = "linux")]
     {
@@ -0,0 +1,402 @@
+//! Trace context propagation across network channels, used by the code inserted by the
+//! [`trace_network`](crate::rewrites::trace_network) rewrite.
+//!
+//! Every traced message carries a 16-byte header with its trace ID and the ID of its send span.
+//! While a received message is being handled (see [`scoped`]), its trace is the current trace of
+//! this thread, so messages sent and operators run while handling it become part of the same
+//! trace. Messages sent with no current trace start a new one.
+//!
+//! Spans are recorded as `tracing` spans with the [`TRACE_TARGET`] target. [`TraceLayer`] prints
+//! each of them to stdout when it closes, for Hydro Deploy to collect (see
+//! [`hydro_deploy_integration::trace`]).
+
+use std::cell::{Cell, RefCell};
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::sync::Once;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use dfir_rs::serde_json;
+use dfir_rs::tracing::field::{Field, Visit};
+use dfir_rs::tracing::span::{Attributes, Id};
+use dfir_rs::tracing::subscriber::Interest;
+use dfir_rs::tracing::{self, Metadata, Span, Subscriber};
+use hydro_deploy_integration::trace::{SpanKind, SpanRecord, TRACE_PREFIX};
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+
+/// The `tracing` target of trace spans.
+pub const TRACE_TARGET: &str = "hydro_trace";
+
+const HEADER_LEN: usize = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct TraceContext {
+    trace_id: u64,
+    span_id: u64,
+}
+
+/// The context and open receive span of a received message, until its handling starts.
+struct Received {
+    context: TraceContext,
+    /// Closed when the message has been handled.
+    _span: Span,
+}
+
+thread_local! {
+    static CURRENT: Cell<Option<TraceContext>> = const { Cell::new(None) };
+    static RECEIVED: RefCell<Option<Received>> = const { RefCell::new(None) };
+}
+
+/// A serialized message, possibly tagged with a cluster member ID.
+pub trait TracedPayload {
+    fn map_bytes(self, f: impl FnOnce(Bytes) -> Bytes) -> Self;
+}
+
+impl TracedPayload for Bytes {
+    fn map_bytes(self, f: impl FnOnce(Bytes) -> Bytes) -> Self {
+        f(self)
+    }
+}
+
+impl TracedPayload for (u32, Bytes) {
+    fn map_bytes(self, f: impl FnOnce(Bytes) -> Bytes) -> Self {
+        (self.0, f(self.1))
+    }
+}
+
+/// A received message, possibly tagged with a cluster member ID.
+pub trait TracedReceived {
+    fn map_bytes(self, f: impl FnOnce(BytesMut) -> BytesMut) -> Self;
+}
+
+impl<E> TracedReceived for Result<BytesMut, E> {
+    fn map_bytes(self, f: impl FnOnce(BytesMut) -> BytesMut) -> Self {
+        self.map(f)
+    }
+}
+
+impl<E> TracedReceived for Result<(u32, BytesMut), E> {
+    fn map_bytes(self, f: impl FnOnce(BytesMut) -> BytesMut) -> Self {
+        self.map(|(id, b)| (id, f(b)))
+    }
+}
+
+/// Records a send span for a message on `channel`, and prepends the trace header to it.
+pub fn send<P: TracedPayload>(channel: &str, payload: P) -> P {
+    install_layer();
+    let parent = CURRENT.get();
+    let context = TraceContext {
+        trace_id: parent.map_or_else(random_id, |parent| parent.trace_id),
+        span_id: random_id(),
+    };
+    let _span = trace_span(SpanKind::Send, channel, context, parent.map(|p| p.span_id));
+
+    payload.map_bytes(|bytes| {
+        let mut out = BytesMut::with_capacity(HEADER_LEN + bytes.len());
+        out.put_u64(context.trace_id);
+        out.put_u64(context.span_id);
+        out.extend_from_slice(&bytes);
+        out.freeze()
+    })
+}
+
+/// Strips the trace header from a message received on `channel`, opens its receive span, and
+/// deserializes it with `deserialize`. The message's trace becomes current once its handling
+/// starts in [`scoped`].
+///
+/// Messages too short to carry a trace header are passed through untraced.
+pub fn recv<R: TracedReceived, T>(
+    channel: &str,
+    received: R,
+    deserialize: impl FnOnce(R) -> T,
+) -> T {
+    install_layer();
+    deserialize(received.map_bytes(|mut bytes| {
+        if bytes.len() < HEADER_LEN {
+            return bytes;
+        }
+        let mut header = bytes.split_to(HEADER_LEN);
+        let trace_id = header.get_u64();
+        let send_span_id = header.get_u64();
+
+        let context = TraceContext {
+            trace_id,
+            span_id: random_id(),
+        };
+        let span = trace_span(SpanKind::Recv, channel, context, Some(send_span_id));
+        RECEIVED.set(Some(Received {
+            context,
+            _span: span,
+        }));
+        bytes
+    }))
+}
+
+/// Strips the trace header from a message which overflowed the buffer of its channel instead of
+/// being sent. Its send span has already been recorded.
+pub fn strip<P: TracedPayload>(payload: P) -> P {
+    payload.map_bytes(|bytes| {
+        if bytes.len() < HEADER_LEN {
+            bytes
+        } else {
+            bytes.slice(HEADER_LEN..)
+        }
+    })
+}
+
+/// Yields `item`, the message last passed to [`recv`], with its trace as the current trace until
+/// the item has been handled: the next call to [`Iterator::next`] (made by `flat_map` once the
+/// item has passed through the operators downstream) or dropping the iterator restores the
+/// previous trace and closes the receive span.
+pub fn scoped<T>(item: T) -> MessageScope<T> {
+    MessageScope {
+        item: Some(item),
+        received: RECEIVED.take(),
+        previous: None,
+    }
+}
+
+/// See [`scoped`].
+pub struct MessageScope<T> {
+    item: Option<T>,
+    received: Option<Received>,
+    /// The trace which was current before this message's, while it is being handled.
+    previous: Option<Option<TraceContext>>,
+}
+
+impl<T> MessageScope<T> {
+    fn end(&mut self) {
+        if let Some(previous) = self.previous.take() {
+            CURRENT.set(previous);
+        }
+        self.received = None;
+    }
+}
+
+impl<T> Iterator for MessageScope<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        let item = self.item.take();
+        if item.is_some() {
+            if let Some(received) = &self.received {
+                self.previous = Some(CURRENT.replace(Some(received.context)));
+            }
+        } else {
+            self.end();
+        }
+        item
+    }
+}
+
+impl<T> Drop for MessageScope<T> {
+    fn drop(&mut self) {
+        self.end();
+    }
+}
+
+/// Records an operator span if there is a current trace.
+pub fn operator(name: &str) {
+    if let Some(parent) = CURRENT.get() {
+        let context = TraceContext {
+            trace_id: parent.trace_id,
+            span_id: random_id(),
+        };
+        let _span = trace_span(SpanKind::Operator, name, context, Some(parent.span_id));
+    }
+}
+
+fn trace_span(
+    kind: SpanKind,
+    label: &str,
+    context: TraceContext,
+    parent_span_id: Option<u64>,
+) -> Span {
+    // The trace hierarchy is given by the span IDs, not by the `tracing` span stack.
+    tracing::info_span!(
+        target: TRACE_TARGET,
+        parent: None,
+        "trace",
+        kind = kind_name(kind),
+        label,
+        trace_id = context.trace_id,
+        span_id = context.span_id,
+        parent_span_id = parent_span_id.unwrap_or(0),
+    )
+}
+
+fn kind_name(kind: SpanKind) -> &'static str {
+    match kind {
+        SpanKind::Send => "send",
+        SpanKind::Recv => "recv",
+        SpanKind::Operator => "operator",
+    }
+}
+
+/// Installs [`TraceLayer`] as the global `tracing` subscriber, unless one is already installed.
+fn install_layer() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let _ = tracing::subscriber::set_global_default(
+            tracing_subscriber::registry().with(TraceLayer),
+        );
+    });
+}
+
+/// A `tracing` layer which prints each trace span to stdout, as a [`SpanRecord`] line prefixed
+/// with [`TRACE_PREFIX`], when the span closes. All other spans and events are disabled.
+///
+/// Traced programs install this layer as their global subscriber on their first traced message.
+/// A program which installs its own global subscriber must add this layer to it.
+pub struct TraceLayer;
+
+/// Extension of an open trace span, holding its record until the span closes.
+struct OpenSpan {
+    record: SpanRecord,
+    start: Instant,
+}
+
+impl<S> Layer<S> for TraceLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn register_callsite(&self, metadata: &'static Metadata<'static>) -> Interest {
+        if metadata.target() == TRACE_TARGET {
+            Interest::always()
+        } else {
+            Interest::never()
+        }
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>, _ctx: Context<'_, S>) -> bool {
+        metadata.target() == TRACE_TARGET
+    }
+
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut visitor = SpanVisitor::default();
+        attrs.record(&mut visitor);
+        let (Some(kind), Some(span)) = (visitor.kind, ctx.span(id)) else {
+            return;
+        };
+
+        let record = SpanRecord {
+            kind,
+            name: visitor.label,
+            trace_id: visitor.trace_id,
+            span_id: visitor.span_id,
+            parent_span_id: (0 != visitor.parent_span_id).then_some(visitor.parent_span_id),
+            start_us: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_micros() as u64,
+            duration_us: 0,
+        };
+        span.extensions_mut().insert(OpenSpan {
+            record,
+            start: Instant::now(),
+        });
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let Some(OpenSpan { mut record, start }) = span.extensions_mut().remove() else {
+            return;
+        };
+        record.duration_us = start.elapsed().as_micros() as u64;
+        println!(
+            "{}{}",
+            TRACE_PREFIX,
+            serde_json::to_string(&record).unwrap()
+        );
+    }
+}
+
+/// Reads the fields of a span created by [`trace_span`].
+#[derive(Default)]
+struct SpanVisitor {
+    kind: Option<SpanKind>,
+    label: String,
+    trace_id: u64,
+    span_id: u64,
+    parent_span_id: u64,
+}
+
+impl Visit for SpanVisitor {
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        match field.name() {
+            "trace_id" => self.trace_id = value,
+            "span_id" => self.span_id = value,
+            "parent_span_id" => self.parent_span_id = value,
+            _ => {}
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "kind" => {
+                self.kind = [SpanKind::Send, SpanKind::Recv, SpanKind::Operator]
+                    .into_iter()
+                    .find(|&kind| kind_name(kind) == value);
+            }
+            "label" => self.label = value.to_owned(),
+            _ => {}
+        }
+    }
+
+    fn record_debug(&mut self, _field: &Field, _value: &dyn std::fmt::Debug) {}
+}
+
+/// A random nonzero ID, unique within this process.
+fn random_id() -> u64 {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    RandomState::new().hash_one(COUNTER.fetch_add(1, Ordering::Relaxed)) | 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip_propagates_trace() {
+        CURRENT.set(None);
+        let sent = send("to b", (7, Bytes::from_static(b"hello")));
+        assert_eq!(7, sent.0);
+        assert_eq!(HEADER_LEN + 5, sent.1.len());
+        let trace_id = (&sent.1[..8]).get_u64();
+
+        let received = recv(
+            "from a",
+            Ok::<_, ()>((3, BytesMut::from(&sent.1[..]))),
+            |r| r,
+        );
+        assert_eq!(Ok((3, BytesMut::from(&b"hello"[..]))), received);
+
+        let mut scope = scoped(received.unwrap());
+        assert!(scope.next().is_some());
+        assert_eq!(Some(trace_id), CURRENT.get().map(|c| c.trace_id));
+
+        // A reply is part of the same trace.
+        let reply = send("to a", Bytes::from_static(b"hi"));
+        assert_eq!(trace_id, (&reply[..8]).get_u64());
+
+        // Once the message has been handled, its trace is no longer current.
+        assert!(scope.next().is_none());
+        assert_eq!(None, CURRENT.get());
+    }
+
+    #[test]
+    fn short_payload_is_untraced() {
+        CURRENT.set(None);
+        let received = recv("from a", Ok::<_, ()>(BytesMut::from(&b"short"[..])), |r| r);
+        assert_eq!(Ok(BytesMut::from(&b"short"[..])), received);
+
+        let mut scope = scoped(received.unwrap());
+        assert!(scope.next().is_some());
+        assert_eq!(None, CURRENT.get());
+    }
+}
@@ -0,0 +1,899 @@
+//! Runtime support for running Hydro programs in the deterministic simulator.
+//!
+//! Programs are compiled with [`crate::deploy::SimDeploy`], which backs every network edge with
+//! an in-memory queue owned by a [`SimNetwork`]. Every process and every cluster member is then
+//! instantiated in the same binary from a [`SimPorts`], and a [`Simulation`] (behind the `sim`
+//! feature) steps ticks and message deliveries in an order chosen by the test.
+//!
+//! The order in which messages from different senders arrive, and which of them land in the same
+//! tick, is exactly the nondeterminism that `NoOrder` streams and `tick_batch` expose. A
+//! [`SimExplorer`] runs a program under many such schedules, randomly or exhaustively up to a
+//! bound, and reports any two schedules that produce different outputs.
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::io;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll};
+
+use bytes::{Bytes, BytesMut};
+#[cfg(feature = "sim")]
+use dfir_rs::scheduled::graph::Dfir;
+use futures::{Sink, Stream, StreamExt};
+#[cfg(feature = "sim")]
+use rand::rngs::StdRng;
+#[cfg(feature = "sim")]
+use rand::{Rng, RngCore, SeedableRng};
+use stageleft::{QuotedWithContext, RuntimeData, q};
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel};
+use tokio_stream::wrappers::UnboundedReceiverStream;
+
+use crate::location::cluster::ClusterMembership;
+
+/// A single running instance of a location in the simulation: either a process, or one member of
+/// a cluster.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SimMember {
+    /// The raw ID of the process or cluster location.
+    pub location_id: usize,
+    /// The raw [`crate::ClusterId`] of the member, or `None` for a process.
+    pub cluster_id: Option<u32>,
+}
+
+/// A message that has been sent by one member but not yet delivered to another.
+#[derive(Debug, Clone)]
+pub struct SimMessage {
+    from: SimMember,
+    to: SimMember,
+    port: String,
+    payload: Bytes,
+}
+
+impl SimMessage {
+    /// The member that sent the message.
+    pub fn from(&self) -> SimMember {
+        self.from
+    }
+
+    /// The member the message is addressed to.
+    pub fn to(&self) -> SimMember {
+        self.to
+    }
+
+    /// The serialized contents of the message.
+    pub fn payload(&self) -> &Bytes {
+        &self.payload
+    }
+}
+
+struct Inbox {
+    sender: UnboundedSender<(Option<u32>, BytesMut)>,
+    receiver: Option<UnboundedReceiver<(Option<u32>, BytesMut)>>,
+}
+
+impl Default for Inbox {
+    fn default() -> Self {
+        let (sender, receiver) = unbounded_channel();
+        Inbox {
+            sender,
+            receiver: Some(receiver),
+        }
+    }
+}
+
+#[derive(Default)]
+struct SimNetworkState {
+    processes: BTreeSet<usize>,
+    clusters: BTreeMap<usize, Vec<u32>>,
+    memberships: BTreeMap<usize, ClusterMembership>,
+    in_flight: Vec<SimMessage>,
+    inboxes: HashMap<(SimMember, String), Inbox>,
+}
+
+/// The in-memory network connecting all the members of a simulated Hydro program. Messages sent by
+/// a member are held in flight until the test delivers or drops them.
+///
+/// Cloning a [`SimNetwork`] produces another handle to the same network.
+#[derive(Clone, Default)]
+pub struct SimNetwork {
+    state: Rc<RefCell<SimNetworkState>>,
+}
+
+impl SimNetwork {
+    /// Creates a network with an empty topology.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the process with the given raw location ID to the simulated topology.
+    pub fn with_process(self, location_id: usize) -> Self {
+        self.state.borrow_mut().processes.insert(location_id);
+        self
+    }
+
+    /// Adds the cluster with the given raw location ID and `size` members to the simulated
+    /// topology. Members are assigned the IDs `0..size`, and all of them are initially part of
+    /// the cluster's membership.
+    pub fn with_cluster(self, location_id: usize, size: usize) -> Self {
+        let mut state = self.state.borrow_mut();
+        state
+            .clusters
+            .insert(location_id, (0..size as u32).collect());
+        state
+            .memberships
+            .insert(location_id, ClusterMembership::new(0..size as u32));
+        drop(state);
+        self
+    }
+
+    /// Creates the [`SimPorts`] of every member in the topology, in a deterministic order. Each
+    /// [`SimPorts`] is passed to the compiled program to instantiate that member.
+    pub fn members(&self) -> Vec<SimPorts> {
+        let state = self.state.borrow();
+        let processes = state.processes.iter().map(|&location_id| SimMember {
+            location_id,
+            cluster_id: None,
+        });
+        let cluster_members = state.clusters.iter().flat_map(|(&location_id, ids)| {
+            ids.iter().map(move |&id| SimMember {
+                location_id,
+                cluster_id: Some(id),
+            })
+        });
+
+        let clusters = state
+            .clusters
+            .iter()
+            .map(|(&k, v)| (k, v.clone()))
+            .collect::<HashMap<_, _>>();
+        let memberships = state
+            .memberships
+            .iter()
+            .map(|(&k, v)| (k, v.clone()))
+            .collect::<HashMap<_, _>>();
+        processes
+            .chain(cluster_members)
+            .map(|member| SimPorts {
+                network: self.clone(),
+                member,
+                clusters: clusters.clone(),
+                memberships: memberships.clone(),
+                no_members: ClusterMembership::default(),
+            })
+            .collect()
+    }
+
+    /// Returns a snapshot of the messages currently in flight, in the order they were sent.
+    pub fn in_flight(&self) -> Vec<SimMessage> {
+        self.state.borrow().in_flight.clone()
+    }
+
+    /// The number of messages currently in flight.
+    pub fn in_flight_len(&self) -> usize {
+        self.state.borrow().in_flight.len()
+    }
+
+    /// The indices of the in-flight messages that can be delivered next. Each channel between a
+    /// pair of members is FIFO, like the TCP connections used in a real deployment, so only the
+    /// oldest in-flight message of each channel is deliverable.
+    pub fn deliverable(&self) -> Vec<usize> {
+        let state = self.state.borrow();
+        let mut seen = HashSet::new();
+        state
+            .in_flight
+            .iter()
+            .enumerate()
+            .filter(|(_, message)| seen.insert((message.from, message.to, &message.port)))
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// Delivers the in-flight message at `index` to its destination, which is returned.
+    pub fn deliver(&self, index: usize) -> SimMember {
+        let mut state = self.state.borrow_mut();
+        let message = state.in_flight.remove(index);
+        let to = message.to;
+        let inbox = state.inboxes.entry((to, message.port)).or_default();
+        // The receiver may have been dropped if the member's program was dropped.
+        let _ = inbox.sender.send((
+            message.from.cluster_id,
+            BytesMut::from(&message.payload[..]),
+        ));
+        to
+    }
+
+    /// Drops the in-flight message at `index` without delivering it.
+    pub fn drop_message(&self, index: usize) -> SimMessage {
+        self.state.borrow_mut().in_flight.remove(index)
+    }
+
+    #[cfg(feature = "sim")]
+    fn membership(&self, member: SimMember) -> ClusterMembership {
+        let state = self.state.borrow();
+        let membership = state
+            .memberships
+            .get(&member.location_id)
+            .unwrap_or_else(|| panic!("{:?} is not a member of a cluster", member));
+        assert!(
+            state.clusters[&member.location_id].contains(&member.cluster_id.unwrap()),
+            "{:?} was not provisioned in its cluster",
+            member
+        );
+        membership.clone()
+    }
+
+    fn send(&self, from: SimMember, to: SimMember, port: &str, payload: Bytes) {
+        self.state.borrow_mut().in_flight.push(SimMessage {
+            from,
+            to,
+            port: port.to_string(),
+            payload,
+        });
+    }
+
+    fn take_inbox(
+        &self,
+        member: SimMember,
+        port: &str,
+    ) -> UnboundedReceiver<(Option<u32>, BytesMut)> {
+        self.state
+            .borrow_mut()
+            .inboxes
+            .entry((member, port.to_string()))
+            .or_default()
+            .receiver
+            .take()
+            .unwrap_or_else(|| panic!("Port {} of {:?} was connected twice", port, member))
+    }
+}
+
+type SimSource<T> = Pin<Box<dyn Stream<Item = Result<T, io::Error>>>>;
+
+/// The sending side of a simulated network channel. Elements are put in flight as soon as they
+/// are sent, so everything a member sends during a tick is in flight once the tick ends.
+pub struct SimSink<T> {
+    send: Box<dyn FnMut(T)>,
+}
+
+impl<T> SimSink<T> {
+    fn new(send: impl FnMut(T) + 'static) -> Self {
+        SimSink {
+            send: Box::new(send),
+        }
+    }
+
+    /// Puts `item` in flight.
+    pub fn send_now(&mut self, item: T) {
+        (self.send)(item)
+    }
+}
+
+impl<T> Sink<T> for SimSink<T> {
+    type Error = io::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: T) -> io::Result<()> {
+        self.get_mut().send_now(item);
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// The view of the [`SimNetwork`] from a single member. The compiled program uses it to connect
+/// its network sinks and sources.
+pub struct SimPorts {
+    network: SimNetwork,
+    member: SimMember,
+    clusters: HashMap<usize, Vec<u32>>,
+    memberships: HashMap<usize, ClusterMembership>,
+    no_members: ClusterMembership,
+}
+
+impl SimPorts {
+    /// The member this [`SimPorts`] belongs to.
+    pub fn member(&self) -> SimMember {
+        self.member
+    }
+
+    /// The raw location ID of the member, used to select its graph from the compiled program.
+    pub fn location_id(&self) -> usize {
+        self.member.location_id
+    }
+
+    /// The IDs the members of cluster `of_cluster` were provisioned with.
+    pub fn cluster_members(&self, of_cluster: usize) -> &[u32] {
+        // we default to empty slice because this is the scenario where the cluster is unused in the graph
+        self.clusters
+            .get(&of_cluster)
+            .map(|v| v.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// The live membership of cluster `of_cluster`, which changes as members join and leave.
+    pub fn cluster_membership(&self, of_cluster: usize) -> &ClusterMembership {
+        // we default to no members because this is the scenario where the cluster is unused in the graph
+        self.memberships
+            .get(&of_cluster)
+            .unwrap_or(&self.no_members)
+    }
+
+    /// The raw ID of this member within its cluster.
+    ///
+    /// # Panics
+    /// Panics if this member is a process.
+    pub fn cluster_self_id(&self) -> u32 {
+        self.member
+            .cluster_id
+            .expect("Tried to read Cluster ID on a non-cluster node")
+    }
+
+    /// A sink sending messages to `port` of the process `to_location`.
+    pub fn process_sink(&self, to_location: usize, port: &str) -> SimSink<Bytes> {
+        let network = self.network.clone();
+        let from = self.member;
+        let to = SimMember {
+            location_id: to_location,
+            cluster_id: None,
+        };
+        let port = port.to_string();
+        SimSink::new(move |payload| network.send(from, to, &port, payload))
+    }
+
+    /// A sink sending `(member_id, message)` pairs to `port` of the given members of the cluster
+    /// `to_location`.
+    pub fn cluster_sink(&self, to_location: usize, port: &str) -> SimSink<(u32, Bytes)> {
+        let network = self.network.clone();
+        let from = self.member;
+        let port = port.to_string();
+        SimSink::new(move |(id, payload)| {
+            let to = SimMember {
+                location_id: to_location,
+                cluster_id: Some(id),
+            };
+            network.send(from, to, &port, payload)
+        })
+    }
+
+    /// The messages delivered to `port` of this member.
+    ///
+    /// # Panics
+    /// Panics if the port was already taken.
+    pub fn source(&self, port: &str) -> SimSource<BytesMut> {
+        Box::pin(
+            UnboundedReceiverStream::new(self.network.take_inbox(self.member, port))
+                .map(|(_, payload)| Ok(payload)),
+        )
+    }
+
+    /// The messages delivered to `port` of this member, tagged with the ID of the cluster member
+    /// which sent them.
+    ///
+    /// # Panics
+    /// Panics if the port was already taken.
+    pub fn tagged_source(&self, port: &str) -> SimSource<(u32, BytesMut)> {
+        Box::pin(
+            UnboundedReceiverStream::new(self.network.take_inbox(self.member, port)).map(
+                |(from, payload)| {
+                    Ok((
+                        from.expect("Tagged message was not sent by a cluster member"),
+                        payload,
+                    ))
+                },
+            ),
+        )
+    }
+}
+
+/// The IDs of the members of cluster `of_cluster`, see [`SimPorts::cluster_members`].
+pub fn sim_cluster_members(
+    env: RuntimeData<&SimPorts>,
+    of_cluster: usize,
+) -> impl QuotedWithContext<&[u32], ()> + Copy {
+    q!(env.cluster_members(of_cluster))
+}
+
+/// The ID of the current member, see [`SimPorts::cluster_self_id`].
+pub fn sim_cluster_self_id(env: RuntimeData<&SimPorts>) -> impl QuotedWithContext<u32, ()> + Copy {
+    q!(env.cluster_self_id())
+}
+
+/// The membership of cluster `of_cluster`, see [`SimPorts::cluster_membership`].
+pub fn sim_cluster_membership(
+    env: RuntimeData<&SimPorts>,
+    of_cluster: usize,
+) -> impl QuotedWithContext<&ClusterMembership, ()> + Copy {
+    q!(env.cluster_membership(of_cluster))
+}
+
+/// The sink and source expressions of a channel from a process to process `p2_id`.
+pub fn sim_o2o(env: RuntimeData<&SimPorts>, p2_id: usize, p2_port: &str) -> (syn::Expr, syn::Expr) {
+    (
+        q!(env.process_sink(p2_id, p2_port)).splice_untyped_ctx(&()),
+        q!(env.source(p2_port)).splice_untyped_ctx(&()),
+    )
+}
+
+/// The sink and source expressions of a channel from a process to cluster `c2_id`.
+pub fn sim_o2m(env: RuntimeData<&SimPorts>, c2_id: usize, c2_port: &str) -> (syn::Expr, syn::Expr) {
+    (
+        q!(env.cluster_sink(c2_id, c2_port)).splice_untyped_ctx(&()),
+        q!(env.source(c2_port)).splice_untyped_ctx(&()),
+    )
+}
+
+/// The sink and source expressions of a channel from a cluster to process `p2_id`.
+pub fn sim_m2o(env: RuntimeData<&SimPorts>, p2_id: usize, p2_port: &str) -> (syn::Expr, syn::Expr) {
+    (
+        q!(env.process_sink(p2_id, p2_port)).splice_untyped_ctx(&()),
+        q!(env.tagged_source(p2_port)).splice_untyped_ctx(&()),
+    )
+}
+
+/// The sink and source expressions of a channel from a cluster to cluster `c2_id`.
+pub fn sim_m2m(env: RuntimeData<&SimPorts>, c2_id: usize, c2_port: &str) -> (syn::Expr, syn::Expr) {
+    (
+        q!(env.cluster_sink(c2_id, c2_port)).splice_untyped_ctx(&()),
+        q!(env.tagged_source(c2_port)).splice_untyped_ctx(&()),
+    )
+}
+
+#[cfg(feature = "sim")]
+#[cfg_attr(docsrs, doc(cfg(feature = "sim")))]
+/// A single scheduling decision made by a [`Simulation`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SimStep {
+    /// Run one tick on the member.
+    Tick(SimMember),
+    /// Deliver the in-flight message at the given index.
+    Deliver(usize),
+    /// Drop the in-flight message at the given index.
+    Drop(usize),
+}
+
+#[cfg(feature = "sim")]
+#[cfg_attr(docsrs, doc(cfg(feature = "sim")))]
+/// Runs every member of a Hydro program in a single-threaded scheduler. All interleavings of
+/// ticks and message deliveries are chosen by the test, either explicitly or by a random
+/// scheduler seeded at construction, so a failing schedule can be replayed from its seed.
+///
+/// Must be driven from within a Tokio `LocalSet`, since the network sinks of each member run as
+/// local tasks.
+pub struct Simulation<'a> {
+    network: SimNetwork,
+    members: BTreeMap<SimMember, Dfir<'a>>,
+    /// Members that have not run a tick yet, or have received a message since their last tick.
+    pending: BTreeSet<SimMember>,
+    rng: StdRng,
+    drop_rate: f64,
+}
+
+#[cfg(feature = "sim")]
+impl<'a> Simulation<'a> {
+    /// Creates a simulation over `network`, whose random scheduler is seeded with `seed`.
+    pub fn new(network: &SimNetwork, seed: u64) -> Self {
+        Simulation {
+            network: network.clone(),
+            members: BTreeMap::new(),
+            pending: BTreeSet::new(),
+            rng: StdRng::seed_from_u64(seed),
+            drop_rate: 0.0,
+        }
+    }
+
+    /// Sets the probability, in `[0, 1]`, that the random scheduler drops a message instead of
+    /// delivering it.
+    pub fn with_drop_rate(mut self, drop_rate: f64) -> Self {
+        assert!(
+            (0.0..=1.0).contains(&drop_rate),
+            "Drop rate must be between 0 and 1"
+        );
+        self.drop_rate = drop_rate;
+        self
+    }
+
+    /// Adds the instantiated program of the member owning `ports`.
+    pub fn add_member(&mut self, ports: &SimPorts, dfir: Dfir<'a>) {
+        assert!(
+            self.members.insert(ports.member(), dfir).is_none(),
+            "Member {:?} was added twice",
+            ports.member()
+        );
+        self.pending.insert(ports.member());
+    }
+
+    /// All the members of the simulation, in a deterministic order.
+    pub fn members(&self) -> impl Iterator<Item = SimMember> + '_ {
+        self.members.keys().copied()
+    }
+
+    /// The network connecting the members.
+    pub fn network(&self) -> &SimNetwork {
+        &self.network
+    }
+
+    /// The scheduling decisions that can usefully be made next, in a deterministic order: ticking
+    /// a member that has not run yet or has received a message since its last tick, or delivering
+    /// the oldest in-flight message of a channel (see [`SimNetwork::deliverable`]). Empty once the
+    /// simulation is quiescent.
+    ///
+    /// Work that a member defers to later ticks without receiving a message (for example through
+    /// `defer_tick`) is not tracked, so such ticks must be stepped explicitly.
+    pub fn enabled_steps(&self) -> Vec<SimStep> {
+        self.pending
+            .iter()
+            .map(|&member| SimStep::Tick(member))
+            .chain(self.network.deliverable().into_iter().map(SimStep::Deliver))
+            .collect()
+    }
+
+    /// Adds `member` to the membership of its cluster. Returns false if it was already a member.
+    /// Every member may observe the change, so all of them become eligible to tick.
+    ///
+    /// Only members the cluster was created with can join, since every member's view of the
+    /// cluster's IDs is fixed when its [`SimPorts`] are created.
+    pub fn join(&mut self, member: SimMember) -> bool {
+        let changed = self
+            .network
+            .membership(member)
+            .join(member.cluster_id.unwrap());
+        self.pending.extend(self.members.keys().copied());
+        changed
+    }
+
+    /// Removes `member` from the membership of its cluster. Returns false if it was not a member.
+    /// Every member may observe the change, so all of them become eligible to tick.
+    pub fn leave(&mut self, member: SimMember) -> bool {
+        let changed = self
+            .network
+            .membership(member)
+            .leave(member.cluster_id.unwrap());
+        self.pending.extend(self.members.keys().copied());
+        changed
+    }
+
+    /// Runs a single tick on `member`. Returns true if any work was done.
+    pub async fn tick(&mut self, member: SimMember) -> bool {
+        let dfir = self
+            .members
+            .get_mut(&member)
+            .unwrap_or_else(|| panic!("No member {:?} in the simulation", member));
+        let work_done = dfir.run_tick();
+
+        // Unbuffered channels send synchronously (see `SimDeploy::sync_network_sink`), so their
+        // messages are already in flight. Buffered channels are drained by a task which is woken
+        // by every push, and which empties the buffer in a single poll since a `SimSink` is
+        // always ready. Yielding once lets every such task run before this one resumes.
+        tokio::task::yield_now().await;
+
+        self.pending.remove(&member);
+        work_done
+    }
+
+    /// Runs a single tick on every member, in order. Returns true if any work was done.
+    pub async fn tick_all(&mut self) -> bool {
+        let mut work_done = false;
+        for member in self.members.keys().copied().collect::<Vec<_>>() {
+            work_done |= self.tick(member).await;
+        }
+        work_done
+    }
+
+    /// Delivers every message currently in flight, in the order they were sent. Returns true
+    /// if any message was delivered.
+    pub fn deliver_all(&mut self) -> bool {
+        let count = self.network.in_flight_len();
+        for _ in 0..count {
+            self.deliver(0);
+        }
+        count > 0
+    }
+
+    fn deliver(&mut self, index: usize) {
+        let to = self.network.deliver(index);
+        if self.members.contains_key(&to) {
+            self.pending.insert(to);
+        }
+    }
+
+    /// Executes a single scheduling decision.
+    pub async fn step(&mut self, step: SimStep) {
+        match step {
+            SimStep::Tick(member) => {
+                self.tick(member).await;
+            }
+            SimStep::Deliver(index) => self.deliver(index),
+            SimStep::Drop(index) => {
+                self.network.drop_message(index);
+            }
+        }
+    }
+
+    /// Makes up to `steps` random scheduling decisions, stopping early if the simulation becomes
+    /// quiescent. At each step, the scheduler uniformly picks one of the [`Self::enabled_steps`],
+    /// dropping rather than delivering a message with the configured drop rate. Returns the
+    /// decisions that were made, which can be re-executed with [`Self::replay`].
+    pub async fn run_random(&mut self, steps: usize) -> Vec<SimStep> {
+        let mut trace = Vec::with_capacity(steps);
+        for _ in 0..steps {
+            let enabled = self.enabled_steps();
+            if enabled.is_empty() {
+                break;
+            }
+
+            let step = match enabled[self.rng.gen_range(0..enabled.len())] {
+                SimStep::Deliver(index) if self.rng.gen_bool(self.drop_rate) => {
+                    SimStep::Drop(index)
+                }
+                step => step,
+            };
+            self.step(step).await;
+            trace.push(step);
+        }
+        trace
+    }
+
+    /// Re-executes scheduling decisions recorded by [`Self::run_random`] or a [`SimSchedule`].
+    pub async fn replay(&mut self, trace: &[SimStep]) {
+        for &step in trace {
+            self.step(step).await;
+        }
+    }
+
+    /// Makes the scheduling decisions chosen by `schedule`, then runs the simulation to
+    /// quiescence with [`Self::run_until_quiescent`].
+    pub async fn run_schedule(&mut self, schedule: &mut SimSchedule) {
+        while schedule.trace.len() < schedule.max_steps {
+            let enabled = self.enabled_steps();
+            if enabled.is_empty() {
+                break;
+            }
+
+            let step = enabled[schedule.choose(enabled.len())];
+            self.step(step).await;
+            schedule.trace.push(step);
+        }
+
+        self.run_until_quiescent().await;
+    }
+
+    /// Alternates between ticking members and delivering every in-flight message until no
+    /// messages are in flight and every member has run a tick since it last received one.
+    pub async fn run_until_quiescent(&mut self) {
+        loop {
+            for member in self.pending.iter().copied().collect::<Vec<_>>() {
+                self.tick(member).await;
+            }
+
+            if !self.deliver_all() && self.pending.is_empty() {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(feature = "sim")]
+#[cfg_attr(docsrs, doc(cfg(feature = "sim")))]
+/// Picks which of the [`Simulation::enabled_steps`] to take at each point of a run of
+/// [`Simulation::run_schedule`], and records the decisions that were made.
+pub struct SimSchedule {
+    prefix: Vec<usize>,
+    rng: Option<StdRng>,
+    max_steps: usize,
+    /// For each decision, the index of the chosen step and the number of enabled steps.
+    choices: Vec<(usize, usize)>,
+    trace: Vec<SimStep>,
+}
+
+#[cfg(feature = "sim")]
+impl SimSchedule {
+    /// A schedule that always takes the first enabled step, for at most `max_steps` steps.
+    pub fn new(max_steps: usize) -> Self {
+        Self::with_prefix(Vec::new(), max_steps)
+    }
+
+    /// A schedule that takes uniformly random enabled steps, for at most `max_steps` steps.
+    pub fn random(seed: u64, max_steps: usize) -> Self {
+        SimSchedule {
+            rng: Some(StdRng::seed_from_u64(seed)),
+            ..Self::new(max_steps)
+        }
+    }
+
+    fn with_prefix(prefix: Vec<usize>, max_steps: usize) -> Self {
+        SimSchedule {
+            prefix,
+            rng: None,
+            max_steps,
+            choices: Vec::new(),
+            trace: Vec::new(),
+        }
+    }
+
+    /// The scheduling decisions made so far, which can be re-executed with
+    /// [`Simulation::replay`].
+    pub fn trace(&self) -> &[SimStep] {
+        &self.trace
+    }
+
+    fn choose(&mut self, enabled: usize) -> usize {
+        let choice = if let Some(&choice) = self.prefix.get(self.choices.len()) {
+            assert!(
+                choice < enabled,
+                "The simulation made different steps available when replaying a schedule; is the program nondeterministic outside of the simulator's control?"
+            );
+            choice
+        } else if let Some(rng) = &mut self.rng {
+            rng.gen_range(0..enabled)
+        } else {
+            0
+        };
+
+        self.choices.push((choice, enabled));
+        choice
+    }
+}
+
+#[cfg(feature = "sim")]
+#[cfg_attr(docsrs, doc(cfg(feature = "sim")))]
+/// Checks that the output of a Hydro program does not depend on the nondeterminism introduced by
+/// unordered network receives and tick batching, by running it under many schedules of the
+/// [`Simulation`] and comparing the outputs.
+///
+/// Each run is performed by a test-provided closure, which must instantiate a fresh
+/// [`Simulation`], drive it with [`Simulation::run_schedule`] using the given [`SimSchedule`], and
+/// return the outputs to compare.
+pub struct SimExplorer {
+    max_steps: usize,
+    max_schedules: usize,
+}
+
+#[cfg(feature = "sim")]
+impl Default for SimExplorer {
+    fn default() -> Self {
+        SimExplorer {
+            max_steps: 100,
+            max_schedules: 1000,
+        }
+    }
+}
+
+#[cfg(feature = "sim")]
+#[cfg_attr(docsrs, doc(cfg(feature = "sim")))]
+/// Summary of a [`SimExplorer`] run that found no divergent schedules.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SimExploration {
+    /// The number of schedules that were run.
+    pub schedules: usize,
+    /// Whether every schedule (up to the step bound) was explored.
+    pub complete: bool,
+}
+
+#[cfg(feature = "sim")]
+#[cfg_attr(docsrs, doc(cfg(feature = "sim")))]
+/// Two schedules under which a program produced different outputs. Either trace can be
+/// re-executed with [`Simulation::replay`] followed by [`Simulation::run_until_quiescent`].
+#[derive(Debug, Clone)]
+pub struct SimDivergence<O> {
+    /// The output of the first schedule that was run.
+    pub expected: O,
+    /// The decisions made by the first schedule that was run.
+    pub expected_trace: Vec<SimStep>,
+    /// The output of the schedule that diverged.
+    pub found: O,
+    /// The decisions made by the schedule that diverged.
+    pub found_trace: Vec<SimStep>,
+}
+
+#[cfg(feature = "sim")]
+impl SimExplorer {
+    /// Creates an explorer with the default bounds.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the maximum number of scheduling decisions made in each run, after which the
+    /// simulation is run to quiescence in a fixed order.
+    pub fn max_steps(mut self, max_steps: usize) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    /// Sets the maximum number of schedules to run.
+    pub fn max_schedules(mut self, max_schedules: usize) -> Self {
+        self.max_schedules = max_schedules;
+        self
+    }
+
+    /// Runs the program under `max_schedules` random schedules derived from `seed`.
+    pub async fn explore_random<O: PartialEq>(
+        &self,
+        seed: u64,
+        mut run: impl AsyncFnMut(&mut SimSchedule) -> O,
+    ) -> Result<SimExploration, SimDivergence<O>> {
+        let mut seeds = StdRng::seed_from_u64(seed);
+        let mut expected = None;
+        for _ in 0..self.max_schedules {
+            let mut schedule = SimSchedule::random(seeds.next_u64(), self.max_steps);
+            let found = run(&mut schedule).await;
+            check_output(&mut expected, found, schedule.trace)?;
+        }
+
+        Ok(SimExploration {
+            schedules: self.max_schedules,
+            complete: false,
+        })
+    }
+
+    /// Enumerates schedules in depth-first order, re-running the program from scratch for each
+    /// one, until every schedule has been explored or `max_schedules` is reached.
+    pub async fn explore_exhaustive<O: PartialEq>(
+        &self,
+        mut run: impl AsyncFnMut(&mut SimSchedule) -> O,
+    ) -> Result<SimExploration, SimDivergence<O>> {
+        let mut prefix = Vec::new();
+        let mut expected = None;
+        for schedules in 1..=self.max_schedules {
+            let mut schedule = SimSchedule::with_prefix(prefix, self.max_steps);
+            let found = run(&mut schedule).await;
+            let mut choices = schedule.choices;
+            check_output(&mut expected, found, schedule.trace)?;
+
+            // Backtrack to the latest decision that still has unexplored alternatives.
+            while let Some((choice, enabled)) = choices.pop() {
+                if choice + 1 < enabled {
+                    choices.push((choice + 1, enabled));
+                    break;
+                }
+            }
+
+            if choices.is_empty() {
+                return Ok(SimExploration {
+                    schedules,
+                    complete: true,
+                });
+            }
+
+            prefix = choices.into_iter().map(|(choice, _)| choice).collect();
+        }
+
+        Ok(SimExploration {
+            schedules: self.max_schedules,
+            complete: false,
+        })
+    }
+}
+
+#[cfg(feature = "sim")]
+fn check_output<O: PartialEq>(
+    expected: &mut Option<(O, Vec<SimStep>)>,
+    found: O,
+    found_trace: Vec<SimStep>,
+) -> Result<(), SimDivergence<O>> {
+    match expected.take() {
+        Some((expected_output, expected_trace)) if expected_output != found => Err(SimDivergence {
+            expected: expected_output,
+            expected_trace,
+            found,
+            found_trace,
+        }),
+        Some(first) => {
+            *expected = Some(first);
+            Ok(())
+        }
+        None => {
+            *expected = Some((found, found_trace));
+            Ok(())
+        }
+    }
+}
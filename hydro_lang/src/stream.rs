@@ -14,14 +14,14 @@ use tokio::time::Instant;
 
 use crate::builder::FLOW_USED_MESSAGE;
 use crate::cycle::{CycleCollection, CycleComplete, DeferTick, ForwardRefMarker, TickCycleMarker};
-use crate::ir::{DebugInstantiate, HydroLeaf, HydroNode, TeeNode};
+use crate::ir::{DebugInstantiate, HydroLeaf, HydroNetworkBuffer, HydroNode, TeeNode};
 use crate::location::external_process::{ExternalBincodeStream, ExternalBytesPort};
 use crate::location::tick::{Atomic, NoAtomic};
 use crate::location::{
     CanSend, ExternalProcess, Location, LocationId, NoTick, Tick, check_matching_location,
 };
 use crate::staging_util::get_this_crate;
-use crate::{Bounded, Cluster, ClusterId, Optional, Singleton, Unbounded};
+use crate::{Bounded, Cluster, ClusterId, NetworkBuffer, Optional, Singleton, Unbounded};
 
 /// Marks the stream as being totally ordered, which means that there are
 /// no sources of non-determinism (other than intentional ones) that will
@@ -67,7 +67,7 @@ impl MinOrder<TotalOrder> for NoOrder {
 /// - `Order`: the ordering of the stream, which is either [`TotalOrder`]
 ///   or [`NoOrder`] (default is [`TotalOrder`])
 pub struct Stream<T, L, B, Order = TotalOrder> {
-    location: L,
+    pub(crate) location: L,
     pub(crate) ir_node: RefCell<HydroNode>,
 
     _phantom: PhantomData<(T, L, B, Order)>,
@@ -147,6 +147,30 @@ impl<'a, T, L: Location<'a>, Order> CycleComplete<'a, TickCycleMarker>
     }
 }
 
+impl<'a, T, L: Location<'a>, Order> CycleComplete<'a, ForwardRefMarker>
+    for Stream<T, Tick<L>, Bounded, Order>
+{
+    fn complete(self, ident: syn::Ident, expected_location: LocationId) {
+        assert_eq!(
+            self.location.id(),
+            expected_location,
+            "locations do not match"
+        );
+        self.location
+            .flow_state()
+            .borrow_mut()
+            .leaves
+            .as_mut()
+            .expect(FLOW_USED_MESSAGE)
+            .push(HydroLeaf::CycleSink {
+                ident,
+                location_kind: self.location_kind(),
+                input: Box::new(self.ir_node.into_inner()),
+                metadata: self.location.new_node_metadata::<T>(),
+            });
+    }
+}
+
 impl<'a, T, L: Location<'a> + NoTick, B, Order> CycleCollection<'a, ForwardRefMarker>
     for Stream<T, L, B, Order>
 {
@@ -1774,6 +1798,47 @@ impl<'a, T, L: Location<'a> + NoTick, B, Order> Stream<T, L, B, Order> {
 }
 
 impl<'a, T, L: Location<'a>, Order> Stream<T, Tick<L>, Bounded, Order> {
+    /// Produces a new stream that interleaves the elements of the two input streams
+    /// within the current tick. Unlike [`Stream::chain`], this does not wait for all
+    /// the elements of the first stream, so it may be used within a cycle created by
+    /// [`Tick::forward_ref`]. The result has [`NoOrder`] because the order of elements
+    /// from the two inputs is nondeterministic.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// let tick = process.tick();
+    /// let numbers = process.source_iter(q!(vec![1, 2, 3, 4]));
+    /// let batch = unsafe { numbers.tick_batch(&tick) };
+    /// batch.clone().map(q!(|x| x + 1)).union(batch).all_ticks()
+    /// # }, |mut stream| async move {
+    /// // 2, 3, 4, 5, and 1, 2, 3, 4 interleaved in unknown order
+    /// # let mut results = Vec::new();
+    /// # for _ in 0..8 {
+    /// #     results.push(stream.next().await.unwrap());
+    /// # }
+    /// # results.sort();
+    /// # assert_eq!(results, vec![1, 2, 2, 3, 3, 4, 4, 5]);
+    /// # }));
+    /// ```
+    pub fn union<O2>(
+        self,
+        other: Stream<T, Tick<L>, Bounded, O2>,
+    ) -> Stream<T, Tick<L>, Bounded, NoOrder> {
+        check_matching_location(&self.location, &other.location);
+
+        Stream::new(
+            self.location.clone(),
+            HydroNode::Union {
+                first: Box::new(self.ir_node.into_inner()),
+                second: Box::new(other.ir_node.into_inner()),
+                metadata: self.location.new_node_metadata::<T>(),
+            },
+        )
+    }
+
     pub fn all_ticks(self) -> Stream<T, L, Unbounded, Order> {
         Stream::new(
             self.location.outer().clone(),
@@ -1879,6 +1944,31 @@ pub(super) fn deserialize_bincode<T: DeserializeOwned>(tagged: Option<&syn::Type
     deserialize_bincode_with_type(tagged, &stageleft::quote_type::<T>())
 }
 
+/// Turns the serialized elements dropped by a network buffer back into the elements that were
+/// sent, which are tagged with their destination for demux channels.
+fn deserialize_overflow<T>(is_demux: bool) -> syn::Expr {
+    let root = get_this_crate();
+    let t_type = stageleft::quote_type::<T>();
+
+    if is_demux {
+        parse_quote! {
+            ::#root::runtime_support::stageleft::runtime_support::fn1_type_hint::<(u32, #root::runtime_support::dfir_rs::bytes::Bytes), #t_type>(
+                |(id, b)| {
+                    (#root::ClusterId::from_raw(id), #root::runtime_support::bincode::deserialize(&b).unwrap())
+                }
+            )
+        }
+    } else {
+        parse_quote! {
+            ::#root::runtime_support::stageleft::runtime_support::fn1_type_hint::<#root::runtime_support::dfir_rs::bytes::Bytes, #t_type>(
+                |b| {
+                    #root::runtime_support::bincode::deserialize(&b).unwrap()
+                }
+            )
+        }
+    }
+}
+
 impl<'a, T, L: Location<'a> + NoTick, B, Order> Stream<T, L, B, Order> {
     pub fn send_bincode<L2: Location<'a>, CoreType>(
         self,
@@ -1904,9 +1994,94 @@ impl<'a, T, L: Location<'a> + NoTick, B, Order> Stream<T, L, B, Order> {
                 serialize_fn: serialize_pipeline.map(|e| e.into()),
                 instantiate_fn: DebugInstantiate::Building,
                 deserialize_fn: deserialize_pipeline.map(|e| e.into()),
+                buffer: None,
+                input: Box::new(self.ir_node.into_inner()),
+                metadata: other.new_node_metadata::<CoreType>(),
+            },
+        )
+    }
+
+    /// Like [`Stream::send_bincode`], but queues elements for the network in a bounded buffer
+    /// configured by `buffer` rather than an unbounded one, so that a slow receiver cannot make
+    /// the sender's memory grow without limit.
+    ///
+    /// Returns the received stream along with an overflow stream on the sending location, which
+    /// contains the elements dropped by the buffer's [`Overflow`](crate::network_buffer::Overflow)
+    /// policy. With [`Overflow::Block`](crate::network_buffer::Overflow::Block), the sender's tick
+    /// waits for the buffer instead (on multi-threaded runtimes) and the overflow stream is always
+    /// empty.
+    #[expect(clippy::type_complexity, reason = "ordering semantics for send")]
+    pub fn send_bincode_buffered<L2: Location<'a>, CoreType>(
+        self,
+        other: &L2,
+        buffer: NetworkBuffer,
+    ) -> (
+        Stream<<L::Root as CanSend<'a, L2>>::Out<CoreType>, L2, Unbounded, Order::Min>,
+        Stream<T, L, Unbounded, Order>,
+    )
+    where
+        L::Root: CanSend<'a, L2, In<CoreType> = T>,
+        CoreType: Serialize + DeserializeOwned,
+        Order: MinOrder<<L::Root as CanSend<'a, L2>>::OutStrongestOrder<Order>>,
+    {
+        let serialize_pipeline = Some(serialize_bincode::<CoreType>(L::Root::is_demux()));
+
+        let deserialize_pipeline = Some(deserialize_bincode::<CoreType>(
+            L::Root::tagged_type().as_ref(),
+        ));
+
+        let (buffer, overflow) =
+            Self::network_overflow::<T>(&self.location, buffer, L::Root::is_demux());
+
+        let received = Stream::new(
+            other.clone(),
+            HydroNode::Network {
+                from_key: None,
+                to_location: other.id(),
+                to_key: None,
+                serialize_fn: serialize_pipeline.map(|e| e.into()),
+                instantiate_fn: DebugInstantiate::Building,
+                deserialize_fn: deserialize_pipeline.map(|e| e.into()),
+                buffer: Some(Box::new(buffer)),
                 input: Box::new(self.ir_node.into_inner()),
                 metadata: other.new_node_metadata::<CoreType>(),
             },
+        );
+
+        (received, overflow)
+    }
+
+    /// Creates the buffer for a network sent from `location`, along with the stream of elements
+    /// that the buffer drops.
+    fn network_overflow<OverflowT>(
+        location: &L,
+        buffer: NetworkBuffer,
+        is_demux: bool,
+    ) -> (HydroNetworkBuffer, Stream<OverflowT, L, Unbounded, Order>) {
+        let overflow_ident = syn::Ident::new(
+            &format!("network_overflow_{}", location.next_node_id()),
+            proc_macro2::Span::call_site(),
+        );
+
+        let overflow = Stream::new(
+            location.clone(),
+            HydroNode::Persist {
+                inner: Box::new(HydroNode::NetworkOverflow {
+                    ident: overflow_ident.clone(),
+                    deserialize_fn: deserialize_overflow::<OverflowT>(is_demux).into(),
+                    location_kind: location.id(),
+                    metadata: location.new_node_metadata::<OverflowT>(),
+                }),
+                metadata: location.new_node_metadata::<OverflowT>(),
+            },
+        );
+
+        (
+            HydroNetworkBuffer {
+                config: buffer,
+                overflow_ident,
+            },
+            overflow,
         )
     }
 
@@ -1941,6 +2116,7 @@ impl<'a, T, L: Location<'a> + NoTick, B, Order> Stream<T, L, B, Order> {
                 serialize_fn: serialize_pipeline.map(|e| e.into()),
                 instantiate_fn: DebugInstantiate::Building,
                 deserialize_fn: None,
+                buffer: None,
                 input: Box::new(self.ir_node.into_inner()),
                 metadata: metadata.clone(),
             }),
@@ -1978,6 +2154,7 @@ impl<'a, T, L: Location<'a> + NoTick, B, Order> Stream<T, L, B, Order> {
                     let expr: syn::Expr = parse_quote!(|b| b.unwrap().freeze());
                     Some(expr.into())
                 },
+                buffer: None,
                 input: Box::new(self.ir_node.into_inner()),
                 metadata: other.new_node_metadata::<Bytes>(),
             },
@@ -2007,6 +2184,7 @@ impl<'a, T, L: Location<'a> + NoTick, B, Order> Stream<T, L, B, Order> {
                 serialize_fn: None,
                 instantiate_fn: DebugInstantiate::Building,
                 deserialize_fn: None,
+                buffer: None,
                 input: Box::new(self.ir_node.into_inner()),
                 metadata: metadata.clone(),
             }),
@@ -2042,6 +2220,8 @@ impl<'a, T, L: Location<'a> + NoTick, B, Order> Stream<T, L, B, Order> {
         self.send_bytes::<L2>(other).map(q!(|(_, b)| b))
     }
 
+    /// Sends every element to each current member of `other`. Members that have left the
+    /// cluster's [`Cluster::membership`] are skipped.
     #[expect(clippy::type_complexity, reason = "ordering semantics for broadcast")]
     pub fn broadcast_bincode<C2: 'a>(
         self,
@@ -2058,10 +2238,15 @@ impl<'a, T, L: Location<'a> + NoTick, B, Order> Stream<T, L, B, Order> {
         Order: MinOrder<<L::Root as CanSend<'a, Cluster<'a, C2>>>::OutStrongestOrder<Order>>,
     {
         let ids = other.members();
+        let membership = other.membership();
 
         let to_send: Stream<(u32, Bytes), L, B, Order> = self
             .map::<Bytes, _>(q!(|v| bincode::serialize(&v).unwrap().into()))
-            .flat_map_ordered(q!(|v| { ids.iter().map(move |id| (id.raw_id, v.clone())) }));
+            .flat_map_ordered(q!(|v| {
+                ids.iter()
+                    .filter(|id| membership.contains(id.raw_id))
+                    .map(move |id| (id.raw_id, v.clone()))
+            }));
 
         let deserialize_pipeline = Some(deserialize_bincode::<T>(L::Root::tagged_type().as_ref()));
 
@@ -2074,12 +2259,73 @@ impl<'a, T, L: Location<'a> + NoTick, B, Order> Stream<T, L, B, Order> {
                 serialize_fn: None,
                 instantiate_fn: DebugInstantiate::Building,
                 deserialize_fn: deserialize_pipeline.map(|e| e.into()),
+                buffer: None,
                 input: Box::new(to_send.ir_node.into_inner()),
                 metadata: other.new_node_metadata::<T>(),
             },
         )
     }
 
+    /// Like [`Stream::broadcast_bincode`], but queues elements for the network in a bounded
+    /// buffer configured by `buffer`; see [`Stream::send_bincode_buffered`]. The buffer limits
+    /// apply across all destination members, and the overflow stream tags each dropped element
+    /// with the member it was addressed to.
+    #[expect(clippy::type_complexity, reason = "ordering semantics for broadcast")]
+    pub fn broadcast_bincode_buffered<C2: 'a>(
+        self,
+        other: &Cluster<'a, C2>,
+        buffer: NetworkBuffer,
+    ) -> (
+        Stream<
+            <L::Root as CanSend<'a, Cluster<'a, C2>>>::Out<T>,
+            Cluster<'a, C2>,
+            Unbounded,
+            Order::Min,
+        >,
+        Stream<(ClusterId<C2>, T), L, Unbounded, Order>,
+    )
+    where
+        L::Root: CanSend<'a, Cluster<'a, C2>, In<T> = (ClusterId<C2>, T)>,
+        T: Clone + Serialize + DeserializeOwned,
+        Order: MinOrder<<L::Root as CanSend<'a, Cluster<'a, C2>>>::OutStrongestOrder<Order>>,
+    {
+        let ids = other.members();
+        let membership = other.membership();
+
+        let to_send: Stream<(u32, Bytes), L, B, Order> = self
+            .map::<Bytes, _>(q!(|v| bincode::serialize(&v).unwrap().into()))
+            .flat_map_ordered(q!(|v| {
+                ids.iter()
+                    .filter(|id| membership.contains(id.raw_id))
+                    .map(move |id| (id.raw_id, v.clone()))
+            }));
+
+        let deserialize_pipeline = Some(deserialize_bincode::<T>(L::Root::tagged_type().as_ref()));
+
+        let (buffer, overflow) = Stream::<T, L, B, Order>::network_overflow::<(ClusterId<C2>, T)>(
+            &to_send.location,
+            buffer,
+            true,
+        );
+
+        let received = Stream::new(
+            other.clone(),
+            HydroNode::Network {
+                from_key: None,
+                to_location: other.id(),
+                to_key: None,
+                serialize_fn: None,
+                instantiate_fn: DebugInstantiate::Building,
+                deserialize_fn: deserialize_pipeline.map(|e| e.into()),
+                buffer: Some(Box::new(buffer)),
+                input: Box::new(to_send.ir_node.into_inner()),
+                metadata: other.new_node_metadata::<T>(),
+            },
+        );
+
+        (received, overflow)
+    }
+
     pub fn broadcast_bincode_anonymous<C2: 'a, Tag>(
         self,
         other: &Cluster<'a, C2>,
@@ -2108,11 +2354,15 @@ impl<'a, T, L: Location<'a> + NoTick, B, Order> Stream<T, L, B, Order> {
         Order: MinOrder<<L::Root as CanSend<'a, Cluster<'a, C2>>>::OutStrongestOrder<Order>>,
     {
         let ids = other.members();
-
-        self.flat_map_ordered(q!(|b| ids.iter().map(move |id| (
-            ::std::clone::Clone::clone(id),
-            ::std::clone::Clone::clone(&b)
-        ))))
+        let membership = other.membership();
+
+        self.flat_map_ordered(q!(|b| ids
+            .iter()
+            .filter(|id| membership.contains(id.raw_id))
+            .map(move |id| (
+                ::std::clone::Clone::clone(id),
+                ::std::clone::Clone::clone(&b)
+            ))))
         .send_bytes(other)
     }
 
@@ -2132,6 +2382,10 @@ impl<'a, T, L: Location<'a> + NoTick, B, Order> Stream<T, L, B, Order> {
 
 #[expect(clippy::type_complexity, reason = "ordering semantics for round-robin")]
 impl<'a, T, L: Location<'a> + NoTick, B> Stream<T, L, B, TotalOrder> {
+    /// Sends each element to a single member of `other`, cycling through the members in order.
+    /// Members that have left the cluster's [`Cluster::membership`] are skipped, and elements
+    /// sent while the cluster has no members are dropped (see
+    /// [`ClusterMembership::unroutable`](crate::location::cluster::ClusterMembership::unroutable)).
     pub fn round_robin_bincode<C2: 'a>(
         self,
         other: &Cluster<'a, C2>,
@@ -2150,9 +2404,12 @@ impl<'a, T, L: Location<'a> + NoTick, B> Stream<T, L, B, TotalOrder> {
             MinOrder<<L::Root as CanSend<'a, Cluster<'a, C2>>>::OutStrongestOrder<TotalOrder>>,
     {
         let ids = other.members();
+        let membership = other.membership();
 
         self.enumerate()
-            .map(q!(|(i, w)| (ids[i % ids.len()], w)))
+            .filter_map(q!(|(i, w)| membership
+                .round_robin(ids, i)
+                .map(|id| (id, w))))
             .send_bincode(other)
     }
 
@@ -2194,9 +2451,12 @@ impl<'a, T, L: Location<'a> + NoTick, B> Stream<T, L, B, TotalOrder> {
             MinOrder<<L::Root as CanSend<'a, Cluster<'a, C2>>>::OutStrongestOrder<TotalOrder>>,
     {
         let ids = other.members();
+        let membership = other.membership();
 
         self.enumerate()
-            .map(q!(|(i, w)| (ids[i % ids.len()], w)))
+            .filter_map(q!(|(i, w)| membership
+                .round_robin(ids, i)
+                .map(|id| (id, w))))
             .send_bytes(other)
     }
 
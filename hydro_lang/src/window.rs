@@ -0,0 +1,653 @@
+//! Time-based windows over unbounded streams.
+//!
+//! Windows group the elements of an unbounded stream by time, either the *event time* carried
+//! by each element or the *processing time* at which the element arrives. Once a window is
+//! closed, its elements are released inside a [`Tick`] as a bounded stream tagged with the
+//! [`WindowBounds`], which can then be aggregated with operators such as
+//! [`Stream::fold_keyed`] or [`Stream::reduce_keyed`].
+//!
+//! For event-time windows, progress is tracked with a *watermark*: the largest timestamp seen
+//! so far minus the allowed lateness. A window is closed once the watermark passes its end,
+//! and elements that arrive after all of their windows have been closed are considered late.
+//! Late elements are returned in a separate stream, which can be ignored to drop them.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::rc::Rc;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use stageleft::{IntoQuotedMut, QuotedWithContext, q};
+use syn::parse_quote;
+
+use crate::ir::HydroNode;
+use crate::location::tick::NoAtomic;
+use crate::location::{Location, NoTick};
+use crate::{Bounded, Singleton, Stream, Tick, TotalOrder, Unbounded};
+
+/// The shape of the windows that elements are grouped into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Window {
+    /// Non-overlapping windows of a fixed size, aligned to multiples of the size.
+    Tumbling { size: Duration },
+    /// Windows of a fixed size that start every `slide`, so an element may belong to
+    /// several windows.
+    Sliding { size: Duration, slide: Duration },
+    /// Per-key windows that extend as long as elements arrive less than `gap` apart.
+    Session { gap: Duration },
+}
+
+impl Window {
+    /// Non-overlapping windows of the given size.
+    pub fn tumbling(size: Duration) -> Self {
+        assert!(!size.is_zero(), "window size must be non-zero");
+        Window::Tumbling { size }
+    }
+
+    /// Windows of the given size that start every `slide`.
+    pub fn sliding(size: Duration, slide: Duration) -> Self {
+        assert!(!size.is_zero(), "window size must be non-zero");
+        assert!(!slide.is_zero(), "window slide must be non-zero");
+        Window::Sliding { size, slide }
+    }
+
+    /// Windows that are closed once no element has arrived for `gap`.
+    pub fn session(gap: Duration) -> Self {
+        assert!(!gap.is_zero(), "session gap must be non-zero");
+        Window::Session { gap }
+    }
+
+    /// How often windows of this shape may close, which is used to pace processing-time windows.
+    pub fn granularity(&self) -> Duration {
+        match self {
+            Window::Tumbling { size } => *size,
+            Window::Sliding { slide, .. } => *slide,
+            Window::Session { gap } => *gap,
+        }
+    }
+
+    /// The windows that an element with the given timestamp belongs to, in ascending order.
+    /// Session windows depend on the other elements, so this returns the window that the
+    /// element would form on its own.
+    pub fn assign(&self, timestamp: Duration) -> Vec<WindowBounds> {
+        match self {
+            Window::Tumbling { size } => {
+                let start = align(timestamp, *size);
+                vec![WindowBounds {
+                    start,
+                    end: start + *size,
+                }]
+            }
+            Window::Sliding { size, slide } => {
+                let mut windows = Vec::new();
+                let mut start = Some(align(timestamp, *slide));
+                while let Some(s) = start {
+                    if s + *size <= timestamp {
+                        break;
+                    }
+                    windows.push(WindowBounds {
+                        start: s,
+                        end: s + *size,
+                    });
+                    start = s.checked_sub(*slide);
+                }
+                windows.reverse();
+                windows
+            }
+            Window::Session { gap } => vec![WindowBounds {
+                start: timestamp,
+                end: timestamp + *gap,
+            }],
+        }
+    }
+}
+
+fn align(timestamp: Duration, size: Duration) -> Duration {
+    let offset = timestamp.as_nanos() % size.as_nanos();
+    timestamp - Duration::from_nanos(offset as u64)
+}
+
+/// The time range `[start, end)` covered by a window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct WindowBounds {
+    pub start: Duration,
+    pub end: Duration,
+}
+
+/// The elements that have been assigned to windows which are not yet closed, used by the
+/// generated code for the windowing operators.
+#[doc(hidden)]
+pub struct WindowState<K, V> {
+    window: Window,
+    allowed_lateness: Duration,
+    max_timestamp: Option<Duration>,
+    watermark: Option<Duration>,
+    closed_through: Option<Duration>,
+    /// The end of the last session window released for each key.
+    sessions_closed_through: HashMap<K, Duration>,
+    /// Elements in arrival order.
+    pending: Vec<(K, Duration, V)>,
+}
+
+impl<K: Eq + Hash + Clone, V: Clone> WindowState<K, V> {
+    pub fn new(window: Window, allowed_lateness: Duration) -> Self {
+        WindowState {
+            window,
+            allowed_lateness,
+            max_timestamp: None,
+            watermark: None,
+            closed_through: None,
+            sessions_closed_through: HashMap::new(),
+            pending: Vec::new(),
+        }
+    }
+
+    /// Creates a handle to the state shared by the ticks of a windowing operator, so that the
+    /// pending elements are not copied between ticks.
+    pub fn new_shared(window: Window, allowed_lateness: Duration) -> Rc<RefCell<Self>> {
+        Rc::new(RefCell::new(Self::new(window, allowed_lateness)))
+    }
+
+    /// Adds elements to their windows, returning the elements that are late because all of
+    /// their windows have already been closed.
+    pub fn insert_all(&mut self, elements: Vec<(K, (Duration, V))>) -> Vec<(K, V)> {
+        let mut late = Vec::new();
+        for (key, (timestamp, value)) in elements {
+            if self.is_late(&key, timestamp) {
+                late.push((key, value));
+            } else {
+                self.max_timestamp = self.max_timestamp.max(Some(timestamp));
+                self.pending.push((key, timestamp, value));
+            }
+        }
+        late
+    }
+
+    fn is_late(&self, key: &K, timestamp: Duration) -> bool {
+        let Some(closed_through) = self.closed_through else {
+            return false;
+        };
+
+        match self.window {
+            // an element at or before the end of a released session would have extended it
+            Window::Session { gap } => {
+                timestamp + gap <= closed_through
+                    || self
+                        .sessions_closed_through
+                        .get(key)
+                        .is_some_and(|end| timestamp <= *end)
+            }
+            _ => self
+                .window
+                .assign(timestamp)
+                .last()
+                .is_none_or(|window| window.end <= closed_through),
+        }
+    }
+
+    /// Advances the watermark to at least `time`, regardless of the timestamps seen so far.
+    pub fn advance_to(&mut self, time: Duration) {
+        self.watermark = self.watermark.max(Some(time));
+    }
+
+    /// Closes every window whose end has been passed by the watermark, returning their elements
+    /// tagged with the window. Windows are returned in order of their bounds, and the elements
+    /// of each window in the order they arrived.
+    pub fn close(&mut self) -> Vec<((K, WindowBounds), V)> {
+        if let Some(max_timestamp) = self.max_timestamp {
+            self.advance_to(max_timestamp.saturating_sub(self.allowed_lateness));
+        }
+
+        let Some(watermark) = self.watermark else {
+            return Vec::new();
+        };
+        let previous = self.closed_through.replace(watermark);
+        if previous == Some(watermark) {
+            return Vec::new();
+        }
+
+        // (window, arrival index, key, value)
+        let mut closed = Vec::new();
+        match self.window {
+            Window::Session { gap } => {
+                let mut by_key = HashMap::<&K, Vec<usize>>::new();
+                for (index, (key, _, _)) in self.pending.iter().enumerate() {
+                    by_key.entry(key).or_default().push(index);
+                }
+
+                let mut done = vec![false; self.pending.len()];
+                for (_, mut indices) in by_key {
+                    indices.sort_by_key(|i| self.pending[*i].1);
+                    let mut session: Vec<usize> = Vec::new();
+                    for i in indices.into_iter().map(Some).chain([None]) {
+                        let last = session.last().map(|j| self.pending[*j].1);
+                        let extends = match (i, last) {
+                            (Some(i), Some(last)) => self.pending[i].1 < last + gap,
+                            _ => false,
+                        };
+
+                        if !extends && !session.is_empty() {
+                            let bounds = WindowBounds {
+                                start: self.pending[session[0]].1,
+                                end: last.unwrap() + gap,
+                            };
+                            if bounds.end <= watermark {
+                                let key = &self.pending[session[0]].0;
+                                let end =
+                                    self.sessions_closed_through.entry(key.clone()).or_default();
+                                *end = (*end).max(bounds.end);
+                                for j in session.drain(..) {
+                                    let (key, _, value) = &self.pending[j];
+                                    closed.push((bounds, j, key.clone(), value.clone()));
+                                    done[j] = true;
+                                }
+                            }
+                            session.clear();
+                        }
+
+                        session.extend(i);
+                    }
+                }
+
+                let mut done = done.into_iter();
+                self.pending.retain(|_| !done.next().unwrap());
+            }
+            _ => {
+                for (index, (key, timestamp, value)) in self.pending.iter().enumerate() {
+                    for bounds in self.window.assign(*timestamp) {
+                        if previous.is_none_or(|p| bounds.end > p) && bounds.end <= watermark {
+                            closed.push((bounds, index, key.clone(), value.clone()));
+                        }
+                    }
+                }
+
+                let window = self.window;
+                self.pending.retain(|(_, timestamp, _)| {
+                    window
+                        .assign(*timestamp)
+                        .last()
+                        .is_some_and(|bounds| bounds.end > watermark)
+                });
+            }
+        }
+
+        closed.sort_by_key(|(bounds, index, _, _)| (*bounds, *index));
+        closed
+            .into_iter()
+            .map(|(bounds, _, key, value)| ((key, bounds), value))
+            .collect()
+    }
+}
+
+/// Runs the windowing state machine over a stream of elements stamped with their timestamps.
+#[expect(clippy::type_complexity, reason = "stream types with ordering")]
+fn window_stamped<
+    'a,
+    K: Eq + Hash + Clone + 'a,
+    V: Clone + 'a,
+    L: Location<'a> + NoTick + NoAtomic,
+    Order,
+>(
+    stamped: Stream<(K, (Duration, V)), L, Unbounded, Order>,
+    tick: &Tick<L>,
+    state: Singleton<Rc<RefCell<WindowState<K, V>>>, L, Unbounded>,
+    clock: Option<Stream<tokio::time::Instant, L, Unbounded>>,
+) -> (
+    Stream<((K, WindowBounds), V), Tick<L>, Bounded, Order>,
+    Stream<(K, V), Tick<L>, Bounded, Order>,
+) {
+    let batch = unsafe {
+        // SAFETY: the order of the outputs is downgraded back to the order of the input
+        stamped.tick_batch(tick).assume_ordering::<TotalOrder>()
+    }
+    .fold(q!(Vec::new), q!(|batch, element| batch.push(element)));
+
+    let state = unsafe {
+        // SAFETY: the state handle is created once and never changes
+        state.latest_tick(tick)
+    };
+    let inserted = state.zip(batch).map(q!(|(state, batch)| {
+        let late = state.borrow_mut().insert_all(batch);
+        (state, late)
+    }));
+
+    let inserted = match clock {
+        None => inserted,
+        Some(clock) => {
+            let wakeups = unsafe {
+                // SAFETY: the clock only wakes up the tick to close processing-time windows
+                clock.tick_batch(tick)
+            }
+            .count();
+
+            inserted.zip(wakeups).map(q!(|((state, late), _)| {
+                state.borrow_mut().advance_to(
+                    std::time::SystemTime::now()
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .unwrap(),
+                );
+                (state, late)
+            }))
+        }
+    };
+
+    let stepped = inserted.map(q!(|(state, late)| {
+        let closed = state.borrow_mut().close();
+        (closed, late)
+    }));
+
+    unsafe {
+        // SAFETY: see above
+        (
+            stepped
+                .clone()
+                .flat_map_ordered(q!(|(closed, _)| closed))
+                .assume_ordering::<Order>(),
+            stepped
+                .flat_map_ordered(q!(|(_, late)| late))
+                .assume_ordering::<Order>(),
+        )
+    }
+}
+
+impl<'a, K: Eq + Hash + Clone + 'a, V: Clone + 'a, L: Location<'a> + NoTick + NoAtomic, Order>
+    Stream<(K, V), L, Unbounded, Order>
+{
+    /// Groups the elements of each key into event-time windows, using `timestamp` to extract
+    /// the event time of each value. Returns a stream of the elements of each window, tagged
+    /// with the key and window, which is released in the first tick after the watermark passes
+    /// the end of the window, and a stream of late elements that arrived after all of their
+    /// windows were closed.
+    ///
+    /// The watermark trails the largest timestamp seen so far by `allowed_lateness`, so
+    /// elements that are out of order by up to `allowed_lateness` are not late. Windows are
+    /// released in order of their bounds, with the elements of each window in arrival order.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// let tick = process.tick();
+    /// // (sensor, (reading, timestamp in seconds))
+    /// let readings = process.source_iter(q!(vec![
+    ///     (1, (1, 0)),
+    ///     (2, (2, 3)),
+    ///     (1, (3, 8)),
+    ///     (1, (4, 12)),
+    /// ]));
+    /// let (windows, _late) = unsafe {
+    ///     readings.window_keyed_event_time(
+    ///         &tick,
+    ///         q!(Window::tumbling(std::time::Duration::from_secs(10))),
+    ///         q!(std::time::Duration::ZERO),
+    ///         q!(|(_, ts)| std::time::Duration::from_secs(*ts)),
+    ///     )
+    /// };
+    /// windows
+    ///     .map(q!(|((sensor, window), (reading, _))| (
+    ///         (sensor, window.start.as_secs()),
+    ///         reading
+    ///     )))
+    ///     .reduce_keyed(q!(|sum, reading| *sum += reading))
+    ///     .all_ticks()
+    /// # }, |mut stream| async move {
+    /// // ((1, 0), 4), ((2, 0), 2) in any order; the window starting at 10 is still open
+    /// # let mut results = vec![stream.next().await.unwrap(), stream.next().await.unwrap()];
+    /// # results.sort();
+    /// # assert_eq!(results, vec![((1, 0), 4), ((2, 0), 2)]);
+    /// # }));
+    /// ```
+    ///
+    /// # Safety
+    /// The watermark advances as elements are batched into ticks, so whether an out-of-order
+    /// element is late depends on the batch boundaries, which are non-deterministic.
+    #[expect(clippy::type_complexity, reason = "stream types with ordering")]
+    pub unsafe fn window_keyed_event_time<F: Fn(&V) -> Duration + 'a>(
+        self,
+        tick: &Tick<L>,
+        window: impl QuotedWithContext<'a, Window, L> + Copy + 'a,
+        allowed_lateness: impl QuotedWithContext<'a, Duration, L> + Copy + 'a,
+        timestamp: impl IntoQuotedMut<'a, F, L>,
+    ) -> (
+        Stream<((K, WindowBounds), V), Tick<L>, Bounded, Order>,
+        Stream<(K, V), Tick<L>, Bounded, Order>,
+    ) {
+        let f = timestamp.splice_fn1_borrow_ctx(&self.location);
+        let stamp: syn::Expr = parse_quote!({
+            let timestamp_fn = #f;
+            move |(key, value)| (key, (timestamp_fn(&value), value))
+        });
+
+        let stamped = Stream::new(
+            self.location.clone(),
+            HydroNode::Map {
+                f: stamp.into(),
+                input: Box::new(self.ir_node.into_inner()),
+                metadata: self.location.new_node_metadata::<(K, (Duration, V))>(),
+            },
+        );
+
+        let state = tick
+            .outer()
+            .singleton(q!(WindowState::new_shared(window, allowed_lateness)));
+        window_stamped(stamped, tick, state, None)
+    }
+
+    /// Groups the elements of each key into windows by the time at which they arrive. Returns
+    /// a stream of the elements of each window, tagged with the key and window, which is
+    /// released in a tick shortly after the end of the window. Timestamps are measured from
+    /// the UNIX epoch, so windows are aligned across locations with synchronized clocks.
+    ///
+    /// # Safety
+    /// Processing time is read from the system clock, so the windows that elements are assigned
+    /// to and when windows are released are non-deterministic.
+    #[expect(clippy::type_complexity, reason = "stream types with ordering")]
+    pub unsafe fn window_keyed_processing_time(
+        self,
+        tick: &Tick<L>,
+        window: impl QuotedWithContext<'a, Window, L> + Copy + 'a,
+    ) -> Stream<((K, WindowBounds), V), Tick<L>, Bounded, Order> {
+        let stamped = self.map(q!(|(key, value)| (
+            key,
+            (
+                std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap(),
+                value
+            )
+        )));
+
+        let clock = unsafe {
+            // SAFETY: source of intentional non-determinism
+            stamped.location.source_interval(q!(window.granularity()))
+        };
+
+        let state = tick
+            .outer()
+            .singleton(q!(WindowState::new_shared(window, Duration::ZERO)));
+        window_stamped(stamped, tick, state, Some(clock)).0
+    }
+}
+
+impl<'a, T: Clone + 'a, L: Location<'a> + NoTick + NoAtomic, Order> Stream<T, L, Unbounded, Order> {
+    /// Groups the elements into event-time windows, using `timestamp` to extract the event
+    /// time of each element. See [`Stream::window_keyed_event_time`] for details.
+    ///
+    /// # Safety
+    /// The watermark advances as elements are batched into ticks, so whether an out-of-order
+    /// element is late depends on the batch boundaries, which are non-deterministic.
+    #[expect(clippy::type_complexity, reason = "stream types with ordering")]
+    pub unsafe fn window_event_time<F: Fn(&T) -> Duration + 'a>(
+        self,
+        tick: &Tick<L>,
+        window: impl QuotedWithContext<'a, Window, L> + Copy + 'a,
+        allowed_lateness: impl QuotedWithContext<'a, Duration, L> + Copy + 'a,
+        timestamp: impl IntoQuotedMut<'a, F, L>,
+    ) -> (
+        Stream<(WindowBounds, T), Tick<L>, Bounded, Order>,
+        Stream<T, Tick<L>, Bounded, Order>,
+    ) {
+        let (windows, late) = unsafe {
+            // SAFETY: see above
+            self.map(q!(|element| ((), element)))
+                .window_keyed_event_time(tick, window, allowed_lateness, timestamp)
+        };
+
+        (
+            windows.map(q!(|(((), bounds), element)| (bounds, element))),
+            late.map(q!(|((), element)| element)),
+        )
+    }
+
+    /// Groups the elements into windows by the time at which they arrive. See
+    /// [`Stream::window_keyed_processing_time`] for details.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// let tick = process.tick();
+    /// let numbers = process.source_iter(q!(vec![1, 2, 3]));
+    /// unsafe {
+    ///     numbers.window_processing_time(
+    ///         &tick,
+    ///         q!(Window::tumbling(std::time::Duration::from_millis(100))),
+    ///     )
+    /// }
+    /// .map(q!(|(_window, n)| n))
+    /// .all_ticks()
+    /// # }, |mut stream| async move {
+    /// // 1, 2, 3 once the windows they arrived in have ended
+    /// # for w in 1..4 {
+    /// #     assert_eq!(stream.next().await.unwrap(), w);
+    /// # }
+    /// # }));
+    /// ```
+    ///
+    /// # Safety
+    /// Processing time is read from the system clock, so the windows that elements are assigned
+    /// to and when windows are released are non-deterministic.
+    pub unsafe fn window_processing_time(
+        self,
+        tick: &Tick<L>,
+        window: impl QuotedWithContext<'a, Window, L> + Copy + 'a,
+    ) -> Stream<(WindowBounds, T), Tick<L>, Bounded, Order> {
+        unsafe {
+            // SAFETY: see above
+            self.map(q!(|element| ((), element)))
+                .window_keyed_processing_time(tick, window)
+        }
+        .map(q!(|(((), bounds), element)| (bounds, element)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Window, WindowBounds, WindowState};
+
+    fn secs(s: u64) -> Duration {
+        Duration::from_secs(s)
+    }
+
+    fn bounds(start: u64, end: u64) -> WindowBounds {
+        WindowBounds {
+            start: secs(start),
+            end: secs(end),
+        }
+    }
+
+    #[test]
+    fn assign_sliding() {
+        assert_eq!(
+            Window::sliding(secs(10), secs(5)).assign(secs(12)),
+            vec![bounds(5, 15), bounds(10, 20)]
+        );
+        assert_eq!(
+            Window::sliding(secs(10), secs(5)).assign(secs(3)),
+            vec![bounds(0, 10)]
+        );
+    }
+
+    #[test]
+    fn tumbling_watermark_and_late() {
+        let mut state = WindowState::new(Window::tumbling(secs(10)), secs(2));
+        let late = state.insert_all(vec![("a", (secs(1), 1)), ("a", (secs(11), 2))]);
+        assert!(late.is_empty());
+        // the watermark is at 9, so no window has closed yet
+        assert_eq!(state.close(), vec![]);
+
+        state.insert_all(vec![("a", (secs(9), 3)), ("b", (secs(13), 4))]);
+        assert_eq!(
+            state.close(),
+            vec![(("a", bounds(0, 10)), 1), (("a", bounds(0, 10)), 3)]
+        );
+
+        let late = state.insert_all(vec![("a", (secs(8), 5)), ("a", (secs(25), 6))]);
+        assert_eq!(late, vec![("a", 5)]);
+        assert_eq!(
+            state.close(),
+            vec![(("a", bounds(10, 20)), 2), (("b", bounds(10, 20)), 4)]
+        );
+    }
+
+    #[test]
+    fn sliding_emits_each_window_once() {
+        let mut state = WindowState::new(Window::sliding(secs(10), secs(5)), secs(0));
+        state.insert_all(vec![((), (secs(7), 'x')), ((), (secs(16), 'y'))]);
+        assert_eq!(
+            state.close(),
+            vec![(((), bounds(0, 10)), 'x'), (((), bounds(5, 15)), 'x')]
+        );
+        state.insert_all(vec![((), (secs(30), 'z'))]);
+        assert_eq!(
+            state.close(),
+            vec![(((), bounds(10, 20)), 'y'), (((), bounds(15, 25)), 'y')]
+        );
+    }
+
+    #[test]
+    fn sessions_close_after_gap() {
+        let mut state = WindowState::new(Window::session(secs(5)), secs(0));
+        state.insert_all(vec![
+            ("a", (secs(0), 1)),
+            ("b", (secs(1), 2)),
+            ("a", (secs(3), 3)),
+            ("a", (secs(10), 4)),
+        ]);
+        assert_eq!(
+            state.close(),
+            vec![
+                (("a", bounds(0, 8)), 1),
+                (("a", bounds(0, 8)), 3),
+                (("b", bounds(1, 6)), 2)
+            ]
+        );
+
+        // an element within the released session of `a` must not reopen it
+        let late = state.insert_all(vec![
+            ("b", (secs(2), 5)),
+            ("a", (secs(7), 7)),
+            ("a", (secs(12), 6)),
+        ]);
+        assert_eq!(late, vec![("b", 5), ("a", 7)]);
+        state.advance_to(secs(20));
+        assert_eq!(
+            state.close(),
+            vec![(("a", bounds(10, 17)), 4), (("a", bounds(10, 17)), 6)]
+        );
+    }
+
+    #[test]
+    fn processing_time_advances_watermark() {
+        let mut state = WindowState::new(Window::tumbling(secs(10)), secs(0));
+        state.insert_all(vec![((), (secs(3), 1))]);
+        assert_eq!(state.close(), vec![]);
+        state.advance_to(secs(10));
+        assert_eq!(state.close(), vec![(((), bounds(0, 10)), 1)]);
+    }
+}
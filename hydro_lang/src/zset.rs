@@ -0,0 +1,447 @@
+//! Collections with retractions, represented as Z-sets.
+//!
+//! A [`ZSet`] is a multiset in which every element carries a signed multiplicity, so that
+//! elements can be deleted as well as inserted. It is stored as a stream of *changes*
+//! `(element, diff)`: a positive `diff` inserts copies of the element and a negative `diff`
+//! retracts them. The contents of the Z-set are the sum of all the changes received so far, and
+//! an element is present while its multiplicity is positive.
+//!
+//! Operators on Z-sets consume and produce changes, so a retraction in the input is propagated
+//! as a retraction of everything that was derived from it. Stateless operators such as
+//! [`ZSet::map`] and [`ZSet::filter`] transform each change independently, [`ZSet::join`]
+//! multiplies the multiplicities of the matching changes, and stateful operators such as
+//! [`ZSet::fold_keyed`] and [`ZSet::unique`] track the contents of their input across ticks
+//! and emit a retraction of each output that is no longer valid alongside its replacement.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use stageleft::{IntoQuotedMut, QuotedWithContext, q, quote_type};
+use syn::parse_quote;
+
+use crate::ir::HydroNode;
+use crate::location::tick::NoAtomic;
+use crate::location::{Location, NoTick};
+use crate::{Bounded, NoOrder, Singleton, Stream, Tick, Unbounded};
+
+/// An unbounded collection in which each element carries a signed multiplicity, represented
+/// by the stream of changes to the collection.
+pub struct ZSet<T, L> {
+    changes: Stream<(T, isize), L, Unbounded, NoOrder>,
+}
+
+impl<'a, T, L: Location<'a> + NoTick + NoAtomic> ZSet<T, L> {
+    /// Creates a Z-set from a stream of `(element, diff)` changes.
+    pub fn from_changes(changes: Stream<(T, isize), L, Unbounded, NoOrder>) -> Self {
+        ZSet { changes }
+    }
+
+    /// The stream of `(element, diff)` changes to this Z-set. The same element may appear in
+    /// several changes, including changes that cancel out.
+    pub fn changes(self) -> Stream<(T, isize), L, Unbounded, NoOrder> {
+        self.changes
+    }
+
+    /// Transforms each element of the Z-set with `f`, keeping its multiplicity. Elements that
+    /// are mapped to the same value have their multiplicities added together.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// ZSet::from_changes(
+    ///     process
+    ///         .source_iter(q!(vec![(1, 1), (2, 1), (1, -1)]))
+    ///         .into(),
+    /// )
+    /// .map(q!(|x| x * 10))
+    /// .changes()
+    /// # }, |mut stream| async move {
+    /// // (10, 1), (20, 1), (10, -1) in any order
+    /// # let mut results = Vec::new();
+    /// # for _ in 0..3 {
+    /// #     results.push(stream.next().await.unwrap());
+    /// # }
+    /// # results.sort();
+    /// # assert_eq!(results, vec![(10, -1), (10, 1), (20, 1)]);
+    /// # }));
+    /// ```
+    pub fn map<U, F: Fn(T) -> U + 'a>(self, f: impl IntoQuotedMut<'a, F, L>) -> ZSet<U, L> {
+        let f = f.splice_fn1_ctx(&self.changes.location);
+        let lifted: syn::Expr = parse_quote!({
+            let map_fn = #f;
+            move |(value, diff)| (map_fn(value), diff)
+        });
+
+        ZSet::from_changes(Stream::new(
+            self.changes.location.clone(),
+            HydroNode::Map {
+                f: lifted.into(),
+                input: Box::new(self.changes.ir_node.into_inner()),
+                metadata: self.changes.location.new_node_metadata::<(U, isize)>(),
+            },
+        ))
+    }
+
+    /// Keeps only the elements of the Z-set for which `f` returns `true`, along with their
+    /// retractions.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// ZSet::from_changes(
+    ///     process
+    ///         .source_iter(q!(vec![(1, 1), (2, 1), (2, -1)]))
+    ///         .into(),
+    /// )
+    /// .filter(q!(|x| x % 2 == 0))
+    /// .changes()
+    /// # }, |mut stream| async move {
+    /// // (2, 1), (2, -1) in any order
+    /// # let mut results = vec![stream.next().await.unwrap(), stream.next().await.unwrap()];
+    /// # results.sort();
+    /// # assert_eq!(results, vec![(2, -1), (2, 1)]);
+    /// # }));
+    /// ```
+    pub fn filter<F: Fn(&T) -> bool + 'a>(self, f: impl IntoQuotedMut<'a, F, L>) -> ZSet<T, L> {
+        let f = f.splice_fn1_borrow_ctx(&self.changes.location);
+        let lifted: syn::Expr = parse_quote!({
+            let filter_fn = #f;
+            move |change: &(_, isize)| filter_fn(&change.0)
+        });
+
+        ZSet::from_changes(Stream::new(
+            self.changes.location.clone(),
+            HydroNode::Filter {
+                f: lifted.into(),
+                input: Box::new(self.changes.ir_node.into_inner()),
+                metadata: self.changes.location.new_node_metadata::<(T, isize)>(),
+            },
+        ))
+    }
+
+    /// Inverts the multiplicity of every element, so that insertions become retractions and
+    /// vice versa.
+    pub fn negate(self) -> ZSet<T, L> {
+        ZSet::from_changes(self.changes.map(q!(|(value, diff)| (value, -diff))))
+    }
+
+    /// Adds the multiplicities of the elements of two Z-sets. Combined with [`ZSet::negate`],
+    /// this can be used to subtract one Z-set from another.
+    pub fn union(self, other: ZSet<T, L>) -> ZSet<T, L> {
+        ZSet::from_changes(self.changes.union(other.changes))
+    }
+
+    /// Removes duplicates, so that each element with a positive multiplicity is present
+    /// exactly once. An insertion is emitted when the multiplicity of an element becomes
+    /// positive, and a retraction when it drops back to zero or below.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// let tick = process.tick();
+    /// ZSet::from_changes(
+    ///     process
+    ///         .source_iter(q!(vec![(1, 2), (2, 1), (2, -1)]))
+    ///         .into(),
+    /// )
+    /// .unique(&tick)
+    /// .changes()
+    /// # }, |mut stream| async move {
+    /// // (1, 1); 2 was retracted in the same tick it was inserted
+    /// # assert_eq!(stream.next().await.unwrap(), (1, 1));
+    /// # }));
+    /// ```
+    pub fn unique(self, tick: &Tick<L>) -> ZSet<T, L>
+    where
+        T: Eq + Hash + Clone + 'a,
+    {
+        self.map(q!(|value| (value, ())))
+            .fold_keyed(tick, q!(|| ()), q!(|_, _| {}), q!(|_, _| {}))
+            .map(q!(|(value, ())| value))
+    }
+}
+
+impl<'a, K, V1, L: Location<'a> + NoTick + NoAtomic> ZSet<(K, V1), L> {
+    /// Joins two Z-sets of key-value pairs on their keys. The multiplicity of each output pair
+    /// is the product of the multiplicities of its inputs, so a retraction on either side
+    /// retracts the pairs it contributed to.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// let left = ZSet::from_changes(process.source_iter(q!(vec![((1, 'a'), 1)])).into());
+    /// let right = ZSet::from_changes(process.source_iter(q!(vec![((1, 'x'), 2)])).into());
+    /// left.join(right).changes()
+    /// # }, |mut stream| async move {
+    /// # assert_eq!(stream.next().await.unwrap(), ((1, ('a', 'x')), 2));
+    /// # }));
+    /// ```
+    pub fn join<V2>(self, other: ZSet<(K, V2), L>) -> ZSet<(K, (V1, V2)), L>
+    where
+        K: Eq + Hash,
+    {
+        let left = self.changes.map(q!(|((key, v1), diff)| (key, (v1, diff))));
+        let right = other.changes.map(q!(|((key, v2), diff)| (key, (v2, diff))));
+        ZSet::from_changes(
+            left.join(right)
+                .map(q!(|(key, ((v1, d1), (v2, d2)))| ((key, (v1, v2)), d1 * d2))),
+        )
+    }
+
+    /// Aggregates the values of each key with an invertible aggregate, producing a Z-set with
+    /// the aggregate of every key that has a positive number of values. Each value is added to
+    /// its aggregate with `add` once for every insertion, and removed with `remove` once for
+    /// every retraction, so `remove` must undo the effect of `add`.
+    ///
+    /// The aggregates are maintained across ticks. At the end of each tick in which a key
+    /// received changes, its previous aggregate is retracted and its new aggregate inserted.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// let tick = process.tick();
+    /// ZSet::from_changes(
+    ///     process
+    ///         .source_iter(q!(vec![((1, 2), 1), ((1, 3), 1), ((2, 5), 1)]))
+    ///         .into(),
+    /// )
+    /// .fold_keyed(
+    ///     &tick,
+    ///     q!(|| 0),
+    ///     q!(|sum, x| *sum += x),
+    ///     q!(|sum, x| *sum -= x),
+    /// )
+    /// .changes()
+    /// # }, |mut stream| async move {
+    /// // ((1, 5), 1), ((2, 5), 1) in any order
+    /// # let mut results = vec![stream.next().await.unwrap(), stream.next().await.unwrap()];
+    /// # results.sort();
+    /// # assert_eq!(results, vec![((1, 5), 1), ((2, 5), 1)]);
+    /// # }));
+    /// ```
+    pub fn fold_keyed<A, I: Fn() -> A + 'a, F: Fn(&mut A, V1) + 'a, G: Fn(&mut A, V1) + 'a>(
+        self,
+        tick: &Tick<L>,
+        init: impl IntoQuotedMut<'a, I, Tick<L>>,
+        add: impl IntoQuotedMut<'a, F, Tick<L>>,
+        remove: impl IntoQuotedMut<'a, G, Tick<L>>,
+    ) -> ZSet<(K, A), L>
+    where
+        K: Eq + Hash + Clone + 'a,
+        V1: Clone + 'a,
+        A: Clone + PartialEq + 'a,
+    {
+        let init = init.splice_fn0_ctx(tick);
+        let add = add.splice_fn2_borrow_mut_ctx(tick);
+        let remove = remove.splice_fn2_borrow_mut_ctx(tick);
+        let groups_type = quote_type::<ZSetGroups<K, A>>();
+        let update: syn::Expr = parse_quote!({
+            let init_fn = #init;
+            let add_fn = #add;
+            let remove_fn = #remove;
+            move |(mut groups, batch): (#groups_type, _)| {
+                let changes = groups.apply(batch, &init_fn, &add_fn, &remove_fn);
+                (groups, changes)
+            }
+        });
+
+        let batch = unsafe {
+            // SAFETY: the contents of a Z-set are the sum of its changes, which does not depend
+            // on how the changes are batched into ticks
+            self.changes.tick_batch(tick)
+        }
+        .fold_commutative(q!(Vec::new), q!(|batch, change| batch.push(change)));
+
+        let initial: Singleton<ZSetGroups<K, A>, _, _> = tick.singleton(q!(ZSetGroups::new()));
+        let (state_complete_cycle, state) = tick.cycle_with_initial(initial);
+        let zipped = state.zip(batch);
+        let applied = Singleton::<(ZSetGroups<K, A>, Vec<((K, A), isize)>), _, Bounded>::new(
+            tick.clone(),
+            HydroNode::Map {
+                f: update.into(),
+                input: Box::new(zipped.ir_node.into_inner()),
+                metadata: tick.new_node_metadata::<(ZSetGroups<K, A>, Vec<((K, A), isize)>)>(),
+            },
+        );
+        state_complete_cycle.complete_next_tick(applied.clone().map(q!(|(groups, _)| groups)));
+
+        ZSet::from_changes(
+            applied
+                .flat_map_unordered(q!(|(_, changes)| changes))
+                .all_ticks()
+                .into(),
+        )
+    }
+}
+
+impl<'a, T, L: Location<'a> + NoTick + NoAtomic, Order> Stream<T, L, Unbounded, Order> {
+    /// Converts the stream into a Z-set in which every element is inserted once.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// process.source_iter(q!(vec![1, 2])).into_zset().changes()
+    /// # }, |mut stream| async move {
+    /// // (1, 1), (2, 1) in any order
+    /// # let mut results = vec![stream.next().await.unwrap(), stream.next().await.unwrap()];
+    /// # results.sort();
+    /// # assert_eq!(results, vec![(1, 1), (2, 1)]);
+    /// # }));
+    /// ```
+    pub fn into_zset(self) -> ZSet<T, L> {
+        let changes = unsafe {
+            // SAFETY: Z-sets are unordered
+            self.map(q!(|value| (value, 1)))
+                .assume_ordering::<NoOrder>()
+        };
+        ZSet::from_changes(changes)
+    }
+}
+
+/// The aggregate and multiplicity of each key of a Z-set, used by the generated code for
+/// [`ZSet::fold_keyed`].
+#[doc(hidden)]
+#[derive(Clone)]
+pub struct ZSetGroups<K, A> {
+    groups: HashMap<K, (A, isize)>,
+}
+
+impl<K: Eq + Hash + Clone, A: Clone + PartialEq> ZSetGroups<K, A> {
+    #[expect(
+        clippy::new_without_default,
+        reason = "only constructed by generated code"
+    )]
+    pub fn new() -> Self {
+        ZSetGroups {
+            groups: HashMap::new(),
+        }
+    }
+
+    /// Applies a batch of changes to the groups, returning the retractions of the aggregates
+    /// which changed followed by the insertions of their new values.
+    pub fn apply<V: Clone>(
+        &mut self,
+        changes: Vec<((K, V), isize)>,
+        init: impl Fn() -> A,
+        add: impl Fn(&mut A, V),
+        remove: impl Fn(&mut A, V),
+    ) -> Vec<((K, A), isize)> {
+        let mut previous = HashMap::new();
+        for ((key, value), diff) in changes {
+            if diff == 0 {
+                continue;
+            }
+
+            let (accumulator, count) = self
+                .groups
+                .entry(key.clone())
+                .or_insert_with(|| (init(), 0));
+            previous
+                .entry(key)
+                .or_insert_with(|| (*count > 0).then(|| accumulator.clone()));
+
+            let update: &dyn Fn(&mut A, V) = if diff > 0 { &add } else { &remove };
+            for _ in 1..diff.unsigned_abs() {
+                update(accumulator, value.clone());
+            }
+            update(accumulator, value);
+            *count += diff;
+        }
+
+        let mut retractions = Vec::new();
+        let mut insertions = Vec::new();
+        for (key, previous) in previous {
+            let current = match self.groups.get(&key) {
+                Some((accumulator, count)) if *count > 0 => Some(accumulator.clone()),
+                Some((_, 0)) => {
+                    self.groups.remove(&key);
+                    None
+                }
+                _ => None,
+            };
+
+            if previous != current {
+                if let Some(previous) = previous {
+                    retractions.push(((key.clone(), previous), -1));
+                }
+                if let Some(current) = current {
+                    insertions.push(((key, current), 1));
+                }
+            }
+        }
+
+        retractions.extend(insertions);
+        retractions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply_sum(
+        groups: &mut ZSetGroups<char, i32>,
+        changes: Vec<((char, i32), isize)>,
+    ) -> Vec<((char, i32), isize)> {
+        let mut out = groups.apply(changes, || 0, |sum, x| *sum += x, |sum, x| *sum -= x);
+        out.sort();
+        out
+    }
+
+    #[test]
+    fn test_fold_keyed_insert_and_retract() {
+        let mut groups = ZSetGroups::new();
+        assert_eq!(
+            apply_sum(
+                &mut groups,
+                vec![(('a', 1), 1), (('a', 2), 2), (('b', 5), 1)]
+            ),
+            vec![(('a', 5), 1), (('b', 5), 1)]
+        );
+        assert_eq!(
+            apply_sum(&mut groups, vec![(('a', 2), -1)]),
+            vec![(('a', 3), 1), (('a', 5), -1)]
+        );
+        assert_eq!(
+            apply_sum(&mut groups, vec![(('b', 5), -1)]),
+            vec![(('b', 5), -1)]
+        );
+        assert!(!groups.groups.contains_key(&'b'));
+    }
+
+    #[test]
+    fn test_fold_keyed_unchanged_aggregate() {
+        let mut groups = ZSetGroups::new();
+        apply_sum(&mut groups, vec![(('a', 1), 1)]);
+        assert_eq!(
+            apply_sum(
+                &mut groups,
+                vec![(('a', 0), 1), (('a', 1), 1), (('a', 1), -1)]
+            ),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn test_fold_keyed_retraction_before_insertion() {
+        let mut groups = ZSetGroups::new();
+        assert_eq!(apply_sum(&mut groups, vec![(('a', 1), -1)]), vec![]);
+        assert_eq!(apply_sum(&mut groups, vec![(('a', 1), 1)]), vec![]);
+        assert_eq!(
+            apply_sum(&mut groups, vec![(('a', 4), 1)]),
+            vec![(('a', 4), 1)]
+        );
+    }
+}
@@ -0,0 +1,416 @@
+//! Delta-state anti-entropy, which keeps a lattice value replicated across the members of a
+//! cluster by gossiping only the parts of the value that each peer is missing.
+//!
+//! Each member merges its local updates into its replica of the value, and logs the part of
+//! each update that changed the value (extracted with [`Atomize`]) under a sequence number.
+//! Periodically, each member sends every peer the join of the logged deltas that the peer has
+//! not acknowledged, and the peer replies with an acknowledgement once it has merged them. Deltas
+//! received from peers are logged in the same way, so they are propagated transitively.
+//!
+//! Once every peer has acknowledged a delta, it is removed from the log. A peer which needs
+//! deltas that have already been removed (for example because it has just joined) catches up
+//! with a digest: it sends the hashes of the atoms of its value, and receives the atoms it is
+//! missing.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::hash::{Hash, Hasher};
+use std::time::Duration;
+
+use hydro_lang::*;
+use lattices::{Atomize, Lattice};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use stageleft::QuotedWithContext;
+
+/// A message between two members of the cluster running [`delta_sync`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DeltaSyncMessage<Lat> {
+    /// Deltas which bring the receiver up to date with the sender's log, up to (but excluding)
+    /// sequence number `seq`. The receiver acknowledges them with [`DeltaSyncMessage::Ack`].
+    Delta { delta: Lat, seq: u64 },
+    /// The sender has merged every delta of the receiver's log before sequence number `seq`.
+    Ack { seq: u64 },
+    /// The receiver's acknowledgement is behind the sender's log, so the receiver should reply
+    /// with a [`DeltaSyncMessage::Digest`] of its value.
+    DigestRequest,
+    /// The hashes of the atoms of the sender's value.
+    Digest { hashes: BTreeSet<u64> },
+}
+
+/// The state of a single member running [`delta_sync`]: its replica of the lattice value, the
+/// log of deltas that may not have been acknowledged by every peer, and the acknowledgements.
+pub struct DeltaSyncState<P, Lat> {
+    value: Lat,
+    next_seq: u64,
+    /// The deltas by sequence number, along with the peer they were received from.
+    log: BTreeMap<u64, (Option<ClusterId<P>>, Lat)>,
+    /// For each peer, the sequence number before which it has merged every delta of the log.
+    acks: HashMap<ClusterId<P>, u64>,
+}
+
+impl<P, Lat: Default> Default for DeltaSyncState<P, Lat> {
+    fn default() -> Self {
+        DeltaSyncState {
+            value: Lat::default(),
+            next_seq: 0,
+            log: BTreeMap::new(),
+            acks: HashMap::new(),
+        }
+    }
+}
+
+impl<P, Lat: Clone> Clone for DeltaSyncState<P, Lat> {
+    fn clone(&self) -> Self {
+        DeltaSyncState {
+            value: self.value.clone(),
+            next_seq: self.next_seq,
+            log: self.log.clone(),
+            acks: self.acks.clone(),
+        }
+    }
+}
+
+impl<P, Lat> DeltaSyncState<P, Lat>
+where
+    Lat: Lattice + Atomize + Default + Clone,
+    Lat::Atom: Clone + Hash,
+{
+    /// This member's replica of the lattice value.
+    pub fn value(&self) -> &Lat {
+        &self.value
+    }
+
+    /// The number of deltas in the log, which have not been acknowledged by every peer.
+    pub fn log_len(&self) -> usize {
+        self.log.len()
+    }
+
+    /// Processes a batch of `local` updates and messages `received` from peers, and if `gossip`
+    /// sends the unacknowledged deltas to each of the current `peers`.
+    ///
+    /// Returns the messages to send, and the deltas which changed the value.
+    #[expect(clippy::type_complexity, reason = "messages to peers")]
+    pub fn step(
+        &mut self,
+        peers: &[ClusterId<P>],
+        local: Vec<Lat>,
+        received: Vec<(ClusterId<P>, DeltaSyncMessage<Lat>)>,
+        gossip: bool,
+    ) -> (Vec<(ClusterId<P>, DeltaSyncMessage<Lat>)>, Vec<Lat>) {
+        let mut outgoing = Vec::new();
+        let mut applied = Vec::new();
+
+        for delta in local {
+            applied.extend(self.apply(None, delta));
+        }
+
+        for (peer, message) in received {
+            match message {
+                DeltaSyncMessage::Delta { delta, seq } => {
+                    applied.extend(self.apply(Some(peer), delta));
+                    outgoing.push((peer, DeltaSyncMessage::Ack { seq }));
+                }
+                DeltaSyncMessage::Ack { seq } => {
+                    let acked = self.acks.entry(peer).or_insert(0);
+                    *acked = (*acked).max(seq);
+                }
+                DeltaSyncMessage::DigestRequest => {
+                    let hashes = self.value.clone().atomize().map(|atom| hash(&atom));
+                    outgoing.push((
+                        peer,
+                        DeltaSyncMessage::Digest {
+                            hashes: hashes.collect(),
+                        },
+                    ));
+                }
+                DeltaSyncMessage::Digest { hashes } => {
+                    let mut missing = Lat::default();
+                    for atom in self.value.clone().atomize() {
+                        if !hashes.contains(&hash(&atom)) {
+                            missing.merge(atom);
+                        }
+                    }
+                    outgoing.push((
+                        peer,
+                        DeltaSyncMessage::Delta {
+                            delta: missing,
+                            seq: self.next_seq,
+                        },
+                    ));
+                }
+            }
+        }
+
+        // Peers which have left no longer hold back the log.
+        self.acks.retain(|peer, _| peers.contains(peer));
+
+        if gossip {
+            let first_seq = self.log.keys().next().copied().unwrap_or(self.next_seq);
+            for &peer in peers {
+                let acked = self.acks.get(&peer).copied().unwrap_or(0);
+                if acked < first_seq {
+                    outgoing.push((peer, DeltaSyncMessage::DigestRequest));
+                } else if acked < self.next_seq {
+                    let mut delta = Lat::default();
+                    for (origin, logged) in self.log.range(acked..).map(|(_, entry)| entry) {
+                        if *origin != Some(peer) {
+                            delta.merge(logged.clone());
+                        }
+                    }
+
+                    if delta.is_bot() {
+                        // The peer sent us every delta it has not acknowledged.
+                        self.acks.insert(peer, self.next_seq);
+                    } else {
+                        outgoing.push((
+                            peer,
+                            DeltaSyncMessage::Delta {
+                                delta,
+                                seq: self.next_seq,
+                            },
+                        ));
+                    }
+                }
+            }
+        }
+
+        let acked = peers
+            .iter()
+            .map(|peer| self.acks.get(peer).copied().unwrap_or(0))
+            .min()
+            .unwrap_or(self.next_seq);
+        self.log = self.log.split_off(&acked);
+
+        (outgoing, applied)
+    }
+
+    /// Merges the atoms of `delta` which are not already in the value, and logs them as a delta
+    /// received from `origin`. Returns the logged delta, if any atoms were new.
+    fn apply(&mut self, origin: Option<ClusterId<P>>, delta: Lat) -> Option<Lat> {
+        let mut novel = Lat::default();
+        for atom in delta.atomize() {
+            if self.value.merge(atom.clone()) {
+                novel.merge(atom);
+            }
+        }
+
+        if novel.is_bot() {
+            return None;
+        }
+
+        self.log.insert(self.next_seq, (origin, novel.clone()));
+        self.next_seq += 1;
+        Some(novel)
+    }
+}
+
+fn hash<T: Hash>(value: &T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Replicates a lattice value across the members of `replicas` with delta-state anti-entropy
+/// (see the [module documentation](self)). Each member merges its local `updates` into its
+/// replica of the value, and gossips the deltas its peers have not acknowledged every
+/// `gossip_interval`.
+///
+/// Returns the deltas merged into each member's replica, from local updates or from peers. The
+/// replicas of all the members converge to the join of all their updates.
+///
+/// # Non-Determinism
+/// Which deltas are returned depends on the order in which updates reach each member, but the
+/// join of the returned deltas does not. Members start gossiping to a peer once they observe
+/// that it has joined, and a peer that never acknowledges deltas keeps them in the log until it
+/// leaves the cluster.
+pub fn delta_sync<'a, P: 'a, Lat>(
+    replicas: &Cluster<'a, P>,
+    updates: Stream<Lat, Cluster<'a, P>, Unbounded, NoOrder>,
+    gossip_interval: impl QuotedWithContext<'a, Duration, Cluster<'a, P>> + Copy + 'a,
+) -> LatticeValue<Lat, Cluster<'a, P>>
+where
+    Lat: Lattice + Atomize + Default + Clone + Serialize + DeserializeOwned + 'a,
+    Lat::Atom: Clone + Hash,
+{
+    let tick = replicas.tick();
+
+    let (messages_complete, messages) = replicas.forward_ref::<Stream<
+        (ClusterId<P>, DeltaSyncMessage<Lat>),
+        Cluster<'a, P>,
+        Unbounded,
+        NoOrder,
+    >>();
+
+    let peers = unsafe {
+        // SAFETY: a peer that is observed late catches up from the digest of the value.
+        replicas
+            .source_cluster_members(replicas)
+            .fold(
+                // kept sorted by ID
+                q!(Vec::new),
+                q!(move |peers, (id, event)| {
+                    if id == CLUSTER_SELF_ID {
+                        return;
+                    }
+                    match (event, peers.binary_search(&id)) {
+                        (MembershipEvent::Joined, Err(index)) => peers.insert(index, id),
+                        (MembershipEvent::Left, Ok(index)) => {
+                            peers.remove(index);
+                        }
+                        _ => {}
+                    }
+                }),
+            )
+            .latest_tick(&tick)
+    };
+
+    let local = unsafe {
+        // SAFETY: merging is commutative, so the value does not depend on batching.
+        updates.tick_batch(&tick)
+    }
+    .fold_commutative(q!(Vec::new), q!(|batch, delta| batch.push(delta)));
+
+    let received = unsafe {
+        // SAFETY: messages are idempotent and acknowledgements are monotonic, so the value does
+        // not depend on batching.
+        messages.tick_batch(&tick)
+    }
+    .fold_commutative(q!(Vec::new), q!(|batch, message| batch.push(message)));
+
+    let gossip = unsafe {
+        // SAFETY: gossip timing only affects when peers converge.
+        replicas.source_interval(gossip_interval).tick_batch(&tick)
+    }
+    .count();
+
+    let (state_complete, state) =
+        tick.cycle_with_initial(tick.singleton(q!(DeltaSyncState::default())));
+
+    let stepped = state
+        .zip(local)
+        .zip(received)
+        .zip(peers)
+        .zip(gossip)
+        .map(q!(|((((mut state, local), received), peers), gossip)| {
+            let (outgoing, applied) = state.step(&peers, local, received, gossip > 0);
+            (state, outgoing, applied)
+        }));
+
+    state_complete.complete_next_tick(stepped.clone().map(q!(|(state, _, _)| state)));
+
+    messages_complete.complete(
+        stepped
+            .clone()
+            .flat_map_unordered(q!(|(_, outgoing, _)| outgoing))
+            .all_ticks()
+            .send_bincode(replicas),
+    );
+
+    stepped
+        .flat_map_unordered(q!(|(_, _, applied)| applied))
+        .all_ticks()
+        .into_lattice()
+}
+
+#[cfg(test)]
+mod tests {
+    use lattices::crdt::GCounter;
+
+    use super::*;
+
+    type State = DeltaSyncState<(), GCounter<u32>>;
+    type Message = (ClusterId<()>, DeltaSyncMessage<GCounter<u32>>);
+
+    fn increment(id: u32, amount: u64) -> GCounter<u32> {
+        let mut counter = GCounter::new();
+        counter.increment(id, amount);
+        counter
+    }
+
+    /// The ids of every member of a cluster of `len` members other than `id`.
+    fn peers_of(id: u32, len: usize) -> Vec<ClusterId<()>> {
+        (0..len as u32)
+            .filter(|peer| *peer != id)
+            .map(ClusterId::from_raw)
+            .collect()
+    }
+
+    /// Delivers the messages sent by `from` to the members in `states`, returning their replies.
+    fn deliver(
+        from: u32,
+        outgoing: Vec<Message>,
+        states: &mut [State],
+    ) -> Vec<(u32, Vec<Message>)> {
+        let len = states.len();
+        let mut replies = Vec::new();
+        for (to, message) in outgoing {
+            let (reply, _) = states[to.raw_id as usize].step(
+                &peers_of(to.raw_id, len),
+                Vec::new(),
+                vec![(ClusterId::from_raw(from), message)],
+                false,
+            );
+            replies.push((to.raw_id, reply));
+        }
+        replies
+    }
+
+    fn gossip_round(states: &mut [State]) {
+        for id in 0..states.len() as u32 {
+            let peers = peers_of(id, states.len());
+            let (outgoing, _) = states[id as usize].step(&peers, Vec::new(), Vec::new(), true);
+            let mut pending = vec![(id, outgoing)];
+            while let Some((from, outgoing)) = pending.pop() {
+                pending.extend(deliver(from, outgoing, states));
+            }
+        }
+    }
+
+    #[test]
+    fn converges_and_truncates_log() {
+        let mut states = vec![State::default(), State::default(), State::default()];
+        let peers = [ClusterId::from_raw(1), ClusterId::from_raw(2)];
+        let (_, applied) = states[0].step(
+            &peers,
+            vec![increment(0, 1), increment(0, 2)],
+            vec![],
+            false,
+        );
+        assert_eq!(vec![increment(0, 1), increment(0, 2)], applied);
+
+        let peers = [ClusterId::from_raw(0), ClusterId::from_raw(2)];
+        states[1].step(
+            &peers,
+            vec![increment(1, 5), increment(1, 5)],
+            vec![],
+            false,
+        );
+
+        gossip_round(&mut states);
+        for state in &states {
+            assert_eq!(7, state.value().value());
+        }
+
+        // Every delta has been acknowledged by every peer, so the logs are truncated.
+        gossip_round(&mut states);
+        for state in &states {
+            assert_eq!(0, state.log_len());
+        }
+    }
+
+    #[test]
+    fn lagging_peer_catches_up_from_digest() {
+        let mut states = vec![State::default(), State::default()];
+        let peers = [ClusterId::from_raw(1)];
+        states[0].step(&peers, vec![increment(0, 3)], vec![], false);
+        gossip_round(&mut states);
+        gossip_round(&mut states);
+        assert_eq!(0, states[0].log_len());
+
+        // A new member joins after the log has been truncated.
+        states.push(State::default());
+        gossip_round(&mut states);
+        assert_eq!(3, states[2].value().value());
+    }
+}
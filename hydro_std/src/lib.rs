@@ -2,7 +2,9 @@ stageleft::stageleft_no_entry_crate!();
 
 pub mod bench_client;
 pub mod compartmentalize;
+pub mod delta_sync;
 pub mod quorum;
+pub mod replication;
 pub mod request_response;
 
 #[cfg(test)]
@@ -0,0 +1,453 @@
+use std::collections::{BTreeMap, HashMap};
+
+use hydro_lang::*;
+use location::NoTick;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use super::kv_replica::{KvKey, KvPayload, KvValue};
+
+/// The order in which the live members of the replica cluster are arranged into a chain.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChainOrder {
+    /// The member with the smallest [`ClusterId`] is the head, and the largest is the tail.
+    Ascending,
+    /// The member with the largest [`ClusterId`] is the head, and the smallest is the tail.
+    Descending,
+}
+
+/// Which replicas serve reads.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChainReads {
+    /// Only the tail serves reads, as in classic chain replication.
+    Tail,
+    /// Every replica serves reads, as in CRAQ. A replica answers from its own state if the key
+    /// is clean (it has no uncommitted write to it), and otherwise forwards the read to the tail.
+    Apportioned,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ChainConfig {
+    /// How the replicas are arranged into a chain.
+    pub order: ChainOrder,
+    /// Which replicas serve reads.
+    pub reads: ChainReads,
+}
+
+/// A write from `client`, which has been assigned the sequence number `seq` by the head
+/// of the chain. Writes are ordered by their sequence number.
+#[derive(Serialize, Deserialize)]
+#[serde(bound(
+    serialize = "K: Serialize, V: Serialize",
+    deserialize = "K: DeserializeOwned, V: DeserializeOwned"
+))]
+pub struct ChainWrite<K, V, C> {
+    pub seq: usize,
+    pub client: ClusterId<C>,
+    pub payload: KvPayload<K, V>,
+}
+
+impl<K: Clone, V: Clone, C> Clone for ChainWrite<K, V, C> {
+    fn clone(&self) -> Self {
+        ChainWrite {
+            seq: self.seq,
+            client: self.client,
+            payload: self.payload.clone(),
+        }
+    }
+}
+
+impl<K, V, C> PartialEq for ChainWrite<K, V, C> {
+    fn eq(&self, other: &Self) -> bool {
+        self.seq == other.seq
+    }
+}
+
+impl<K, V, C> Eq for ChainWrite<K, V, C> {}
+
+impl<K, V, C> PartialOrd for ChainWrite<K, V, C> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<K, V, C> Ord for ChainWrite<K, V, C> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.seq.cmp(&other.seq)
+    }
+}
+
+type ResolvedRead<K, V, C, R> =
+    Result<(ClusterId<C>, (K, Option<V>)), (ClusterId<R>, (ClusterId<C>, K))>;
+
+/// The state of a single replica in the chain, which tracks the latest committed (clean)
+/// value of each key and the writes it has applied that the tail has not yet committed.
+pub struct ChainStore<K, V, C> {
+    clean: HashMap<K, (usize, V)>,
+    latest_dirty: HashMap<K, usize>,
+    dirty: BTreeMap<usize, ChainWrite<K, V, C>>,
+}
+
+impl<K, V, C> Default for ChainStore<K, V, C> {
+    fn default() -> Self {
+        ChainStore {
+            clean: HashMap::new(),
+            latest_dirty: HashMap::new(),
+            dirty: BTreeMap::new(),
+        }
+    }
+}
+
+impl<K: Clone, V: Clone, C> Clone for ChainStore<K, V, C> {
+    fn clone(&self) -> Self {
+        ChainStore {
+            clean: self.clean.clone(),
+            latest_dirty: self.latest_dirty.clone(),
+            dirty: self.dirty.clone(),
+        }
+    }
+}
+
+impl<K: KvKey, V: KvValue, C> ChainStore<K, V, C> {
+    /// Applies a write that has not yet been committed by the tail.
+    pub fn apply(&mut self, write: ChainWrite<K, V, C>) {
+        let key = &write.payload.key;
+        if self
+            .clean
+            .get(key)
+            .is_some_and(|(seq, _)| *seq >= write.seq)
+        {
+            return;
+        }
+
+        let latest = self.latest_dirty.entry(key.clone()).or_insert(write.seq);
+        *latest = (*latest).max(write.seq);
+        self.dirty.insert(write.seq, write);
+    }
+
+    /// Marks a write as committed, since it has been applied by the tail.
+    pub fn commit(&mut self, write: ChainWrite<K, V, C>) {
+        self.dirty.remove(&write.seq);
+        let KvPayload { key, value } = write.payload;
+        if self.latest_dirty.get(&key) == Some(&write.seq) {
+            self.latest_dirty.remove(&key);
+        }
+
+        if self.clean.get(&key).is_none_or(|(seq, _)| *seq < write.seq) {
+            self.clean.insert(key, (write.seq, value));
+        }
+    }
+
+    /// Returns the committed value of `key`, or `None` if the replica has applied a newer write
+    /// to the key that has not been committed yet.
+    pub fn committed(&self, key: &K) -> Option<Option<V>> {
+        let clean = self.clean.get(key);
+        match self.latest_dirty.get(key) {
+            Some(dirty_seq) if clean.is_none_or(|(seq, _)| dirty_seq > seq) => None,
+            _ => Some(clean.map(|(_, value)| value.clone())),
+        }
+    }
+
+    /// Returns the latest value of `key` applied by the replica, whether committed or not.
+    pub fn latest(&self, key: &K) -> Option<V> {
+        self.committed(key).unwrap_or_else(|| {
+            let seq = self.latest_dirty.get(key)?;
+            self.dirty.get(seq).map(|write| write.payload.value.clone())
+        })
+    }
+
+    /// Decides how the replica `self_id` handles a batch of reads. Each read is either answered
+    /// to the client (`Ok`), or forwarded to the tail (`Err`).
+    ///
+    /// The tail answers with the latest value it has applied, since every write it has applied is
+    /// committed. If `serve_committed`, other replicas answer reads to clean keys from their own
+    /// state, as in CRAQ.
+    pub fn resolve_reads<R>(
+        &self,
+        reads: Vec<(ClusterId<C>, K)>,
+        chain: &[ClusterId<R>],
+        self_id: ClusterId<R>,
+        serve_committed: bool,
+    ) -> Vec<ResolvedRead<K, V, C, R>> {
+        let tail = chain.last().copied();
+        reads
+            .into_iter()
+            .filter_map(|(client, key)| {
+                if tail == Some(self_id) {
+                    let value = self.latest(&key);
+                    Some(Ok((client, (key, value))))
+                } else if let Some(value) = self.committed(&key).filter(|_| serve_committed) {
+                    Some(Ok((client, (key, value))))
+                } else {
+                    tail.map(|tail| Err((tail, (client, key))))
+                }
+            })
+            .collect()
+    }
+
+    /// Consumes the store, returning the writes that have not been committed in sequence order.
+    pub fn into_uncommitted(self) -> Vec<ChainWrite<K, V, C>> {
+        self.dirty.into_values().collect()
+    }
+}
+
+/// Tracks the live members of `replicas` at `location`, arranged into a chain by `order`.
+fn chain_members<'a, L: Location<'a> + NoTick, R: 'a>(
+    location: &L,
+    replicas: &Cluster<'a, R>,
+    order: ChainOrder,
+) -> Singleton<Vec<ClusterId<R>>, L, Unbounded> {
+    let members = location.source_cluster_members(replicas).fold(
+        // kept sorted by ID
+        q!(Vec::new),
+        q!(|members, (id, event)| {
+            match (event, members.binary_search(&id)) {
+                (MembershipEvent::Joined, Err(index)) => members.insert(index, id),
+                (MembershipEvent::Left, Ok(index)) => {
+                    members.remove(index);
+                }
+                _ => {}
+            }
+        }),
+    );
+
+    match order {
+        ChainOrder::Ascending => members,
+        ChainOrder::Descending => members.map(q!(|members| members.into_iter().rev().collect())),
+    }
+}
+
+/// Implements chain replication of a key-value store over the members of `replicas`, with
+/// optional apportioned reads as in CRAQ ("Object Storage on CRAQ", Terrace and Freedman).
+///
+/// The live members of `replicas` are arranged into a chain according to `config.order`.
+/// Clients send their writes to the head, which assigns each write a sequence number and
+/// forwards it down the chain. Once the tail applies a write, it is committed: the tail
+/// acknowledges it to the client and notifies every replica, which then mark it as clean.
+/// Reads are served according to `config.reads`.
+///
+/// When a member leaves the cluster, the chain is re-formed from the remaining members. The
+/// predecessor of the removed member re-sends its uncommitted writes to its new successor,
+/// and if the removed member was the tail, its predecessor becomes the tail and commits all
+/// of its uncommitted writes. Members that (re)join start with an empty store, so they should
+/// only join before the chain serves any writes.
+///
+/// Returns a stream of acknowledged writes and a stream of read results at the clients.
+///
+/// # Guarantees
+/// While the chain is stable, writes are committed in the order assigned by the head, and
+/// reads are strongly consistent: they observe every write acknowledged before the read
+/// was issued. A write is acknowledged at least once, and may be acknowledged more than once
+/// if the tail changes while it is being committed.
+///
+/// # Safety
+/// Clients send requests to the head or tail in their current view of the membership, which
+/// may be stale, so writes and reads sent to a member that has left the chain are
+/// non-deterministically dropped. The order in which the head sequences concurrent writes
+/// from different clients is also non-deterministic.
+#[expect(clippy::type_complexity, reason = "stream types with ordering")]
+pub unsafe fn chain_replication<'a, R: 'a, C: 'a, K: KvKey + 'a, V: KvValue + 'a>(
+    replicas: &Cluster<'a, R>,
+    clients: &Cluster<'a, C>,
+    config: ChainConfig,
+    writes: Stream<KvPayload<K, V>, Cluster<'a, C>, Unbounded>,
+    reads: Stream<K, Cluster<'a, C>, Unbounded>,
+) -> (
+    Stream<KvPayload<K, V>, Cluster<'a, C>, Unbounded, NoOrder>,
+    Stream<(K, Option<V>), Cluster<'a, C>, Unbounded, NoOrder>,
+) {
+    let client_tick = clients.tick();
+    let c_chain = unsafe {
+        // SAFETY: clients route requests with their latest view of the membership, which
+        // may be stale (documented non-determinism).
+        chain_members(clients, replicas, config.order).latest_tick(&client_tick)
+    };
+
+    let c_to_head = unsafe {
+        // SAFETY: see above
+        writes.tick_batch(&client_tick)
+    }
+    .cross_singleton(c_chain.clone())
+    .filter_map(q!(|(write, chain)| chain
+        .first()
+        .map(|head| (*head, write))))
+    .all_ticks()
+    .send_bincode(replicas);
+
+    let c_to_readers = unsafe {
+        // SAFETY: see above
+        reads.enumerate().tick_batch(&client_tick)
+    }
+    .cross_singleton(c_chain);
+    let c_to_readers = match config.reads {
+        ChainReads::Tail => c_to_readers.filter_map(q!(|((_, key), chain)| chain
+            .last()
+            .map(|tail| (*tail, key)))),
+        ChainReads::Apportioned => c_to_readers.filter_map(q!(|((i, key), chain)| {
+            // spread reads over the replicas in a round-robin
+            (!chain.is_empty()).then(|| (chain[i % chain.len()], key))
+        })),
+    }
+    .all_ticks()
+    .send_bincode(replicas);
+
+    let (r_forwarded_writes_complete, r_forwarded_writes) =
+        replicas.forward_ref::<Stream<ChainWrite<K, V, C>, _, _, NoOrder>>();
+    let (r_committed_writes_complete, r_committed_writes) =
+        replicas.forward_ref::<Stream<ChainWrite<K, V, C>, _, _, NoOrder>>();
+    let (r_forwarded_reads_complete, r_forwarded_reads) =
+        replicas.forward_ref::<Stream<(ClusterId<C>, K), _, _, NoOrder>>();
+
+    let replica_tick = replicas.tick();
+    let r_chain = unsafe {
+        // SAFETY: replicas react to membership changes at some point after they happen, which
+        // is resolved by re-sending uncommitted writes down the new chain.
+        chain_members(replicas, replicas, config.order).latest_tick(&replica_tick)
+    };
+    let r_is_head = r_chain
+        .clone()
+        .filter(q!(move |chain| chain.first() == Some(&CLUSTER_SELF_ID)));
+    let r_successor = r_chain.clone().map(q!(move |chain| {
+        let position = chain.iter().position(|id| *id == CLUSTER_SELF_ID);
+        position.and_then(|i| chain.get(i + 1).copied())
+    }));
+    let r_is_tail = r_chain
+        .clone()
+        .map(q!(move |chain| chain.last() == Some(&CLUSTER_SELF_ID)));
+    let r_successor_changed = r_successor.clone().delta();
+    let r_became_tail = r_is_tail.clone().delta().filter(q!(|is_tail| *is_tail));
+    let r_is_tail = r_is_tail.filter(q!(|is_tail| *is_tail));
+
+    // The head sequences the writes from clients.
+    let (r_next_seq_complete_cycle, r_next_seq) =
+        replica_tick.cycle_with_initial(replica_tick.singleton(q!(0)));
+    let r_sequenced_writes = unsafe {
+        // SAFETY: the order in which the head sequences writes from different clients is
+        // documented non-determinism.
+        c_to_head
+            .tick_batch(&replica_tick)
+            .assume_ordering::<TotalOrder>()
+    }
+    .continue_if(r_is_head)
+    .enumerate()
+    .cross_singleton(r_next_seq.clone())
+    .map(q!(|((index, (client, payload)), base_seq)| ChainWrite {
+        seq: base_seq + index,
+        client,
+        payload,
+    }));
+
+    // Other replicas apply the writes forwarded by their predecessor. Writes are forwarded in
+    // sequence order over a FIFO channel, so any write before the next expected sequence number
+    // is a duplicate that was re-sent after a membership change.
+    let r_applied_writes = unsafe {
+        // SAFETY: see above
+        r_forwarded_writes.tick_batch(&replica_tick)
+    }
+    .cross_singleton(r_next_seq.clone())
+    .filter(q!(|(write, next_seq)| write.seq >= *next_seq))
+    .map(q!(|(write, _)| write))
+    .chain(r_sequenced_writes);
+
+    r_next_seq_complete_cycle.complete_next_tick(
+        r_applied_writes
+            .clone()
+            .map(q!(|write| write.seq + 1))
+            .chain(r_next_seq.into_stream())
+            .max()
+            .unwrap_or(replica_tick.singleton(q!(0))),
+    );
+
+    let r_store = r_applied_writes
+        .clone()
+        .map(q!(|write| (false, write)))
+        .chain(
+            unsafe {
+                // SAFETY: applying and committing writes commutes, since writes are
+                // identified by their sequence number.
+                r_committed_writes.tick_batch(&replica_tick)
+            }
+            .map(q!(|write| (true, write))),
+        )
+        .persist()
+        .fold_commutative(
+            q!(ChainStore::default),
+            q!(|store, (committed, write)| {
+                if committed {
+                    store.commit(write);
+                } else {
+                    store.apply(write);
+                }
+            }),
+        );
+
+    // When the successor changes, re-send every uncommitted write to the new successor.
+    let r_to_successor = r_applied_writes
+        .clone()
+        .continue_unless(r_successor_changed.clone())
+        .chain(
+            r_store
+                .clone()
+                .continue_if(r_successor_changed)
+                .flat_map_unordered(q!(|store| store.into_uncommitted())),
+        )
+        .sort()
+        .cross_singleton(r_successor)
+        .filter_map(q!(|(write, successor)| successor.map(|s| (s, write))))
+        .all_ticks();
+    r_forwarded_writes_complete.complete(r_to_successor.send_bincode_anonymous(replicas));
+
+    // The tail commits the writes it applies, and a replica that becomes the tail commits all of
+    // the writes that were not committed by the previous tail.
+    let r_committed = r_applied_writes
+        .continue_if(r_is_tail)
+        .continue_unless(r_became_tail.clone())
+        .chain(
+            r_store
+                .clone()
+                .continue_if(r_became_tail)
+                .flat_map_unordered(q!(|store| store.into_uncommitted())),
+        )
+        .all_ticks();
+    r_committed_writes_complete.complete(r_committed.clone().broadcast_bincode_anonymous(replicas));
+
+    let write_acks = r_committed
+        .map(q!(|write| (write.client, write.payload)))
+        .send_bincode_anonymous(clients);
+
+    let r_reads = unsafe {
+        // SAFETY: reads are answered with the state of the replica when they arrive, which is
+        // consistent because clean values are committed and the tail answers with its latest state.
+        c_to_readers
+            .tick_batch(&replica_tick)
+            .chain(r_forwarded_reads.tick_batch(&replica_tick))
+    }
+    .fold_commutative(q!(Vec::new), q!(|reads, read| reads.push(read)))
+    .zip(r_store)
+    .zip(r_chain);
+    let r_resolved_reads = match config.reads {
+        ChainReads::Tail => r_reads.flat_map_unordered(q!(move |((reads, store), chain)| {
+            store.resolve_reads(reads, &chain, CLUSTER_SELF_ID, false)
+        })),
+        ChainReads::Apportioned => {
+            r_reads.flat_map_unordered(q!(move |((reads, store), chain)| {
+                store.resolve_reads(reads, &chain, CLUSTER_SELF_ID, true)
+            }))
+        }
+    };
+
+    r_forwarded_reads_complete.complete(
+        r_resolved_reads
+            .clone()
+            .filter_map(q!(|resolved| resolved.err()))
+            .all_ticks()
+            .send_bincode_anonymous(replicas),
+    );
+
+    let read_results = r_resolved_reads
+        .filter_map(q!(|resolved| resolved.ok()))
+        .all_ticks()
+        .send_bincode_anonymous(clients);
+
+    (write_acks, read_results)
+}
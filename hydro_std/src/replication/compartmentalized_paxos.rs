@@ -1,8 +1,6 @@
 use std::collections::HashMap;
 
 use hydro_lang::*;
-use hydro_std::quorum::collect_quorum;
-use hydro_std::request_response::join_responses;
 use serde::{Deserialize, Serialize};
 
 use super::paxos::{
@@ -10,6 +8,8 @@ use super::paxos::{
     index_payloads, leader_election, recommit_after_leader_election,
 };
 use super::paxos_with_client::PaxosLike;
+use crate::quorum::collect_quorum;
+use crate::request_response::join_responses;
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct ProxyLeader {}
@@ -145,6 +145,7 @@ pub unsafe fn compartmentalized_paxos_core<'a, P: PaxosPayload>(
             config.paxos_config,
             sequencing_max_ballot_forward_reference,
             a_log_forward_reference,
+            None,
         )
     };
 
@@ -289,6 +290,7 @@ unsafe fn sequence_payload<'a, P: PaxosPayload>(
         a_max_ballot.clone(),
         pl_to_acceptors_p2a_thrifty,
         a_checkpoint,
+        None,
         proxy_leaders,
     );
 
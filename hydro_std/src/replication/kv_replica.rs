@@ -0,0 +1,44 @@
+use std::fmt::Debug;
+use std::hash::Hash;
+
+use hydro_lang::*;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use super::state_machine::state_machine_replica;
+
+pub struct Replica {}
+
+pub trait KvKey: Serialize + DeserializeOwned + Hash + Eq + Clone + Debug {}
+impl<K: Serialize + DeserializeOwned + Hash + Eq + Clone + Debug> KvKey for K {}
+
+pub trait KvValue: Serialize + DeserializeOwned + Eq + Clone + Debug {}
+impl<V: Serialize + DeserializeOwned + Eq + Clone + Debug> KvValue for V {}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Debug)]
+pub struct KvPayload<K, V> {
+    pub key: K,
+    pub value: V,
+}
+
+/// Implements the replicas of a replicated key-value store, on top of
+/// [`state_machine_replica`]. Expects the payloads sequenced by a consensus protocol, and
+/// outputs a stream of checkpoints and the payloads applied by each replica in slot order.
+#[expect(clippy::type_complexity, reason = "stream types with ordering")]
+pub fn kv_replica<'a, K: KvKey, V: KvValue>(
+    replicas: &Cluster<'a, Replica>,
+    p_to_replicas: impl Into<
+        Stream<(usize, Option<KvPayload<K, V>>), Cluster<'a, Replica>, Unbounded, NoOrder>,
+    >,
+    checkpoint_frequency: usize,
+) -> (
+    Stream<usize, Cluster<'a, Replica>, Unbounded>,
+    Stream<KvPayload<K, V>, Cluster<'a, Replica>, Unbounded>,
+) {
+    let (r_checkpoints, r_processed_payloads) =
+        state_machine_replica(replicas, p_to_replicas, checkpoint_frequency);
+
+    // Tell clients that the payload has been committed. All payloads contain the client's machine ID as part of the value.
+    let r_to_clients = r_processed_payloads.filter_map(q!(|payload| payload.command));
+    (r_checkpoints, r_to_clients)
+}
@@ -0,0 +1,32 @@
+//! Replication protocols for building fault-tolerant services out of Hydro clusters.
+//!
+//! - [`paxos`] implements multi-Paxos, which sequences the payloads sent to a cluster of
+//!   proposers into a single log that is stored on a cluster of acceptors. It tolerates
+//!   `f` failures with `f + 1` proposers and `2f + 1` acceptors (see [`paxos::PaxosConfig`]).
+//!   Acceptor state can be made durable across restarts with [`paxos_storage`].
+//! - [`compartmentalized_paxos`] scales out multi-Paxos by offloading the second phase to a
+//!   cluster of proxy leaders and arranging acceptors in a grid.
+//! - [`paxos_with_client`] defines the [`PaxosLike`](paxos_with_client::PaxosLike) trait
+//!   shared by both, which connects a cluster of clients to the sequenced log.
+//! - [`state_machine`] implements the replicas of a replicated state machine, which apply
+//!   the sequenced log in slot order and emit checkpoints to garbage-collect it.
+//!   [`kv_replica`] specializes it to a key-value store.
+//! - [`chain`] implements chain replication of a key-value store, optionally with CRAQ's
+//!   apportioned reads, as a cheaper alternative to consensus for read-heavy data. The chain
+//!   is re-formed from the live members of the replica cluster when members leave.
+//! - [`two_pc`] implements two-phase commit between a coordinator and a cluster of
+//!   participants.
+//!
+//! All sequenced logs are gap-free: every slot is eventually filled, either with a payload or
+//! with a hole (`None`) that replicas skip. While the Paxos leader is stable, payloads are
+//! sequenced in the order the leader receives them. Payloads sent while leadership is changing
+//! may be dropped, which is why the protocol entrypoints are `unsafe`.
+
+pub mod chain;
+pub mod compartmentalized_paxos;
+pub mod kv_replica;
+pub mod paxos;
+pub mod paxos_storage;
+pub mod paxos_with_client;
+pub mod state_machine;
+pub mod two_pc;
@@ -4,12 +4,13 @@ use std::hash::Hash;
 use std::time::Duration;
 
 use hydro_lang::*;
-use hydro_std::quorum::{collect_quorum, collect_quorum_with_response};
-use hydro_std::request_response::join_responses;
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 
+use super::paxos_storage::AcceptorLog;
 use super::paxos_with_client::PaxosLike;
+use crate::quorum::{collect_quorum, collect_quorum_with_response};
+use crate::request_response::join_responses;
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Proposer {}
@@ -68,6 +69,9 @@ pub struct CorePaxos<'a> {
     pub proposers: Cluster<'a, Proposer>,
     pub acceptors: Cluster<'a, Acceptor>,
     pub paxos_config: PaxosConfig,
+    /// Durable storage for the state of each acceptor, so that acceptors recover their ballot
+    /// and log when they restart. See [`local_file_acceptor_log`](super::paxos_storage::local_file_acceptor_log).
+    pub acceptor_log: Option<Singleton<AcceptorLog, Cluster<'a, Acceptor>, Unbounded>>,
 }
 
 impl<'a> PaxosLike<'a> for CorePaxos<'a> {
@@ -102,6 +106,7 @@ impl<'a> PaxosLike<'a> for CorePaxos<'a> {
                 &self.proposers,
                 &self.acceptors,
                 a_checkpoint,
+                self.acceptor_log,
                 with_ballot,
                 self.paxos_config,
             )
@@ -121,6 +126,9 @@ impl<'a> PaxosLike<'a> for CorePaxos<'a> {
 /// and a stream of sequenced payloads with an index and optional payload (in the case of
 /// holes in the log).
 ///
+/// If `a_durable_log` is provided, acceptors persist their promised ballots and accepted values
+/// before responding to proposers, and recover them when restarted.
+///
 /// # Safety
 /// When the leader is stable, the algorithm will commit incoming payloads to the leader
 /// in deterministic order. However, when the leader is changing, payloads may be
@@ -131,6 +139,7 @@ pub unsafe fn paxos_core<'a, P: PaxosPayload>(
     proposers: &Cluster<'a, Proposer>,
     acceptors: &Cluster<'a, Acceptor>,
     a_checkpoint: Optional<usize, Cluster<'a, Acceptor>, Unbounded>,
+    a_durable_log: Option<Singleton<AcceptorLog, Cluster<'a, Acceptor>, Unbounded>>,
     c_to_proposers: impl FnOnce(
         Stream<Ballot, Cluster<'a, Proposer>, Unbounded>,
     ) -> Stream<P, Cluster<'a, Proposer>, Unbounded>,
@@ -152,6 +161,11 @@ pub unsafe fn paxos_core<'a, P: PaxosPayload>(
     let proposer_tick = proposers.tick();
     let acceptor_tick = acceptors.tick();
 
+    let a_durable_log = a_durable_log.map(|log| unsafe {
+        // SAFETY: the log handle is created once and never changes
+        log.latest_tick(&acceptor_tick)
+    });
+
     let (sequencing_max_ballot_complete_cycle, sequencing_max_ballot_forward_reference) =
         proposers.forward_ref::<Stream<Ballot, _, _, NoOrder>>();
     let (a_log_complete_cycle, a_log_forward_reference) =
@@ -173,6 +187,7 @@ pub unsafe fn paxos_core<'a, P: PaxosPayload>(
             config,
             sequencing_max_ballot_forward_reference,
             a_log_forward_reference,
+            a_durable_log.clone(),
         )
     };
 
@@ -204,6 +219,7 @@ pub unsafe fn paxos_core<'a, P: PaxosPayload>(
             p_relevant_p1bs,
             f,
             a_max_ballot,
+            a_durable_log,
         )
     };
 
@@ -222,10 +238,15 @@ pub unsafe fn paxos_core<'a, P: PaxosPayload>(
     )
 }
 
+/// Implements the leader election phase (phase 1) of Paxos, shared by [`paxos_core`] and
+/// Compartmentalized Paxos.
+///
+/// # Safety
+/// Proposers non-deterministically time out the current leader and compete to become the
+/// next one, so the elected ballots depend on timing.
 #[expect(
     clippy::type_complexity,
     clippy::too_many_arguments,
-    clippy::missing_safety_doc,
     reason = "internal paxos code // TODO"
 )]
 pub unsafe fn leader_election<'a, L: Clone + Debug + Serialize + DeserializeOwned>(
@@ -238,6 +259,7 @@ pub unsafe fn leader_election<'a, L: Clone + Debug + Serialize + DeserializeOwne
     paxos_config: PaxosConfig,
     p_received_p2b_ballots: Stream<Ballot, Cluster<'a, Proposer>, Unbounded, NoOrder>,
     a_log: Singleton<(Option<usize>, L), Tick<Cluster<'a, Acceptor>>, Bounded>,
+    a_durable_log: Option<Singleton<AcceptorLog, Tick<Cluster<'a, Acceptor>>, Bounded>>,
 ) -> (
     Singleton<Ballot, Tick<Cluster<'a, Proposer>>, Bounded>,
     Optional<(), Tick<Cluster<'a, Proposer>>, Bounded>,
@@ -298,6 +320,7 @@ pub unsafe fn leader_election<'a, L: Clone + Debug + Serialize + DeserializeOwne
             p_to_acceptors_p1a.tick_batch(acceptor_tick)
         },
         a_log,
+        a_durable_log,
         proposers,
     );
 
@@ -427,14 +450,28 @@ fn acceptor_p1<'a, L: Serialize + DeserializeOwned + Clone>(
     acceptor_tick: &Tick<Cluster<'a, Acceptor>>,
     p_to_acceptors_p1a: Stream<Ballot, Tick<Cluster<'a, Acceptor>>, Bounded, NoOrder>,
     a_log: Singleton<(Option<usize>, L), Tick<Cluster<'a, Acceptor>>, Bounded>,
+    a_durable_log: Option<Singleton<AcceptorLog, Tick<Cluster<'a, Acceptor>>, Bounded>>,
     proposers: &Cluster<'a, Proposer>,
 ) -> (
     Singleton<Ballot, Tick<Cluster<'a, Acceptor>>, Bounded>,
     Stream<(Ballot, Result<(Option<usize>, L), Ballot>), Cluster<'a, Proposer>, Unbounded, NoOrder>,
 ) {
-    let a_max_ballot = p_to_acceptors_p1a
+    let a_ballots = p_to_acceptors_p1a
         .clone()
-        .inspect(q!(|p1a| println!("Acceptor received P1a: {:?}", p1a)))
+        .inspect(q!(|p1a| println!("Acceptor received P1a: {:?}", p1a)));
+    let a_ballots = if let Some(a_durable_log) = &a_durable_log {
+        // rejoin with the highest ballot promised before restarting
+        a_ballots.chain(
+            a_durable_log
+                .clone()
+                .continue_if(acceptor_tick.optional_first_tick(q!(())))
+                .flat_map_unordered(q!(|log| log.max_ballot())),
+        )
+    } else {
+        a_ballots
+    };
+
+    let a_max_ballot = a_ballots
         .persist()
         .max()
         .unwrap_or(acceptor_tick.singleton(q!(Ballot {
@@ -442,22 +479,41 @@ fn acceptor_p1<'a, L: Serialize + DeserializeOwned + Clone>(
             proposer_id: ClusterId::from_raw(0)
         })));
 
-    (
-        a_max_ballot.clone(),
-        p_to_acceptors_p1a
-            .cross_singleton(a_max_ballot)
-            .cross_singleton(a_log)
-            .map(q!(|((ballot, max_ballot), log)| (
-                ballot.proposer_id,
-                (
-                    ballot,
-                    if ballot == max_ballot {
-                        Ok(log)
-                    } else {
-                        Err(max_ballot)
+    let a_to_proposers_p1b = p_to_acceptors_p1a
+        .cross_singleton(a_max_ballot.clone())
+        .cross_singleton(a_log)
+        .map(q!(|((ballot, max_ballot), log)| (
+            ballot.proposer_id,
+            (
+                ballot,
+                if ballot == max_ballot {
+                    Ok(log)
+                } else {
+                    Err(max_ballot)
+                }
+            )
+        )));
+
+    let a_to_proposers_p1b = if let Some(a_durable_log) = a_durable_log {
+        // the promise must be durable before it is sent, otherwise the proposer times out
+        a_to_proposers_p1b
+            .cross_singleton(a_max_ballot.clone().zip(a_durable_log))
+            .filter_map(q!(
+                |(p1b, (max_ballot, log))| match log.persist_ballot(max_ballot) {
+                    Ok(()) => Some(p1b),
+                    Err(err) => {
+                        eprintln!("Acceptor failed to persist ballot: {}", err);
+                        None
                     }
-                )
-            )))
+                }
+            ))
+    } else {
+        a_to_proposers_p1b
+    };
+
+    (
+        a_max_ballot,
+        a_to_proposers_p1b
             .all_ticks()
             .send_bincode_anonymous(proposers),
     )
@@ -631,6 +687,7 @@ unsafe fn sequence_payload<'a, P: PaxosPayload>(
     f: usize,
 
     a_max_ballot: Singleton<Ballot, Tick<Cluster<'a, Acceptor>>, Bounded>,
+    a_durable_log: Option<Singleton<AcceptorLog, Tick<Cluster<'a, Acceptor>>, Bounded>>,
 ) -> (
     Stream<(usize, Option<P>), Cluster<'a, Proposer>, Unbounded, NoOrder>,
     Singleton<
@@ -677,6 +734,7 @@ unsafe fn sequence_payload<'a, P: PaxosPayload>(
             }))
             .broadcast_bincode_anonymous(acceptors),
         a_checkpoint,
+        a_durable_log,
         proposers,
     );
 
@@ -703,6 +761,8 @@ unsafe fn sequence_payload<'a, P: PaxosPayload>(
 pub enum CheckpointOrP2a<P, S> {
     Checkpoint(usize),
     P2a(P2a<P, S>),
+    /// A log entry recovered from durable storage when the acceptor restarted.
+    Recovered(usize, LogValue<P>),
 }
 
 // Proposer logic to send p2as, outputting the next slot and the p2as to send to acceptors.
@@ -742,6 +802,7 @@ pub fn acceptor_p2<'a, P: PaxosPayload, S: Clone>(
     a_max_ballot: Singleton<Ballot, Tick<Cluster<'a, Acceptor>>, Bounded>,
     p_to_acceptors_p2a: Stream<P2a<P, S>, Cluster<'a, Acceptor>, Unbounded, NoOrder>,
     a_checkpoint: Optional<usize, Cluster<'a, Acceptor>, Unbounded>,
+    a_durable_log: Option<Singleton<AcceptorLog, Tick<Cluster<'a, Acceptor>>, Bounded>>,
     proposers: &Cluster<'a, S>,
 ) -> (
     Singleton<
@@ -764,7 +825,20 @@ pub fn acceptor_p2<'a, P: PaxosPayload, S: Clone>(
         // since a delayed garbage collection does not affect correctness
         a_checkpoint.latest_tick(acceptor_tick)
     }
-    .delta()
+    .delta();
+    let a_new_checkpoint = if let Some(a_durable_log) = &a_durable_log {
+        a_new_checkpoint
+            .zip(a_durable_log.clone())
+            .map(q!(|(min_seq, log)| {
+                // a lost checkpoint only means that extra values are recovered
+                if let Err(err) = log.persist_checkpoint(min_seq) {
+                    eprintln!("Acceptor failed to persist checkpoint: {}", err);
+                }
+                min_seq
+            }))
+    } else {
+        a_new_checkpoint
+    }
     .map(q!(|min_seq| CheckpointOrP2a::Checkpoint(min_seq)));
     // .inspect(q!(|(min_seq, p2a): &(i32, P2a)| println!("Acceptor new checkpoint: {:?}", min_seq)));
 
@@ -778,6 +852,31 @@ pub fn acceptor_p2<'a, P: PaxosPayload, S: Clone>(
                 None
             }
         ));
+    let a_p2as_to_place_in_log = if let Some(a_durable_log) = &a_durable_log {
+        a_p2as_to_place_in_log.chain(
+            a_durable_log
+                .clone()
+                .continue_if(acceptor_tick.optional_first_tick(q!(())))
+                .flat_map_unordered(q!(|log| {
+                    let recovered = log
+                        .take_recovered()
+                        .expect("Failed to decode the recovered acceptor log.");
+                    recovered
+                        .checkpoint
+                        .map(CheckpointOrP2a::Checkpoint)
+                        .into_iter()
+                        .chain(
+                            recovered
+                                .entries
+                                .into_iter()
+                                .map(|(slot, value)| CheckpointOrP2a::Recovered(slot, value)),
+                        )
+                })),
+        )
+    } else {
+        a_p2as_to_place_in_log
+    };
+
     let a_log = a_p2as_to_place_in_log
         .chain(a_new_checkpoint.into_stream())
         .all_ticks_atomic()
@@ -814,12 +913,76 @@ pub fn acceptor_p2<'a, P: PaxosPayload, S: Clone>(
                             );
                         }
                     }
+                    CheckpointOrP2a::Recovered(slot, value) => {
+                        if prev_checkpoint.map(|prev| slot > prev).unwrap_or(true)
+                            && log
+                                .get(&slot)
+                                .map(|prev_value: &LogValue<_>| value.ballot > prev_value.ballot)
+                                .unwrap_or(true)
+                        {
+                            log.insert(slot, value);
+                        }
+                    }
                 }
             }),
         );
 
-    let a_to_proposers_p2b = p_to_acceptors_p2a_batch
-        .cross_singleton(a_max_ballot)
+    let a_p2as_with_max_ballot = p_to_acceptors_p2a_batch.cross_singleton(a_max_ballot);
+    let a_p2as_with_max_ballot = if let Some(a_durable_log) = a_durable_log {
+        // all values accepted in this batch must be durable before they are acknowledged
+        let persisted = a_p2as_with_max_ballot
+            .cross_singleton(a_durable_log.clone())
+            .map(q!(|((p2a, max_ballot), log)| {
+                let persisted = p2a.ballot < max_ballot
+                    || match log.persist_accepted(p2a.slot, p2a.ballot, &p2a.value) {
+                        Ok(()) => true,
+                        Err(err) => {
+                            eprintln!("Acceptor failed to persist accepted value: {}", err);
+                            false
+                        }
+                    };
+                (p2a, max_ballot, persisted)
+            }));
+
+        let synced = persisted
+            .clone()
+            .fold_commutative(
+                q!(|| false),
+                q!(|accepted_any, (p2a, max_ballot, persisted)| {
+                    *accepted_any |= persisted && p2a.ballot >= max_ballot;
+                }),
+            )
+            .zip(a_durable_log)
+            .map(q!(|(accepted_any, log)| {
+                if !accepted_any {
+                    return true;
+                }
+
+                match log.sync() {
+                    Ok(()) => true,
+                    Err(err) => {
+                        eprintln!("Acceptor failed to sync accepted values: {}", err);
+                        false
+                    }
+                }
+            }));
+
+        // values that failed to persist are not acknowledged, so the proposer retries them
+        persisted.cross_singleton(synced).filter_map(q!(|(
+            (p2a, max_ballot, persisted),
+            synced,
+        )| {
+            if persisted && (synced || p2a.ballot < max_ballot) {
+                Some((p2a, max_ballot))
+            } else {
+                None
+            }
+        }))
+    } else {
+        a_p2as_with_max_ballot
+    };
+
+    let a_to_proposers_p2b = a_p2as_with_max_ballot
         .map(q!(|(p2a, max_ballot)| (
             p2a.sender,
             (
@@ -0,0 +1,389 @@
+//! Durable storage for the state of Paxos acceptors, so that acceptors can restart without
+//! losing the ballots they have promised or the values they have accepted.
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use hydro_lang::*;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+use super::paxos::{Acceptor, Ballot, LogValue};
+
+const BALLOT_RECORD: u8 = 0;
+const ACCEPTED_RECORD: u8 = 1;
+const CHECKPOINT_RECORD: u8 = 2;
+
+/// A durable, append-only log of opaque records, used by [`AcceptorLog`] to persist the state of
+/// a Paxos acceptor.
+pub trait AcceptorStorage {
+    /// Reads back every record that was appended before the last successful
+    /// [`AcceptorStorage::sync`], in the order they were appended.
+    fn read_all(&mut self) -> io::Result<Vec<Vec<u8>>>;
+
+    /// Appends a record, which does not have to be durable until the next
+    /// [`AcceptorStorage::sync`].
+    fn append(&mut self, record: &[u8]) -> io::Result<()>;
+
+    /// Makes all appended records durable.
+    fn sync(&mut self) -> io::Result<()>;
+
+    /// Atomically replaces all records in the log with `records`, which are durable on return.
+    fn replace_all(&mut self, records: &[Vec<u8>]) -> io::Result<()>;
+}
+
+/// An [`AcceptorStorage`] backed by a length-prefixed log file in a local directory.
+pub struct LocalFileStorage {
+    dir: PathBuf,
+    file: BufWriter<File>,
+}
+
+impl LocalFileStorage {
+    const LOG_FILE: &str = "acceptor.log";
+    const LOG_TMP_FILE: &str = "acceptor.log.tmp";
+
+    /// Opens the log in `dir`, creating the directory and an empty log if they do not exist.
+    pub fn open(dir: impl Into<PathBuf>) -> io::Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+        let file = Self::open_log(&dir)?;
+        Ok(LocalFileStorage { dir, file })
+    }
+
+    fn open_log(dir: &Path) -> io::Result<BufWriter<File>> {
+        let file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(dir.join(Self::LOG_FILE))?;
+        Ok(BufWriter::new(file))
+    }
+}
+
+impl AcceptorStorage for LocalFileStorage {
+    fn read_all(&mut self) -> io::Result<Vec<Vec<u8>>> {
+        self.file.flush()?;
+        let mut bytes = Vec::new();
+        File::open(self.dir.join(Self::LOG_FILE))?.read_to_end(&mut bytes)?;
+
+        let mut records = Vec::new();
+        let mut valid_len = 0;
+        while let Some(len_bytes) = bytes.get(valid_len..valid_len + 4) {
+            let len = u32::from_le_bytes(len_bytes.try_into().unwrap()) as usize;
+            let Some(record) = bytes.get(valid_len + 4..valid_len + 4 + len) else {
+                break;
+            };
+            records.push(record.to_vec());
+            valid_len += 4 + len;
+        }
+
+        if valid_len < bytes.len() {
+            // drop a record that was only partially written before a crash
+            self.file.get_ref().set_len(valid_len as u64)?;
+            self.file.get_ref().sync_all()?;
+        }
+
+        Ok(records)
+    }
+
+    fn append(&mut self, record: &[u8]) -> io::Result<()> {
+        let len = u32::try_from(record.len()).map_err(io::Error::other)?;
+        self.file.write_all(&len.to_le_bytes())?;
+        self.file.write_all(record)
+    }
+
+    fn sync(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        self.file.get_ref().sync_data()
+    }
+
+    fn replace_all(&mut self, records: &[Vec<u8>]) -> io::Result<()> {
+        self.file.flush()?;
+
+        let tmp_path = self.dir.join(Self::LOG_TMP_FILE);
+        let mut tmp = BufWriter::new(File::create(&tmp_path)?);
+        for record in records {
+            let len = u32::try_from(record.len()).map_err(io::Error::other)?;
+            tmp.write_all(&len.to_le_bytes())?;
+            tmp.write_all(record)?;
+        }
+        tmp.into_inner()?.sync_all()?;
+
+        std::fs::rename(&tmp_path, self.dir.join(Self::LOG_FILE))?;
+        // Make sure the rename itself is durable.
+        #[cfg(unix)]
+        File::open(&self.dir)?.sync_all()?;
+
+        self.file = Self::open_log(&self.dir)?;
+        Ok(())
+    }
+}
+
+/// The log of a Paxos acceptor that was recovered from its [`AcceptorStorage`].
+pub struct RecoveredLog<P> {
+    pub checkpoint: Option<usize>,
+    pub entries: Vec<(usize, LogValue<P>)>,
+}
+
+struct AcceptorLogState {
+    storage: Box<dyn AcceptorStorage>,
+    max_ballot: Option<Ballot>,
+    recovered_checkpoint: Option<usize>,
+    /// The raw accepted records recovered from storage, by slot, until they are taken.
+    recovered_entries: BTreeMap<usize, Vec<u8>>,
+}
+
+/// A handle to the persisted state of a Paxos acceptor, which is passed to
+/// [`CorePaxos`](super::paxos::CorePaxos) as a singleton on the acceptors.
+///
+/// Opening the log recovers the state persisted by a previous run of the acceptor and compacts
+/// the storage to only contain that state. The acceptor then persists each ballot before
+/// promising it and syncs each batch of accepted values before acknowledging them.
+///
+/// Cloning an [`AcceptorLog`] produces another handle to the same log.
+#[derive(Clone)]
+pub struct AcceptorLog {
+    state: Rc<RefCell<AcceptorLogState>>,
+}
+
+impl AcceptorLog {
+    /// Opens the log stored in `storage`, recovering and compacting any state persisted by a
+    /// previous run.
+    pub fn open(mut storage: impl AcceptorStorage + 'static) -> io::Result<Self> {
+        let mut max_ballot = None;
+        let mut checkpoint = None;
+        let mut entries = BTreeMap::<usize, (Ballot, Vec<u8>)>::new();
+
+        for record in storage.read_all()? {
+            let (&tag, body) = record.split_first().ok_or_else(malformed_record)?;
+            match tag {
+                BALLOT_RECORD => {
+                    let ballot: Ballot = bincode::deserialize(body).map_err(io::Error::other)?;
+                    max_ballot = max_ballot.max(Some(ballot));
+                }
+                ACCEPTED_RECORD => {
+                    // the value follows the slot and ballot, so we can decode just the prefix
+                    let (slot, ballot): (usize, Ballot) =
+                        bincode::deserialize(body).map_err(io::Error::other)?;
+                    max_ballot = max_ballot.max(Some(ballot));
+                    if entries.get(&slot).is_none_or(|(prev, _)| ballot > *prev) {
+                        entries.insert(slot, (ballot, record));
+                    }
+                }
+                CHECKPOINT_RECORD => {
+                    let new_checkpoint: usize =
+                        bincode::deserialize(body).map_err(io::Error::other)?;
+                    checkpoint = checkpoint.max(Some(new_checkpoint));
+                }
+                _ => return Err(malformed_record()),
+            }
+        }
+
+        if let Some(checkpoint) = checkpoint {
+            entries = entries.split_off(&checkpoint);
+        }
+
+        let mut compacted = Vec::new();
+        if let Some(ballot) = max_ballot {
+            compacted.push(encode_record(BALLOT_RECORD, &ballot));
+        }
+        if let Some(checkpoint) = checkpoint {
+            compacted.push(encode_record(CHECKPOINT_RECORD, &checkpoint));
+        }
+        compacted.extend(entries.values().map(|(_, record)| record.clone()));
+        storage.replace_all(&compacted)?;
+
+        Ok(AcceptorLog {
+            state: Rc::new(RefCell::new(AcceptorLogState {
+                storage: Box::new(storage),
+                max_ballot,
+                recovered_checkpoint: checkpoint,
+                recovered_entries: entries
+                    .into_iter()
+                    .map(|(slot, (_, record))| (slot, record))
+                    .collect(),
+            })),
+        })
+    }
+
+    /// The highest ballot that has been persisted, including the ballots of accepted values.
+    pub fn max_ballot(&self) -> Option<Ballot> {
+        self.state.borrow().max_ballot
+    }
+
+    /// Takes the checkpoint and accepted values recovered when the log was opened. Later calls
+    /// return an empty log. Fails if a recovered value cannot be decoded as a `P`.
+    pub fn take_recovered<P: DeserializeOwned>(&self) -> io::Result<RecoveredLog<P>> {
+        let mut state = self.state.borrow_mut();
+        let entries = std::mem::take(&mut state.recovered_entries)
+            .into_iter()
+            .map(|(slot, record)| {
+                let (_, ballot, value): (usize, Ballot, Option<P>) =
+                    bincode::deserialize(&record[1..]).map_err(|_| malformed_record())?;
+                Ok((slot, LogValue { ballot, value }))
+            })
+            .collect::<io::Result<_>>()?;
+
+        Ok(RecoveredLog {
+            checkpoint: state.recovered_checkpoint.take(),
+            entries,
+        })
+    }
+
+    /// Durably persists a ballot promised by the acceptor, if it is higher than any ballot
+    /// persisted before. Must succeed before responding to the proposer.
+    pub fn persist_ballot(&self, ballot: Ballot) -> io::Result<()> {
+        let mut state = self.state.borrow_mut();
+        if state.max_ballot.is_some_and(|max| max >= ballot) {
+            return Ok(());
+        }
+
+        let record = encode_record(BALLOT_RECORD, &ballot);
+        state.storage.append(&record)?;
+        state.storage.sync()?;
+        state.max_ballot = Some(ballot);
+        Ok(())
+    }
+
+    /// Appends a value accepted by the acceptor, which is durable after the next
+    /// [`AcceptorLog::sync`].
+    pub fn persist_accepted<P: Serialize>(
+        &self,
+        slot: usize,
+        ballot: Ballot,
+        value: &Option<P>,
+    ) -> io::Result<()> {
+        let record = encode_record(ACCEPTED_RECORD, &(slot, ballot, value));
+        let mut state = self.state.borrow_mut();
+        state.storage.append(&record)?;
+        state.max_ballot = state.max_ballot.max(Some(ballot));
+        Ok(())
+    }
+
+    /// Appends a checkpoint, below which accepted values no longer need to be recovered. This
+    /// does not need to be synced, since recovering extra values is safe.
+    pub fn persist_checkpoint(&self, checkpoint: usize) -> io::Result<()> {
+        let record = encode_record(CHECKPOINT_RECORD, &checkpoint);
+        self.state.borrow_mut().storage.append(&record)
+    }
+
+    /// Makes all appended values durable.
+    pub fn sync(&self) -> io::Result<()> {
+        self.state.borrow_mut().storage.sync()
+    }
+}
+
+fn encode_record(tag: u8, body: &impl Serialize) -> Vec<u8> {
+    let mut record = vec![tag];
+    bincode::serialize_into(&mut record, body).unwrap();
+    record
+}
+
+fn malformed_record() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "malformed acceptor log record")
+}
+
+/// Creates an [`AcceptorLog`] on each acceptor, stored in a [`LocalFileStorage`] under a
+/// subdirectory of `dir` named after the acceptor's ID.
+///
+/// An acceptor whose log cannot be opened panics when it starts, since it cannot safely take
+/// part in the protocol without the state it persisted before.
+pub fn local_file_acceptor_log<'a>(
+    acceptors: &Cluster<'a, Acceptor>,
+    dir: &'a str,
+) -> Singleton<AcceptorLog, Cluster<'a, Acceptor>, Unbounded> {
+    acceptors.singleton(q!({
+        let dir = std::path::Path::new(dir).join(format!("acceptor-{}", CLUSTER_SELF_ID.raw_id));
+        LocalFileStorage::open(dir)
+            .and_then(AcceptorLog::open)
+            .expect("Failed to open acceptor log.")
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::OpenOptions;
+    use std::io::Write;
+
+    use hydro_lang::ClusterId;
+
+    use super::*;
+
+    fn ballot(num: u32) -> Ballot {
+        Ballot {
+            num,
+            proposer_id: ClusterId::from_raw(0),
+        }
+    }
+
+    #[test]
+    fn recovers_and_compacts() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let log = AcceptorLog::open(LocalFileStorage::open(dir.path()).unwrap()).unwrap();
+        assert_eq!(log.max_ballot(), None);
+        log.persist_ballot(ballot(1)).unwrap();
+        log.persist_accepted(0, ballot(1), &Some("a".to_owned()))
+            .unwrap();
+        log.persist_accepted(1, ballot(1), &Some("b".to_owned()))
+            .unwrap();
+        log.persist_ballot(ballot(2)).unwrap();
+        log.persist_accepted(1, ballot(2), &None::<String>).unwrap();
+        log.persist_accepted(2, ballot(2), &Some("c".to_owned()))
+            .unwrap();
+        log.persist_checkpoint(1).unwrap();
+        log.sync().unwrap();
+        drop(log);
+
+        for _ in 0..2 {
+            let log = AcceptorLog::open(LocalFileStorage::open(dir.path()).unwrap()).unwrap();
+            assert_eq!(log.max_ballot(), Some(ballot(2)));
+
+            let recovered = log.take_recovered::<String>().unwrap();
+            assert_eq!(recovered.checkpoint, Some(1));
+            assert_eq!(
+                recovered
+                    .entries
+                    .into_iter()
+                    .map(|(slot, entry)| (slot, entry.ballot, entry.value))
+                    .collect::<Vec<_>>(),
+                vec![(1, ballot(2), None), (2, ballot(2), Some("c".to_owned()))]
+            );
+            assert!(log.take_recovered::<String>().unwrap().entries.is_empty());
+        }
+
+        // the log was compacted to the ballot, checkpoint, and two remaining entries
+        let mut storage = LocalFileStorage::open(dir.path()).unwrap();
+        assert_eq!(storage.read_all().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn ignores_partially_written_record() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let log = AcceptorLog::open(LocalFileStorage::open(dir.path()).unwrap()).unwrap();
+        log.persist_ballot(ballot(3)).unwrap();
+        drop(log);
+
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(dir.path().join(LocalFileStorage::LOG_FILE))
+            .unwrap();
+        file.write_all(&[100, 0, 0, 0, ACCEPTED_RECORD]).unwrap();
+        drop(file);
+
+        let log = AcceptorLog::open(LocalFileStorage::open(dir.path()).unwrap()).unwrap();
+        assert_eq!(log.max_ballot(), Some(ballot(3)));
+        assert!(log.take_recovered::<String>().unwrap().entries.is_empty());
+
+        log.persist_ballot(ballot(4)).unwrap();
+        drop(log);
+
+        let log = AcceptorLog::open(LocalFileStorage::open(dir.path()).unwrap()).unwrap();
+        assert_eq!(log.max_ballot(), Some(ballot(4)));
+    }
+}
@@ -1,69 +1,68 @@
-use std::collections::HashMap;
 use std::fmt::Debug;
-use std::hash::Hash;
 
 use hydro_lang::*;
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 
-pub struct Replica {}
-
-pub trait KvKey: Serialize + DeserializeOwned + Hash + Eq + Clone + Debug {}
-impl<K: Serialize + DeserializeOwned + Hash + Eq + Clone + Debug> KvKey for K {}
-
-pub trait KvValue: Serialize + DeserializeOwned + Eq + Clone + Debug {}
-impl<V: Serialize + DeserializeOwned + Eq + Clone + Debug> KvValue for V {}
-
-#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Debug)]
-pub struct KvPayload<K, V> {
-    pub key: K,
-    pub value: V,
-}
+/// A command that can be replicated through a sequenced log.
+pub trait Command: Serialize + DeserializeOwned + Eq + Clone + Debug {}
+impl<C: Serialize + DeserializeOwned + Eq + Clone + Debug> Command for C {}
 
+/// A command that has been assigned a slot `seq` in the replicated log. A slot without a
+/// command is a hole left behind by a leader change, which replicas skip over.
 #[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Debug)]
-pub struct SequencedKv<K, V> {
+pub struct Sequenced<C> {
     // Note: Important that seq is the first member of the struct for sorting
     pub seq: usize,
-    pub kv: Option<KvPayload<K, V>>,
+    pub command: Option<C>,
 }
 
-impl<K: KvKey, V: KvValue> Ord for SequencedKv<K, V> {
+impl<C: Command> Ord for Sequenced<C> {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
         self.seq.cmp(&other.seq)
     }
 }
 
-impl<K: KvKey, V: KvValue> PartialOrd for SequencedKv<K, V> {
+impl<C: Command> PartialOrd for Sequenced<C> {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
         Some(self.cmp(other))
     }
 }
 
-// Replicas. All relations for replicas will be prefixed with r. Expects ReplicaPayload on p_to_replicas, outputs a stream of (client address, ReplicaPayload) after processing.
-#[expect(clippy::type_complexity, reason = "internal paxos code // TODO")]
-pub fn kv_replica<'a, K: KvKey, V: KvValue>(
-    replicas: &Cluster<'a, Replica>,
-    p_to_replicas: impl Into<
-        Stream<(usize, Option<KvPayload<K, V>>), Cluster<'a, Replica>, Unbounded, NoOrder>,
-    >,
+/// Implements the replicas of a replicated state machine, which receive the commands
+/// sequenced by a consensus protocol (such as [`super::paxos::paxos_core`]) in arbitrary order
+/// and release them in slot order.
+///
+/// Returns a stream of checkpoints, emitted every `checkpoint_frequency` slots, which can be
+/// sent to the acceptors so that they garbage-collect their logs, and the stream of commands
+/// in slot order (including holes). Applying these commands in order with a `fold` yields the
+/// state of the replicated state machine.
+///
+/// # Guarantees
+/// Every replica that receives the same sequenced commands outputs exactly the same stream,
+/// regardless of the order and batching in which the commands arrive. A command is only
+/// released once every slot before it has been filled, so a replica stalls on a missing slot
+/// rather than skipping it.
+#[expect(clippy::type_complexity, reason = "stream types with ordering")]
+pub fn state_machine_replica<'a, R, C: Command>(
+    replicas: &Cluster<'a, R>,
+    sequenced: impl Into<Stream<(usize, Option<C>), Cluster<'a, R>, Unbounded, NoOrder>>,
     checkpoint_frequency: usize,
 ) -> (
-    Stream<usize, Cluster<'a, Replica>, Unbounded>,
-    Stream<KvPayload<K, V>, Cluster<'a, Replica>, Unbounded>,
+    Stream<usize, Cluster<'a, R>, Unbounded>,
+    Stream<Sequenced<C>, Cluster<'a, R>, Unbounded>,
 ) {
-    let p_to_replicas: Stream<SequencedKv<K, V>, Cluster<'a, Replica>, Unbounded, NoOrder> =
-        p_to_replicas
-            .into()
-            .map(q!(|(slot, kv)| SequencedKv { seq: slot, kv }));
+    let sequenced: Stream<Sequenced<C>, Cluster<'a, R>, Unbounded, NoOrder> = sequenced
+        .into()
+        .map(q!(|(slot, command)| Sequenced { seq: slot, command }));
 
     let replica_tick = replicas.tick();
 
     let (r_buffered_payloads_complete_cycle, r_buffered_payloads) = replica_tick.cycle();
-    // p_to_replicas.inspect(q!(|payload: ReplicaPayload| println!("Replica received payload: {:?}", payload)));
     let r_sorted_payloads = unsafe {
         // SAFETY: because we fill slots one-by-one, we can safely batch
         // because non-determinism is resolved when we sort by slots
-        p_to_replicas.tick_batch(&replica_tick)
+        sequenced.tick_batch(&replica_tick)
     }
         .chain(r_buffered_payloads) // Combine with all payloads that we've received and not processed yet
         .sort();
@@ -100,18 +99,14 @@ pub fn kv_replica<'a, K: KvKey, V: KvValue>(
     // Save these, we can process them once the hole has been filled
     r_buffered_payloads_complete_cycle.complete_next_tick(r_new_non_processable_payloads);
 
-    let r_kv_store = r_processable_payloads
+    let r_processed_next_slot = r_processable_payloads
         .clone()
         .persist() // Optimization: all_ticks() + fold() = fold<static>, where the state of the previous fold is saved and persisted values are deleted.
-        .fold(q!(|| (HashMap::new(), 0)), q!(|(kv_store, next_slot), payload| {
-            if let Some(kv) = payload.kv {
-                kv_store.insert(kv.key, kv.value);
-            }
+        .fold(q!(|| 0), q!(|next_slot, payload| {
             *next_slot = payload.seq + 1;
         }));
     // Update the highest seq for the next tick
-    r_next_slot_complete_cycle
-        .complete_next_tick(r_kv_store.map(q!(|(_kv_store, next_slot)| next_slot)));
+    r_next_slot_complete_cycle.complete_next_tick(r_processed_next_slot);
 
     // Send checkpoints to the acceptors when we've processed enough payloads
     let (r_checkpointed_seqs_complete_cycle, r_checkpointed_seqs) =
@@ -131,9 +126,8 @@ pub fn kv_replica<'a, K: KvKey, V: KvValue>(
         ));
     r_checkpointed_seqs_complete_cycle.complete_next_tick(r_checkpoint_seq_new.clone());
 
-    // Tell clients that the payload has been committed. All ReplicaPayloads contain the client's machine ID (to string) as value.
-    let r_to_clients = r_processable_payloads
-        .filter_map(q!(|payload| payload.kv))
-        .all_ticks();
-    (r_checkpoint_seq_new.all_ticks(), r_to_clients)
+    (
+        r_checkpoint_seq_new.all_ticks(),
+        r_processable_payloads.all_ticks(),
+    )
 }
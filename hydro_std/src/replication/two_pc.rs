@@ -0,0 +1,105 @@
+use std::fmt::Debug;
+use std::hash::Hash;
+
+use hydro_lang::*;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+use crate::quorum::collect_quorum;
+
+// if the variable start with p, that means current work is at the participant side. if start with c, at coordinator side.
+
+/// A transaction that can be decided by [`two_pc`].
+pub trait Transaction: Serialize + DeserializeOwned + Eq + Hash + Clone + Debug {}
+impl<T: Serialize + DeserializeOwned + Eq + Hash + Clone + Debug> Transaction for T {}
+
+/// The decisions made by [`two_pc`], both as seen by the participants and once they have been
+/// acknowledged by every participant.
+pub struct TwoPcDecisions<'a, T, C, P> {
+    /// The transactions that each participant has been told to commit.
+    pub participant_commits: Stream<T, Cluster<'a, P>, Unbounded, NoOrder>,
+    /// The transactions that each participant has been told to abort.
+    pub participant_aborts: Stream<T, Cluster<'a, P>, Unbounded, NoOrder>,
+    /// The transactions that every participant has acknowledged committing.
+    pub committed: Stream<T, Process<'a, C>, Unbounded, NoOrder>,
+    /// The transactions that every participant has acknowledged aborting.
+    pub aborted: Stream<T, Process<'a, C>, Unbounded, NoOrder>,
+}
+
+/// Implements two-phase commit, where a coordinator atomically commits or aborts each
+/// transaction across a cluster of `num_participants` participants.
+///
+/// The coordinator broadcasts each transaction to the participants, which vote on it with
+/// `participant_votes` (`true` to commit). A transaction is committed only if every
+/// participant votes to commit, and aborted as soon as any participant votes to abort.
+/// The decision is broadcast to the participants, and reported back at the coordinator
+/// once every participant has acknowledged it.
+///
+/// # Guarantees
+/// Each transaction is decided exactly once, and all participants receive the same decision.
+/// Transactions must be unique, since votes are collected by transaction. As with any
+/// two-phase commit, the protocol blocks if a participant or the coordinator fails.
+pub fn two_pc<'a, C: 'a, P: 'a, T: Transaction, Votes>(
+    coordinator: &Process<'a, C>,
+    participants: &Cluster<'a, P>,
+    num_participants: usize,
+    transactions: Stream<T, Process<'a, C>, Unbounded>,
+    participant_votes: impl FnOnce(Stream<T, Cluster<'a, P>, Unbounded>) -> Votes,
+) -> TwoPcDecisions<'a, T, C, P>
+where
+    Votes: Into<Stream<(T, bool), Cluster<'a, P>, Unbounded, NoOrder>>,
+{
+    // broadcast prepare message to participants.
+    let p_receive_prepare = transactions.broadcast_bincode(participants);
+    let c_received_votes = participant_votes(p_receive_prepare)
+        .into()
+        .send_bincode(coordinator);
+
+    // collect votes from participants.
+    let (c_all_commit, c_participant_voted_abort) = collect_quorum(
+        c_received_votes
+            .map(q!(|(id, (t, commit))| (
+                t,
+                if commit { Ok(()) } else { Err(id) }
+            )))
+            .atomic(&coordinator.tick()),
+        num_participants,
+        num_participants,
+    );
+
+    let c_abort = c_participant_voted_abort
+        .end_atomic()
+        .map(q!(|(t, _id)| t))
+        .unique(); // multiple participants may vote to abort the same transaction
+
+    // broadcast decisions to participants.
+    let p_receive_commit = c_all_commit.end_atomic().broadcast_bincode(participants);
+    let p_receive_abort = c_abort.broadcast_bincode(participants);
+
+    // wait for every participant to acknowledge each decision.
+    let (c_committed, _) = collect_quorum::<_, _, _, ()>(
+        p_receive_commit
+            .clone()
+            .send_bincode(coordinator)
+            .map(q!(|(_id, t)| (t, Ok(()))))
+            .atomic(&coordinator.tick()),
+        num_participants,
+        num_participants,
+    );
+    let (c_aborted, _) = collect_quorum::<_, _, _, ()>(
+        p_receive_abort
+            .clone()
+            .send_bincode(coordinator)
+            .map(q!(|(_id, t)| (t, Ok(()))))
+            .atomic(&coordinator.tick()),
+        num_participants,
+        num_participants,
+    );
+
+    TwoPcDecisions {
+        participant_commits: p_receive_commit,
+        participant_aborts: p_receive_abort,
+        committed: c_committed.end_atomic(),
+        aborted: c_aborted.end_atomic(),
+    }
+}
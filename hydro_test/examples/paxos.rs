@@ -3,7 +3,7 @@ use std::sync::Arc;
 use hydro_deploy::gcp::GcpNetwork;
 use hydro_deploy::{Deployment, Host};
 use hydro_lang::deploy::TrybuildHost;
-use hydro_test::cluster::paxos::{CorePaxos, PaxosConfig};
+use hydro_std::replication::paxos::{CorePaxos, PaxosConfig};
 use tokio::sync::RwLock;
 
 type HostCreator = Box<dyn Fn(&mut Deployment) -> Arc<dyn Host>>;
@@ -60,6 +60,7 @@ async fn main() {
                 i_am_leader_check_timeout,
                 i_am_leader_check_timeout_delay_multiplier,
             },
+            acceptor_log: None,
         },
         &clients,
         &replicas,
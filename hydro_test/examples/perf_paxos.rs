@@ -11,7 +11,7 @@ use hydro_lang::rewrites::analyze_counter::COUNTER_PREFIX;
 use hydro_lang::rewrites::analyze_perf::CPU_USAGE_PREFIX;
 use hydro_lang::rewrites::analyze_perf_and_counters::analyze_results;
 use hydro_lang::rewrites::{insert_counter, persist_pullup};
-use hydro_test::cluster::paxos::{CorePaxos, PaxosConfig};
+use hydro_std::replication::paxos::{CorePaxos, PaxosConfig};
 use tokio::sync::RwLock;
 
 type HostCreator = Box<dyn Fn(&mut Deployment) -> Arc<dyn Host>>;
@@ -96,6 +96,7 @@ async fn main() {
                 i_am_leader_check_timeout,
                 i_am_leader_check_timeout_delay_multiplier,
             },
+            acceptor_log: None,
         },
         &clients,
         &replicas,
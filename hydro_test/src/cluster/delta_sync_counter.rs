@@ -0,0 +1,77 @@
+use hydro_lang::*;
+use hydro_std::delta_sync::delta_sync;
+use lattices::crdt::GCounter;
+
+pub struct Replica {}
+
+/// Each replica of a grow-only counter counts its own events, and the replicas converge on the
+/// counts of every replica by gossiping deltas with each other.
+pub fn delta_sync_counter<'a>(replicas: &Cluster<'a, Replica>) {
+    let increments = replicas
+        .source_iter(q!([CLUSTER_SELF_ID.raw_id]))
+        .map(q!(|id| {
+            let mut counter = GCounter::new();
+            counter.increment(id, u64::from(id) + 1);
+            counter
+        }))
+        .into();
+
+    delta_sync(
+        replicas,
+        increments,
+        q!(std::time::Duration::from_millis(100)),
+    )
+    .deltas()
+    .flat_map_unordered(q!(|delta| delta
+        .as_reveal_ref()
+        .iter()
+        .map(|(id, count)| (*id, count))
+        .collect::<Vec<_>>()))
+    .for_each(q!(|(id, count)| println!("replica {} count {}", id, count)));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use hydro_deploy::Deployment;
+    use hydro_lang::deploy::DeployCrateWrapper;
+
+    #[tokio::test]
+    async fn delta_sync_counter() {
+        let mut deployment = Deployment::new();
+
+        let builder = hydro_lang::FlowBuilder::new();
+        let replicas = builder.cluster();
+        super::delta_sync_counter(&replicas);
+
+        let nodes = builder
+            .with_default_optimize()
+            .with_cluster(&replicas, (0..3).map(|_| deployment.Localhost()))
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let stdouts = futures::future::join_all(
+            nodes
+                .get_cluster(&replicas)
+                .members()
+                .iter()
+                .map(|node| node.stdout()),
+        )
+        .await;
+
+        deployment.start().await.unwrap();
+
+        // Every replica eventually receives the count of every replica.
+        let expected = (0..3)
+            .map(|id| format!("replica {} count {}", id, id + 1))
+            .collect::<HashSet<_>>();
+        for mut stdout in stdouts {
+            let mut seen = HashSet::new();
+            while !expected.is_subset(&seen) {
+                seen.insert(stdout.recv().await.unwrap());
+            }
+        }
+    }
+}
@@ -1,12 +1,9 @@
 pub mod chat;
-pub mod compartmentalized_paxos;
 pub mod compute_pi;
+pub mod delta_sync_counter;
 pub mod echo_server;
-pub mod kv_replica;
 pub mod many_to_many;
 pub mod map_reduce;
-pub mod paxos;
 pub mod paxos_bench;
-pub mod paxos_with_client;
 pub mod simple_cluster;
 pub mod two_pc;
@@ -1,9 +1,8 @@
 use hydro_lang::*;
 use hydro_std::bench_client::{bench_client, print_bench_results};
 use hydro_std::quorum::collect_quorum;
-
-use super::kv_replica::{KvPayload, Replica, kv_replica};
-use super::paxos_with_client::PaxosLike;
+use hydro_std::replication::kv_replica::{KvPayload, Replica, kv_replica};
+use hydro_std::replication::paxos_with_client::PaxosLike;
 
 pub struct Client;
 
@@ -102,14 +101,21 @@ pub fn paxos_bench<'a>(
 #[cfg(test)]
 mod tests {
     use dfir_lang::graph::WriteConfig;
-    use hydro_lang::deploy::DeployRuntime;
+    use hydro_deploy::Deployment;
+    use hydro_lang::deploy::{DeployCrateWrapper, DeployRuntime};
+    use hydro_lang::{ClusterId, FlowBuilder};
+    use hydro_std::replication::kv_replica::KvPayload;
+    use hydro_std::replication::paxos::{CorePaxos, PaxosConfig};
+    use hydro_std::replication::paxos_storage::{
+        AcceptorLog, LocalFileStorage, local_file_acceptor_log,
+    };
     use stageleft::RuntimeData;
 
-    use crate::cluster::paxos::{CorePaxos, PaxosConfig};
+    use super::Client;
 
     #[test]
     fn paxos_ir() {
-        let builder = hydro_lang::FlowBuilder::new();
+        let builder = FlowBuilder::new();
         let proposers = builder.cluster();
         let acceptors = builder.cluster();
         let clients = builder.cluster();
@@ -129,6 +135,7 @@ mod tests {
                     i_am_leader_check_timeout: 1,
                     i_am_leader_check_timeout_delay_multiplier: 1,
                 },
+                acceptor_log: None,
             },
             &clients,
             &replicas,
@@ -156,4 +163,120 @@ mod tests {
 
         let _ = built.compile(&RuntimeData::new("FAKE"));
     }
+
+    #[tokio::test]
+    async fn acceptors_recover_after_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir_path = dir.path().to_str().unwrap();
+        let mut persisted_checkpoints: Vec<usize> = vec![];
+
+        for restarted in [false, true] {
+            let mut deployment = Deployment::new();
+
+            let builder = FlowBuilder::new();
+            let proposers = builder.cluster();
+            let acceptors = builder.cluster();
+            let clients = builder.cluster();
+            let replicas = builder.cluster();
+
+            super::paxos_bench(
+                1,
+                1_000_000,
+                1,
+                2,
+                CorePaxos {
+                    proposers: proposers.clone(),
+                    acceptors: acceptors.clone(),
+                    paxos_config: PaxosConfig {
+                        f: 1,
+                        i_am_leader_send_timeout: 1,
+                        i_am_leader_check_timeout: 2,
+                        i_am_leader_check_timeout_delay_multiplier: 1,
+                    },
+                    acceptor_log: Some(local_file_acceptor_log(&acceptors, dir_path)),
+                },
+                &clients,
+                &replicas,
+            );
+
+            let nodes = builder
+                .with_default_optimize()
+                .with_cluster(&proposers, (0..2).map(|_| deployment.Localhost()))
+                .with_cluster(&acceptors, (0..3).map(|_| deployment.Localhost()))
+                .with_cluster(&clients, [deployment.Localhost()])
+                .with_cluster(&replicas, (0..2).map(|_| deployment.Localhost()))
+                .deploy(&mut deployment);
+
+            deployment.deploy().await.unwrap();
+
+            let mut client_stdout = nodes.get_cluster(&clients).members()[0].stdout().await;
+            let proposer_stdouts = futures::future::join_all(
+                nodes
+                    .get_cluster(&proposers)
+                    .members()
+                    .iter()
+                    .map(|proposer| proposer.stdout()),
+            )
+            .await;
+
+            deployment.start().await.unwrap();
+
+            if restarted {
+                // the replicas are not durable, so we only check that the acceptors rejoin with
+                // the state they persisted before the restart, which the leader sees in its P1bs
+                let recovered_p1b = |line: &str| {
+                    line.starts_with("Proposer received P1b")
+                        && (!line.ends_with("{})))")
+                            || persisted_checkpoints
+                                .iter()
+                                .any(|c| line.contains(&format!("Ok((Some({}),", c))))
+                };
+                futures::future::select_ok(proposer_stdouts.into_iter().map(|mut stdout| {
+                    Box::pin(async move {
+                        while let Some(line) = stdout.recv().await {
+                            if recovered_p1b(&line) {
+                                return Ok(());
+                            }
+                        }
+                        Err(())
+                    })
+                }))
+                .await
+                .unwrap();
+
+                deployment.stop().await.unwrap();
+                continue;
+            }
+
+            // wait until some payloads have been committed
+            loop {
+                let line = client_stdout.recv().await.unwrap();
+                if line.starts_with("Latency") && !line.ends_with("(0 samples)") {
+                    break;
+                }
+            }
+
+            deployment.stop().await.unwrap();
+            drop(nodes);
+
+            let mut acceptors_with_values = 0;
+            for i in 0..3 {
+                let log = AcceptorLog::open(
+                    LocalFileStorage::open(dir.path().join(format!("acceptor-{}", i))).unwrap(),
+                )
+                .unwrap();
+                let recovered = log
+                    .take_recovered::<KvPayload<u32, (ClusterId<Client>, u32)>>()
+                    .unwrap();
+                // values below the checkpoint have been compacted away
+                persisted_checkpoints.extend(recovered.checkpoint);
+                if recovered.checkpoint.is_some() || !recovered.entries.is_empty() {
+                    acceptors_with_values += 1;
+                }
+            }
+
+            // a quorum of acceptors must have persisted the committed values
+            assert!(acceptors_with_values >= 2);
+        }
+    }
 }
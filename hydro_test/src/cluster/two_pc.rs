@@ -1,8 +1,5 @@
 use hydro_lang::*;
-use hydro_std::quorum::collect_quorum;
-
-// if the variable start with p, that means current work is at the participant side. if start with c, at coordinator side.
-//
+use hydro_std::replication::two_pc::two_pc as two_pc_core;
 
 pub struct Participants {}
 
@@ -30,63 +27,40 @@ pub fn two_pc<'a>(
     // assume 3 transactions are generated from 0 to 3
     let client_transaction = client.source_iter(q!(0..3));
 
-    let c_receive_client_transactions = client_transaction.send_bincode(&coordinator);
-    c_receive_client_transactions
-        .clone()
-        .for_each(q!(|t| println!(
-            "receive transaction {}, ready to broadcast",
-            t
-        )));
-
-    // broadcast prepare message to participants.
-    let p_receive_prepare = c_receive_client_transactions.broadcast_bincode(&participants);
+    let c_receive_client_transactions =
+        client_transaction
+            .send_bincode(&coordinator)
+            .inspect(q!(|t| println!(
+                "receive transaction {}, ready to broadcast",
+                t
+            )));
 
-    // participant 1 aborts transaction 1
-    let p_ready_to_commit = p_receive_prepare.map(q!(move |t| (
-        t,
-        if t == 1 && CLUSTER_SELF_ID.raw_id == 1 {
-            "abort".to_string()
-        } else {
-            "commit".to_string()
-        }
-    )));
-    let c_received_reply = p_ready_to_commit.send_bincode(&coordinator);
-    // c_received_reply.clone().inspect(q!(|(id, (t, reply))| println!("participant {id} said {reply} for transaction {t}")));
-
-    // collect votes from participant.
-    let coordinator_tick = coordinator.tick();
-    let (c_all_commit, c_participant_voted_abort) = collect_quorum(
-        c_received_reply
-            .map(q!(|(id, (t, reply))| (
-                t,
-                if reply == "commit" { Ok(()) } else { Err(id) }
-            )))
-            .atomic(&coordinator_tick),
-        num_participants as usize,
+    let decisions = two_pc_core(
+        &coordinator,
+        &participants,
         num_participants as usize,
+        c_receive_client_transactions,
+        |p_receive_prepare| {
+            // participant 1 aborts transaction 1
+            p_receive_prepare.map(q!(move |t| (t, !(t == 1 && CLUSTER_SELF_ID.raw_id == 1))))
+        },
     );
 
-    let p_receive_abort = c_participant_voted_abort
-        // TODO(shadaj): if multiple participants vote abort we should deduplicate
-        .inspect(q!(|(t, id)| println!(
-            "{} vote abort for transaction {}",
-            id, t
-        )))
-        .broadcast_bincode(&participants);
-    let c_receive_ack = p_receive_abort.send_bincode(&coordinator);
-    c_receive_ack.for_each(q!(|(id, (t, _))| println!(
-        "Coordinator receive participant {} abort for transaction {}",
-        id, t
-    )));
-
-    // broadcast commit transactions to participants.
-    let p_receive_commit = c_all_commit.broadcast_bincode(&participants);
-    // p_receive_commit.clone().for_each(q!(|t| println!("commit for transaction {}", t)));
+    decisions
+        .participant_commits
+        .for_each(q!(|t| println!("commit for transaction {}", t)));
+    decisions
+        .participant_aborts
+        .for_each(q!(|t| println!("abort for transaction {}", t)));
 
-    let c_receive_ack = p_receive_commit.send_bincode(&coordinator);
-    c_receive_ack.for_each(q!(|(id, t)| println!(
-        "receive participant {} commit for transaction {}",
-        id, t
+    decisions.committed.for_each(q!(|t| println!(
+        "all participants committed transaction {}",
+        t
     )));
+    decisions.aborted.for_each(q!(|t| println!(
+        "all participants aborted transaction {}",
+        t
+    )));
+
     (coordinator, participants, client)
 }
@@ -0,0 +1,98 @@
+use hydro_lang::*;
+
+pub struct Leader {}
+pub struct Worker {}
+
+/// Each worker holds the edges of a graph. The leader broadcasts queries for nodes to the workers,
+/// which reply with the number of nodes reachable from each queried node and whether it is on a
+/// cycle, to be printed by the leader.
+pub fn datalog_reachability<'a>(leader: &Process<'a, Leader>, workers: &Cluster<'a, Worker>) {
+    let edges = workers.source_iter(q!(vec![(1, 2), (2, 3), (3, 4), (4, 2), (5, 1)]));
+    let queries = leader.source_iter(q!(vec![(1,), (5,)]));
+
+    unsafe {
+        // SAFETY: each reply only depends on its query and on the edges, which all arrive in the
+        // first tick of each worker.
+        dfir_datalog::datalog_hydro!(
+            r#"
+            .process leader `leader`
+            .cluster workers `workers`
+
+            .input edges@workers `edges`
+            .input queries@leader `queries`
+            .output reach_counts `for_each(q!(|(node, count)| println!("reaches {} {}", node, count)))`
+            .output acyclic_nodes `for_each(q!(|(node,)| println!("acyclic {}", node)))`
+
+            .persist edges
+
+            worker_queries@workers(x) :~ queries(x)
+            path(x, y) :- edges(x, y)
+            path(x, z) :- path(x, y), edges(y, z)
+            reaches(x, count(*)) :- worker_queries(x), path(x, _)
+            acyclic(x) :- worker_queries(x), !path(x, x)
+
+            reach_counts@leader(x, c) :~ reaches(x, c)
+            acyclic_nodes@leader(x) :~ acyclic(x)
+            "#
+        )
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use hydro_deploy::Deployment;
+    use hydro_lang::deploy::DeployCrateWrapper;
+
+    #[tokio::test]
+    async fn datalog_reachability() {
+        let mut deployment = Deployment::new();
+
+        let builder = hydro_lang::FlowBuilder::new();
+        let leader = builder.process();
+        let workers = builder.cluster();
+        super::datalog_reachability(&leader, &workers);
+
+        let built = builder.with_default_optimize();
+
+        insta::assert_debug_snapshot!(built.ir());
+
+        let nodes = built
+            .with_process(&leader, deployment.Localhost())
+            .with_cluster(&workers, (0..2).map(|_| deployment.Localhost()))
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut leader_stdout = nodes.get_process(&leader).stdout().await;
+
+        deployment.start().await.unwrap();
+
+        // Each of the two workers replies to each query once.
+        let mut lines = Vec::new();
+        for _ in 0..8 {
+            lines.push(leader_stdout.recv().await.unwrap());
+        }
+        lines.sort();
+        assert_eq!(
+            lines,
+            [
+                "acyclic 1",
+                "acyclic 1",
+                "acyclic 5",
+                "acyclic 5",
+                "reaches 1 3",
+                "reaches 1 3",
+                "reaches 5 4",
+                "reaches 5 4",
+            ]
+        );
+        assert!(
+            tokio::time::timeout(Duration::from_secs(1), leader_stdout.recv())
+                .await
+                .is_err(),
+            "unexpected output after the replies"
+        );
+    }
+}
@@ -0,0 +1,79 @@
+use hydro_lang::*;
+use lattices::{Max, Min};
+
+pub struct Leader {}
+pub struct Worker {}
+
+/// Each worker reports readings for a set of sensors, which the leader merges into the largest
+/// and smallest reading of each sensor. The readings are sent as lattice deltas, so the leader
+/// computes the same ranges regardless of the order in which the workers' messages arrive.
+pub fn lattice_ranges<'a>(leader: &Process<'a, Leader>, workers: &Cluster<'a, Worker>) {
+    let readings = workers.source_iter(q!(vec![
+        ('a', CLUSTER_SELF_ID.raw_id + 1),
+        ('b', (CLUSTER_SELF_ID.raw_id + 1) * 10),
+    ]));
+
+    let maxes = readings
+        .clone()
+        .map(q!(|(sensor, reading)| (sensor, Max::new(reading))))
+        .into_keyed_lattice()
+        .send_bincode_anonymous(leader);
+    let mins = readings
+        .map(q!(|(sensor, reading)| (sensor, Min::new(reading))))
+        .into_keyed_lattice()
+        .send_bincode_anonymous(leader);
+
+    maxes
+        .map_values(q!(lattices::closure_to_morphism(|max: Max<u32>| Max::new(
+            max.into_reveal() * 2
+        ))))
+        .join(mins, q!(lattices::PairBimorphism))
+        .deltas()
+        .for_each(q!(|(sensor, range)| {
+            let (max, min) = range.into_reveal();
+            println!("{} {} {}", sensor, min.into_reveal(), max.into_reveal());
+        }));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use hydro_deploy::Deployment;
+    use hydro_lang::deploy::DeployCrateWrapper;
+
+    #[tokio::test]
+    async fn lattice_ranges() {
+        let mut deployment = Deployment::new();
+
+        let builder = hydro_lang::FlowBuilder::new();
+        let leader = builder.process();
+        let workers = builder.cluster();
+        super::lattice_ranges(&leader, &workers);
+
+        let built = builder.with_default_optimize();
+
+        insta::assert_debug_snapshot!(built.ir());
+
+        let nodes = built
+            .with_process(&leader, deployment.Localhost())
+            .with_cluster(&workers, (0..2).map(|_| deployment.Localhost()))
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut leader_stdout = nodes.get_process(&leader).stdout().await;
+
+        deployment.start().await.unwrap();
+
+        // Partial ranges are printed while the readings are still arriving.
+        let expected = ["a 1 4", "b 10 40"]
+            .map(String::from)
+            .into_iter()
+            .collect::<HashSet<_>>();
+        let mut seen = HashSet::new();
+        while !expected.is_subset(&seen) {
+            seen.insert(leader_stdout.recv().await.unwrap());
+        }
+    }
+}
@@ -1 +1,3 @@
+pub mod datalog;
 pub mod first_ten;
+pub mod lattice_ranges;
@@ -5,4 +5,9 @@ pub mod first_ten;
 pub mod futures;
 pub mod graph_reachability;
 pub mod negation;
+pub mod sim_broadcast;
+pub mod sim_buffered_send;
+pub mod sim_chain_replication;
+pub mod sim_fold;
+pub mod sim_membership;
 pub mod teed_join;
@@ -0,0 +1,117 @@
+use dfir_rs::scheduled::graph::Dfir;
+use hydro_lang::deploy::SimDeploy;
+use hydro_lang::sim_runtime::SimPorts;
+use hydro_lang::*;
+use stageleft::{Quoted, RuntimeData};
+use tokio::sync::mpsc::UnboundedSender;
+
+struct Leader {}
+struct Worker {}
+
+#[stageleft::entry]
+pub fn sim_broadcast<'a>(
+    flow: FlowBuilder<'a>,
+    env: RuntimeData<&'a SimPorts>,
+    output: RuntimeData<&'a UnboundedSender<(u32, u32)>>,
+) -> impl Quoted<'a, Dfir<'a>> {
+    let leader = flow.process::<Leader>();
+    let workers = flow.cluster::<Worker>();
+
+    leader
+        .source_iter(q!(0..3))
+        .broadcast_bincode(&workers)
+        .map(q!(|n| n * 2))
+        .send_bincode(&leader)
+        .for_each(q!(|(id, n)| {
+            output.send((id.raw_id, n)).unwrap();
+        }));
+
+    flow.compile::<SimDeploy>(&env)
+        .with_dynamic_id(q!(env.location_id()))
+}
+
+#[cfg(stageleft_runtime)]
+#[cfg(test)]
+mod tests {
+    use dfir_rs::util::collect_ready_async;
+    use hydro_lang::sim_runtime::{SimNetwork, SimStep, Simulation};
+
+    async fn run_random(seed: u64, trace: Option<&[SimStep]>) -> (Vec<SimStep>, Vec<(u32, u32)>) {
+        let network = SimNetwork::new().with_process(0).with_cluster(1, 3);
+        let members = network.members();
+        let (out, mut out_recv) = dfir_rs::util::unbounded_channel();
+
+        let mut sim = Simulation::new(&network, seed).with_drop_rate(0.1);
+        for ports in &members {
+            sim.add_member(ports, super::sim_broadcast!(ports, &out));
+        }
+
+        let trace = if let Some(trace) = trace {
+            sim.replay(trace).await;
+            trace.to_vec()
+        } else {
+            sim.run_random(100).await
+        };
+
+        (trace, collect_ready_async(&mut out_recv).await)
+    }
+
+    #[tokio::test]
+    async fn test_sim_broadcast() {
+        tokio::task::LocalSet::new()
+            .run_until(async {
+                let network = SimNetwork::new().with_process(0).with_cluster(1, 3);
+                let members = network.members();
+                let (out, mut out_recv) = dfir_rs::util::unbounded_channel();
+
+                let mut sim = Simulation::new(&network, 0);
+                for ports in &members {
+                    sim.add_member(ports, super::sim_broadcast!(ports, &out));
+                }
+
+                // Nothing is received until the broadcast is delivered.
+                sim.tick_all().await;
+                assert_eq!(sim.network().in_flight_len(), 9);
+                sim.tick_all().await;
+                assert!(
+                    collect_ready_async::<Vec<_>, _>(&mut out_recv)
+                        .await
+                        .is_empty()
+                );
+
+                sim.run_until_quiescent().await;
+
+                let mut results = collect_ready_async::<Vec<_>, _>(&mut out_recv).await;
+                results.sort();
+                assert_eq!(
+                    results,
+                    &[
+                        (0, 0),
+                        (0, 2),
+                        (0, 4),
+                        (1, 0),
+                        (1, 2),
+                        (1, 4),
+                        (2, 0),
+                        (2, 2),
+                        (2, 4)
+                    ]
+                );
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_sim_broadcast_replay() {
+        tokio::task::LocalSet::new()
+            .run_until(async {
+                let (trace, outputs) = run_random(42, None).await;
+                assert!(!outputs.is_empty());
+
+                assert_eq!(run_random(42, None).await, (trace.clone(), outputs.clone()));
+                assert_eq!(run_random(7, Some(&trace)).await.1, outputs);
+                assert_ne!(run_random(43, None).await.0, trace);
+            })
+            .await;
+    }
+}
@@ -0,0 +1,128 @@
+use dfir_rs::scheduled::graph::Dfir;
+use hydro_lang::deploy::SimDeploy;
+use hydro_lang::network_buffer::Overflow;
+use hydro_lang::sim_runtime::SimPorts;
+use hydro_lang::*;
+use stageleft::{Quoted, RuntimeData};
+use tokio::sync::mpsc::UnboundedSender;
+
+struct Leader {}
+struct Receiver {}
+struct Worker {}
+
+#[stageleft::entry]
+pub fn sim_buffered_send<'a>(
+    flow: FlowBuilder<'a>,
+    env: RuntimeData<&'a SimPorts>,
+    received: RuntimeData<&'a UnboundedSender<u32>>,
+    dropped: RuntimeData<&'a UnboundedSender<u32>>,
+    blocked: RuntimeData<&'a UnboundedSender<u32>>,
+    broadcast_received: RuntimeData<&'a UnboundedSender<(u32, u32)>>,
+    broadcast_dropped: RuntimeData<&'a UnboundedSender<(u32, u32)>>,
+) -> impl Quoted<'a, Dfir<'a>> {
+    let leader = flow.process::<Leader>();
+    let receiver = flow.process::<Receiver>();
+    let workers = flow.cluster::<Worker>();
+
+    let (to_receiver, overflow) = leader.source_iter(q!(0..5)).send_bincode_buffered(
+        &receiver,
+        NetworkBuffer::new(Overflow::DropNewest).max_items(2),
+    );
+    to_receiver.for_each(q!(|n| {
+        received.send(n).unwrap();
+    }));
+    overflow.for_each(q!(|n| {
+        dropped.send(n).unwrap();
+    }));
+
+    // Blocking never drops elements, so the overflow stream can be ignored.
+    let (to_receiver, _) = leader
+        .source_iter(q!(0..5))
+        .send_bincode_buffered(&receiver, NetworkBuffer::new(Overflow::Block).max_items(1));
+    to_receiver.for_each(q!(|n| {
+        blocked.send(n).unwrap();
+    }));
+
+    let (to_workers, overflow) = leader.source_iter(q!(0..3)).broadcast_bincode_buffered(
+        &workers,
+        NetworkBuffer::new(Overflow::DropOldest).max_items(4),
+    );
+    to_workers.for_each(q!(move |n| {
+        broadcast_received
+            .send((CLUSTER_SELF_ID.raw_id, n))
+            .unwrap();
+    }));
+    overflow.for_each(q!(|(id, n)| {
+        broadcast_dropped.send((id.raw_id, n)).unwrap();
+    }));
+
+    flow.compile::<SimDeploy>(&env)
+        .with_dynamic_id(q!(env.location_id()))
+}
+
+#[cfg(stageleft_runtime)]
+#[cfg(test)]
+mod tests {
+    use dfir_rs::util::collect_ready_async;
+    use hydro_lang::sim_runtime::{SimNetwork, Simulation};
+
+    #[tokio::test]
+    async fn test_sim_buffered_send() {
+        tokio::task::LocalSet::new()
+            .run_until(async {
+                let network = SimNetwork::new()
+                    .with_process(0)
+                    .with_process(1)
+                    .with_cluster(2, 3);
+                let members = network.members();
+                let (received, mut received_recv) = dfir_rs::util::unbounded_channel();
+                let (dropped, mut dropped_recv) = dfir_rs::util::unbounded_channel();
+                let (blocked, mut blocked_recv) = dfir_rs::util::unbounded_channel();
+                let (broadcast_received, mut broadcast_received_recv) =
+                    dfir_rs::util::unbounded_channel();
+                let (broadcast_dropped, mut broadcast_dropped_recv) =
+                    dfir_rs::util::unbounded_channel();
+
+                let mut sim = Simulation::new(&network, 0);
+                for ports in &members {
+                    sim.add_member(
+                        ports,
+                        super::sim_buffered_send!(
+                            ports,
+                            &received,
+                            &dropped,
+                            &blocked,
+                            &broadcast_received,
+                            &broadcast_dropped
+                        ),
+                    );
+                }
+                sim.run_until_quiescent().await;
+
+                // All elements are pushed in one tick, before the network drains the buffers.
+                assert_eq!(
+                    collect_ready_async::<Vec<_>, _>(&mut received_recv).await,
+                    &[0, 1]
+                );
+                assert_eq!(
+                    collect_ready_async::<Vec<_>, _>(&mut dropped_recv).await,
+                    &[2, 3, 4]
+                );
+
+                assert_eq!(
+                    collect_ready_async::<Vec<_>, _>(&mut blocked_recv).await,
+                    &[0, 1, 2, 3, 4]
+                );
+
+                let mut broadcast =
+                    collect_ready_async::<Vec<_>, _>(&mut broadcast_received_recv).await;
+                broadcast.sort();
+                assert_eq!(broadcast, &[(0, 2), (1, 2), (2, 1), (2, 2)]);
+                assert_eq!(
+                    collect_ready_async::<Vec<_>, _>(&mut broadcast_dropped_recv).await,
+                    &[(0, 0), (1, 0), (2, 0), (0, 1), (1, 1)]
+                );
+            })
+            .await;
+    }
+}
@@ -0,0 +1,167 @@
+use dfir_rs::scheduled::graph::Dfir;
+use dfir_rs::tokio_stream::wrappers::UnboundedReceiverStream;
+use hydro_lang::deploy::SimDeploy;
+use hydro_lang::sim_runtime::SimPorts;
+use hydro_lang::*;
+use hydro_std::replication::chain::{ChainConfig, ChainOrder, ChainReads, chain_replication};
+use hydro_std::replication::kv_replica::KvPayload;
+use stageleft::{Quoted, RuntimeData};
+use tokio::sync::mpsc::UnboundedSender;
+
+struct Replica {}
+struct Client {}
+
+#[stageleft::entry]
+pub fn sim_chain_replication<'a>(
+    flow: FlowBuilder<'a>,
+    env: RuntimeData<&'a SimPorts>,
+    writes: RuntimeData<UnboundedReceiverStream<(u32, u32)>>,
+    reads: RuntimeData<UnboundedReceiverStream<u32>>,
+    acks: RuntimeData<&'a UnboundedSender<(u32, u32)>>,
+    results: RuntimeData<&'a UnboundedSender<(u32, Option<u32>)>>,
+    apportioned: bool,
+) -> impl Quoted<'a, Dfir<'a>> {
+    let replicas = flow.cluster::<Replica>();
+    let clients = flow.cluster::<Client>();
+
+    let (write_acks, read_results) = unsafe {
+        // SAFETY: the test only sends requests once the chain is stable
+        chain_replication(
+            &replicas,
+            &clients,
+            ChainConfig {
+                order: ChainOrder::Ascending,
+                reads: if apportioned {
+                    ChainReads::Apportioned
+                } else {
+                    ChainReads::Tail
+                },
+            },
+            clients
+                .source_stream(writes)
+                .map(q!(|(key, value)| KvPayload { key, value })),
+            clients.source_stream(reads),
+        )
+    };
+
+    write_acks.for_each(q!(|payload| {
+        acks.send((payload.key, payload.value)).unwrap();
+    }));
+    read_results.for_each(q!(|result| {
+        results.send(result).unwrap();
+    }));
+
+    flow.compile::<SimDeploy>(&env)
+        .with_dynamic_id(q!(env.location_id()))
+}
+
+#[cfg(stageleft_runtime)]
+#[cfg(test)]
+mod tests {
+    use dfir_rs::util::collect_ready_async;
+    use hydro_lang::sim_runtime::{SimMember, SimNetwork, Simulation};
+
+    const REPLICAS: usize = 0;
+
+    async fn chain_replication(apportioned: bool) {
+        let network = SimNetwork::new()
+            .with_cluster(REPLICAS, 3)
+            .with_cluster(1, 1);
+        let members = network.members();
+        let (acks, mut acks_recv) = dfir_rs::util::unbounded_channel();
+        let (results, mut results_recv) = dfir_rs::util::unbounded_channel();
+
+        let mut sim = Simulation::new(&network, 0);
+        let mut inputs = None;
+        for ports in &members {
+            let (writes_send, writes) = dfir_rs::util::unbounded_channel();
+            let (reads_send, reads) = dfir_rs::util::unbounded_channel();
+            if ports.location_id() != REPLICAS {
+                inputs = Some((writes_send, reads_send));
+            }
+
+            let dfir = if apportioned {
+                super::sim_chain_replication!(ports, writes, reads, &acks, &results, true)
+            } else {
+                super::sim_chain_replication!(ports, writes, reads, &acks, &results, false)
+            };
+            sim.add_member(ports, dfir);
+        }
+        let (writes, reads) = inputs.unwrap();
+        sim.run_until_quiescent().await;
+
+        // Requests from outside the simulation do not wake up the client, so tick it manually.
+        writes.send((1, 10)).unwrap();
+        writes.send((2, 20)).unwrap();
+        sim.tick_all().await;
+        sim.run_until_quiescent().await;
+        let mut acked = collect_ready_async::<Vec<_>, _>(&mut acks_recv).await;
+        acked.sort();
+        assert_eq!(acked, &[(1, 10), (2, 20)]);
+
+        for key in 1..=3 {
+            reads.send(key).unwrap();
+        }
+        sim.tick_all().await;
+        sim.run_until_quiescent().await;
+        let mut read = collect_ready_async::<Vec<_>, _>(&mut results_recv).await;
+        read.sort();
+        assert_eq!(read, &[(1, Some(10)), (2, Some(20)), (3, None)]);
+
+        // When the tail leaves, its predecessor takes over.
+        assert!(sim.leave(SimMember {
+            location_id: REPLICAS,
+            cluster_id: Some(2),
+        }));
+        sim.run_until_quiescent().await;
+        writes.send((1, 11)).unwrap();
+        sim.tick_all().await;
+        sim.run_until_quiescent().await;
+        assert_eq!(
+            collect_ready_async::<Vec<_>, _>(&mut acks_recv).await,
+            &[(1, 11)]
+        );
+        reads.send(1).unwrap();
+        reads.send(2).unwrap();
+        sim.tick_all().await;
+        sim.run_until_quiescent().await;
+        let mut read = collect_ready_async::<Vec<_>, _>(&mut results_recv).await;
+        read.sort();
+        assert_eq!(read, &[(1, Some(11)), (2, Some(20))]);
+
+        // When the head leaves, its successor takes over.
+        assert!(sim.leave(SimMember {
+            location_id: REPLICAS,
+            cluster_id: Some(0),
+        }));
+        sim.run_until_quiescent().await;
+        writes.send((3, 30)).unwrap();
+        sim.tick_all().await;
+        sim.run_until_quiescent().await;
+        assert_eq!(
+            collect_ready_async::<Vec<_>, _>(&mut acks_recv).await,
+            &[(3, 30)]
+        );
+        reads.send(1).unwrap();
+        reads.send(3).unwrap();
+        sim.tick_all().await;
+        sim.run_until_quiescent().await;
+        let mut read = collect_ready_async::<Vec<_>, _>(&mut results_recv).await;
+        read.sort();
+        assert_eq!(read, &[(1, Some(11)), (3, Some(30))]);
+    }
+
+    #[tokio::test]
+    async fn test_sim_chain_replication_tail_reads() {
+        tokio::task::LocalSet::new()
+            .run_until(chain_replication(false))
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_sim_chain_replication_apportioned_reads() {
+        tokio::task::LocalSet::new()
+            .run_until(chain_replication(true))
+            .await;
+    }
+}
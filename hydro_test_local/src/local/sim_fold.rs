@@ -0,0 +1,140 @@
+use dfir_rs::scheduled::graph::Dfir;
+use hydro_lang::deploy::SimDeploy;
+use hydro_lang::sim_runtime::SimPorts;
+use hydro_lang::*;
+use stageleft::{Quoted, RuntimeData};
+use tokio::sync::mpsc::UnboundedSender;
+
+struct Leader {}
+struct Worker {}
+
+#[stageleft::entry]
+pub fn sim_fold<'a>(
+    flow: FlowBuilder<'a>,
+    env: RuntimeData<&'a SimPorts>,
+    output: RuntimeData<&'a UnboundedSender<u32>>,
+    commutative: bool,
+) -> impl Quoted<'a, Dfir<'a>> {
+    let leader = flow.process::<Leader>();
+    let workers = flow.cluster::<Worker>();
+
+    let tick = leader.tick();
+    let batch = unsafe {
+        // SAFETY: the test checks that the output does not depend on the batching
+        workers
+            .source_iter(q!([CLUSTER_SELF_ID.raw_id + 1]))
+            .send_bincode_anonymous(&leader)
+            .tick_batch(&tick)
+    };
+
+    let folded = if commutative {
+        batch.fold_commutative(q!(|| 0), q!(|acc, n| *acc += n))
+    } else {
+        unsafe {
+            // SAFETY: intentionally wrong, the digits depend on the arrival order and batching
+            batch.assume_ordering::<TotalOrder>()
+        }
+        .fold(q!(|| 0), q!(|acc, n| *acc = *acc * 10 + n))
+    };
+
+    folded.all_ticks().for_each(q!(|n| {
+        output.send(n).unwrap();
+    }));
+
+    flow.compile::<SimDeploy>(&env)
+        .with_dynamic_id(q!(env.location_id()))
+}
+
+#[cfg(stageleft_runtime)]
+#[cfg(test)]
+mod tests {
+    use dfir_rs::util::collect_ready_async;
+    use hydro_lang::sim_runtime::{SimExplorer, SimNetwork, SimSchedule, Simulation};
+
+    /// Runs the program with `workers` cluster members under `schedule`, and returns the sum of
+    /// the per-tick outputs.
+    async fn run(schedule: &mut SimSchedule, workers: usize, commutative: bool) -> u32 {
+        let network = SimNetwork::new().with_process(0).with_cluster(1, workers);
+        let members = network.members();
+        let (out, mut out_recv) = dfir_rs::util::unbounded_channel();
+
+        let mut sim = Simulation::new(&network, 0);
+        for ports in &members {
+            let dfir = if commutative {
+                super::sim_fold!(ports, &out, true)
+            } else {
+                super::sim_fold!(ports, &out, false)
+            };
+            sim.add_member(ports, dfir);
+        }
+        sim.run_schedule(schedule).await;
+
+        collect_ready_async::<Vec<_>, _>(&mut out_recv)
+            .await
+            .into_iter()
+            .sum()
+    }
+
+    #[tokio::test]
+    async fn test_sim_explore_exhaustive() {
+        tokio::task::LocalSet::new()
+            .run_until(async {
+                let explorer = SimExplorer::new();
+
+                let divergence = explorer
+                    .explore_exhaustive(async |schedule| run(schedule, 2, false).await)
+                    .await
+                    .unwrap_err();
+                assert_ne!(divergence.expected, divergence.found);
+
+                let exploration = explorer
+                    .explore_exhaustive(async |schedule| run(schedule, 2, true).await)
+                    .await
+                    .unwrap();
+                assert!(exploration.complete);
+                assert!(exploration.schedules > 1);
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_sim_explore_random() {
+        tokio::task::LocalSet::new()
+            .run_until(async {
+                let explorer = SimExplorer::new().max_schedules(50);
+
+                let divergence = explorer
+                    .explore_random(0, async |schedule| run(schedule, 3, false).await)
+                    .await
+                    .unwrap_err();
+
+                // The divergent schedule can be replayed to reproduce its output.
+                let network = SimNetwork::new().with_process(0).with_cluster(1, 3);
+                let members = network.members();
+                let (out, mut out_recv) = dfir_rs::util::unbounded_channel();
+                let mut sim = Simulation::new(&network, 0);
+                for ports in &members {
+                    sim.add_member(ports, super::sim_fold!(ports, &out, false));
+                }
+                sim.replay(&divergence.found_trace).await;
+                sim.run_until_quiescent().await;
+                assert_eq!(
+                    collect_ready_async::<Vec<u32>, _>(&mut out_recv)
+                        .await
+                        .into_iter()
+                        .sum::<u32>(),
+                    divergence.found
+                );
+
+                assert_eq!(
+                    explorer
+                        .explore_random(0, async |schedule| run(schedule, 3, true).await)
+                        .await
+                        .unwrap()
+                        .schedules,
+                    50
+                );
+            })
+            .await;
+    }
+}
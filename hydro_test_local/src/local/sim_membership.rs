@@ -0,0 +1,102 @@
+use dfir_rs::scheduled::graph::Dfir;
+use hydro_lang::deploy::SimDeploy;
+use hydro_lang::sim_runtime::SimPorts;
+use hydro_lang::*;
+use stageleft::{Quoted, RuntimeData};
+use tokio::sync::mpsc::UnboundedSender;
+
+struct Leader {}
+struct Worker {}
+
+#[stageleft::entry]
+pub fn sim_membership<'a>(
+    flow: FlowBuilder<'a>,
+    env: RuntimeData<&'a SimPorts>,
+    events: RuntimeData<&'a UnboundedSender<(u32, MembershipEvent)>>,
+    replies: RuntimeData<&'a UnboundedSender<(u32, u32)>>,
+) -> impl Quoted<'a, Dfir<'a>> {
+    let leader = flow.process::<Leader>();
+    let workers = flow.cluster::<Worker>();
+
+    let changes = leader.source_cluster_members(&workers);
+    changes.clone().for_each(q!(|(id, event)| {
+        events.send((id.raw_id, event)).unwrap();
+    }));
+
+    changes
+        .map(q!(|(id, _)| id.raw_id))
+        .broadcast_bincode(&workers)
+        .send_bincode(&leader)
+        .for_each(q!(|(id, n)| {
+            replies.send((id.raw_id, n)).unwrap();
+        }));
+
+    flow.compile::<SimDeploy>(&env)
+        .with_dynamic_id(q!(env.location_id()))
+}
+
+#[cfg(stageleft_runtime)]
+#[cfg(test)]
+mod tests {
+    use dfir_rs::util::collect_ready_async;
+    use hydro_lang::MembershipEvent;
+    use hydro_lang::sim_runtime::{SimMember, SimNetwork, Simulation};
+
+    #[tokio::test]
+    async fn test_sim_membership() {
+        tokio::task::LocalSet::new()
+            .run_until(async {
+                let network = SimNetwork::new().with_process(0).with_cluster(1, 3);
+                let members = network.members();
+                let (events, mut events_recv) = dfir_rs::util::unbounded_channel();
+                let (replies, mut replies_recv) = dfir_rs::util::unbounded_channel();
+
+                let mut sim = Simulation::new(&network, 0);
+                for ports in &members {
+                    sim.add_member(ports, super::sim_membership!(ports, &events, &replies));
+                }
+
+                sim.run_until_quiescent().await;
+                assert_eq!(
+                    collect_ready_async::<Vec<_>, _>(&mut events_recv).await,
+                    &[
+                        (0, MembershipEvent::Joined),
+                        (1, MembershipEvent::Joined),
+                        (2, MembershipEvent::Joined)
+                    ]
+                );
+                let mut initial = collect_ready_async::<Vec<_>, _>(&mut replies_recv).await;
+                initial.sort();
+                assert_eq!(initial.len(), 9);
+
+                let worker_2 = SimMember {
+                    location_id: 1,
+                    cluster_id: Some(2),
+                };
+
+                // Broadcasts skip the member once it has left.
+                assert!(sim.leave(worker_2));
+                assert!(!sim.leave(worker_2));
+                sim.run_until_quiescent().await;
+                assert_eq!(
+                    collect_ready_async::<Vec<_>, _>(&mut events_recv).await,
+                    &[(2, MembershipEvent::Left)]
+                );
+                let mut after_leave = collect_ready_async::<Vec<_>, _>(&mut replies_recv).await;
+                after_leave.sort();
+                assert_eq!(after_leave, &[(0, 2), (1, 2)]);
+
+                // And reach it again once it rejoins.
+                assert!(sim.join(worker_2));
+                sim.run_until_quiescent().await;
+                assert_eq!(
+                    collect_ready_async::<Vec<_>, _>(&mut events_recv).await,
+                    &[(2, MembershipEvent::Joined)]
+                );
+                let mut after_join = collect_ready_async::<Vec<_>, _>(&mut replies_recv).await;
+                after_join.sort();
+                assert_eq!(after_join, &[(0, 2), (1, 2), (2, 2)]);
+            })
+            .await;
+    }
+}
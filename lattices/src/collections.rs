@@ -1,6 +1,6 @@
 //! Simple singleton or array collection with [`cc_traits`] implementations.
 
-use std::borrow::Borrow;
+use std::borrow::{Borrow, Cow};
 use std::collections::{BTreeMap, HashMap};
 use std::hash::Hash;
 use std::marker::PhantomData;
@@ -1202,6 +1202,219 @@ impl<K, OldVal, const N: usize> MapMapValues<OldVal> for ArrayMap<K, OldVal, N>
     }
 }
 
+/// Integer types which can be stored in a [`RangeSet`].
+pub trait RangeSetItem: Copy + Ord {
+    /// The next value after `self`, or `None` if `self` is the maximum value.
+    fn successor(self) -> Option<Self>;
+
+    /// The number of values in `start..=end`, saturating at `usize::MAX`.
+    fn range_len(start: Self, end: Self) -> usize;
+}
+macro_rules! range_set_item_impl {
+    ($($t:ty),*) => {
+        $(
+            impl RangeSetItem for $t {
+                fn successor(self) -> Option<Self> {
+                    self.checked_add(1)
+                }
+
+                fn range_len(start: Self, end: Self) -> usize {
+                    usize::try_from(end.abs_diff(start))
+                        .unwrap_or(usize::MAX)
+                        .saturating_add(1)
+                }
+            }
+        )*
+    };
+}
+range_set_item_impl!(
+    u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize
+);
+
+/// A set of integers, stored as a sorted list of disjoint inclusive ranges.
+///
+/// Runs of consecutive items are stored as a single range, so this is a compact representation
+/// for sets of mostly-consecutive items, such as the tombstones of sequentially-allocated keys.
+/// Adjacent ranges are always coalesced, so the representation is canonical.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RangeSet<T> {
+    ranges: Vec<(T, T)>,
+}
+impl<T> Default for RangeSet<T> {
+    fn default() -> Self {
+        Self { ranges: Vec::new() }
+    }
+}
+impl<T> RangeSet<T>
+where
+    T: RangeSetItem,
+{
+    /// Create a new empty `RangeSet`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Inserts `item`, returning `true` if it was not already in the set.
+    pub fn insert(&mut self, item: T) -> bool {
+        // Index of the first range which ends at or after `item`.
+        let idx = self.ranges.partition_point(|&(_start, end)| end < item);
+        if self
+            .ranges
+            .get(idx)
+            .is_some_and(|&(start, _end)| start <= item)
+        {
+            return false;
+        }
+
+        let joins_prev = idx > 0 && self.ranges[idx - 1].1.successor() == Some(item);
+        let joins_next = self
+            .ranges
+            .get(idx)
+            .is_some_and(|&(start, _end)| item.successor() == Some(start));
+        match (joins_prev, joins_next) {
+            (true, true) => {
+                self.ranges[idx - 1].1 = self.ranges.remove(idx).1;
+            }
+            (true, false) => self.ranges[idx - 1].1 = item,
+            (false, true) => self.ranges[idx].0 = item,
+            (false, false) => self.ranges.insert(idx, (item, item)),
+        }
+        true
+    }
+
+    /// If `item` is in the set.
+    pub fn contains(&self, item: &T) -> bool {
+        let idx = self.ranges.partition_point(|(_start, end)| end < item);
+        self.ranges
+            .get(idx)
+            .is_some_and(|(start, _end)| start <= item)
+    }
+
+    /// Iterates the inclusive `(start, end)` ranges of the set, in order.
+    pub fn ranges(&self) -> impl Iterator<Item = (T, T)> + '_ {
+        self.ranges.iter().copied()
+    }
+}
+impl<T> IntoIterator for RangeSet<T>
+where
+    T: RangeSetItem,
+{
+    type Item = T;
+    type IntoIter = RangeSetIter<std::vec::IntoIter<(T, T)>, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        RangeSetIter::new(self.ranges.into_iter())
+    }
+}
+impl<T> FromIterator<T> for RangeSet<T>
+where
+    T: RangeSetItem,
+{
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut out = Self::new();
+        out.extend(iter);
+        out
+    }
+}
+impl<T> Extend<T> for RangeSet<T>
+where
+    T: RangeSetItem,
+{
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for item in iter {
+            self.insert(item);
+        }
+    }
+}
+impl<T> Collection for RangeSet<T> {
+    type Item = T;
+}
+impl<T> Len for RangeSet<T>
+where
+    T: RangeSetItem,
+{
+    fn len(&self) -> usize {
+        self.ranges
+            .iter()
+            .map(|&(start, end)| T::range_len(start, end))
+            .fold(0, usize::saturating_add)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+}
+impl<T> CollectionRef for RangeSet<T>
+where
+    T: Clone,
+{
+    // Items are not stored individually, so references are owned copies.
+    type ItemRef<'a>
+        = Cow<'a, T>
+    where
+        Self: 'a;
+
+    covariant_item_ref!();
+}
+impl<'a, T> Get<&'a T> for RangeSet<T>
+where
+    T: RangeSetItem,
+{
+    fn get(&self, key: &'a T) -> Option<Self::ItemRef<'_>> {
+        self.contains(key).then_some(Cow::Owned(*key))
+    }
+}
+impl<T> Iter for RangeSet<T>
+where
+    T: RangeSetItem,
+{
+    type Iter<'a>
+        = std::iter::Map<
+        RangeSetIter<std::iter::Copied<std::slice::Iter<'a, (T, T)>>, T>,
+        fn(T) -> Cow<'a, T>,
+    >
+    where
+        Self: 'a;
+
+    fn iter(&self) -> Self::Iter<'_> {
+        RangeSetIter::new(self.ranges.iter().copied()).map(Cow::Owned)
+    }
+}
+
+/// Iterator over the items of a [`RangeSet`].
+#[derive(Debug, Clone)]
+pub struct RangeSetIter<I, T> {
+    ranges: I,
+    current: Option<(T, T)>,
+}
+impl<I, T> RangeSetIter<I, T> {
+    fn new(ranges: I) -> Self {
+        Self {
+            ranges,
+            current: None,
+        }
+    }
+}
+impl<I, T> Iterator for RangeSetIter<I, T>
+where
+    I: Iterator<Item = (T, T)>,
+    T: RangeSetItem,
+{
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (start, end) = match self.current.take() {
+            Some(range) => range,
+            None => self.ranges.next()?,
+        };
+        if start < end {
+            self.current = start.successor().map(|next| (next, end));
+        }
+        Some(start)
+    }
+}
+
 // /// A boolean-masked fixed-size array wrapper which implements `Collection`.
 // #[derive(Clone, Copy, PartialEq, Eq, Hash)]
 // pub struct MaskedArray<T, const N: usize> {
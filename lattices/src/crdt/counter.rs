@@ -0,0 +1,310 @@
+use std::cmp::Ordering::{self, *};
+
+use crate::vector_clock::{Dot, VClock};
+use crate::{Atomize, IsBot, IsTop, LatticeFrom, LatticeOrd, Merge};
+
+/// Grow-only counter CRDT.
+///
+/// Each replica increments its own count, and the value of the counter is the sum of the counts
+/// of all replicas. Merging takes the max of each replica's count.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(deserialize = "Id: Ord + serde::Deserialize<'de>"))
+)]
+pub struct GCounter<Id> {
+    counts: VClock<Id>,
+}
+
+impl<Id> Default for GCounter<Id> {
+    fn default() -> Self {
+        Self {
+            counts: VClock::default(),
+        }
+    }
+}
+
+impl<Id> GCounter<Id>
+where
+    Id: Ord + Clone,
+{
+    /// Create a new `GCounter` with value zero.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `amount` to the count of replica `id`. Returns the delta.
+    pub fn increment(&mut self, id: Id, amount: u64) -> Self {
+        let count = self.counts.get(&id) + amount;
+        let dot = Dot::new(id, count);
+        self.counts.observe(dot.clone());
+        Self {
+            counts: VClock::new_from([(dot.id, dot.counter)]),
+        }
+    }
+
+    /// The value of the counter, the sum of all replicas' counts.
+    pub fn value(&self) -> u64 {
+        self.counts.iter().map(|(_id, count)| count).sum()
+    }
+}
+
+impl<Id> GCounter<Id> {
+    /// Reveal the inner value as a shared reference.
+    pub fn as_reveal_ref(&self) -> &VClock<Id> {
+        &self.counts
+    }
+
+    /// Gets the inner by value, consuming self.
+    pub fn into_reveal(self) -> VClock<Id> {
+        self.counts
+    }
+}
+
+impl<Id> Merge<GCounter<Id>> for GCounter<Id>
+where
+    Id: Ord,
+{
+    fn merge(&mut self, other: GCounter<Id>) -> bool {
+        self.counts.merge(other.counts)
+    }
+}
+
+impl<Id> LatticeFrom<GCounter<Id>> for GCounter<Id> {
+    fn lattice_from(other: GCounter<Id>) -> Self {
+        other
+    }
+}
+
+impl<Id> PartialOrd for GCounter<Id>
+where
+    Id: Ord,
+{
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.counts.partial_cmp(&other.counts)
+    }
+}
+impl<Id> LatticeOrd<Self> for GCounter<Id> where Self: PartialOrd<Self> {}
+
+impl<Id> IsBot for GCounter<Id> {
+    fn is_bot(&self) -> bool {
+        self.counts.is_bot()
+    }
+}
+
+impl<Id> IsTop for GCounter<Id> {
+    fn is_top(&self) -> bool {
+        false
+    }
+}
+
+impl<Id> Atomize for GCounter<Id>
+where
+    Id: 'static + Ord,
+{
+    type Atom = Self;
+
+    // TODO: use impl trait, then remove 'static.
+    type AtomIter = Box<dyn Iterator<Item = Self::Atom>>;
+
+    fn atomize(self) -> Self::AtomIter {
+        Box::new(self.counts.atomize().map(|counts| Self { counts }))
+    }
+}
+
+/// Positive-negative counter CRDT, which can be incremented and decremented.
+///
+/// Made of two [`GCounter`]s, one counting increments and the other counting decrements.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(deserialize = "Id: Ord + serde::Deserialize<'de>"))
+)]
+pub struct PnCounter<Id> {
+    increments: GCounter<Id>,
+    decrements: GCounter<Id>,
+}
+
+impl<Id> Default for PnCounter<Id> {
+    fn default() -> Self {
+        Self {
+            increments: GCounter::default(),
+            decrements: GCounter::default(),
+        }
+    }
+}
+
+impl<Id> PnCounter<Id>
+where
+    Id: Ord + Clone,
+{
+    /// Create a new `PnCounter` with value zero.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `amount` on behalf of replica `id`. Returns the delta.
+    pub fn increment(&mut self, id: Id, amount: u64) -> Self {
+        Self {
+            increments: self.increments.increment(id, amount),
+            decrements: GCounter::new(),
+        }
+    }
+
+    /// Subtracts `amount` on behalf of replica `id`. Returns the delta.
+    pub fn decrement(&mut self, id: Id, amount: u64) -> Self {
+        Self {
+            increments: GCounter::new(),
+            decrements: self.decrements.increment(id, amount),
+        }
+    }
+
+    /// The value of the counter, the increments minus the decrements.
+    pub fn value(&self) -> i64 {
+        self.increments.value() as i64 - self.decrements.value() as i64
+    }
+}
+
+impl<Id> PnCounter<Id> {
+    /// Reveal the inner value as a shared reference.
+    pub fn as_reveal_ref(&self) -> (&GCounter<Id>, &GCounter<Id>) {
+        (&self.increments, &self.decrements)
+    }
+
+    /// Gets the inner by value, consuming self.
+    pub fn into_reveal(self) -> (GCounter<Id>, GCounter<Id>) {
+        (self.increments, self.decrements)
+    }
+}
+
+impl<Id> Merge<PnCounter<Id>> for PnCounter<Id>
+where
+    Id: Ord,
+{
+    fn merge(&mut self, other: PnCounter<Id>) -> bool {
+        let increments_changed = self.increments.merge(other.increments);
+        let decrements_changed = self.decrements.merge(other.decrements);
+        increments_changed || decrements_changed
+    }
+}
+
+impl<Id> LatticeFrom<PnCounter<Id>> for PnCounter<Id> {
+    fn lattice_from(other: PnCounter<Id>) -> Self {
+        other
+    }
+}
+
+impl<Id> PartialOrd for PnCounter<Id>
+where
+    Id: Ord,
+{
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        let increments = self.increments.partial_cmp(&other.increments)?;
+        let decrements = self.decrements.partial_cmp(&other.decrements)?;
+        match (increments, decrements) {
+            (Equal, ord) | (ord, Equal) => Some(ord),
+            (Less, Less) => Some(Less),
+            (Greater, Greater) => Some(Greater),
+            _ => None,
+        }
+    }
+}
+impl<Id> LatticeOrd<Self> for PnCounter<Id> where Self: PartialOrd<Self> {}
+
+impl<Id> IsBot for PnCounter<Id> {
+    fn is_bot(&self) -> bool {
+        self.increments.is_bot() && self.decrements.is_bot()
+    }
+}
+
+impl<Id> IsTop for PnCounter<Id> {
+    fn is_top(&self) -> bool {
+        false
+    }
+}
+
+impl<Id> Atomize for PnCounter<Id>
+where
+    Id: 'static + Ord,
+{
+    type Atom = Self;
+
+    // TODO: use impl trait, then remove 'static.
+    type AtomIter = Box<dyn Iterator<Item = Self::Atom>>;
+
+    fn atomize(self) -> Self::AtomIter {
+        let increments = self.increments.atomize().map(|increments| Self {
+            increments,
+            decrements: GCounter::default(),
+        });
+        let decrements = self.decrements.atomize().map(|decrements| Self {
+            increments: GCounter::default(),
+            decrements,
+        });
+        Box::new(increments.chain(decrements))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::{check_all, check_atomize_each};
+
+    #[test]
+    fn consistency_g_counter() {
+        let mut test_vec = Vec::new();
+        for a in 0..3 {
+            for b in 0..3 {
+                let mut counter = GCounter::new();
+                counter.increment('a', a);
+                counter.increment('b', b);
+                test_vec.push(counter);
+            }
+        }
+
+        check_all(&test_vec);
+        check_atomize_each(&test_vec);
+    }
+
+    #[test]
+    fn consistency_pn_counter() {
+        let mut test_vec = Vec::new();
+        for inc in 0..3 {
+            for dec_a in 0..2 {
+                for dec_b in 0..2 {
+                    let mut counter = PnCounter::new();
+                    counter.increment('a', inc);
+                    counter.decrement('a', dec_a);
+                    counter.decrement('b', dec_b);
+                    test_vec.push(counter);
+                }
+            }
+        }
+
+        check_all(&test_vec);
+        check_atomize_each(&test_vec);
+    }
+
+    #[test]
+    fn pn_counter_deltas() {
+        let mut a = PnCounter::new();
+        let mut b = PnCounter::new();
+
+        let deltas_a = [a.increment('a', 5), a.decrement('a', 2)];
+        let deltas_b = [b.decrement('b', 4)];
+        assert_eq!(3, a.value());
+        assert_eq!(-4, b.value());
+
+        // Deltas may be duplicated and reordered.
+        for delta in deltas_a.iter().rev().chain(&deltas_a).cloned() {
+            b.merge(delta);
+        }
+        for delta in deltas_b {
+            a.merge(delta);
+        }
+        assert_eq!(a, b);
+        assert_eq!(-1, a.value());
+    }
+}
@@ -0,0 +1,244 @@
+use std::cmp::Ordering::{self, *};
+use std::collections::BTreeMap;
+
+use crate::vector_clock::{Dot, DotContext};
+use crate::{Atomize, IsBot, IsTop, LatticeFrom, LatticeOrd, Merge};
+
+/// Causal dot store lattice, mapping [`Dot`]s (write events) to values, along with the
+/// [`DotContext`] of all the events which have been seen.
+///
+/// An event which is in the context but has no entry has been overwritten or removed. When
+/// merging, an entry is kept if it is present on both sides or if the other side has not seen its
+/// event, so writes which have been observed are removed while concurrent writes are kept.
+///
+/// Every entry's dot is in the context, and each dot is only ever written with a single value.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(
+        serialize = "Id: serde::Serialize, V: serde::Serialize",
+        deserialize = "Id: Ord + serde::Deserialize<'de>, V: serde::Deserialize<'de>"
+    ))
+)]
+pub struct DotFun<Id, V> {
+    entries: BTreeMap<Dot<Id>, V>,
+    context: DotContext<Id>,
+}
+
+impl<Id, V> Default for DotFun<Id, V> {
+    fn default() -> Self {
+        Self {
+            entries: BTreeMap::new(),
+            context: DotContext::default(),
+        }
+    }
+}
+
+impl<Id, V> DotFun<Id, V>
+where
+    Id: Ord + Clone,
+{
+    /// Create a new empty `DotFun`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Writes `value` as a new event of replica `id`, removing the entries for which `overwrite`
+    /// returns `true`. Returns the delta.
+    pub fn write(&mut self, id: Id, value: V, overwrite: impl FnMut(&V) -> bool) -> Self
+    where
+        V: Clone,
+    {
+        let mut delta = self.remove(overwrite);
+        let dot = self.context.next_dot(id);
+        delta.context.insert(dot.clone());
+        delta.entries.insert(dot.clone(), value.clone());
+        self.entries.insert(dot, value);
+        delta
+    }
+
+    /// Removes the entries for which `remove` returns `true`. Returns the delta.
+    pub fn remove(&mut self, mut remove: impl FnMut(&V) -> bool) -> Self {
+        let mut removed = Vec::new();
+        self.entries.retain(|dot, value| {
+            let keep = !remove(value);
+            if !keep {
+                removed.push(dot.clone());
+            }
+            keep
+        });
+        Self {
+            entries: BTreeMap::new(),
+            context: DotContext::new_from(removed),
+        }
+    }
+
+    /// Iterates the live entries, in order of their dots.
+    pub fn iter(&self) -> impl Iterator<Item = (&Dot<Id>, &V)> {
+        self.entries.iter()
+    }
+
+    /// Iterates the values of the live entries, in order of their dots.
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.entries.values()
+    }
+
+    /// The context of all events which have been seen.
+    pub fn context(&self) -> &DotContext<Id> {
+        &self.context
+    }
+
+    /// If every event of `self` is in `other`, and `other` has not removed any of the entries of
+    /// `self`, i.e. if merging `self` into `other` would not change `other`.
+    fn is_le(&self, other: &Self) -> bool {
+        self.context <= other.context
+            && other
+                .entries
+                .keys()
+                .all(|dot| !self.context.contains(dot) || self.entries.contains_key(dot))
+    }
+}
+
+impl<Id, V> DotFun<Id, V> {
+    /// Reveal the inner value as a shared reference.
+    pub fn as_reveal_ref(&self) -> (&BTreeMap<Dot<Id>, V>, &DotContext<Id>) {
+        (&self.entries, &self.context)
+    }
+
+    /// Gets the inner by value, consuming self.
+    pub fn into_reveal(self) -> (BTreeMap<Dot<Id>, V>, DotContext<Id>) {
+        (self.entries, self.context)
+    }
+}
+
+impl<Id, V> Merge<DotFun<Id, V>> for DotFun<Id, V>
+where
+    Id: Ord + Clone,
+{
+    fn merge(&mut self, other: DotFun<Id, V>) -> bool {
+        if other.is_le(self) {
+            return false;
+        }
+        let Self { entries, context } = other;
+        self.entries
+            .retain(|dot, _| entries.contains_key(dot) || !context.contains(dot));
+        for (dot, value) in entries {
+            if !self.context.contains(&dot) {
+                self.entries.insert(dot, value);
+            }
+        }
+        self.context.merge(context);
+        true
+    }
+}
+
+impl<Id, V> LatticeFrom<DotFun<Id, V>> for DotFun<Id, V> {
+    fn lattice_from(other: DotFun<Id, V>) -> Self {
+        other
+    }
+}
+
+impl<Id, V> PartialOrd for DotFun<Id, V>
+where
+    Id: Ord + Clone,
+    V: PartialEq,
+{
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self.is_le(other), other.is_le(self)) {
+            (true, true) => Some(Equal),
+            (true, false) => Some(Less),
+            (false, true) => Some(Greater),
+            (false, false) => None,
+        }
+    }
+}
+impl<Id, V> LatticeOrd<Self> for DotFun<Id, V> where Self: PartialOrd<Self> {}
+
+impl<Id, V> IsBot for DotFun<Id, V> {
+    fn is_bot(&self) -> bool {
+        self.entries.is_empty() && self.context.is_bot()
+    }
+}
+
+impl<Id, V> IsTop for DotFun<Id, V> {
+    fn is_top(&self) -> bool {
+        false
+    }
+}
+
+impl<Id, V> Atomize for DotFun<Id, V>
+where
+    Id: 'static + Ord + Clone,
+    V: 'static,
+{
+    type Atom = Self;
+
+    // TODO: use impl trait, then remove 'static.
+    type AtomIter = Box<dyn Iterator<Item = Self::Atom>>;
+
+    /// Atomizes into one atom per live entry, and one atom per replica for the events which have
+    /// been overwritten or removed.
+    fn atomize(self) -> Self::AtomIter {
+        let mut removed = BTreeMap::<Id, Vec<Dot<Id>>>::new();
+        for dot in self.context.dots() {
+            if !self.entries.contains_key(&dot) {
+                removed.entry(dot.id.clone()).or_default().push(dot);
+            }
+        }
+        let removed = removed.into_values().map(|dots| Self {
+            entries: BTreeMap::new(),
+            context: DotContext::new_from(dots),
+        });
+        let entries = self.entries.into_iter().map(|(dot, value)| Self {
+            context: DotContext::new_from([dot.clone()]),
+            entries: BTreeMap::from([(dot, value)]),
+        });
+        Box::new(entries.chain(removed))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::{check_all, check_atomize_each};
+
+    #[test]
+    fn consistency() {
+        let mut a = DotFun::new();
+        let mut b = DotFun::new();
+        let mut test_vec = vec![DotFun::default()];
+
+        test_vec.push(a.write('a', 1, |_| true));
+        test_vec.push(a.clone());
+        test_vec.push(b.write('b', 2, |_| true));
+        b.merge(a.clone());
+        test_vec.push(b.clone());
+        test_vec.push(a.write('a', 3, |&v| v == 1));
+        test_vec.push(a.clone());
+        test_vec.push(b.remove(|&v| v == 1));
+        test_vec.push(b.clone());
+
+        check_all(&test_vec);
+        check_atomize_each(&test_vec);
+    }
+
+    #[test]
+    fn concurrent_writes_are_kept() {
+        let mut a = DotFun::new();
+        let mut b = DotFun::new();
+        let delta_a = a.write('a', "x", |_| true);
+        let delta_b = b.write('b', "y", |_| true);
+
+        assert!(a.merge(delta_b));
+        assert!(b.merge(delta_a));
+        assert_eq!(a, b);
+        assert_eq!(vec![&"x", &"y"], a.values().collect::<Vec<_>>());
+
+        // An observed write is overwritten.
+        let delta = a.write('a', "z", |_| true);
+        assert!(b.merge(delta));
+        assert_eq!(a, b);
+        assert_eq!(vec![&"z"], b.values().collect::<Vec<_>>());
+    }
+}
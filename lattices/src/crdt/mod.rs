@@ -0,0 +1,37 @@
+//! Module containing conflict-free replicated data types (CRDTs) built from lattices.
+//!
+//! Each CRDT is a [`Lattice`](crate::Lattice), so replicas converge by merging their states.
+//! Mutators update the state in place and return a delta, a small value of the same type which
+//! can be sent to other replicas and merged instead of the whole state. States and deltas can be
+//! further split into atoms with [`Atomize`](crate::Atomize).
+//!
+//! * [`GCounter`] and [`PnCounter`] - grow-only and increment/decrement counters.
+//! * [`LwwRegister`] - register where the write with the largest timestamp wins.
+//! * [`MvRegister`] - register which keeps all concurrent writes.
+//! * [`OrSet`] - add-wins observed-remove set.
+//! * [`OrMap`] - update-wins observed-remove map of nested lattice values.
+//! * [`Rga`] - replicated growable array, a sequence (list) CRDT.
+//!
+//! The register, set, and map CRDTs identify writes by [`Dot`](crate::Dot)s and are built on the
+//! [`DotFun`] causal store, so removes do not leave tombstones.
+
+// Hidden modules are public for the same reason as the crate's hidden modules.
+#[doc(hidden)]
+pub mod counter;
+#[doc(hidden)]
+pub mod dot_fun;
+#[doc(hidden)]
+pub mod or_map;
+#[doc(hidden)]
+pub mod or_set;
+#[doc(hidden)]
+pub mod register;
+#[doc(hidden)]
+pub mod rga;
+
+pub use counter::{GCounter, PnCounter};
+pub use dot_fun::DotFun;
+pub use or_map::OrMap;
+pub use or_set::OrSet;
+pub use register::{LwwRegister, MvRegister};
+pub use rga::{Rga, RgaKey};
@@ -0,0 +1,225 @@
+use std::cmp::Ordering;
+use std::collections::BTreeSet;
+
+use super::DotFun;
+use crate::{Atomize, IsBot, IsTop, LatticeFrom, LatticeOrd, Merge};
+
+/// Observed-remove map CRDT, mapping keys to nested lattice values, with update-wins semantics.
+///
+/// Each update of a key is tagged with a unique [`Dot`](crate::Dot), and the value of a key is the
+/// merge of all its updates which have not been removed. A remove only removes the updates which
+/// the removing replica has seen, so if a key is updated and removed concurrently, the update
+/// wins. Unlike a [`MapUnion`](crate::map_union::MapUnion), removing a key resets its value, so a
+/// key which is removed and then updated again only has the value of the later updates.
+///
+/// To keep the state small, each update carries the merge of the key's value seen by the updating
+/// replica, and overwrites the updates it has seen.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(
+        serialize = "Id: serde::Serialize, K: serde::Serialize, V: serde::Serialize",
+        deserialize = "Id: Ord + serde::Deserialize<'de>, K: serde::Deserialize<'de>, V: serde::Deserialize<'de>"
+    ))
+)]
+pub struct OrMap<Id, K, V> {
+    updates: DotFun<Id, (K, V)>,
+}
+
+impl<Id, K, V> Default for OrMap<Id, K, V> {
+    fn default() -> Self {
+        Self {
+            updates: DotFun::default(),
+        }
+    }
+}
+
+impl<Id, K, V> OrMap<Id, K, V>
+where
+    Id: Ord + Clone,
+    K: Ord,
+{
+    /// Create a new empty `OrMap`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Merges `value` into the value of `key` on behalf of replica `id`. Returns the delta.
+    pub fn update(&mut self, id: Id, key: K, value: V) -> Self
+    where
+        K: Clone,
+        V: Merge<V> + Clone,
+    {
+        let mut merged = value;
+        for (_key, other) in self.updates.values().filter(|(other, _)| other == &key) {
+            merged.merge(other.clone());
+        }
+        Self {
+            updates: self
+                .updates
+                .write(id, (key.clone(), merged), |(other, _)| other == &key),
+        }
+    }
+
+    /// Removes all the observed updates of `key`. Returns the delta.
+    pub fn remove(&mut self, key: &K) -> Self {
+        Self {
+            updates: self.updates.remove(|(other, _)| other == key),
+        }
+    }
+
+    /// The value of `key`, if it is in the map.
+    pub fn get(&self, key: &K) -> Option<V>
+    where
+        V: Merge<V> + Clone,
+    {
+        self.updates
+            .values()
+            .filter(|(other, _)| other == key)
+            .map(|(_key, value)| value.clone())
+            .reduce(|mut merged, value| {
+                merged.merge(value);
+                merged
+            })
+    }
+
+    /// If `key` is in the map.
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.updates.values().any(|(other, _)| other == key)
+    }
+
+    /// Iterates the keys in the map, in order.
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.updates
+            .values()
+            .map(|(key, _value)| key)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+    }
+}
+
+impl<Id, K, V> OrMap<Id, K, V> {
+    /// Reveal the inner value as a shared reference.
+    pub fn as_reveal_ref(&self) -> &DotFun<Id, (K, V)> {
+        &self.updates
+    }
+
+    /// Gets the inner by value, consuming self.
+    pub fn into_reveal(self) -> DotFun<Id, (K, V)> {
+        self.updates
+    }
+}
+
+impl<Id, K, V> Merge<OrMap<Id, K, V>> for OrMap<Id, K, V>
+where
+    Id: Ord + Clone,
+{
+    fn merge(&mut self, other: OrMap<Id, K, V>) -> bool {
+        self.updates.merge(other.updates)
+    }
+}
+
+impl<Id, K, V> LatticeFrom<OrMap<Id, K, V>> for OrMap<Id, K, V> {
+    fn lattice_from(other: OrMap<Id, K, V>) -> Self {
+        other
+    }
+}
+
+impl<Id, K, V> PartialOrd for OrMap<Id, K, V>
+where
+    Id: Ord + Clone,
+    K: PartialEq,
+    V: PartialEq,
+{
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.updates.partial_cmp(&other.updates)
+    }
+}
+impl<Id, K, V> LatticeOrd<Self> for OrMap<Id, K, V> where Self: PartialOrd<Self> {}
+
+impl<Id, K, V> IsBot for OrMap<Id, K, V> {
+    fn is_bot(&self) -> bool {
+        self.updates.is_bot()
+    }
+}
+
+impl<Id, K, V> IsTop for OrMap<Id, K, V> {
+    fn is_top(&self) -> bool {
+        false
+    }
+}
+
+impl<Id, K, V> Atomize for OrMap<Id, K, V>
+where
+    Id: 'static + Ord + Clone,
+    K: 'static,
+    V: 'static,
+{
+    type Atom = Self;
+
+    // TODO: use impl trait, then remove 'static.
+    type AtomIter = Box<dyn Iterator<Item = Self::Atom>>;
+
+    fn atomize(self) -> Self::AtomIter {
+        Box::new(self.updates.atomize().map(|updates| Self { updates }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Max;
+    use crate::test::{check_all, check_atomize_each};
+
+    #[test]
+    fn consistency() {
+        let mut a = OrMap::new();
+        let mut b = OrMap::new();
+        let mut test_vec = vec![OrMap::default()];
+
+        test_vec.push(a.update('a', "k", Max::new(1)));
+        test_vec.push(a.update('a', "l", Max::new(2)));
+        test_vec.push(a.clone());
+        b.merge(a.clone());
+        test_vec.push(b.update('b', "k", Max::new(3)));
+        test_vec.push(b.remove(&"l"));
+        test_vec.push(b.clone());
+        test_vec.push(a.update('a', "k", Max::new(0)));
+        test_vec.push(a.clone());
+        a.merge(b.clone());
+        test_vec.push(a.clone());
+
+        check_all(&test_vec);
+        check_atomize_each(&test_vec);
+    }
+
+    #[test]
+    fn concurrent_updates_merge() {
+        let mut a = OrMap::new();
+        let mut b = OrMap::new();
+        b.merge(a.update('a', "k", Max::new(1)));
+
+        // Concurrent updates are merged.
+        let update_a = a.update('a', "k", Max::new(2));
+        let update_b = b.update('b', "k", Max::new(3));
+        a.merge(update_b);
+        b.merge(update_a);
+        assert_eq!(a, b);
+        assert_eq!(Some(Max::new(3)), a.get(&"k"));
+
+        // A concurrent update wins over a remove, keeping the value seen by the updating replica.
+        let remove = a.remove(&"k");
+        let update = b.update('b', "k", Max::new(0));
+        a.merge(update);
+        b.merge(remove);
+        assert_eq!(a, b);
+        assert_eq!(Some(Max::new(3)), a.get(&"k"));
+
+        // A key which has been removed is reset.
+        b.merge(a.remove(&"k"));
+        b.update('b', "k", Max::new(1));
+        assert_eq!(Some(Max::new(1)), b.get(&"k"));
+        assert_eq!(vec![&"k"], b.keys().collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,198 @@
+use std::cmp::Ordering;
+use std::collections::BTreeSet;
+
+use super::DotFun;
+use crate::{Atomize, IsBot, IsTop, LatticeFrom, LatticeOrd, Merge};
+
+/// Observed-remove set CRDT, with add-wins semantics.
+///
+/// Each insert is tagged with a unique [`Dot`](crate::Dot), and a remove only removes the inserts
+/// which the removing replica has seen. So if an item is inserted and removed concurrently, the
+/// insert wins.
+///
+/// Removes are tracked by the [`DotContext`](crate::DotContext) of seen events rather than by
+/// tombstones of the removed items, so the state only grows with the number of replicas and the
+/// number of events received out of order.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(
+        serialize = "Id: serde::Serialize, T: serde::Serialize",
+        deserialize = "Id: Ord + serde::Deserialize<'de>, T: serde::Deserialize<'de>"
+    ))
+)]
+pub struct OrSet<Id, T> {
+    items: DotFun<Id, T>,
+}
+
+impl<Id, T> Default for OrSet<Id, T> {
+    fn default() -> Self {
+        Self {
+            items: DotFun::default(),
+        }
+    }
+}
+
+impl<Id, T> OrSet<Id, T>
+where
+    Id: Ord + Clone,
+    T: Ord,
+{
+    /// Create a new empty `OrSet`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Inserts `item` on behalf of replica `id`. Returns the delta.
+    pub fn insert(&mut self, id: Id, item: T) -> Self
+    where
+        T: Clone,
+    {
+        Self {
+            items: self.items.write(id, item.clone(), |other| other == &item),
+        }
+    }
+
+    /// Removes all the observed inserts of `item`. Returns the delta.
+    pub fn remove(&mut self, item: &T) -> Self {
+        Self {
+            items: self.items.remove(|other| other == item),
+        }
+    }
+
+    /// If `item` is in the set.
+    pub fn contains(&self, item: &T) -> bool {
+        self.items.values().any(|other| other == item)
+    }
+
+    /// Iterates the items in the set, in order.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.items.values().collect::<BTreeSet<_>>().into_iter()
+    }
+
+    /// The number of items in the set.
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    /// If the set has no items.
+    pub fn is_empty(&self) -> bool {
+        self.items.values().next().is_none()
+    }
+}
+
+impl<Id, T> OrSet<Id, T> {
+    /// Reveal the inner value as a shared reference.
+    pub fn as_reveal_ref(&self) -> &DotFun<Id, T> {
+        &self.items
+    }
+
+    /// Gets the inner by value, consuming self.
+    pub fn into_reveal(self) -> DotFun<Id, T> {
+        self.items
+    }
+}
+
+impl<Id, T> Merge<OrSet<Id, T>> for OrSet<Id, T>
+where
+    Id: Ord + Clone,
+{
+    fn merge(&mut self, other: OrSet<Id, T>) -> bool {
+        self.items.merge(other.items)
+    }
+}
+
+impl<Id, T> LatticeFrom<OrSet<Id, T>> for OrSet<Id, T> {
+    fn lattice_from(other: OrSet<Id, T>) -> Self {
+        other
+    }
+}
+
+impl<Id, T> PartialOrd for OrSet<Id, T>
+where
+    Id: Ord + Clone,
+    T: PartialEq,
+{
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.items.partial_cmp(&other.items)
+    }
+}
+impl<Id, T> LatticeOrd<Self> for OrSet<Id, T> where Self: PartialOrd<Self> {}
+
+impl<Id, T> IsBot for OrSet<Id, T> {
+    fn is_bot(&self) -> bool {
+        self.items.is_bot()
+    }
+}
+
+impl<Id, T> IsTop for OrSet<Id, T> {
+    fn is_top(&self) -> bool {
+        false
+    }
+}
+
+impl<Id, T> Atomize for OrSet<Id, T>
+where
+    Id: 'static + Ord + Clone,
+    T: 'static,
+{
+    type Atom = Self;
+
+    // TODO: use impl trait, then remove 'static.
+    type AtomIter = Box<dyn Iterator<Item = Self::Atom>>;
+
+    fn atomize(self) -> Self::AtomIter {
+        Box::new(self.items.atomize().map(|items| Self { items }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::{check_all, check_atomize_each};
+
+    #[test]
+    fn consistency() {
+        let mut a = OrSet::new();
+        let mut b = OrSet::new();
+        let mut test_vec = vec![OrSet::default()];
+
+        test_vec.push(a.insert('a', 1));
+        test_vec.push(a.insert('a', 2));
+        test_vec.push(a.clone());
+        b.merge(a.clone());
+        test_vec.push(b.remove(&1));
+        test_vec.push(b.clone());
+        test_vec.push(a.insert('a', 1));
+        test_vec.push(a.clone());
+        a.merge(b.clone());
+        test_vec.push(a.clone());
+
+        check_all(&test_vec);
+        check_atomize_each(&test_vec);
+    }
+
+    #[test]
+    fn add_wins() {
+        let mut a = OrSet::new();
+        let mut b = OrSet::new();
+        b.merge(a.insert('a', "x"));
+
+        // Concurrent insert and remove.
+        let insert = a.insert('a', "x");
+        let remove = b.remove(&"x");
+        assert!(!b.contains(&"x"));
+
+        a.merge(remove);
+        b.merge(insert);
+        assert_eq!(a, b);
+        assert!(a.contains(&"x"));
+
+        // An observed insert is removed.
+        let remove = a.remove(&"x");
+        b.merge(remove);
+        assert_eq!(a, b);
+        assert!(b.is_empty());
+    }
+}
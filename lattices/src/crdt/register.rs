@@ -0,0 +1,320 @@
+use std::cmp::Ordering;
+
+use super::DotFun;
+use crate::{Atomize, IsBot, IsTop, LatticeFrom, LatticeOrd, Merge};
+
+/// Last-writer-wins register CRDT.
+///
+/// Each write is tagged with a timestamp `Ts`, and merging keeps the write with the largest
+/// timestamp. Writes with equal timestamps are ordered by value, so every replica keeps the same
+/// write. To avoid losing concurrent writes with equal timestamps, `Ts` should include the id of
+/// the writing replica, e.g. `(wall_clock, replica_id)`.
+///
+/// The unwritten register is bottom.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LwwRegister<Ts, T> {
+    write: Option<(Ts, T)>,
+}
+
+impl<Ts, T> Default for LwwRegister<Ts, T> {
+    fn default() -> Self {
+        Self { write: None }
+    }
+}
+
+impl<Ts, T> LwwRegister<Ts, T>
+where
+    Ts: Ord,
+    T: Ord,
+{
+    /// Create a new unwritten `LwwRegister`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a new `LwwRegister` containing `value` written at `timestamp`.
+    pub fn new_from(timestamp: Ts, value: T) -> Self {
+        Self {
+            write: Some((timestamp, value)),
+        }
+    }
+
+    /// Writes `value` at `timestamp`. Has no effect if the register already holds a later write.
+    /// Returns the delta.
+    pub fn write(&mut self, timestamp: Ts, value: T) -> Self
+    where
+        Ts: Clone,
+        T: Clone,
+    {
+        let delta = Self::new_from(timestamp, value);
+        self.merge(delta.clone());
+        delta
+    }
+
+    /// The current value, if the register has been written.
+    pub fn read(&self) -> Option<&T> {
+        self.write.as_ref().map(|(_timestamp, value)| value)
+    }
+
+    /// The timestamp of the current value, if the register has been written.
+    pub fn timestamp(&self) -> Option<&Ts> {
+        self.write.as_ref().map(|(timestamp, _value)| timestamp)
+    }
+}
+
+impl<Ts, T> LwwRegister<Ts, T> {
+    /// Reveal the inner value as a shared reference.
+    pub fn as_reveal_ref(&self) -> Option<&(Ts, T)> {
+        self.write.as_ref()
+    }
+
+    /// Gets the inner by value, consuming self.
+    pub fn into_reveal(self) -> Option<(Ts, T)> {
+        self.write
+    }
+}
+
+impl<Ts, T> Merge<LwwRegister<Ts, T>> for LwwRegister<Ts, T>
+where
+    Ts: Ord,
+    T: Ord,
+{
+    fn merge(&mut self, other: LwwRegister<Ts, T>) -> bool {
+        if other.write > self.write {
+            self.write = other.write;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+impl<Ts, T> LatticeFrom<LwwRegister<Ts, T>> for LwwRegister<Ts, T> {
+    fn lattice_from(other: LwwRegister<Ts, T>) -> Self {
+        other
+    }
+}
+
+impl<Ts, T> PartialOrd for LwwRegister<Ts, T>
+where
+    Ts: Ord,
+    T: Ord,
+{
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.write.cmp(&other.write))
+    }
+}
+impl<Ts, T> LatticeOrd<Self> for LwwRegister<Ts, T> where Self: PartialOrd<Self> {}
+
+impl<Ts, T> IsBot for LwwRegister<Ts, T> {
+    fn is_bot(&self) -> bool {
+        self.write.is_none()
+    }
+}
+
+impl<Ts, T> IsTop for LwwRegister<Ts, T> {
+    fn is_top(&self) -> bool {
+        false
+    }
+}
+
+impl<Ts, T> Atomize for LwwRegister<Ts, T>
+where
+    Ts: 'static + Ord,
+    T: 'static + Ord,
+{
+    type Atom = Self;
+
+    type AtomIter = std::option::IntoIter<Self>;
+
+    fn atomize(self) -> Self::AtomIter {
+        self.write
+            .map(|write| Self { write: Some(write) })
+            .into_iter()
+    }
+}
+
+/// Multi-value register CRDT.
+///
+/// A write overwrites all the values which the writing replica has seen. Concurrent writes are all
+/// kept, so reading the register returns every value which has not been overwritten.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(
+        serialize = "Id: serde::Serialize, T: serde::Serialize",
+        deserialize = "Id: Ord + serde::Deserialize<'de>, T: serde::Deserialize<'de>"
+    ))
+)]
+pub struct MvRegister<Id, T> {
+    values: DotFun<Id, T>,
+}
+
+impl<Id, T> Default for MvRegister<Id, T> {
+    fn default() -> Self {
+        Self {
+            values: DotFun::default(),
+        }
+    }
+}
+
+impl<Id, T> MvRegister<Id, T>
+where
+    Id: Ord + Clone,
+{
+    /// Create a new unwritten `MvRegister`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Writes `value` on behalf of replica `id`, overwriting all current values. Returns the
+    /// delta.
+    pub fn write(&mut self, id: Id, value: T) -> Self
+    where
+        T: Clone,
+    {
+        Self {
+            values: self.values.write(id, value, |_| true),
+        }
+    }
+
+    /// Iterates the current values, which were written concurrently.
+    pub fn read(&self) -> impl Iterator<Item = &T> {
+        self.values.values()
+    }
+}
+
+impl<Id, T> MvRegister<Id, T> {
+    /// Reveal the inner value as a shared reference.
+    pub fn as_reveal_ref(&self) -> &DotFun<Id, T> {
+        &self.values
+    }
+
+    /// Gets the inner by value, consuming self.
+    pub fn into_reveal(self) -> DotFun<Id, T> {
+        self.values
+    }
+}
+
+impl<Id, T> Merge<MvRegister<Id, T>> for MvRegister<Id, T>
+where
+    Id: Ord + Clone,
+{
+    fn merge(&mut self, other: MvRegister<Id, T>) -> bool {
+        self.values.merge(other.values)
+    }
+}
+
+impl<Id, T> LatticeFrom<MvRegister<Id, T>> for MvRegister<Id, T> {
+    fn lattice_from(other: MvRegister<Id, T>) -> Self {
+        other
+    }
+}
+
+impl<Id, T> PartialOrd for MvRegister<Id, T>
+where
+    Id: Ord + Clone,
+    T: PartialEq,
+{
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.values.partial_cmp(&other.values)
+    }
+}
+impl<Id, T> LatticeOrd<Self> for MvRegister<Id, T> where Self: PartialOrd<Self> {}
+
+impl<Id, T> IsBot for MvRegister<Id, T> {
+    fn is_bot(&self) -> bool {
+        self.values.is_bot()
+    }
+}
+
+impl<Id, T> IsTop for MvRegister<Id, T> {
+    fn is_top(&self) -> bool {
+        false
+    }
+}
+
+impl<Id, T> Atomize for MvRegister<Id, T>
+where
+    Id: 'static + Ord + Clone,
+    T: 'static,
+{
+    type Atom = Self;
+
+    // TODO: use impl trait, then remove 'static.
+    type AtomIter = Box<dyn Iterator<Item = Self::Atom>>;
+
+    fn atomize(self) -> Self::AtomIter {
+        Box::new(self.values.atomize().map(|values| Self { values }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::{check_all, check_atomize_each};
+
+    #[test]
+    fn consistency_lww_register() {
+        let mut test_vec = vec![LwwRegister::new()];
+        for timestamp in [(0, 'a'), (0, 'b'), (1, 'a')] {
+            for value in ["x", "y"] {
+                test_vec.push(LwwRegister::new_from(timestamp, value));
+            }
+        }
+
+        check_all(&test_vec);
+        check_atomize_each(&test_vec);
+    }
+
+    #[test]
+    fn lww_register_later_write_wins() {
+        let mut a = LwwRegister::new();
+        let mut b = LwwRegister::new();
+        let delta_a = a.write((2, 'a'), "x");
+        let delta_b = b.write((1, 'b'), "y");
+
+        assert!(!a.merge(delta_b));
+        assert!(b.merge(delta_a));
+        assert_eq!(a, b);
+        assert_eq!(Some(&"x"), b.read());
+    }
+
+    #[test]
+    fn consistency_mv_register() {
+        let mut a = MvRegister::new();
+        let mut b = MvRegister::new();
+        let mut test_vec = vec![MvRegister::default()];
+
+        test_vec.push(a.write('a', "x"));
+        test_vec.push(b.write('b', "y"));
+        test_vec.push(b.clone());
+        a.merge(b.clone());
+        test_vec.push(a.clone());
+        test_vec.push(a.write('a', "z"));
+        test_vec.push(a.clone());
+
+        check_all(&test_vec);
+        check_atomize_each(&test_vec);
+    }
+
+    #[test]
+    fn mv_register_concurrent_writes() {
+        let mut a = MvRegister::new();
+        let mut b = MvRegister::new();
+        let delta_a = a.write('a', "x");
+        let delta_b = b.write('b', "y");
+
+        a.merge(delta_b);
+        b.merge(delta_a);
+        assert_eq!(a, b);
+        assert_eq!(vec![&"x", &"y"], a.read().collect::<Vec<_>>());
+
+        let delta = b.write('b', "z");
+        a.merge(delta);
+        assert_eq!(a, b);
+        assert_eq!(vec![&"z"], a.read().collect::<Vec<_>>());
+    }
+}
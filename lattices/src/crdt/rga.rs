@@ -0,0 +1,284 @@
+use std::cmp::Ordering::{self, *};
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{Atomize, IsBot, IsTop, LatticeFrom, LatticeOrd, Merge};
+
+/// Identifier of an element of an [`Rga`]: a Lamport timestamp and the id of the inserting
+/// replica.
+pub type RgaKey<Id> = (u64, Id);
+
+/// Replicated growable array (RGA) sequence CRDT.
+///
+/// Each element is inserted after an existing element (or at the start), and is identified by an
+/// [`RgaKey`] larger than the keys of all elements the inserting replica has seen. The sequence
+/// is the pre-order traversal of this tree, visiting the elements inserted after the same element
+/// from largest key to smallest. So concurrent inserts at the same position are ordered the same
+/// way by every replica, and elements inserted by one replica keep their relative order.
+///
+/// Removed elements are kept as tombstones, as later inserts may be positioned after them.
+/// Elements whose predecessor has not been received yet are hidden until it arrives.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(
+        serialize = "Id: serde::Serialize, T: serde::Serialize",
+        deserialize = "Id: Ord + serde::Deserialize<'de>, T: serde::Deserialize<'de>"
+    ))
+)]
+pub struct Rga<Id, T> {
+    /// Each element's predecessor and value.
+    nodes: BTreeMap<RgaKey<Id>, (Option<RgaKey<Id>>, T)>,
+    removed: BTreeSet<RgaKey<Id>>,
+}
+
+impl<Id, T> Default for Rga<Id, T> {
+    fn default() -> Self {
+        Self {
+            nodes: BTreeMap::new(),
+            removed: BTreeSet::new(),
+        }
+    }
+}
+
+impl<Id, T> Rga<Id, T>
+where
+    Id: Ord + Clone,
+{
+    /// Create a new empty `Rga`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Inserts `value` at `index` on behalf of replica `id`, shifting the following elements.
+    /// Returns the delta.
+    ///
+    /// # Panics
+    /// Panics if `index > len`.
+    pub fn insert(&mut self, id: Id, index: usize, value: T) -> Self
+    where
+        T: Clone,
+    {
+        let after = match index {
+            0 => None,
+            _ => Some(self.visible_key(index - 1).clone()),
+        };
+        let timestamp = self.nodes.keys().next_back().map_or(0, |key| key.0) + 1;
+        let node = (after, value);
+        self.nodes.insert((timestamp, id.clone()), node.clone());
+        Self {
+            nodes: BTreeMap::from([((timestamp, id), node)]),
+            removed: BTreeSet::new(),
+        }
+    }
+
+    /// Removes the element at `index`, shifting the following elements. Returns the delta.
+    ///
+    /// # Panics
+    /// Panics if `index >= len`.
+    pub fn remove(&mut self, index: usize) -> Self {
+        let key = self.visible_key(index).clone();
+        self.removed.insert(key.clone());
+        Self {
+            nodes: BTreeMap::new(),
+            removed: BTreeSet::from([key]),
+        }
+    }
+
+    /// Iterates the elements of the sequence, in order.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.visible_keys()
+            .into_iter()
+            .map(|key| &self.nodes[key].1)
+    }
+
+    /// The number of elements in the sequence.
+    pub fn len(&self) -> usize {
+        self.visible_keys().len()
+    }
+
+    /// If the sequence has no elements.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn visible_key(&self, index: usize) -> &RgaKey<Id> {
+        let keys = self.visible_keys();
+        let len = keys.len();
+        keys.get(index).unwrap_or_else(|| {
+            panic!("index (is {}) should be < len (is {})", index, len);
+        })
+    }
+
+    /// The keys of the elements which have not been removed, in sequence order.
+    fn visible_keys(&self) -> Vec<&RgaKey<Id>> {
+        let mut children = BTreeMap::<Option<&RgaKey<Id>>, Vec<&RgaKey<Id>>>::new();
+        for (key, (after, _value)) in self.nodes.iter() {
+            children.entry(after.as_ref()).or_default().push(key);
+        }
+
+        // Children are in ascending order, so the largest is popped first.
+        let mut stack = children.remove(&None).unwrap_or_default();
+        let mut keys = Vec::new();
+        while let Some(key) = stack.pop() {
+            if !self.removed.contains(key) {
+                keys.push(key);
+            }
+            stack.extend(children.remove(&Some(key)).unwrap_or_default());
+        }
+        keys
+    }
+}
+
+impl<Id, T> Rga<Id, T> {
+    /// Reveal the inner value as a shared reference.
+    #[expect(clippy::type_complexity, reason = "internal representation")]
+    pub fn as_reveal_ref(
+        &self,
+    ) -> (
+        &BTreeMap<RgaKey<Id>, (Option<RgaKey<Id>>, T)>,
+        &BTreeSet<RgaKey<Id>>,
+    ) {
+        (&self.nodes, &self.removed)
+    }
+
+    /// Gets the inner by value, consuming self.
+    #[expect(clippy::type_complexity, reason = "internal representation")]
+    pub fn into_reveal(
+        self,
+    ) -> (
+        BTreeMap<RgaKey<Id>, (Option<RgaKey<Id>>, T)>,
+        BTreeSet<RgaKey<Id>>,
+    ) {
+        (self.nodes, self.removed)
+    }
+}
+
+impl<Id, T> Merge<Rga<Id, T>> for Rga<Id, T>
+where
+    Id: Ord,
+{
+    fn merge(&mut self, other: Rga<Id, T>) -> bool {
+        let old_len = (self.nodes.len(), self.removed.len());
+        for (key, node) in other.nodes {
+            self.nodes.entry(key).or_insert(node);
+        }
+        self.removed.extend(other.removed);
+        old_len != (self.nodes.len(), self.removed.len())
+    }
+}
+
+impl<Id, T> LatticeFrom<Rga<Id, T>> for Rga<Id, T> {
+    fn lattice_from(other: Rga<Id, T>) -> Self {
+        other
+    }
+}
+
+impl<Id, T> PartialOrd for Rga<Id, T>
+where
+    Id: Ord,
+    T: PartialEq,
+{
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        let self_le = self.nodes.keys().all(|key| other.nodes.contains_key(key))
+            && self.removed.is_subset(&other.removed);
+        let other_le = other.nodes.keys().all(|key| self.nodes.contains_key(key))
+            && other.removed.is_subset(&self.removed);
+        match (self_le, other_le) {
+            (true, true) => Some(Equal),
+            (true, false) => Some(Less),
+            (false, true) => Some(Greater),
+            (false, false) => None,
+        }
+    }
+}
+impl<Id, T> LatticeOrd<Self> for Rga<Id, T> where Self: PartialOrd<Self> {}
+
+impl<Id, T> IsBot for Rga<Id, T> {
+    fn is_bot(&self) -> bool {
+        self.nodes.is_empty() && self.removed.is_empty()
+    }
+}
+
+impl<Id, T> IsTop for Rga<Id, T> {
+    fn is_top(&self) -> bool {
+        false
+    }
+}
+
+impl<Id, T> Atomize for Rga<Id, T>
+where
+    Id: 'static + Ord,
+    T: 'static,
+{
+    type Atom = Self;
+
+    // TODO: use impl trait, then remove 'static.
+    type AtomIter = Box<dyn Iterator<Item = Self::Atom>>;
+
+    fn atomize(self) -> Self::AtomIter {
+        let nodes = self.nodes.into_iter().map(|(key, node)| Self {
+            nodes: BTreeMap::from([(key, node)]),
+            removed: BTreeSet::new(),
+        });
+        let removed = self.removed.into_iter().map(|key| Self {
+            nodes: BTreeMap::new(),
+            removed: BTreeSet::from([key]),
+        });
+        Box::new(nodes.chain(removed))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::{check_all, check_atomize_each};
+
+    #[test]
+    fn consistency() {
+        let mut a = Rga::new();
+        let mut b = Rga::new();
+        let mut test_vec = vec![Rga::default()];
+
+        test_vec.push(a.insert('a', 0, "x"));
+        test_vec.push(a.insert('a', 1, "y"));
+        test_vec.push(a.clone());
+        b.merge(a.clone());
+        test_vec.push(b.insert('b', 1, "z"));
+        test_vec.push(b.remove(0));
+        test_vec.push(b.clone());
+        test_vec.push(a.insert('a', 1, "w"));
+        test_vec.push(a.clone());
+        a.merge(b.clone());
+        test_vec.push(a.clone());
+
+        check_all(&test_vec);
+        check_atomize_each(&test_vec);
+    }
+
+    #[test]
+    fn concurrent_inserts() {
+        let mut a = Rga::new();
+        let mut b = Rga::new();
+        for (index, value) in "ad".chars().enumerate() {
+            b.merge(a.insert('a', index, value));
+        }
+
+        // Concurrent inserts at the same position.
+        let deltas_a = [a.insert('a', 1, 'b'), a.insert('a', 2, 'c')];
+        let deltas_b = [b.insert('b', 1, 'x'), b.remove(0)];
+        assert_eq!("abcd", a.iter().collect::<String>());
+        assert_eq!("xd", b.iter().collect::<String>());
+
+        // Deltas may be reordered.
+        for delta in deltas_a.into_iter().rev() {
+            b.merge(delta);
+        }
+        for delta in deltas_b {
+            a.merge(delta);
+        }
+        assert_eq!(a, b);
+        assert_eq!("xbcd", a.iter().collect::<String>());
+        assert_eq!(4, a.len());
+    }
+}
@@ -9,23 +9,35 @@ pub use {cc_traits, variadics};
 /// Module for definiting algebraic structures and properties.
 pub mod algebra;
 pub mod collections;
-mod conflict;
-mod dom_pair;
+// Hidden modules are public so that code generated from `std::any::type_name` (such as Hydro's
+// staged code) can refer to the lattice types by the paths of their definitions.
+#[doc(hidden)]
+pub mod conflict;
+pub mod crdt;
+#[doc(hidden)]
+pub mod dom_pair;
 pub mod ght;
 pub mod map_union;
 pub mod map_union_with_tombstones;
-mod ord;
-mod pair;
-mod point;
+#[doc(hidden)]
+pub mod ord;
+#[doc(hidden)]
+pub mod pair;
+#[doc(hidden)]
+pub mod point;
 pub mod semiring_application;
 pub mod set_union;
 pub mod set_union_with_tombstones;
 pub mod test;
 pub mod union_find;
 mod unit;
-mod vec_union;
-mod with_bot;
-mod with_top;
+#[doc(hidden)]
+pub mod vec_union;
+pub mod vector_clock;
+#[doc(hidden)]
+pub mod with_bot;
+#[doc(hidden)]
+pub mod with_top;
 
 pub use conflict::Conflict;
 pub use dom_pair::DomPair;
@@ -34,6 +46,7 @@ pub use ord::{Max, Min};
 pub use pair::{Pair, PairBimorphism};
 pub use point::Point;
 pub use vec_union::VecUnion;
+pub use vector_clock::{Dot, DotContext, VClock, VersionVector};
 pub use with_bot::WithBot;
 pub use with_top::WithTop;
 
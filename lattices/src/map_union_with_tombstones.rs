@@ -7,7 +7,7 @@ use std::fmt::Debug;
 use cc_traits::{Get, Iter, Len, Remove};
 
 use crate::cc_traits::{GetMut, Keyed, Map, MapIter, SimpleKeyedRef};
-use crate::collections::{EmptyMap, EmptySet, SingletonMap, SingletonSet};
+use crate::collections::{EmptyMap, EmptySet, RangeSet, SingletonMap, SingletonSet};
 use crate::{IsBot, IsTop, LatticeFrom, LatticeOrd, Merge};
 
 /// Map-union-with-tombstones compound lattice.
@@ -24,6 +24,10 @@ use crate::{IsBot, IsTop, LatticeFrom, LatticeOrd, Merge};
 /// For example, if the user knows that keys will be created and deleted strictly sequentially, then they could create a highly optimized set implementation
 /// which would just be a single integer, correpsonding to the current key value that the set is up to. Queries for keys below that integer would return true,
 /// queries for keys above it would return false.
+///
+/// [`crate::collections::RangeSet`] is such an implementation for integer keys, storing runs of
+/// consecutive tombstones as ranges. Tombstones which every replica has received can also be
+/// dropped with [`Self::compact_tombstones`].
 #[derive(Copy, Clone, Debug, Default)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MapUnionWithTombstones<Map, TombstoneSet> {
@@ -58,6 +62,26 @@ impl<Map, TombstoneSet> MapUnionWithTombstones<Map, TombstoneSet> {
     }
 }
 
+impl<Map, TombstoneSet, K> MapUnionWithTombstones<Map, TombstoneSet>
+where
+    TombstoneSet: Default + Len + IntoIterator<Item = K> + FromIterator<K>,
+{
+    /// Drops the tombstones for which `is_stable` returns `true`. Returns `true` if any were
+    /// dropped.
+    ///
+    /// This is not a lattice operation, so it is only safe for tombstones which are causally
+    /// stable: every replica has received the tombstone, so no replica will send the deleted key
+    /// again. Otherwise a later merge may resurrect the key.
+    pub fn compact_tombstones(&mut self, mut is_stable: impl FnMut(&K) -> bool) -> bool {
+        let old_tombstones_len = self.tombstones.len();
+        self.tombstones = std::mem::take(&mut self.tombstones)
+            .into_iter()
+            .filter(|k| !is_stable(k))
+            .collect();
+        self.tombstones.len() < old_tombstones_len
+    }
+}
+
 impl<MapSelf, MapOther, K, ValSelf, ValOther, TombstoneSetSelf, TombstoneSetOther>
     Merge<MapUnionWithTombstones<MapOther, TombstoneSetOther>>
     for MapUnionWithTombstones<MapSelf, TombstoneSetSelf>
@@ -327,6 +351,11 @@ impl<Map, TombstoneSet> IsTop for MapUnionWithTombstones<Map, TombstoneSet> {
 pub type MapUnionHashMapWithTombstoneHashSet<K, Val> =
     MapUnionWithTombstones<HashMap<K, Val>, HashSet<K>>;
 
+/// [`std::collections::HashMap`]-backed [`MapUnionWithTombstones`] lattice, with
+/// [`crate::collections::RangeSet`]-backed tombstones.
+pub type MapUnionHashMapWithTombstoneRangeSet<K, Val> =
+    MapUnionWithTombstones<HashMap<K, Val>, RangeSet<K>>;
+
 /// [`crate::collections::SingletonMap`]-backed [`MapUnionWithTombstones`] lattice.
 pub type MapUnionWithTombstonesSingletonMapOnly<K, Val> =
     MapUnionWithTombstones<SingletonMap<K, Val>, EmptySet<K>>;
@@ -416,6 +445,51 @@ mod test {
         check_all(&test_vec);
     }
 
+    #[test]
+    fn consistency_range_set() {
+        type V = SetUnion<HashSet<i32>>;
+        type M = MapUnionHashMapWithTombstoneRangeSet<u64, V>;
+
+        let mut test_vec = Vec::new();
+
+        #[rustfmt::skip]
+        {
+            test_vec.push(M::new_from([], RangeSet::new()));
+
+            test_vec.push(M::new_from([], RangeSet::from_iter([0])));
+            test_vec.push(M::new_from([], RangeSet::from_iter([1])));
+            test_vec.push(M::new_from([], RangeSet::from_iter([0, 1])));
+
+            test_vec.push(M::new_from([(0, SetUnionHashSet::new_from([0]))], RangeSet::new()));
+            test_vec.push(M::new_from([(0, SetUnionHashSet::new_from([0, 1]))], RangeSet::new()));
+            test_vec.push(M::new_from([(1, SetUnionHashSet::new_from([0]))], RangeSet::new()));
+            test_vec.push(M::new_from([(1, SetUnionHashSet::new_from([0]))], RangeSet::from_iter([0])));
+        };
+
+        check_all(&test_vec);
+    }
+
+    #[test]
+    fn compact_tombstones() {
+        type V = SetUnion<HashSet<i32>>;
+        type M = MapUnionHashMapWithTombstoneRangeSet<u64, V>;
+
+        let mut map = M::new_from([(3, SetUnionHashSet::new_from([0]))], RangeSet::new());
+        map.merge(M::new_from([], RangeSet::from_iter([0, 1, 2, 4])));
+        assert_eq!(
+            vec![(0, 2), (4, 4)],
+            map.as_reveal_ref().1.ranges().collect::<Vec<_>>()
+        );
+
+        // Drop the tombstones below the stable key.
+        assert!(map.compact_tombstones(|&k| k < 2));
+        assert_eq!(
+            vec![(2, 2), (4, 4)],
+            map.as_reveal_ref().1.ranges().collect::<Vec<_>>()
+        );
+        assert!(map.as_reveal_ref().0.contains_key(&3));
+    }
+
     /// Check that a key with a value of bottom is the same as an empty map, etc.
     #[test]
     fn test_collapses_bot() {
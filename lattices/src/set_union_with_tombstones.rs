@@ -6,7 +6,7 @@ use std::collections::{BTreeSet, HashSet};
 use cc_traits::{Collection, Get, Remove};
 
 use crate::cc_traits::{Iter, Len, Set};
-use crate::collections::{ArraySet, EmptySet, OptionSet, SingletonSet};
+use crate::collections::{ArraySet, EmptySet, OptionSet, RangeSet, SingletonSet};
 use crate::{IsBot, IsTop, LatticeFrom, LatticeOrd, Merge};
 
 /// Set-union lattice with tombstones.
@@ -17,6 +17,10 @@ use crate::{IsBot, IsTop, LatticeFrom, LatticeOrd, Merge};
 /// Merging set-union lattices is done by unioning the keys of both the (set and tombstone) sets,
 /// and then performing `set` = `set` - `tombstones`, to preserve the above invariant.
 ///
+/// Tombstones are kept forever by merging, but those which every replica has received can be
+/// dropped with [`Self::compact_tombstones`]. For sets of integers, a [`RangeSet`] stores runs
+/// of consecutive tombstones compactly.
+///
 /// TODO: I think there is even one more layer of abstraction that can be made here.
 /// this 'SetUnionWithTombstones' can be turned into a kind of interface, because there are multiple underlying implementations.
 /// This implementation is two separate sets. Another implementation could be MapUnion<Key, WithTop<()>>, this would be less hash lookups, but maybe gives you less options for cool storage tricks.
@@ -54,6 +58,28 @@ impl<Set, TombstoneSet> SetUnionWithTombstones<Set, TombstoneSet> {
     }
 }
 
+impl<Set, TombstoneSet, Item> SetUnionWithTombstones<Set, TombstoneSet>
+where
+    TombstoneSet: Default + Len + IntoIterator<Item = Item> + FromIterator<Item>,
+{
+    /// Drops the tombstones for which `is_stable` returns `true`. Returns `true` if any were
+    /// dropped.
+    ///
+    /// This is not a lattice operation, so it is only safe for tombstones which are causally
+    /// stable: every replica has received the tombstone, so no replica will send the deleted item
+    /// again. Otherwise a later merge may resurrect the item. For example, if each deletion is
+    /// tagged with a [`Dot`](crate::Dot), a tombstone is stable once its dot is contained in the
+    /// [meet](crate::VClock::meet) of the version vectors acknowledged by every replica.
+    pub fn compact_tombstones(&mut self, mut is_stable: impl FnMut(&Item) -> bool) -> bool {
+        let old_tombstones_len = self.tombstones.len();
+        self.tombstones = std::mem::take(&mut self.tombstones)
+            .into_iter()
+            .filter(|item| !is_stable(item))
+            .collect();
+        self.tombstones.len() < old_tombstones_len
+    }
+}
+
 impl<Item, SetSelf, TombstoneSetSelf, SetOther, TombstoneSetOther>
     Merge<SetUnionWithTombstones<SetOther, TombstoneSetOther>>
     for SetUnionWithTombstones<SetSelf, TombstoneSetSelf>
@@ -265,13 +291,21 @@ pub type SetUnionWithTombstonesSingletonSet<Item> =
 pub type SetUnionWithTombstonesOptionSet<Item> =
     SetUnionWithTombstones<OptionSet<Item>, OptionSet<Item>>;
 
+/// [`std::collections::HashSet`]-backed [`SetUnionWithTombstones`] lattice, with
+/// [`crate::collections::RangeSet`]-backed tombstones.
+pub type SetUnionWithTombstonesRangeSet<Item> =
+    SetUnionWithTombstones<HashSet<Item>, RangeSet<Item>>;
+
 /// [`crate::collections::SingletonSet`]-backed [`SetUnionWithTombstones`] lattice.
 pub type SetUnionWithTombstonesTombstoneOnlySet<Item> =
     SetUnionWithTombstones<EmptySet<Item>, SingletonSet<Item>>;
 
 #[cfg(test)]
 mod test {
+    use std::collections::HashMap;
+
     use super::*;
+    use crate::VClock;
     use crate::test::check_all;
 
     #[test]
@@ -327,4 +361,70 @@ mod test {
             SetUnionWithTombstonesHashSet::new_from([1], [0]),
         ]);
     }
+
+    #[test]
+    fn consistency_range_set() {
+        check_all(&[
+            SetUnionWithTombstonesRangeSet::new_from([], RangeSet::new()),
+            SetUnionWithTombstonesRangeSet::new_from([0], RangeSet::new()),
+            SetUnionWithTombstonesRangeSet::new_from([], RangeSet::from_iter([0])),
+            SetUnionWithTombstonesRangeSet::new_from([2], RangeSet::from_iter([0, 1])),
+            SetUnionWithTombstonesRangeSet::new_from([], RangeSet::from_iter([0, 2])),
+            SetUnionWithTombstonesRangeSet::new_from([1], RangeSet::from_iter([0, 2])),
+            SetUnionWithTombstonesRangeSet::new_from([], RangeSet::from_iter([0, 1, 2])),
+        ]);
+    }
+
+    #[test]
+    fn range_set_coalesces() {
+        let mut x = SetUnionWithTombstonesRangeSet::new_from([], RangeSet::from_iter([0, 4]));
+        x.merge(SetUnionWithTombstonesRangeSet::new_from(
+            [],
+            RangeSet::from_iter([1, 3, 2]),
+        ));
+        x.merge(SetUnionWithTombstonesRangeSet::new_from(
+            [7],
+            RangeSet::from_iter([5]),
+        ));
+
+        let tombstones = x.as_reveal_ref().1;
+        assert_eq!(vec![(0, 5)], tombstones.ranges().collect::<Vec<_>>());
+        assert_eq!(6, tombstones.len());
+        assert!(!tombstones.contains(&6));
+        assert_eq!(
+            vec![0, 1, 2, 3, 4, 5],
+            tombstones.clone().into_iter().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn compact_stable_tombstones() {
+        let mut a = SetUnionWithTombstonesBTreeSet::new_from([], []);
+        let mut b = SetUnionWithTombstonesBTreeSet::new_from([], []);
+        let mut clock_a = VClock::new();
+        let mut clock_b = VClock::new();
+        let mut deleted_at = HashMap::new();
+
+        a.merge(SetUnionWithTombstonesBTreeSet::new_from(["x", "y"], []));
+        deleted_at.insert("x", clock_a.increment('a'));
+        a.merge(SetUnionWithTombstonesBTreeSet::new_from([], ["x"]));
+        b.merge(a.clone());
+        clock_b.merge(clock_a.clone());
+
+        // `b` has not received the deletion of "y" yet.
+        deleted_at.insert("y", clock_a.increment('a'));
+        a.merge(SetUnionWithTombstonesBTreeSet::new_from([], ["y"]));
+
+        let frontier = clock_a.meet(&clock_b);
+        let is_stable = |item: &&str| frontier.contains(&deleted_at[item]);
+        assert!(a.compact_tombstones(is_stable));
+        assert!(!a.compact_tombstones(is_stable));
+        assert_eq!(&BTreeSet::from(["y"]), a.as_reveal_ref().1);
+
+        // Still converges, as every replica has received the dropped tombstone.
+        b.merge(a.clone());
+        b.compact_tombstones(is_stable);
+        assert_eq!(a, b);
+        assert!(a.as_reveal_ref().0.is_empty());
+    }
 }
@@ -0,0 +1,568 @@
+//! Module containing causality-tracking lattices: [`VClock`] (also known as [`VersionVector`]),
+//! [`Dot`], and [`DotContext`].
+//!
+//! These can be used as the key of a [`DomPair`](crate::DomPair) to detect concurrent writes: if
+//! the clocks of two writes are incomparable then neither write has seen the other, and
+//! [`DomPair`](crate::DomPair) keeps the merge of both values instead of picking one.
+
+use std::cmp::Ordering::{self, *};
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{Atomize, DeepReveal, IsBot, IsTop, LatticeFrom, LatticeOrd, Merge};
+
+/// A single event, identified by the replica `id` which produced it and the replica's `counter`
+/// at the time. Counters start at `1`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Dot<Id> {
+    /// The replica which produced the event.
+    pub id: Id,
+    /// The replica's event counter.
+    pub counter: u64,
+}
+impl<Id> Dot<Id> {
+    /// Create a new `Dot` from a replica `id` and `counter`.
+    pub fn new(id: Id, counter: u64) -> Self {
+        Self { id, counter }
+    }
+}
+
+/// Vector clock lattice, mapping each replica to the number of its events which have been seen.
+///
+/// Merging takes the pointwise max of the counters, and clocks are ordered pointwise. Two clocks
+/// which are incomparable represent concurrent histories.
+///
+/// Replicas with a zero counter are never stored, so the clock only grows with the number of
+/// replicas which have produced events.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(deserialize = "Id: Ord + serde::Deserialize<'de>"))
+)]
+pub struct VClock<Id> {
+    counters: BTreeMap<Id, u64>,
+}
+
+/// Version vector lattice, an alias of [`VClock`].
+///
+/// Version vectors and vector clocks share a representation; "version vector" is conventionally
+/// used when the clock tracks updates to replicated data rather than all events.
+pub type VersionVector<Id> = VClock<Id>;
+
+impl<Id> Default for VClock<Id> {
+    fn default() -> Self {
+        Self {
+            counters: BTreeMap::new(),
+        }
+    }
+}
+
+impl<Id> VClock<Id>
+where
+    Id: Ord,
+{
+    /// Create a new empty `VClock`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a new `VClock` from `(id, counter)` pairs. Repeated ids take the largest counter.
+    pub fn new_from(counters: impl IntoIterator<Item = (Id, u64)>) -> Self {
+        let mut out = Self::new();
+        for (id, counter) in counters {
+            out.observe(Dot::new(id, counter));
+        }
+        out
+    }
+
+    /// The counter of replica `id`, or `0` if it has no events.
+    pub fn get(&self, id: &Id) -> u64 {
+        self.counters.get(id).copied().unwrap_or(0)
+    }
+
+    /// Records a new event from replica `id`, returning its [`Dot`].
+    pub fn increment(&mut self, id: Id) -> Dot<Id>
+    where
+        Id: Clone,
+    {
+        let counter = self.counters.entry(id.clone()).or_insert(0);
+        *counter += 1;
+        Dot::new(id, *counter)
+    }
+
+    /// Advances the counter of `dot.id` to at least `dot.counter`. Returns `true` if the clock
+    /// changed.
+    pub fn observe(&mut self, dot: Dot<Id>) -> bool {
+        if dot.counter <= self.get(&dot.id) {
+            return false;
+        }
+        self.counters.insert(dot.id, dot.counter);
+        true
+    }
+
+    /// If the event `dot` happened before (or is) this clock.
+    pub fn contains(&self, dot: &Dot<Id>) -> bool {
+        dot.counter <= self.get(&dot.id)
+    }
+
+    /// If neither clock happened before the other.
+    pub fn is_concurrent(&self, other: &Self) -> bool {
+        self.partial_cmp(other).is_none()
+    }
+
+    /// Iterates the replicas with non-zero counters and their counters, in order of id.
+    pub fn iter(&self) -> impl Iterator<Item = (&Id, u64)> {
+        self.counters.iter().map(|(id, &counter)| (id, counter))
+    }
+
+    /// The pointwise min of the two clocks, i.e. the events which both clocks have seen.
+    ///
+    /// The meet of the clocks acknowledged by every replica is the causal stability frontier:
+    /// events at or before it have been received by every replica.
+    pub fn meet(&self, other: &Self) -> Self
+    where
+        Id: Clone,
+    {
+        let counters = self
+            .counters
+            .iter()
+            .filter_map(|(id, &counter)| {
+                let min = counter.min(other.get(id));
+                (0 < min).then(|| (id.clone(), min))
+            })
+            .collect();
+        Self { counters }
+    }
+}
+
+impl<Id> VClock<Id> {
+    /// Reveal the inner value as a shared reference.
+    pub fn as_reveal_ref(&self) -> &BTreeMap<Id, u64> {
+        &self.counters
+    }
+
+    /// Gets the inner by value, consuming self.
+    pub fn into_reveal(self) -> BTreeMap<Id, u64> {
+        self.counters
+    }
+}
+
+impl<Id> DeepReveal for VClock<Id> {
+    type Revealed = BTreeMap<Id, u64>;
+
+    fn deep_reveal(self) -> Self::Revealed {
+        self.counters
+    }
+}
+
+impl<Id> Merge<VClock<Id>> for VClock<Id>
+where
+    Id: Ord,
+{
+    fn merge(&mut self, other: VClock<Id>) -> bool {
+        let mut changed = false;
+        for (id, counter) in other.counters {
+            changed |= self.observe(Dot::new(id, counter));
+        }
+        changed
+    }
+}
+
+impl<Id> LatticeFrom<VClock<Id>> for VClock<Id> {
+    fn lattice_from(other: VClock<Id>) -> Self {
+        other
+    }
+}
+
+impl<Id> PartialOrd for VClock<Id>
+where
+    Id: Ord,
+{
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        let self_le = self.iter().all(|(id, counter)| counter <= other.get(id));
+        let other_le = other.iter().all(|(id, counter)| counter <= self.get(id));
+        match (self_le, other_le) {
+            (true, true) => Some(Equal),
+            (true, false) => Some(Less),
+            (false, true) => Some(Greater),
+            (false, false) => None,
+        }
+    }
+}
+impl<Id> LatticeOrd<Self> for VClock<Id> where Self: PartialOrd<Self> {}
+
+impl<Id> IsBot for VClock<Id> {
+    fn is_bot(&self) -> bool {
+        self.counters.is_empty()
+    }
+}
+
+impl<Id> IsTop for VClock<Id> {
+    fn is_top(&self) -> bool {
+        false
+    }
+}
+
+impl<Id> Atomize for VClock<Id>
+where
+    Id: 'static + Ord,
+{
+    type Atom = Self;
+
+    // TODO: use impl trait, then remove 'static.
+    type AtomIter = Box<dyn Iterator<Item = Self::Atom>>;
+
+    fn atomize(self) -> Self::AtomIter {
+        Box::new(self.counters.into_iter().map(|(id, counter)| Self {
+            counters: BTreeMap::from([(id, counter)]),
+        }))
+    }
+}
+
+/// Dotted version vector lattice, the set of [`Dot`]s (events) which have been seen.
+///
+/// The set is stored compactly: each replica's contiguous prefix of events `1..=n` is stored as a
+/// [`VClock`], and only the dots after a gap are stored individually in the "dot cloud". Dots are
+/// folded into the clock as soon as their gap is filled.
+///
+/// Merging takes the union of the sets of dots, and contexts are ordered by set inclusion. Unlike
+/// a [`VClock`], a `DotContext` can record that an event was seen without also recording all the
+/// earlier events of that replica, which is needed to track the dots of delta-state CRDTs.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(deserialize = "Id: Ord + serde::Deserialize<'de>"))
+)]
+pub struct DotContext<Id> {
+    clock: VClock<Id>,
+    cloud: BTreeSet<Dot<Id>>,
+}
+
+impl<Id> Default for DotContext<Id> {
+    fn default() -> Self {
+        Self {
+            clock: VClock::default(),
+            cloud: BTreeSet::new(),
+        }
+    }
+}
+
+impl<Id> DotContext<Id>
+where
+    Id: Ord + Clone,
+{
+    /// Create a new empty `DotContext`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a new `DotContext` containing all the events of `clock`.
+    pub fn new_from_clock(clock: VClock<Id>) -> Self {
+        Self {
+            clock,
+            cloud: BTreeSet::new(),
+        }
+    }
+
+    /// Create a new `DotContext` containing the given `dots`.
+    pub fn new_from(dots: impl IntoIterator<Item = Dot<Id>>) -> Self {
+        let mut out = Self::new();
+        out.cloud.extend(dots);
+        out.compact();
+        out
+    }
+
+    /// If the event `dot` is in this context.
+    pub fn contains(&self, dot: &Dot<Id>) -> bool {
+        self.clock.contains(dot) || self.cloud.contains(dot)
+    }
+
+    /// Adds the event `dot` to this context. Returns `true` if it was not already present.
+    pub fn insert(&mut self, dot: Dot<Id>) -> bool {
+        if self.contains(&dot) {
+            return false;
+        }
+        self.cloud.insert(dot);
+        self.compact();
+        true
+    }
+
+    /// Creates and inserts the next event of replica `id`, after all of its events in this context.
+    pub fn next_dot(&mut self, id: Id) -> Dot<Id> {
+        let dot = self.clock.increment(id);
+        self.compact();
+        dot
+    }
+
+    /// The contiguous prefix of events from each replica.
+    pub fn clock(&self) -> &VClock<Id> {
+        &self.clock
+    }
+
+    /// Iterates the events which are not in [`Self::clock`], in order.
+    pub fn cloud(&self) -> impl Iterator<Item = &Dot<Id>> {
+        self.cloud.iter()
+    }
+
+    /// Iterates all the events in this context, in order.
+    pub fn dots(&self) -> impl Iterator<Item = Dot<Id>> + '_ {
+        self.clock
+            .iter()
+            .flat_map(|(id, counter)| (1..=counter).map(|counter| Dot::new(id.clone(), counter)))
+            .chain(self.cloud.iter().cloned())
+    }
+
+    /// If every event of `self` is in `other`.
+    fn is_subset(&self, other: &Self) -> bool {
+        // `other` is compact, so its clock cannot be extended by its cloud.
+        self.clock
+            .iter()
+            .all(|(id, counter)| counter <= other.clock.get(id))
+            && self.cloud.iter().all(|dot| other.contains(dot))
+    }
+
+    /// Moves dots which extend the clock out of the cloud and drops dots already in the clock.
+    fn compact(&mut self) {
+        // The cloud is ordered by id then counter, so each replica's dots are visited in order.
+        let cloud = std::mem::take(&mut self.cloud);
+        for dot in cloud {
+            let counter = self.clock.get(&dot.id);
+            if dot.counter == counter + 1 {
+                self.clock.observe(dot);
+            } else if counter < dot.counter {
+                self.cloud.insert(dot);
+            }
+        }
+    }
+}
+
+impl<Id> DotContext<Id> {
+    /// Reveal the inner value as a shared reference.
+    pub fn as_reveal_ref(&self) -> (&VClock<Id>, &BTreeSet<Dot<Id>>) {
+        (&self.clock, &self.cloud)
+    }
+
+    /// Gets the inner by value, consuming self.
+    pub fn into_reveal(self) -> (VClock<Id>, BTreeSet<Dot<Id>>) {
+        (self.clock, self.cloud)
+    }
+}
+
+impl<Id> DeepReveal for DotContext<Id> {
+    type Revealed = (BTreeMap<Id, u64>, BTreeSet<Dot<Id>>);
+
+    fn deep_reveal(self) -> Self::Revealed {
+        (self.clock.deep_reveal(), self.cloud)
+    }
+}
+
+impl<Id> Merge<DotContext<Id>> for DotContext<Id>
+where
+    Id: Ord + Clone,
+{
+    fn merge(&mut self, other: DotContext<Id>) -> bool {
+        if other.is_subset(self) {
+            return false;
+        }
+        self.clock.merge(other.clock);
+        self.cloud.extend(other.cloud);
+        self.compact();
+        true
+    }
+}
+
+impl<Id> LatticeFrom<DotContext<Id>> for DotContext<Id> {
+    fn lattice_from(other: DotContext<Id>) -> Self {
+        other
+    }
+}
+
+impl<Id> PartialOrd for DotContext<Id>
+where
+    Id: Ord + Clone,
+{
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self.is_subset(other), other.is_subset(self)) {
+            (true, true) => Some(Equal),
+            (true, false) => Some(Less),
+            (false, true) => Some(Greater),
+            (false, false) => None,
+        }
+    }
+}
+impl<Id> LatticeOrd<Self> for DotContext<Id> where Self: PartialOrd<Self> {}
+
+impl<Id> IsBot for DotContext<Id> {
+    fn is_bot(&self) -> bool {
+        self.clock.is_bot() && self.cloud.is_empty()
+    }
+}
+
+impl<Id> IsTop for DotContext<Id> {
+    fn is_top(&self) -> bool {
+        false
+    }
+}
+
+impl<Id> Atomize for DotContext<Id>
+where
+    Id: 'static + Ord + Clone,
+{
+    type Atom = Self;
+
+    // TODO: use impl trait, then remove 'static.
+    type AtomIter = Box<dyn Iterator<Item = Self::Atom>>;
+
+    fn atomize(self) -> Self::AtomIter {
+        let prefixes = self.clock.atomize().map(|clock| Self {
+            clock,
+            cloud: BTreeSet::new(),
+        });
+        let dots = self.cloud.into_iter().map(|dot| Self {
+            clock: VClock::default(),
+            cloud: BTreeSet::from([dot]),
+        });
+        Box::new(prefixes.chain(dots))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::DomPair;
+    use crate::set_union::SetUnionHashSet;
+    use crate::test::{check_all, check_atomize_each};
+
+    fn clocks() -> Vec<VClock<char>> {
+        let mut test_vec = Vec::new();
+        for a in 0..3 {
+            for b in 0..3 {
+                test_vec.push(VClock::new_from([('a', a), ('b', b)]));
+            }
+        }
+        test_vec
+    }
+
+    #[test]
+    fn consistency_vclock() {
+        let test_vec = clocks();
+        check_all(&test_vec);
+        check_atomize_each(&test_vec);
+    }
+
+    #[test]
+    fn consistency_dot_context() {
+        let mut test_vec = vec![DotContext::default()];
+        for clock in clocks() {
+            for cloud in [
+                vec![],
+                vec![Dot::new('a', 3)],
+                vec![Dot::new('a', 4), Dot::new('b', 3)],
+            ] {
+                let mut context = DotContext::new_from_clock(clock.clone());
+                for dot in cloud {
+                    context.insert(dot);
+                }
+                test_vec.push(context);
+            }
+        }
+
+        check_all(&test_vec);
+        check_atomize_each(&test_vec);
+    }
+
+    #[test]
+    fn vclock_zero_is_absent() {
+        assert_eq!(VClock::<char>::new(), VClock::new_from([('a', 0)]));
+        assert!(VClock::new_from([('a', 0)]).is_bot());
+    }
+
+    #[test]
+    fn vclock_concurrent() {
+        let mut a = VClock::new();
+        a.increment('a');
+        let mut b = VClock::new();
+        b.increment('b');
+        assert!(a.is_concurrent(&b));
+
+        let mut c = Merge::merge_owned(a.clone(), b.clone());
+        assert_eq!(Dot::new('a', 2), c.increment('a'));
+        assert!(a < c && b < c);
+    }
+
+    #[test]
+    fn vclock_meet() {
+        let a = VClock::new_from([('a', 3), ('b', 1)]);
+        let b = VClock::new_from([('a', 2), ('c', 4)]);
+        let meet = a.meet(&b);
+        assert_eq!(VClock::new_from([('a', 2)]), meet);
+        assert!(meet <= a && meet <= b);
+        assert_eq!(a, a.meet(&Merge::merge_owned(a.clone(), b.clone())));
+    }
+
+    #[test]
+    fn dot_context_compaction() {
+        let mut context = DotContext::new();
+        assert!(context.insert(Dot::new('a', 2)));
+        assert!(context.insert(Dot::new('a', 4)));
+        assert!(!context.insert(Dot::new('a', 2)));
+        assert_eq!(0, context.clock().get(&'a'));
+        assert_eq!(2, context.cloud().count());
+
+        // Filling the gap folds the contiguous dots into the clock.
+        assert!(context.insert(Dot::new('a', 1)));
+        assert_eq!(2, context.clock().get(&'a'));
+        assert_eq!(vec![&Dot::new('a', 4)], context.cloud().collect::<Vec<_>>());
+
+        assert_eq!(Dot::new('a', 3), context.next_dot('a'));
+        assert_eq!(4, context.clock().get(&'a'));
+        assert_eq!(0, context.cloud().count());
+        assert_eq!(
+            vec![
+                Dot::new('a', 1),
+                Dot::new('a', 2),
+                Dot::new('a', 3),
+                Dot::new('a', 4),
+            ],
+            context.dots().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn dom_pair_concurrent_writes() {
+        type Register = DomPair<VClock<char>, SetUnionHashSet<&'static str>>;
+
+        let write = |clock: &VClock<char>, value| {
+            Register::new(clock.clone(), SetUnionHashSet::new_from([value]))
+        };
+
+        let mut clock_a = VClock::new();
+        clock_a.increment('a');
+        let mut clock_b = VClock::new();
+        clock_b.increment('b');
+
+        // Concurrent writes keep both values.
+        let mut register = write(&clock_a, "x");
+        assert!(register.merge(write(&clock_b, "y")));
+        assert_eq!(
+            &HashSet::from(["x", "y"]),
+            register.as_reveal_ref().1.as_reveal_ref()
+        );
+
+        // A write which has seen both replaces them.
+        let mut clock_c = register.as_reveal_ref().0.clone();
+        clock_c.increment('a');
+        assert!(register.merge(write(&clock_c, "z")));
+        assert_eq!(
+            &HashSet::from(["z"]),
+            register.as_reveal_ref().1.as_reveal_ref()
+        );
+
+        // A stale write is ignored.
+        assert!(!register.merge(write(&clock_a, "w")));
+    }
+}